@@ -32,7 +32,7 @@ rm -f "${TRACE_LOG}" "${OUT}"
 echo "Running on Spike simulator (trace log: ${TRACE_LOG})..."
 
 # Note: syscall-cycles runs many `ecall`s; this needs a high instruction budget.
-RUST_LOG=info cargo spike run "${BIN}" --isa RV64IMAC --instructions 20000000 -l --log="${TRACE_LOG}" | tee "${OUT}"
+RUST_LOG=info cargo spike run "${BIN}" --isa RV64IMAC --instructions 20000000 --spike-arg -l --spike-arg --log="${TRACE_LOG}" | tee "${OUT}"
 
 grep -q "syscall:unknown" "${OUT}"
 grep -q "Test PASSED" "${OUT}"
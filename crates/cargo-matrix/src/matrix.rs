@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
@@ -27,6 +28,14 @@ pub struct MatrixArgs {
     /// Print commands as they run
     #[arg(long)]
     pub verbose: bool,
+
+    /// Capture each step's stdout/stderr and print it only if the step fails
+    #[arg(long)]
+    pub output_on_failure: bool,
+
+    /// Run every step even after a failure, then report all failed steps
+    #[arg(long)]
+    pub keep_going: bool,
 }
 
 #[derive(serde::Deserialize)]
@@ -154,12 +163,14 @@ fn render_template(
         .replace("{features_flag}", features_flag)
 }
 
-fn run_shell(cmd: &str, cwd: &Path, verbose: bool) -> Result<(), String> {
-    if verbose {
-        println!("$ {}", cmd);
-    }
+/// A failed step: the error message plus any output captured with `--output-on-failure`.
+struct ShellFailure {
+    message: String,
+    captured: Vec<u8>,
+}
 
-    let status = if cfg!(windows) {
+fn shell_command(cmd: &str, cwd: &Path) -> Command {
+    let mut command = if cfg!(windows) {
         // Best-effort: allow running under Windows if a POSIX shell is available.
         if Command::new("sh")
             .arg("-c")
@@ -169,32 +180,59 @@ fn run_shell(cmd: &str, cwd: &Path, verbose: bool) -> Result<(), String> {
             .status()
             .is_ok()
         {
-            Command::new("sh")
-                .arg("-c")
-                .arg(cmd)
-                .current_dir(cwd)
-                .status()
+            let mut c = Command::new("sh");
+            c.arg("-c").arg(cmd);
+            c
         } else {
-            Command::new("cmd")
-                .args(["/C", cmd])
-                .current_dir(cwd)
-                .status()
+            let mut c = Command::new("cmd");
+            c.args(["/C", cmd]);
+            c
         }
     } else {
-        Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
-            .current_dir(cwd)
-            .status()
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(cmd);
+        c
+    };
+    command.current_dir(cwd);
+    command
+}
+
+fn run_shell(
+    cmd: &str,
+    cwd: &Path,
+    verbose: bool,
+    capture: bool,
+    out: &mut dyn Write,
+) -> Result<(), ShellFailure> {
+    if verbose {
+        let _ = writeln!(out, "$ {}", cmd);
     }
-    .map_err(|e| format!("Failed to execute shell: {}", e))?;
+
+    let fail = |message: String| ShellFailure {
+        message,
+        captured: Vec::new(),
+    };
+
+    let mut command = shell_command(cmd, cwd);
+    let (status, captured) = if capture {
+        let output = command
+            .output()
+            .map_err(|e| fail(format!("Failed to execute shell: {}", e)))?;
+        let mut captured = output.stdout;
+        captured.extend_from_slice(&output.stderr);
+        (output.status, captured)
+    } else {
+        let status = command
+            .status()
+            .map_err(|e| fail(format!("Failed to execute shell: {}", e)))?;
+        (status, Vec::new())
+    };
 
     if !status.success() {
-        return Err(format!(
-            "Command failed (exit={:?}): {}",
-            status.code(),
-            cmd
-        ));
+        return Err(ShellFailure {
+            message: format!("Command failed (exit={:?}): {}", status.code(), cmd),
+            captured,
+        });
     }
     Ok(())
 }
@@ -204,6 +242,43 @@ struct Step {
     cmd: String,
 }
 
+fn run_steps(
+    steps: &[Step],
+    cwd: &Path,
+    args: &MatrixArgs,
+    out: &mut dyn Write,
+) -> Result<(), String> {
+    let mut failed: Vec<&str> = Vec::new();
+
+    for (i, step) in steps.iter().enumerate() {
+        let _ = writeln!(out, "[{}/{}] {}", i + 1, steps.len(), step.name);
+        let Err(failure) = run_shell(&step.cmd, cwd, args.verbose, args.output_on_failure, out)
+        else {
+            continue;
+        };
+
+        if args.output_on_failure {
+            let _ = writeln!(out, "--- output of {} ---", step.name);
+            let _ = out.write_all(&failure.captured);
+            let _ = writeln!(out, "--- end of output ---");
+        }
+        if !args.keep_going {
+            return Err(failure.message);
+        }
+        let _ = writeln!(out, "{}", failure.message);
+        failed.push(&step.name);
+    }
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let _ = writeln!(out, "\n{} of {} steps failed:", failed.len(), steps.len());
+    for name in &failed {
+        let _ = writeln!(out, "  {}", name);
+    }
+    Err(format!("{} matrix step(s) failed", failed.len()))
+}
+
 pub fn run(args: MatrixArgs) -> Result<(), String> {
     let command = args.command.clone();
 
@@ -326,10 +401,78 @@ pub fn run(args: MatrixArgs) -> Result<(), String> {
         }
     }
 
-    for (i, step) in steps.iter().enumerate() {
-        println!("[{}/{}] {}", i + 1, steps.len(), step.name);
-        run_shell(&step.cmd, &workspace, args.verbose)?;
+    run_steps(&steps, &workspace, &args, &mut std::io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(output_on_failure: bool, keep_going: bool) -> MatrixArgs {
+        MatrixArgs {
+            config: None,
+            command: None,
+            packages: Vec::new(),
+            verbose: false,
+            output_on_failure,
+            keep_going,
+        }
     }
 
-    Ok(())
+    fn step(name: &str, cmd: &str) -> Step {
+        Step {
+            name: name.to_string(),
+            cmd: cmd.to_string(),
+        }
+    }
+
+    fn steps() -> Vec<Step> {
+        vec![
+            step("ok", "echo pass-stdout; echo pass-stderr >&2"),
+            step("bad", "echo fail-stdout; echo fail-stderr >&2; exit 3"),
+            step("after", "echo after-stdout"),
+        ]
+    }
+
+    #[test]
+    fn test_output_on_failure_prints_only_failing_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = Vec::new();
+        let err = run_steps(&steps(), dir.path(), &args(true, true), &mut out).unwrap_err();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("fail-stdout"), "{}", out);
+        assert!(out.contains("fail-stderr"), "{}", out);
+        assert!(!out.contains("pass-stdout"), "{}", out);
+        assert!(!out.contains("pass-stderr"), "{}", out);
+        assert!(!out.contains("after-stdout"), "{}", out);
+
+        // --keep-going ran the last row and summarized the failure.
+        assert!(out.contains("[3/3] after"), "{}", out);
+        assert!(out.contains("1 of 3 steps failed:\n  bad\n"), "{}", out);
+        assert_eq!(err, "1 matrix step(s) failed");
+    }
+
+    #[test]
+    fn test_stops_at_first_failure_without_keep_going() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = Vec::new();
+        let err = run_steps(&steps(), dir.path(), &args(true, false), &mut out).unwrap_err();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("fail-stdout"), "{}", out);
+        assert!(!out.contains("[3/3]"), "{}", out);
+        assert!(err.contains("exit=Some(3)"), "{}", err);
+    }
+
+    #[test]
+    fn test_all_rows_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = Vec::new();
+        let ok = vec![step("a", "echo a-stdout"), step("b", "true")];
+        run_steps(&ok, dir.path(), &args(true, true), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("a-stdout"), "{}", out);
+        assert!(!out.contains("failed"), "{}", out);
+    }
 }
@@ -2,15 +2,46 @@
 //!
 //! Supported syntax (Jinja-like subset):
 //! - `{% if <ident> %} ... {% else %} ... {% endif %}`
+//! - `{% if <ident> == "literal" %}` / `{% if <ident> != "literal" %}` on string identifiers
+//! - `not`, `and`, `or` in conditions, e.g. `{% if not release %}`, `{% if a and b %}`
+//! - `{% for <var> in <list> %} ... {% endfor %}` over string lists
+//! - `{% set <name> = "literal" %}` / `{% set <name> = <ident> %}`: a string variable visible
+//!   until the end of the enclosing `if`/`for` body (or the template), shadowing outer names
+//! - `{# comment #}`, dropped from the output
+//! - `{{ <ident> }}`, optionally followed by filters: `{{ <ident> | default("literal") }}`
+//! - Trim markers: `{%- ... %}` / `{{- ... }}` drop trailing spaces and tabs from the preceding
+//!   text up to and including its last newline; `... -%}` / `... -}}` drop leading spaces and
+//!   tabs from the following text up to and including its first newline.
 //!
-//! Only boolean identifiers are supported; no expressions, no filters, no loops.
+//! Conditions combine terms strictly left to right with no precedence and no parentheses:
+//! `a or b and c` means `(a or b) and c`. Each term is an optional `not` followed by a boolean
+//! identifier or a string comparison. Every term is evaluated, so an unknown identifier always
+//! errors even if the result is already decided.
+//!
+//! Filters apply left to right to a `{{ }}` expression. The only filter is `default("literal")`,
+//! which substitutes the literal when the identifier is not set.
+//!
+//! Rendering is strict by default: an unknown identifier is an error, so a typo in a template
+//! or a missing context value cannot silently produce a wrong script. [`Context::set_lenient`]
+//! trades that for convenience: unknown booleans are `false`, unknown strings are `""` (in both
+//! `{{ }}` and comparisons), and unknown lists are empty. Syntax errors are still reported.
+//! [`validate`] walks a template the same way but collects every error instead of stopping.
+//!
+//! [`Template::parse`] checks the structure once (tags, block nesting, conditions, filters) and
+//! [`Template::render`] then only looks identifiers up, in the branches and loop bodies that are
+//! actually rendered. [`render`] does both in one call.
+//!
+//! The `{% %}`, `{{ }}` and `{# #}` delimiters can be replaced through [`RenderOptions`] and
+//! [`Template::parse_with`] or [`render_with`].
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Debug, Clone, Default)]
 pub struct Context {
     bools: BTreeMap<String, bool>,
     strs: BTreeMap<String, String>,
+    lists: BTreeMap<String, Vec<String>>,
+    lenient: bool,
 }
 
 impl Context {
@@ -36,6 +67,57 @@ impl Context {
         self
     }
 
+    /// Insert a string only if `validator` accepts it; otherwise return the validator's error.
+    pub fn insert_str_validated(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+        validator: fn(&str) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let value = value.into();
+        validator(&value)?;
+        self.insert_str(name, value);
+        Ok(())
+    }
+
+    pub fn insert_list(&mut self, name: impl Into<String>, values: Vec<String>) {
+        self.lists.insert(name.into(), values);
+    }
+
+    pub fn with_list(mut self, name: impl Into<String>, values: Vec<String>) -> Self {
+        self.insert_list(name, values);
+        self
+    }
+
+    /// Treat unknown identifiers as `false` / `""` / empty instead of erroring.
+    ///
+    /// Off by default; see the crate docs for the tradeoff.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.set_lenient(lenient);
+        self
+    }
+
+    /// Remove every bool, string and list, keeping the lenient setting.
+    ///
+    /// Lets one context be refilled for each render of a loop.
+    pub fn clear(&mut self) {
+        self.clear_bools();
+        self.clear_strs();
+        self.lists.clear();
+    }
+
+    pub fn clear_bools(&mut self) {
+        self.bools.clear();
+    }
+
+    pub fn clear_strs(&mut self) {
+        self.strs.clear();
+    }
+
     fn get_bool(&self, name: &str) -> Option<bool> {
         self.bools.get(name).copied()
     }
@@ -43,205 +125,2046 @@ impl Context {
     fn get_str(&self, name: &str) -> Option<&str> {
         self.strs.get(name).map(|s| s.as_str())
     }
+
+    fn get_list(&self, name: &str) -> Option<&[String]> {
+        self.lists.get(name).map(|v| v.as_slice())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RenderError {
     pub message: String,
     pub byte_offset: usize,
+    /// 1-based line of `byte_offset` in the template.
+    pub line: usize,
+    /// 1-based column (in chars) of `byte_offset` within its line.
+    pub column: usize,
+}
+
+impl RenderError {
+    /// An error at `byte_offset`; the line and column are filled in by [`RenderError::locate`].
+    fn at(message: impl Into<String>, byte_offset: usize) -> Self {
+        Self {
+            message: message.into(),
+            byte_offset,
+            line: 0,
+            column: 0,
+        }
+    }
+
+    fn locate(mut self, template: &str) -> Self {
+        (self.line, self.column) = line_column(template, self.byte_offset);
+        self
+    }
+}
+
+/// 1-based line and column of `offset`, which may equal `template.len()`.
+fn line_column(template: &str, offset: usize) -> (usize, usize) {
+    let before = &template[..offset.min(template.len())];
+    let line_start = before.rfind('\n').map_or(0, |nl| nl + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
 }
 
 impl std::fmt::Display for RenderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} (at byte {})", self.message, self.byte_offset)
+        write!(
+            f,
+            "{} at {}:{} (byte {})",
+            self.message, self.line, self.column, self.byte_offset
+        )
     }
 }
 
 impl std::error::Error for RenderError {}
 
-#[derive(Debug)]
-struct Frame {
-    cond_true: bool,
-    in_else: bool,
+/// A node of a parsed [`Template`].
+#[derive(Debug, Clone)]
+enum Node<'t> {
+    Text {
+        text: &'t str,
+        offset: usize,
+    },
+    Expr {
+        ident: &'t str,
+        filters: Vec<Filter<'t>>,
+        offset: usize,
+    },
+    If {
+        cond: Vec<Term<'t>>,
+        then: Vec<Node<'t>>,
+        otherwise: Vec<Node<'t>>,
+        offset: usize,
+    },
+    For {
+        var: &'t str,
+        list: &'t str,
+        body: Vec<Node<'t>>,
+        offset: usize,
+    },
+    /// A `{% set %}` binding, visible until the end of the enclosing node list.
+    Set {
+        name: &'t str,
+        /// A literal (`Ok`) or an identifier to look up (`Err`).
+        value: Result<&'t str, &'t str>,
+        offset: usize,
+    },
 }
 
-fn should_emit(stack: &[Frame]) -> bool {
-    // Emit only if every active frame selects this branch.
-    stack
-        .iter()
-        .all(|f| if f.in_else { !f.cond_true } else { f.cond_true })
+/// One term of an `if` condition.
+#[derive(Debug, Clone)]
+struct Term<'t> {
+    /// Joined to the result so far with `and` rather than `or`; ignored for the first term.
+    and: bool,
+    negate: bool,
+    test: Test<'t>,
 }
 
-/// Render `template` using `ctx`.
-pub fn render(template: &str, ctx: &Context) -> Result<String, RenderError> {
-    let mut out = String::with_capacity(template.len());
-    let mut stack: Vec<Frame> = Vec::new();
+#[derive(Debug, Clone)]
+enum Test<'t> {
+    Bool(&'t str),
+    Compare {
+        ident: &'t str,
+        literal: &'t str,
+        equal: bool,
+    },
+}
 
-    let mut i = 0;
-    while i < template.len() {
-        let rest = &template[i..];
-        let next_ctrl = rest.find("{%");
-        let next_expr = rest.find("{{");
-        let open = match (next_ctrl, next_expr) {
-            (None, None) => None,
-            (Some(a), None) => Some((a, true)),
-            (None, Some(b)) => Some((b, false)),
-            (Some(a), Some(b)) => Some(if a <= b { (a, true) } else { (b, false) }),
+/// Parse the `<name> = <value>` part of a `set` tag.
+///
+/// Returns the name and either a literal (`Ok`) or an identifier to look up (`Err`).
+fn parse_set(spec: &str) -> Option<(&str, Result<&str, &str>)> {
+    let (name, value) = spec.split_once('=')?;
+    let is_ident = |s: &str| !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == '"');
+    let name = name.trim();
+    let value = value.trim();
+    if !is_ident(name) {
+        return None;
+    }
+    match quoted_literal(value) {
+        Some(literal) => Some((name, Ok(literal))),
+        None if is_ident(value) && !value.contains('=') => Some((name, Err(value))),
+        None => None,
+    }
+}
+
+/// Split `cond` into whitespace-separated token spans, keeping quoted literals whole.
+fn condition_tokens(cond: &str) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+    for (pos, c) in cond.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        if c.is_whitespace() && !in_quotes {
+            if let Some(begin) = start.take() {
+                tokens.push((begin, pos));
+            }
+        } else if start.is_none() {
+            start = Some(pos);
+        }
+    }
+    if let Some(begin) = start {
+        tokens.push((begin, cond.len()));
+    }
+    tokens
+}
+
+/// Strip trailing spaces/tabs and at most one line ending (for `{%-` / `{{-`).
+fn trim_line_end(text: &str) -> &str {
+    let text = text.trim_end_matches([' ', '\t']);
+    match text.strip_suffix('\n') {
+        Some(t) => t.strip_suffix('\r').unwrap_or(t),
+        None => text,
+    }
+}
+
+/// Length of leading spaces/tabs plus at most one line ending (for `-%}` / `-}}`).
+fn line_start_len(text: &str) -> usize {
+    let blanks = text.len() - text.trim_start_matches([' ', '\t']).len();
+    let after = &text[blanks..];
+    if after.starts_with("\r\n") {
+        blanks + 2
+    } else if after.starts_with('\n') {
+        blanks + 1
+    } else {
+        blanks
+    }
+}
+
+/// Slice the inside of a tag whose opening delimiter is `open_len` bytes long and whose closing
+/// delimiter starts at `close`, honouring trim markers.
+///
+/// Returns the trimmed contents and whether the tag asks to trim the text that follows it.
+fn tag_inner(tag: &str, open_len: usize, close: usize) -> (&str, bool) {
+    let start = if tag[open_len..].starts_with('-') {
+        open_len + 1
+    } else {
+        open_len
+    };
+    let trim_after = close > start && tag[..close].ends_with('-');
+    let end = if trim_after { close - 1 } else { close };
+    (tag[start..end].trim(), trim_after)
+}
+
+/// Parse a `"literal"` with no embedded quotes.
+fn quoted_literal(s: &str) -> Option<&str> {
+    s.trim()
+        .strip_prefix('"')
+        .and_then(|l| l.strip_suffix('"'))
+        .filter(|l| !l.contains('"'))
+}
+
+/// A filter in a `{{ ident | filter }}` expression.
+#[derive(Debug, Clone, Copy)]
+enum Filter<'a> {
+    /// `default("literal")`: use `literal` when the identifier is unset.
+    Default(&'a str),
+}
+
+impl<'a> Filter<'a> {
+    fn parse(spec: &'a str, offset: usize) -> Result<Self, RenderError> {
+        let spec = spec.trim();
+        let (name, args) = match spec.split_once('(') {
+            Some((name, rest)) => (name.trim(), rest.strip_suffix(')')),
+            None => (spec, None),
+        };
+        let malformed = |expected: &str| {
+            RenderError::at(
+                format!(
+                    "Malformed filter in {{{{ ... }}}}, expected `{}`: {}",
+                    expected, spec
+                ),
+                offset,
+            )
         };
+        match name {
+            "default" => args
+                .and_then(quoted_literal)
+                .map(Filter::Default)
+                .ok_or_else(|| malformed("default(\"literal\")")),
+            _ => Err(RenderError::at(
+                format!("Unknown filter in template: {}", name),
+                offset,
+            )),
+        }
+    }
 
-        if let Some((open, is_ctrl)) = open {
-            let text = &rest[..open];
-            if should_emit(&stack) {
-                out.push_str(text);
-            }
-            i += open;
-
-            let rest2 = &template[i..];
-            if is_ctrl {
-                let close = rest2.find("%}").ok_or_else(|| RenderError {
-                    message: "Unclosed template tag".to_string(),
-                    byte_offset: i,
-                })?;
-
-                let tag = rest2[2..close].trim();
-                let tag_offset = i;
-                i += close + 2;
-
-                if tag == "else" {
-                    let top = stack.last_mut().ok_or_else(|| RenderError {
-                        message: "{% else %} without matching {% if ... %}".to_string(),
-                        byte_offset: tag_offset,
-                    })?;
-                    if top.in_else {
-                        return Err(RenderError {
-                            message: "Duplicate {% else %} in the same {% if %} block".to_string(),
-                            byte_offset: tag_offset,
-                        });
-                    }
-                    top.in_else = true;
-                    continue;
-                }
+    fn apply(&self, value: Option<&'a str>) -> Option<&'a str> {
+        match self {
+            Filter::Default(literal) => value.or(Some(literal)),
+        }
+    }
+}
 
-                if tag == "endif" {
-                    if stack.pop().is_none() {
-                        return Err(RenderError {
-                            message: "{% endif %} without matching {% if ... %}".to_string(),
-                            byte_offset: tag_offset,
-                        });
-                    }
-                    continue;
-                }
+/// Split `expr` on `|` outside quoted literals.
+fn split_pipes(expr: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (pos, c) in expr.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '|' if !in_quotes => {
+                parts.push(&expr[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
 
-                if let Some(cond) = tag.strip_prefix("if ") {
-                    let ident = cond.trim();
-                    if ident.is_empty() {
-                        return Err(RenderError {
-                            message: "Empty identifier in {% if %}".to_string(),
-                            byte_offset: tag_offset,
-                        });
-                    }
-                    let cond_true = ctx.get_bool(ident).ok_or_else(|| RenderError {
-                        message: format!("Unknown boolean identifier in template: {}", ident),
-                        byte_offset: tag_offset,
-                    })?;
-
-                    stack.push(Frame {
-                        cond_true,
-                        in_else: false,
-                    });
-                    continue;
-                }
+/// Where recoverable errors go: returned at once by `render`, collected by [`validate`].
+struct Diagnostics {
+    collected: Option<Vec<RenderError>>,
+}
 
-                return Err(RenderError {
-                    message: format!("Unknown template tag: {{% {} %}}", tag),
-                    byte_offset: tag_offset,
-                });
-            } else {
-                let close = rest2.find("}}").ok_or_else(|| RenderError {
-                    message: "Unclosed template expression".to_string(),
-                    byte_offset: i,
-                })?;
-                let expr = rest2[2..close].trim();
-                let expr_offset = i;
-                i += close + 2;
-
-                if should_emit(&stack) {
-                    let ident = expr;
-                    if ident.is_empty() {
-                        return Err(RenderError {
-                            message: "Empty identifier in {{ ... }}".to_string(),
-                            byte_offset: expr_offset,
-                        });
+impl Diagnostics {
+    /// Report `err`. When collecting, returns `Ok` so the caller can recover and keep going.
+    fn report(&mut self, err: RenderError) -> Result<(), RenderError> {
+        let Some(collected) = &mut self.collected else {
+            return Err(err);
+        };
+        // Loop bodies are rendered once per item; report each problem once.
+        let seen = collected
+            .iter()
+            .any(|e| e.byte_offset == err.byte_offset && e.message == err.message);
+        if !seen {
+            collected.push(err);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a `{{ }}` expression: an identifier followed by zero or more filters.
+fn parse_expr(expr: &str, offset: usize) -> Result<Node<'_>, RenderError> {
+    let mut parts = split_pipes(expr).into_iter();
+    let ident = parts.next().unwrap_or_default().trim();
+    if ident.is_empty() {
+        return Err(RenderError::at("Empty identifier in {{ ... }}", offset));
+    }
+    let filters = parts
+        .map(|spec| Filter::parse(spec, offset))
+        .collect::<Result<_, _>>()?;
+    Ok(Node::Expr {
+        ident,
+        filters,
+        offset,
+    })
+}
+
+/// Parse an `if` condition into terms joined by `and`/`or`, applied left to right.
+fn parse_condition(cond: &str, offset: usize) -> Result<Vec<Term<'_>>, RenderError> {
+    let malformed = || {
+        RenderError::at(
+            format!("Malformed condition in {{% if %}}: {}", cond.trim()),
+            offset,
+        )
+    };
+
+    let tokens = condition_tokens(cond);
+    if tokens.is_empty() {
+        // Reports the empty-identifier error.
+        parse_test(cond, offset)?;
+    }
+
+    let mut terms = Vec::new();
+    let mut pending_op: Option<&str> = None;
+    let mut term_start = 0;
+    for idx in 0..=tokens.len() {
+        let word = tokens.get(idx).map(|&(a, b)| &cond[a..b]);
+        if !matches!(word, None | Some("and") | Some("or")) {
+            continue;
+        }
+
+        let term = &tokens[term_start..idx];
+        let (negate, term) = match term.first() {
+            Some(&(a, b)) if &cond[a..b] == "not" => (true, &term[1..]),
+            _ => (false, term),
+        };
+        let (Some(first), Some(last)) = (term.first(), term.last()) else {
+            return Err(malformed());
+        };
+        terms.push(Term {
+            and: pending_op == Some("and"),
+            negate,
+            test: parse_test(&cond[first.0..last.1], offset)?,
+        });
+        pending_op = word;
+        term_start = idx + 1;
+    }
+
+    if terms.is_empty() {
+        return Err(malformed());
+    }
+    Ok(terms)
+}
+
+/// Parse one term: a boolean identifier, or `<ident> ==|!= "literal"` on a string.
+fn parse_test(cond: &str, offset: usize) -> Result<Test<'_>, RenderError> {
+    let cond = cond.trim();
+    if cond.is_empty() {
+        return Err(RenderError::at("Empty identifier in {% if %}", offset));
+    }
+
+    // The first operator wins, so a literal may itself contain `==` or `!=`.
+    let comparison = match (cond.find("=="), cond.find("!=")) {
+        (Some(eq), Some(ne)) => Some(if eq < ne { (eq, true) } else { (ne, false) }),
+        (Some(eq), None) => Some((eq, true)),
+        (None, Some(ne)) => Some((ne, false)),
+        (None, None) => None,
+    };
+    let Some((pos, equal)) = comparison else {
+        return Ok(Test::Bool(cond));
+    };
+
+    let malformed = || {
+        RenderError::at(
+            format!(
+                "Malformed comparison in {{% if %}}, expected `<ident> == \"literal\"`: {}",
+                cond
+            ),
+            offset,
+        )
+    };
+
+    let ident = cond[..pos].trim();
+    if ident.is_empty() || ident.contains(char::is_whitespace) {
+        return Err(malformed());
+    }
+    let literal = quoted_literal(&cond[pos + 2..]).ok_or_else(malformed)?;
+    Ok(Test::Compare {
+        ident,
+        literal,
+        equal,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    Ctrl,
+    Expr,
+    Comment,
+}
+
+/// Tag delimiters used by [`Template::parse_with`] and [`render_with`].
+#[derive(Debug, Clone)]
+pub struct RenderOptions<'a> {
+    pub ctrl_open: &'a str,
+    pub ctrl_close: &'a str,
+    pub expr_open: &'a str,
+    pub expr_close: &'a str,
+    pub comment_open: &'a str,
+    pub comment_close: &'a str,
+}
+
+impl Default for RenderOptions<'_> {
+    fn default() -> Self {
+        Self {
+            ctrl_open: "{%",
+            ctrl_close: "%}",
+            expr_open: "{{",
+            expr_close: "}}",
+            comment_open: "{#",
+            comment_close: "#}",
+        }
+    }
+}
+
+/// An `if`/`for` block whose end tag has not been parsed yet.
+enum Open<'t> {
+    If {
+        cond: Vec<Term<'t>>,
+        then: Vec<Node<'t>>,
+        otherwise: Option<Vec<Node<'t>>>,
+        offset: usize,
+    },
+    /// `list` is `None` for a malformed `for`, kept open only so its `endfor` pairs up.
+    For {
+        var: &'t str,
+        list: Option<&'t str>,
+        body: Vec<Node<'t>>,
+        offset: usize,
+    },
+}
+
+impl<'t> Open<'t> {
+    fn body(&mut self) -> &mut Vec<Node<'t>> {
+        match self {
+            Open::If {
+                otherwise: Some(body),
+                ..
+            }
+            | Open::If { then: body, .. }
+            | Open::For { body, .. } => body,
+        }
+    }
+
+    fn close(self) -> Option<Node<'t>> {
+        match self {
+            Open::If {
+                cond,
+                then,
+                otherwise,
+                offset,
+            } => Some(Node::If {
+                cond,
+                then,
+                otherwise: otherwise.unwrap_or_default(),
+                offset,
+            }),
+            Open::For {
+                var,
+                list,
+                body,
+                offset,
+            } => list.map(|list| Node::For {
+                var,
+                list,
+                body,
+                offset,
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Parser<'t> {
+    root: Vec<Node<'t>>,
+    open: Vec<Open<'t>>,
+}
+
+impl<'t> Parser<'t> {
+    fn push(&mut self, node: Node<'t>) {
+        match self.open.last_mut() {
+            Some(block) => block.body().push(node),
+            None => self.root.push(node),
+        }
+    }
+
+    fn close_innermost(&mut self) {
+        if let Some(node) = self.open.pop().and_then(Open::close) {
+            self.push(node);
+        }
+    }
+
+    /// The nodes parsed so far, with any blocks still open closed where they stand.
+    fn finish(mut self) -> Vec<Node<'t>> {
+        while !self.open.is_empty() {
+            self.close_innermost();
+        }
+        self.root
+    }
+}
+
+/// A template parsed once and rendered any number of times.
+///
+/// Parsing checks everything that does not depend on a [`Context`]: tags, block nesting,
+/// conditions and filters. Rendering then only looks identifiers up.
+#[derive(Debug, Clone)]
+pub struct Template<'t> {
+    source: &'t str,
+    nodes: Vec<Node<'t>>,
+}
+
+impl<'t> Template<'t> {
+    pub fn parse(source: &'t str) -> Result<Self, RenderError> {
+        Self::parse_with(source, &RenderOptions::default())
+    }
+
+    /// Parse `source` with custom tag delimiters.
+    ///
+    /// If tags of different kinds open at the same position, control beats expression beats
+    /// comment.
+    pub fn parse_with(source: &'t str, opts: &RenderOptions) -> Result<Self, RenderError> {
+        let mut diag = Diagnostics { collected: None };
+        let (nodes, fatal) = parse_nodes(source, opts, &mut diag);
+        match fatal {
+            Some(err) => Err(err.locate(source)),
+            None => Ok(Self { source, nodes }),
+        }
+    }
+
+    /// The context identifiers this template uses, whichever branches a render would take.
+    ///
+    /// Loop variables and `set` names are not included where they are bound.
+    pub fn referenced_identifiers(&self) -> ReferencedIds {
+        let mut ids = ReferencedIds::default();
+        collect_identifiers(&self.nodes, &mut Vec::new(), &mut ids);
+        ids
+    }
+
+    pub fn render(&self, ctx: &Context) -> Result<String, RenderError> {
+        let mut out = String::with_capacity(self.source.len());
+        self.render_to(ctx, &mut out)?;
+        Ok(out)
+    }
+
+    /// Render using `ctx`, streaming the output into `out`.
+    ///
+    /// On error, `out` may already hold a partial rendering.
+    pub fn render_to(
+        &self,
+        ctx: &Context,
+        out: &mut impl std::fmt::Write,
+    ) -> Result<(), RenderError> {
+        let mut diag = Diagnostics { collected: None };
+        render_nodes(&self.nodes, ctx, &mut Vec::new(), out, &mut diag)
+            .map_err(|e| e.locate(self.source))
+    }
+}
+
+/// Context identifiers referenced by a template, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferencedIds {
+    /// Booleans tested in `if` conditions.
+    pub bools: BTreeSet<String>,
+    /// Strings in `{{ }}`, comparisons and `set` values.
+    pub strs: BTreeSet<String>,
+    /// Lists iterated by `for`.
+    pub lists: BTreeSet<String>,
+}
+
+impl ReferencedIds {
+    fn add_str(&mut self, bound: &[&str], name: &str) {
+        if !bound.contains(&name) {
+            self.strs.insert(name.to_string());
+        }
+    }
+}
+
+/// Parse `template` and list the context identifiers it references; no [`Context`] is needed.
+pub fn referenced_identifiers(template: &str) -> Result<ReferencedIds, RenderError> {
+    Ok(Template::parse(template)?.referenced_identifiers())
+}
+
+fn collect_identifiers<'t>(nodes: &[Node<'t>], bound: &mut Vec<&'t str>, ids: &mut ReferencedIds) {
+    let depth = bound.len();
+    for node in nodes {
+        match node {
+            Node::Text { .. } => {}
+            Node::Expr { ident, .. } => ids.add_str(bound, ident),
+            Node::Set { name, value, .. } => {
+                if let Err(ident) = value {
+                    ids.add_str(bound, ident);
+                }
+                bound.push(name);
+            }
+            Node::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                for term in cond {
+                    match term.test {
+                        Test::Bool(ident) => {
+                            ids.bools.insert(ident.to_string());
+                        }
+                        Test::Compare { ident, .. } => ids.add_str(bound, ident),
                     }
-                    let val = ctx.get_str(ident).ok_or_else(|| RenderError {
-                        message: format!("Unknown string identifier in template: {}", ident),
-                        byte_offset: expr_offset,
-                    })?;
-                    out.push_str(val);
                 }
-                continue;
+                collect_identifiers(then, bound, ids);
+                collect_identifiers(otherwise, bound, ids);
             }
-        } else {
-            if should_emit(&stack) {
-                out.push_str(rest);
+            Node::For {
+                var, list, body, ..
+            } => {
+                ids.lists.insert(list.to_string());
+                bound.push(var);
+                collect_identifiers(body, bound, ids);
+                bound.pop();
             }
-            break;
         }
     }
+    bound.truncate(depth);
+}
 
-    if !stack.is_empty() {
-        return Err(RenderError {
-            message: "Unclosed {% if %} block(s)".to_string(),
-            byte_offset: template.len(),
-        });
+/// Render `template` using `ctx`.
+pub fn render(template: &str, ctx: &Context) -> Result<String, RenderError> {
+    Template::parse(template)?.render(ctx)
+}
+
+/// Render `template` using `ctx` and custom tag delimiters.
+pub fn render_with(
+    template: &str,
+    ctx: &Context,
+    opts: &RenderOptions,
+) -> Result<String, RenderError> {
+    Template::parse_with(template, opts)?.render(ctx)
+}
+
+/// Render `template` using `ctx`, failing once the output would exceed `max_output` bytes.
+///
+/// A safety valve for templates that are not trusted, where a huge literal or a long loop could
+/// otherwise grow the output without bound.
+pub fn render_with_limit(
+    template: &str,
+    ctx: &Context,
+    max_output: usize,
+) -> Result<String, RenderError> {
+    struct Capped {
+        out: String,
+        max: usize,
+        exceeded: bool,
+    }
+    impl std::fmt::Write for Capped {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            if self.out.len() + s.len() > self.max {
+                self.exceeded = true;
+                return Err(std::fmt::Error);
+            }
+            self.out.push_str(s);
+            Ok(())
+        }
     }
 
-    Ok(out)
+    let mut out = Capped {
+        out: String::new(),
+        max: max_output,
+        exceeded: false,
+    };
+    Template::parse(template)?
+        .render_to(ctx, &mut out)
+        .map_err(|mut e| {
+            if out.exceeded {
+                e.message = format!("Rendered output exceeds the limit of {} bytes", max_output);
+            }
+            e
+        })?;
+    Ok(out.out)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Render `template` using `ctx`, streaming the output into `out`.
+///
+/// Nothing is written if the template does not parse.
+pub fn render_to(
+    template: &str,
+    ctx: &Context,
+    out: &mut impl std::fmt::Write,
+) -> Result<(), RenderError> {
+    Template::parse(template)?.render_to(ctx, out)
+}
 
-    #[test]
-    fn if_true_emits_then_branch() {
-        let ctx = Context::new().with_bool("backtrace", true);
-        let s = "a{% if backtrace %}b{% else %}c{% endif %}d";
-        assert_eq!(render(s, &ctx).unwrap(), "abd");
+/// Check `template` against `ctx` without producing output, reporting every problem found.
+///
+/// Every `if` branch and loop body is checked once, whatever the conditions and lists hold, so
+/// an identifier missing from a branch `ctx` would not take is still reported. Stray or
+/// malformed tags are recorded and skipped, so one pass lists them all. An unclosed tag ends
+/// parsing, but what was parsed before it is still checked. Errors are in template order.
+pub fn validate(template: &str, ctx: &Context) -> Result<(), Vec<RenderError>> {
+    let mut diag = Diagnostics {
+        collected: Some(Vec::new()),
+    };
+    let (nodes, fatal) = parse_nodes(template, &RenderOptions::default(), &mut diag);
+    // Collecting diagnostics never fail.
+    let _ = check_nodes(&nodes, ctx, &mut Vec::new(), &mut diag);
+    let mut errors = diag.collected.unwrap_or_default();
+    errors.extend(fatal);
+    if errors.is_empty() {
+        return Ok(());
     }
+    errors.sort_by_key(|e| e.byte_offset);
+    Err(errors.into_iter().map(|e| e.locate(template)).collect())
+}
 
-    #[test]
-    fn if_false_emits_else_branch() {
-        let ctx = Context::new().with_bool("backtrace", false);
-        let s = "a{% if backtrace %}b{% else %}c{% endif %}d";
-        assert_eq!(render(s, &ctx).unwrap(), "acd");
+/// Parse `template`, returning what could be parsed and the error that stopped parsing, if any.
+fn parse_nodes<'t>(
+    template: &'t str,
+    opts: &RenderOptions,
+    diag: &mut Diagnostics,
+) -> (Vec<Node<'t>>, Option<RenderError>) {
+    let mut parser = Parser::default();
+    let fatal = parse_into(&mut parser, template, opts, diag).err();
+    (parser.finish(), fatal)
+}
+
+fn parse_into<'t>(
+    parser: &mut Parser<'t>,
+    template: &'t str,
+    opts: &RenderOptions,
+    diag: &mut Diagnostics,
+) -> Result<(), RenderError> {
+    let delimiters = [
+        opts.ctrl_open,
+        opts.ctrl_close,
+        opts.expr_open,
+        opts.expr_close,
+        opts.comment_open,
+        opts.comment_close,
+    ];
+    if delimiters.iter().any(|d| d.is_empty()) {
+        return Err(RenderError::at("Empty delimiter in RenderOptions", 0));
     }
 
-    #[test]
-    fn if_without_else() {
-        let ctx = Context::new().with_bool("x", false);
-        let s = "a{% if x %}b{% endif %}c";
-        assert_eq!(render(s, &ctx).unwrap(), "ac");
+    let mut i = 0;
+    while i < template.len() {
+        let rest = &template[i..];
+        // `min_by_key` keeps the first of equal positions, so the order here is the tie-break.
+        let open = [
+            (TagKind::Ctrl, opts.ctrl_open),
+            (TagKind::Expr, opts.expr_open),
+            (TagKind::Comment, opts.comment_open),
+        ]
+        .into_iter()
+        .filter_map(|(kind, delim)| rest.find(delim).map(|pos| (pos, kind, delim.len())))
+        .min_by_key(|&(pos, _, _)| pos);
+
+        let Some((open, kind, open_len)) = open else {
+            parser.push(Node::Text {
+                text: rest,
+                offset: i,
+            });
+            break;
+        };
+
+        let trim_before = rest[open + open_len..].starts_with('-');
+        let text = if trim_before {
+            trim_line_end(&rest[..open])
+        } else {
+            &rest[..open]
+        };
+        if !text.is_empty() {
+            parser.push(Node::Text { text, offset: i });
+        }
+        i += open;
+
+        let rest = &template[i..];
+        let (close_delim, unclosed) = match kind {
+            TagKind::Ctrl => (opts.ctrl_close, "Unclosed template tag"),
+            TagKind::Expr => (opts.expr_close, "Unclosed template expression"),
+            TagKind::Comment => (opts.comment_close, "Unclosed template comment"),
+        };
+        let close = rest[open_len..]
+            .find(close_delim)
+            .map(|c| c + open_len)
+            .ok_or_else(|| RenderError::at(unclosed, i))?;
+        let (tag, trim_after) = tag_inner(rest, open_len, close);
+        let tag_offset = i;
+        i += close + close_delim.len();
+        if trim_after {
+            i += line_start_len(&template[i..]);
+        }
+
+        match kind {
+            TagKind::Comment => {}
+            TagKind::Expr => match parse_expr(tag, tag_offset) {
+                Ok(node) => parser.push(node),
+                Err(e) => diag.report(e)?,
+            },
+            TagKind::Ctrl => parse_ctrl(parser, tag, tag_offset, diag)?,
+        }
     }
 
-    #[test]
-    fn nesting_works() {
-        let ctx = Context::new().with_bool("a", true).with_bool("b", false);
-        let s = "{% if a %}A{% if b %}B{% else %}C{% endif %}D{% endif %}";
-        assert_eq!(render(s, &ctx).unwrap(), "ACD");
+    if let Some(block) = parser.open.last() {
+        let message = match block {
+            Open::For { .. } => "Unclosed {% for %} block(s)",
+            Open::If { .. } => "Unclosed {% if %} block(s)",
+        };
+        return Err(RenderError::at(message, template.len()));
     }
 
-    #[test]
-    fn unknown_identifier_errors() {
-        let ctx = Context::new();
-        let err = render("{% if nope %}x{% endif %}", &ctx).unwrap_err();
-        assert!(err.message.contains("Unknown boolean identifier"));
+    Ok(())
+}
+
+/// Handle the contents of one `{% %}` tag.
+fn parse_ctrl<'t>(
+    parser: &mut Parser<'t>,
+    tag: &'t str,
+    offset: usize,
+    diag: &mut Diagnostics,
+) -> Result<(), RenderError> {
+    match tag {
+        "else" => match parser.open.last_mut() {
+            Some(Open::If {
+                otherwise: Some(_), ..
+            }) => diag.report(RenderError::at(
+                "Duplicate {% else %} in the same {% if %} block",
+                offset,
+            )),
+            Some(Open::If { otherwise, .. }) => {
+                *otherwise = Some(Vec::new());
+                Ok(())
+            }
+            _ => diag.report(RenderError::at(
+                "{% else %} without matching {% if ... %}",
+                offset,
+            )),
+        },
+        "endif" => match parser.open.last() {
+            Some(Open::If { .. }) => {
+                parser.close_innermost();
+                Ok(())
+            }
+            _ => diag.report(RenderError::at(
+                "{% endif %} without matching {% if ... %}",
+                offset,
+            )),
+        },
+        "endfor" => match parser.open.last() {
+            Some(Open::For { .. }) => {
+                parser.close_innermost();
+                Ok(())
+            }
+            _ => diag.report(RenderError::at(
+                "{% endfor %} without matching {% for ... %}",
+                offset,
+            )),
+        },
+        _ => {
+            if let Some(spec) = tag.strip_prefix("for ") {
+                let parts: Vec<&str> = spec.split_whitespace().collect();
+                let (var, list) = match parts.as_slice() {
+                    [var, "in", list] => (*var, Some(*list)),
+                    _ => {
+                        diag.report(RenderError::at(
+                            format!(
+                                "Malformed {{% for %}} tag, expected `for <var> in <list>`: {}",
+                                tag
+                            ),
+                            offset,
+                        ))?;
+                        ("", None)
+                    }
+                };
+                parser.open.push(Open::For {
+                    var,
+                    list,
+                    body: Vec::new(),
+                    offset,
+                });
+                return Ok(());
+            }
+
+            if let Some(spec) = tag.strip_prefix("set ") {
+                let Some((name, value)) = parse_set(spec) else {
+                    return diag.report(RenderError::at(
+                        format!(
+                            "Malformed {{% set %}} tag, expected `set <name> = \"literal\"` \
+                             or `set <name> = <ident>`: {}",
+                            tag
+                        ),
+                        offset,
+                    ));
+                };
+                parser.push(Node::Set {
+                    name,
+                    value,
+                    offset,
+                });
+                return Ok(());
+            }
+
+            if let Some(cond) = tag.strip_prefix("if ") {
+                // A malformed condition is recorded and treated as false.
+                let cond = match parse_condition(cond, offset) {
+                    Ok(terms) => terms,
+                    Err(e) => {
+                        diag.report(e)?;
+                        Vec::new()
+                    }
+                };
+                parser.open.push(Open::If {
+                    cond,
+                    then: Vec::new(),
+                    otherwise: None,
+                    offset,
+                });
+                return Ok(());
+            }
+
+            diag.report(RenderError::at(
+                format!("Unknown template tag: {{% {} %}}", tag),
+                offset,
+            ))
+        }
     }
+}
 
-    #[test]
-    fn string_interpolation() {
-        let ctx = Context::new().with_str("MEMORY_ORIGIN", "0x80000000");
-        let s = "ORIGIN={{ MEMORY_ORIGIN }}";
-        assert_eq!(render(s, &ctx).unwrap(), "ORIGIN=0x80000000");
+/// `set` and loop-variable bindings in scope, innermost last.
+type Scope<'a> = Vec<(&'a str, &'a str)>;
+
+/// Resolve a string identifier, preferring the innermost loop variable or `set` of that name.
+fn lookup_str<'a>(scope: &Scope<'a>, ctx: &'a Context, name: &str) -> Option<&'a str> {
+    scope
+        .iter()
+        .rev()
+        .find(|(bound, _)| *bound == name)
+        .map(|&(_, value)| value)
+        .or_else(|| ctx.get_str(name))
+}
+
+/// Unwrap the value found for string identifier `name`; an unknown one is `""` after reporting.
+fn or_unknown_str<'a>(
+    value: Option<&'a str>,
+    name: &str,
+    ctx: &Context,
+    offset: usize,
+    diag: &mut Diagnostics,
+) -> Result<&'a str, RenderError> {
+    match value {
+        Some(value) => Ok(value),
+        None if ctx.lenient => Ok(""),
+        None => {
+            diag.report(RenderError::at(
+                format!("Unknown string identifier in template: {}", name),
+                offset,
+            ))?;
+            Ok("")
+        }
+    }
+}
+
+/// Evaluate a parsed condition. Every term is evaluated; an empty condition is false.
+fn eval_condition(
+    terms: &[Term],
+    scope: &Scope,
+    ctx: &Context,
+    offset: usize,
+    diag: &mut Diagnostics,
+) -> Result<bool, RenderError> {
+    let mut result: Option<bool> = None;
+    for term in terms {
+        let value = match term.test {
+            Test::Bool(ident) => match ctx.get_bool(ident) {
+                Some(value) => value,
+                None if ctx.lenient => false,
+                None => {
+                    diag.report(RenderError::at(
+                        format!("Unknown boolean identifier in template: {}", ident),
+                        offset,
+                    ))?;
+                    false
+                }
+            },
+            Test::Compare {
+                ident,
+                literal,
+                equal,
+            } => {
+                let value = lookup_str(scope, ctx, ident);
+                (or_unknown_str(value, ident, ctx, offset, diag)? == literal) == equal
+            }
+        } != term.negate;
+
+        result = Some(match result {
+            None => value,
+            Some(acc) if term.and => acc && value,
+            Some(acc) => acc || value,
+        });
+    }
+    Ok(result.unwrap_or(false))
+}
+
+/// Write `s` to `out`, reporting a writer failure at `offset`.
+fn emit(out: &mut impl std::fmt::Write, s: &str, offset: usize) -> Result<(), RenderError> {
+    out.write_str(s)
+        .map_err(|_| RenderError::at("Failed to write rendered output", offset))
+}
+
+/// Render `nodes`; bindings made by `set` among them are dropped again at the end.
+fn render_nodes<'a>(
+    nodes: &'a [Node<'a>],
+    ctx: &'a Context,
+    scope: &mut Scope<'a>,
+    out: &mut impl std::fmt::Write,
+    diag: &mut Diagnostics,
+) -> Result<(), RenderError> {
+    let depth = scope.len();
+    for node in nodes {
+        match node {
+            Node::Text { text, offset } => emit(out, text, *offset)?,
+            Node::Expr {
+                ident,
+                filters,
+                offset,
+            } => {
+                let mut value = lookup_str(scope, ctx, ident);
+                for filter in filters {
+                    value = filter.apply(value);
+                }
+                emit(
+                    out,
+                    or_unknown_str(value, ident, ctx, *offset, diag)?,
+                    *offset,
+                )?;
+            }
+            Node::If {
+                cond,
+                then,
+                otherwise,
+                offset,
+            } => {
+                let branch = if eval_condition(cond, scope, ctx, *offset, diag)? {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(branch, ctx, scope, out, diag)?;
+            }
+            Node::For {
+                var,
+                list,
+                body,
+                offset,
+            } => {
+                let items = match ctx.get_list(list) {
+                    Some(items) => items,
+                    None if ctx.lenient => &[],
+                    None => {
+                        diag.report(RenderError::at(
+                            format!("Unknown list identifier in template: {}", list),
+                            *offset,
+                        ))?;
+                        &[]
+                    }
+                };
+                for item in items {
+                    scope.push((var, item));
+                    render_nodes(body, ctx, scope, out, diag)?;
+                    scope.pop();
+                }
+            }
+            Node::Set {
+                name,
+                value,
+                offset,
+            } => {
+                let value = match value {
+                    Ok(literal) => literal,
+                    Err(ident) => {
+                        or_unknown_str(lookup_str(scope, ctx, ident), ident, ctx, *offset, diag)?
+                    }
+                };
+                scope.push((name, value));
+            }
+        }
+    }
+    scope.truncate(depth);
+    Ok(())
+}
+
+/// Report every identifier in `nodes` that `ctx` cannot resolve, walking both arms of each `if`
+/// and each loop body once. Loop variables and `set` names are bound to `""` while in scope.
+fn check_nodes<'a>(
+    nodes: &'a [Node<'a>],
+    ctx: &'a Context,
+    scope: &mut Scope<'a>,
+    diag: &mut Diagnostics,
+) -> Result<(), RenderError> {
+    let depth = scope.len();
+    for node in nodes {
+        match node {
+            Node::Text { .. } => {}
+            Node::Expr {
+                ident,
+                filters,
+                offset,
+            } => {
+                let mut value = lookup_str(scope, ctx, ident);
+                for filter in filters {
+                    value = filter.apply(value);
+                }
+                or_unknown_str(value, ident, ctx, *offset, diag)?;
+            }
+            Node::If {
+                cond,
+                then,
+                otherwise,
+                offset,
+            } => {
+                eval_condition(cond, scope, ctx, *offset, diag)?;
+                check_nodes(then, ctx, scope, diag)?;
+                check_nodes(otherwise, ctx, scope, diag)?;
+            }
+            Node::For {
+                var,
+                list,
+                body,
+                offset,
+            } => {
+                if ctx.get_list(list).is_none() && !ctx.lenient {
+                    diag.report(RenderError::at(
+                        format!("Unknown list identifier in template: {}", list),
+                        *offset,
+                    ))?;
+                }
+                scope.push((var, ""));
+                check_nodes(body, ctx, scope, diag)?;
+                scope.pop();
+            }
+            Node::Set {
+                name,
+                value,
+                offset,
+            } => {
+                if let Err(ident) = value {
+                    or_unknown_str(lookup_str(scope, ctx, ident), ident, ctx, *offset, diag)?;
+                }
+                scope.push((name, ""));
+            }
+        }
+    }
+    scope.truncate(depth);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_true_emits_then_branch() {
+        let ctx = Context::new().with_bool("backtrace", true);
+        let s = "a{% if backtrace %}b{% else %}c{% endif %}d";
+        assert_eq!(render(s, &ctx).unwrap(), "abd");
+    }
+
+    #[test]
+    fn if_false_emits_else_branch() {
+        let ctx = Context::new().with_bool("backtrace", false);
+        let s = "a{% if backtrace %}b{% else %}c{% endif %}d";
+        assert_eq!(render(s, &ctx).unwrap(), "acd");
+    }
+
+    #[test]
+    fn if_without_else() {
+        let ctx = Context::new().with_bool("x", false);
+        let s = "a{% if x %}b{% endif %}c";
+        assert_eq!(render(s, &ctx).unwrap(), "ac");
+    }
+
+    #[test]
+    fn nesting_works() {
+        let ctx = Context::new().with_bool("a", true).with_bool("b", false);
+        let s = "{% if a %}A{% if b %}B{% else %}C{% endif %}D{% endif %}";
+        assert_eq!(render(s, &ctx).unwrap(), "ACD");
+    }
+
+    #[test]
+    fn unknown_identifier_errors() {
+        let ctx = Context::new();
+        let err = render("{% if nope %}x{% endif %}", &ctx).unwrap_err();
+        assert!(err.message.contains("Unknown boolean identifier"));
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let ctx = Context::new().with_str("MEMORY_ORIGIN", "0x80000000");
+        let s = "ORIGIN={{ MEMORY_ORIGIN }}";
+        assert_eq!(render(s, &ctx).unwrap(), "ORIGIN=0x80000000");
+    }
+
+    fn names() -> Vec<String> {
+        vec!["a".to_string(), "b".to_string(), "c".to_string()]
+    }
+
+    #[test]
+    fn for_loop_over_list() {
+        let ctx = Context::new().with_list("names", names());
+        let s = "{% for n in names %}PROVIDE({{ n }});{% endfor %}";
+        assert_eq!(
+            render(s, &ctx).unwrap(),
+            "PROVIDE(a);PROVIDE(b);PROVIDE(c);"
+        );
+    }
+
+    #[test]
+    fn for_loop_empty_list_emits_nothing() {
+        let ctx = Context::new().with_list("names", Vec::new());
+        let s = "x{% for n in names %}{{ n }}{% endfor %}y";
+        assert_eq!(render(s, &ctx).unwrap(), "xy");
+    }
+
+    #[test]
+    fn for_loop_unknown_list_errors() {
+        let ctx = Context::new();
+        let err = render("{% for n in nope %}{% endfor %}", &ctx).unwrap_err();
+        assert!(err.message.contains("Unknown list identifier"));
+        assert_eq!(err.byte_offset, 0);
+    }
+
+    #[test]
+    fn for_loop_variable_is_scoped_to_body() {
+        let ctx = Context::new().with_list("names", names());
+        let err = render("{% for n in names %}{% endfor %}{{ n }}", &ctx).unwrap_err();
+        assert!(err.message.contains("Unknown string identifier"));
+    }
+
+    #[test]
+    fn for_inside_if() {
+        let ctx = Context::new()
+            .with_list("names", names())
+            .with_bool("on", true)
+            .with_bool("off", false);
+        let s = "{% if on %}{% for n in names %}{{ n }}{% endfor %}{% endif %}";
+        assert_eq!(render(s, &ctx).unwrap(), "abc");
+        let s = "{% if off %}{% for n in names %}{{ n }}{% endfor %}{% else %}-{% endif %}";
+        assert_eq!(render(s, &ctx).unwrap(), "-");
+    }
+
+    #[test]
+    fn if_inside_for() {
+        let ctx = Context::new()
+            .with_list("names", names())
+            .with_bool("upper", false)
+            .with_str("SEP", ",");
+        let s =
+            "{% for n in names %}{% if upper %}N{% else %}{{ n }}{% endif %}{{ SEP }}{% endfor %}";
+        assert_eq!(render(s, &ctx).unwrap(), "a,b,c,");
+    }
+
+    #[test]
+    fn nested_for_loops() {
+        let ctx = Context::new()
+            .with_list("xs", vec!["1".to_string(), "2".to_string()])
+            .with_list("ys", vec!["a".to_string(), "b".to_string()]);
+        let s = "{% for x in xs %}{% for y in ys %}{{ x }}{{ y }} {% endfor %}{% endfor %}";
+        assert_eq!(render(s, &ctx).unwrap(), "1a 1b 2a 2b ");
+    }
+
+    #[test]
+    fn mismatched_for_tags_error() {
+        let ctx = Context::new()
+            .with_list("names", names())
+            .with_bool("x", true);
+        assert!(render("{% for n in names %}", &ctx).is_err());
+        assert!(render("{% endfor %}", &ctx).is_err());
+        assert!(render("{% for n in names %}{% endif %}", &ctx).is_err());
+        assert!(render("{% if x %}{% endfor %}", &ctx).is_err());
+        assert!(render("{% for n names %}{% endfor %}", &ctx).is_err());
+    }
+
+    #[test]
+    fn if_string_equality() {
+        let ctx = Context::new().with_str("ARCH", "riscv64");
+        let s = r#"{% if ARCH == "riscv64" %}rv64{% else %}other{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "rv64");
+        let s = r#"{% if ARCH == "riscv32" %}rv32{% else %}other{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "other");
+    }
+
+    #[test]
+    fn if_string_inequality() {
+        let ctx = Context::new().with_str("ARCH", "riscv64");
+        let s = r#"{% if ARCH != "riscv32" %}yes{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "yes");
+        let s = r#"{% if ARCH != "riscv64" %}yes{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "");
+        let s = r#"{% if ARCH != "a==b" %}yes{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "yes");
+    }
+
+    #[test]
+    fn if_comparison_unknown_identifier_errors() {
+        let ctx = Context::new();
+        let err = render(r#"{% if ARCH == "riscv64" %}{% endif %}"#, &ctx).unwrap_err();
+        assert!(err.message.contains("Unknown string identifier"));
+    }
+
+    #[test]
+    fn if_comparison_malformed_errors() {
+        let ctx = Context::new().with_str("ARCH", "riscv64");
+        for cond in [
+            "ARCH == riscv64",
+            r#"ARCH == "riscv64" extra"#,
+            r#"ARCH == "riscv64"#,
+            r#"== "riscv64""#,
+            r#"A B == "x""#,
+        ] {
+            let s = format!("ab{{% if {} %}}{{% endif %}}", cond);
+            let err = render(&s, &ctx).unwrap_err();
+            assert!(err.message.contains("Malformed comparison"), "{}", err);
+            assert_eq!(err.byte_offset, 2);
+        }
+    }
+
+    #[test]
+    fn if_not() {
+        let ctx = Context::new().with_bool("release", false);
+        assert_eq!(
+            render("{% if not release %}dbg{% endif %}", &ctx).unwrap(),
+            "dbg"
+        );
+        let ctx = Context::new().with_bool("release", true);
+        assert_eq!(
+            render("{% if not release %}dbg{% endif %}", &ctx).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn if_and() {
+        let s = "{% if backtrace and std %}y{% else %}n{% endif %}";
+        for (a, b, want) in [
+            (true, true, "y"),
+            (true, false, "n"),
+            (false, true, "n"),
+            (false, false, "n"),
+        ] {
+            let ctx = Context::new().with_bool("backtrace", a).with_bool("std", b);
+            assert_eq!(render(s, &ctx).unwrap(), want);
+        }
+    }
+
+    #[test]
+    fn if_or() {
+        let s = "{% if a or b %}y{% else %}n{% endif %}";
+        for (a, b, want) in [
+            (true, true, "y"),
+            (true, false, "y"),
+            (false, true, "y"),
+            (false, false, "n"),
+        ] {
+            let ctx = Context::new().with_bool("a", a).with_bool("b", b);
+            assert_eq!(render(s, &ctx).unwrap(), want);
+        }
+    }
+
+    #[test]
+    fn if_boolean_ops_are_left_to_right() {
+        // `(a or b) and c`, not `a or (b and c)`.
+        let ctx = Context::new()
+            .with_bool("a", true)
+            .with_bool("b", false)
+            .with_bool("c", false);
+        assert_eq!(
+            render("{% if a or b and c %}y{% endif %}", &ctx).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn if_boolean_ops_with_comparison() {
+        let ctx = Context::new()
+            .with_str("ARCH", "riscv64")
+            .with_bool("std", true);
+        let s = r#"{% if not ARCH == "riscv32" and std %}y{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "y");
+        let s = r#"{% if ARCH == "x and y" or std %}y{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "y");
+    }
+
+    #[test]
+    fn if_boolean_ops_unknown_identifier_errors() {
+        let ctx = Context::new().with_bool("a", false);
+        let err = render("{% if a and nope %}{% endif %}", &ctx).unwrap_err();
+        assert!(err
+            .message
+            .contains("Unknown boolean identifier in template: nope"));
+        let err = render("{% if not nope %}{% endif %}", &ctx).unwrap_err();
+        assert!(err
+            .message
+            .contains("Unknown boolean identifier in template: nope"));
+    }
+
+    #[test]
+    fn if_boolean_ops_malformed_errors() {
+        let ctx = Context::new().with_bool("a", true);
+        for cond in ["a and", "or a", "not", "a and not"] {
+            let s = format!("{{% if {} %}}{{% endif %}}", cond);
+            let err = render(&s, &ctx).unwrap_err();
+            assert!(
+                err.message.contains("Malformed condition"),
+                "{}: {}",
+                cond,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn trim_markers_leave_no_blank_lines() {
+        let s = "a\n{%- if x -%}\nb\n{%- endif -%}\nc\n";
+        let on = Context::new().with_bool("x", true);
+        let off = Context::new().with_bool("x", false);
+        assert_eq!(render(s, &on).unwrap(), "abc\n");
+        assert_eq!(render(s, &off).unwrap(), "ac\n");
+    }
+
+    #[test]
+    fn trim_markers_on_own_lines() {
+        let s = "a\n  {%- if x %}\n  b\n  {%- endif %}\nc\n";
+        let on = Context::new().with_bool("x", true);
+        let off = Context::new().with_bool("x", false);
+        assert_eq!(render(s, &on).unwrap(), "a\n  b\nc\n");
+        assert_eq!(render(s, &off).unwrap(), "a\nc\n");
+    }
+
+    #[test]
+    fn trim_only_removes_one_line() {
+        let ctx = Context::new().with_str("v", "x");
+        let s = "a\n\n{{- v -}}\n\nb";
+        assert_eq!(render(s, &ctx).unwrap(), "a\nx\nb");
+    }
+
+    #[test]
+    fn trim_markers_in_for_loop() {
+        let ctx = Context::new().with_list("xs", vec!["1".into(), "2".into()]);
+        let s = "{% for x in xs -%}\n{{ x }}\n{% endfor -%}\nend";
+        assert_eq!(render(s, &ctx).unwrap(), "1\n2\nend");
+    }
+
+    #[test]
+    fn unknown_expr_in_false_branch_is_skipped() {
+        let ctx = Context::new().with_bool("x", false);
+        let s = "a{% if x %}{{ nope }}{% else %}b{% endif %}c";
+        assert_eq!(render(s, &ctx).unwrap(), "abc");
+    }
+
+    #[test]
+    fn unknown_expr_in_true_branch_errors_at_expr() {
+        let ctx = Context::new().with_bool("x", true);
+        let s = "a{% if x %}{{ nope }}{% endif %}";
+        let err = render(s, &ctx).unwrap_err();
+        assert!(err
+            .message
+            .contains("Unknown string identifier in template: nope"));
+        assert_eq!(err.byte_offset, s.find("{{").unwrap());
+    }
+
+    #[test]
+    fn unclosed_expr_in_false_branch_errors() {
+        let ctx = Context::new().with_bool("x", false);
+        let s = "a{% if x %}{{ nope {% endif %}";
+        let err = render(s, &ctx).unwrap_err();
+        assert!(err.message.contains("Unclosed template expression"));
+        assert_eq!(err.byte_offset, s.find("{{").unwrap());
+    }
+
+    #[test]
+    fn default_filter_absent_ident() {
+        let ctx = Context::new();
+        let s = "a{{ EXTRA | default(\"\") }}b{{ X|default(\"x y\") }}";
+        assert_eq!(render(s, &ctx).unwrap(), "abx y");
+    }
+
+    #[test]
+    fn default_filter_present_ident() {
+        let ctx = Context::new().with_str("EXTRA", "-v");
+        let s = "{{ EXTRA | default(\"none\") }}";
+        assert_eq!(render(s, &ctx).unwrap(), "-v");
+    }
+
+    #[test]
+    fn default_filter_literal_may_contain_pipe() {
+        let ctx = Context::new();
+        assert_eq!(render("{{ X | default(\"a|b\") }}", &ctx).unwrap(), "a|b");
+    }
+
+    #[test]
+    fn filter_errors_even_when_ident_present() {
+        let ctx = Context::new().with_str("X", "x");
+        let err = render("{{ X | upper }}", &ctx).unwrap_err();
+        assert!(err.message.contains("Unknown filter in template: upper"));
+        for s in [
+            "{{ X | default }}",
+            "{{ X | default(x) }}",
+            "{{ X | default(\"x\" }}",
+        ] {
+            let err = render(s, &ctx).unwrap_err();
+            assert!(err.message.contains("Malformed filter"), "{}: {}", s, err);
+        }
+        let err = render("{{ | default(\"x\") }}", &ctx).unwrap_err();
+        assert!(err.message.contains("Empty identifier"));
+    }
+
+    fn hex_address(s: &str) -> Result<(), String> {
+        let digits = s
+            .strip_prefix("0x")
+            .ok_or_else(|| format!("expected 0x-prefixed address: {}", s))?;
+        u64::from_str_radix(digits, 16)
+            .map(|_| ())
+            .map_err(|e| format!("invalid address {}: {}", s, e))
+    }
+
+    #[test]
+    fn insert_str_validated_accepts_valid() {
+        let mut ctx = Context::new();
+        ctx.insert_str_validated("MEMORY_ORIGIN", "0x80000000", hex_address)
+            .unwrap();
+        assert_eq!(render("{{ MEMORY_ORIGIN }}", &ctx).unwrap(), "0x80000000");
+    }
+
+    #[test]
+    fn insert_str_validated_rejects_garbage() {
+        let mut ctx = Context::new().with_str("MEMORY_ORIGIN", "0x1000");
+        let err = ctx
+            .insert_str_validated("MEMORY_ORIGIN", "0xnope", hex_address)
+            .unwrap_err();
+        assert!(err.contains("invalid address 0xnope"));
+        assert!(ctx
+            .insert_str_validated("OTHER", "80000000", hex_address)
+            .is_err());
+        // Rejected values are not stored.
+        assert_eq!(render("{{ MEMORY_ORIGIN }}", &ctx).unwrap(), "0x1000");
+        assert!(render("{{ OTHER }}", &ctx).is_err());
+    }
+
+    #[test]
+    fn render_to_matches_render() {
+        let ctx = Context::new()
+            .with_bool("x", true)
+            .with_str("v", "1")
+            .with_list("xs", vec!["a".into(), "b".into()]);
+        let s = "{% if x %}{{ v }}{% endif %}{% for y in xs %}[{{ y }}]{% endfor %}";
+        let mut out = String::new();
+        render_to(s, &ctx, &mut out).unwrap();
+        assert_eq!(out, render(s, &ctx).unwrap());
+    }
+
+    #[test]
+    fn cleared_context_can_be_reused() {
+        let tpl = Template::parse("{% if on %}{{ name }}{% endif %}").unwrap();
+        let mut ctx = Context::new().with_bool("on", true).with_str("name", "a");
+        let mut out = String::new();
+        tpl.render_to(&ctx, &mut out).unwrap();
+
+        ctx.clear();
+        assert!(tpl.render(&ctx).is_err());
+
+        ctx.insert_bool("on", true);
+        ctx.insert_str("name", "b");
+        out.clear();
+        tpl.render_to(&ctx, &mut out).unwrap();
+        assert_eq!(out, "b");
+
+        ctx.clear_strs();
+        assert!(tpl.render(&ctx).is_err());
+        ctx.clear_bools();
+        ctx.insert_bool("on", false);
+        assert_eq!(tpl.render(&ctx).unwrap(), "");
+    }
+
+    /// Accepts a fixed number of bytes, then fails.
+    struct Limited {
+        buf: String,
+        cap: usize,
+    }
+
+    impl std::fmt::Write for Limited {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            if self.buf.len() + s.len() > self.cap {
+                return Err(std::fmt::Error);
+            }
+            self.buf.push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn render_to_reports_writer_failure() {
+        let ctx = Context::new().with_str("v", "value");
+        let s = "abc{{ v }}def";
+        let mut out = Limited {
+            buf: String::new(),
+            cap: 5,
+        };
+        let err = render_to(s, &ctx, &mut out).unwrap_err();
+        assert!(err.message.contains("Failed to write rendered output"));
+        assert_eq!(err.byte_offset, 3);
+        assert_eq!(out.buf, "abc");
+    }
+
+    #[test]
+    fn render_to_keeps_error_offsets() {
+        let ctx = Context::new().with_bool("x", true);
+        let s = "ab{% if x %}";
+        let mut out = String::new();
+        let err = render_to(s, &ctx, &mut out).unwrap_err();
+        assert_eq!(err.byte_offset, s.len());
+        // Structural errors are found by the parse, before anything is written.
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn error_reports_line_and_column() {
+        let ctx = Context::new();
+        let s = "line one\n  {{ nope }}\nline three";
+        let err = render(s, &ctx).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.byte_offset, 11);
+        assert!(err.to_string().ends_with(" at 2:3 (byte 11)"), "{}", err);
+    }
+
+    #[test]
+    fn error_at_eof_line_and_column() {
+        let ctx = Context::new().with_bool("x", true);
+
+        // No trailing newline: EOF is just past the last char of the last line.
+        let err = render("a\nb{% if x %}", &ctx).unwrap_err();
+        assert!(err.message.contains("Unclosed {% if %}"));
+        assert_eq!((err.line, err.column), (2, 12));
+
+        // Trailing newline: EOF starts a new, empty line.
+        let err = render("a\n{% if x %}\n", &ctx).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+
+        let err = render("{% endif %}", &ctx).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+    }
+
+    #[test]
+    fn error_column_counts_chars() {
+        let ctx = Context::new();
+        let err = render("é{{ nope }}", &ctx).unwrap_err();
+        assert_eq!(err.byte_offset, 2);
+        assert_eq!((err.line, err.column), (1, 2));
+    }
+
+    fn angle_options() -> RenderOptions<'static> {
+        RenderOptions {
+            ctrl_open: "<%",
+            ctrl_close: "%>",
+            expr_open: "<<",
+            expr_close: ">>",
+            comment_open: "<#",
+            comment_close: "#>",
+        }
+    }
+
+    #[test]
+    fn custom_delimiters_render() {
+        let ctx = Context::new().with_bool("x", true).with_str("v", "1");
+        let s = "a<% if x %><< v >><% else %>no<% endif %>b";
+        assert_eq!(render_with(s, &ctx, &angle_options()).unwrap(), "a1b");
+    }
+
+    #[test]
+    fn custom_delimiters_pass_default_syntax_through() {
+        let ctx = Context::new().with_str("v", "1");
+        let s = "echo \"{{ HOME }}\"\n{% raw %}{%- endif %}\n<<- v ->>\n";
+        assert_eq!(
+            render_with(s, &ctx, &angle_options()).unwrap(),
+            "echo \"{{ HOME }}\"\n{% raw %}{%- endif %}1"
+        );
+    }
+
+    #[test]
+    fn custom_delimiters_errors() {
+        let ctx = Context::new();
+        let err = render_with("a<% if x", &ctx, &angle_options()).unwrap_err();
+        assert!(err.message.contains("Unclosed template tag"));
+        assert_eq!(err.byte_offset, 1);
+
+        let opts = RenderOptions {
+            expr_close: "",
+            ..RenderOptions::default()
+        };
+        let err = render_with("a", &ctx, &opts).unwrap_err();
+        assert!(err.message.contains("Empty delimiter"));
+    }
+
+    #[test]
+    fn close_delimiter_is_searched_after_open() {
+        let ctx = Context::new();
+        let err = render("{%}", &ctx).unwrap_err();
+        assert!(err.message.contains("Unclosed template tag"));
+    }
+
+    #[test]
+    fn lenient_mode_renders_unknown_identifiers() {
+        let s = "[{% if nope %}yes{% else %}no{% endif %}|{{ EXTRA }}|\
+                 {% if ARCH == \"\" %}empty{% endif %}|{% for x in xs %}{{ x }}{% endfor %}]";
+
+        let strict = Context::new();
+        let err = render(s, &strict).unwrap_err();
+        assert!(err
+            .message
+            .contains("Unknown boolean identifier in template: nope"));
+
+        let lenient = Context::new().with_lenient(true);
+        assert_eq!(render(s, &lenient).unwrap(), "[no||empty|]");
+    }
+
+    #[test]
+    fn lenient_mode_each_kind_errors_when_strict() {
+        let strict = Context::new();
+        for s in [
+            "{{ EXTRA }}",
+            "{% if ARCH == \"x\" %}{% endif %}",
+            "{% for x in xs %}{% endfor %}",
+        ] {
+            assert!(render(s, &strict).is_err(), "{}", s);
+            assert!(
+                render(s, &strict.clone().with_lenient(true)).is_ok(),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn lenient_mode_keeps_values_and_syntax_errors() {
+        let ctx = Context::new()
+            .with_lenient(true)
+            .with_bool("x", true)
+            .with_str("v", "set");
+        assert_eq!(
+            render("{% if x %}{{ v }}{% endif %}{{ w | default(\"d\") }}", &ctx).unwrap(),
+            "setd"
+        );
+        assert!(render("{% if x %}", &ctx).is_err());
+        assert!(render("{{ v | upper }}", &ctx).is_err());
+    }
+
+    #[test]
+    fn validate_ok() {
+        let ctx = Context::new().with_bool("x", true).with_str("v", "1");
+        assert!(validate("{% if x %}{{ v }}{% endif %}", &ctx).is_ok());
+    }
+
+    #[test]
+    fn validate_collects_all_unknown_identifiers() {
+        let ctx = Context::new().with_str("ORIGIN", "0x80000000");
+        let s = "{{ ORIGIN }} {{ LENGTH }}\n{% if backtrace %}x{% endif %}\n\
+                 {% if ARCH == \"riscv64\" %}{% endif %}{{ STACK }}";
+        let errors = validate(s, &ctx).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Unknown string identifier in template: LENGTH",
+                "Unknown boolean identifier in template: backtrace",
+                "Unknown string identifier in template: ARCH",
+                "Unknown string identifier in template: STACK",
+            ]
+        );
+        assert_eq!(errors[0].byte_offset, s.find("{{ LENGTH").unwrap());
+        assert_eq!((errors[1].line, errors[1].column), (2, 1));
+
+        // render stops at the first one.
+        assert_eq!(render(s, &ctx).unwrap_err().message, messages[0]);
+    }
+
+    #[test]
+    fn validate_checks_branches_not_taken() {
+        let ctx = Context::new()
+            .with_bool("x", false)
+            .with_str("v", "1")
+            .with_list("empty", Vec::new());
+        let s = "{% if x %}{{ missing }}{% set s = v %}{{ s }}{% else %}{{ v }}{% endif %}\
+                 {% for i in empty %}{{ i }}{{ gone }}{% endfor %}{{ s }}";
+        // Rendering takes the else branch and never enters the loop, so only `s` fails.
+        assert_eq!(
+            render(s, &ctx).unwrap_err().message,
+            "Unknown string identifier in template: s"
+        );
+
+        let errors = validate(s, &ctx).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Unknown string identifier in template: missing",
+                "Unknown string identifier in template: gone",
+                "Unknown string identifier in template: s",
+            ]
+        );
+        assert_eq!(errors[0].byte_offset, s.find("{{ missing").unwrap());
+    }
+
+    #[test]
+    fn validate_recovers_from_structural_errors() {
+        let ctx = Context::new().with_bool("x", true);
+        let s = "{% else %}{% endif %}{% bogus %}{% for a b %}{{ a }}{% endfor %}\
+                 {% if x %}{% else %}{% else %}{% endif %}{{ | nope(1) }}{% endfor %}";
+        let errors = validate(s, &ctx).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages.len(), 7, "{:?}", messages);
+        assert!(messages[0].contains("{% else %} without matching"));
+        assert!(messages[1].contains("{% endif %} without matching"));
+        assert!(messages[2].contains("Unknown template tag"));
+        assert!(messages[3].contains("Malformed {% for %}"));
+        assert!(messages[4].contains("Duplicate {% else %}"));
+        assert!(messages[5].contains("Empty identifier"));
+        assert!(messages[6].contains("{% endfor %} without matching"));
+    }
+
+    #[test]
+    fn validate_reports_loop_errors_once_and_fatal_last() {
+        let ctx = Context::new().with_list("xs", vec!["a".into(), "b".into()]);
+        let s = "{% for x in xs %}{{ nope }}{% endfor %}{% if ys %}";
+        let errors = validate(s, &ctx).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Unknown string identifier in template: nope",
+                "Unknown boolean identifier in template: ys",
+                "Unclosed {% if %} block(s)",
+            ]
+        );
+        assert_eq!(errors[2].byte_offset, s.len());
+    }
+
+    #[test]
+    fn comments_are_dropped_in_taken_and_skipped_branches() {
+        let s = "a{# top #}{% if x %}b{# in if {{ nope }} {% bogus %} #}\
+                 {% else %}c{# in else #}{% endif %}d";
+        let on = Context::new().with_bool("x", true);
+        let off = Context::new().with_bool("x", false);
+        assert_eq!(render(s, &on).unwrap(), "abd");
+        assert_eq!(render(s, &off).unwrap(), "acd");
+    }
+
+    #[test]
+    fn comment_trim_markers() {
+        let ctx = Context::new();
+        let s = "a\n{#- note -#}\nb";
+        assert_eq!(render(s, &ctx).unwrap(), "ab");
+    }
+
+    #[test]
+    fn unclosed_comment_errors_at_open() {
+        let ctx = Context::new().with_bool("x", false);
+        let s = "ab{% if x %}{# never closed {% endif %}";
+        let err = render(s, &ctx).unwrap_err();
+        assert_eq!(err.message, "Unclosed template comment");
+        assert_eq!(err.byte_offset, s.find("{#").unwrap());
+    }
+
+    #[test]
+    fn custom_comment_delimiters() {
+        let ctx = Context::new();
+        let s = "{# kept #}<# dropped #>x";
+        assert_eq!(
+            render_with(s, &ctx, &angle_options()).unwrap(),
+            "{# kept #}x"
+        );
+    }
+
+    #[test]
+    fn set_literal_and_reference() {
+        let ctx = Context::new().with_str("ARCH", "riscv64");
+        let s = "{% set A = \"x,y\" %}{% set B = ARCH %}{{ A }} {{ B }}";
+        assert_eq!(render(s, &ctx).unwrap(), "x,y riscv64");
+    }
+
+    #[test]
+    fn set_redefinition_and_shadowing() {
+        let ctx = Context::new().with_str("V", "ctx");
+        let s = "{{ V }}{% set V = \"one\" %}{{ V }}{% set V = \"two\" %}{{ V }}";
+        assert_eq!(render(s, &ctx).unwrap(), "ctxonetwo");
+        let s = r#"{% if V == "one" %}yes{% else %}no{% endif %}{% set V = "one" %}
+{%- if V == "one" %}yes{% else %}no{% endif %}"#;
+        assert_eq!(render(s, &ctx).unwrap(), "noyes");
+    }
+
+    #[test]
+    fn set_is_scoped_to_enclosing_block() {
+        let ctx = Context::new()
+            .with_bool("t", true)
+            .with_bool("f", false)
+            .with_str("V", "outer");
+        let s = "{% if t %}{% set V = \"in\" %}{{ V }}{% endif %} {{ V }}";
+        assert_eq!(render(s, &ctx).unwrap(), "in outer");
+
+        // A set in the branch that is not taken has no effect, in either branch.
+        let s = "{% if f %}{% set V = \"then\" %}{% else %}{{ V }}{% endif %}";
+        assert_eq!(render(s, &ctx).unwrap(), "outer");
+        let s =
+            "{% if t %}{% set V = \"then\" %}{% else %}{% set V = \"else\" %}{% endif %}{{ V }}";
+        assert_eq!(render(s, &ctx).unwrap(), "outer");
+        let s = "{% if t %}{% set V = \"then\" %}{% else %}{{ V }}{% endif %}";
+        assert_eq!(render(s, &ctx).unwrap(), "");
+    }
+
+    #[test]
+    fn set_in_for_loop_is_per_iteration() {
+        let ctx = Context::new().with_list("xs", vec!["a".into(), "b".into()]);
+        let s = "{% for x in xs %}{{ P | default(\"-\") }}{% set P = x %}{{ P }}{% endfor %}";
+        assert_eq!(render(s, &ctx).unwrap(), "-a-b");
+        let s = "{% set x = \"out\" %}{% for x in xs %}{{ x }}{% endfor %}{{ x }}";
+        assert_eq!(render(s, &ctx).unwrap(), "about");
+    }
+
+    #[test]
+    fn set_errors() {
+        let ctx = Context::new();
+        for s in [
+            "{% set X %}",
+            "{% set = \"v\" %}",
+            "{% set X Y = \"v\" %}",
+            "{% set X = a b %}",
+            "{% set X == \"v\" %}",
+        ] {
+            let err = render(s, &ctx).unwrap_err();
+            assert!(
+                err.message.contains("Malformed {% set %}"),
+                "{}: {}",
+                s,
+                err
+            );
+        }
+        let err = render("{% set X = NOPE %}", &ctx).unwrap_err();
+        assert!(err
+            .message
+            .contains("Unknown string identifier in template: NOPE"));
+        assert!(render("{% set X = NOPE %}", &ctx.clone().with_lenient(true)).is_ok());
+    }
+
+    #[test]
+    fn template_renders_with_many_contexts() {
+        let tpl = Template::parse(
+            "{% for n in names %}{% if upper %}N{% else %}{{ n }}{% endif %}{% endfor %}\
+             {{ SEP | default(\";\") }}",
+        )
+        .unwrap();
+        let ctx = Context::new()
+            .with_list("names", names())
+            .with_bool("upper", false);
+        assert_eq!(tpl.render(&ctx).unwrap(), "abc;");
+        let ctx = ctx.with_bool("upper", true).with_str("SEP", "!");
+        assert_eq!(tpl.render(&ctx).unwrap(), "NNN!");
+
+        let err = tpl.render(&Context::new()).unwrap_err();
+        assert!(err.message.contains("Unknown list identifier"));
+    }
+
+    #[test]
+    fn template_parse_reports_structural_errors() {
+        for (s, message) in [
+            ("a\n{% else %}", "{% else %} without matching"),
+            ("{% if x %}", "Unclosed {% if %}"),
+            ("{{ v", "Unclosed template expression"),
+            ("{% bogus %}", "Unknown template tag"),
+            ("{% if a and %}{% endif %}", "Malformed condition"),
+            ("{% if f %}{{ v | upper }}{% endif %}", "Unknown filter"),
+        ] {
+            let err = Template::parse(s).unwrap_err();
+            assert!(err.message.contains(message), "{}: {}", s, err);
+        }
+        let err = Template::parse("a\n{% else %}").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+    }
+
+    #[test]
+    fn structural_errors_win_over_unknown_identifiers() {
+        let ctx = Context::new();
+        let err = render("{{ nope }}{% endif %}", &ctx).unwrap_err();
+        assert!(err.message.contains("{% endif %} without matching"));
+    }
+
+    #[test]
+    fn skipped_branches_are_not_looked_up() {
+        let ctx = Context::new().with_bool("f", false);
+        let s = "{% if f %}{% if nope %}{% endif %}{% for x in xs %}{% endfor %}{% endif %}ok";
+        assert_eq!(render(s, &ctx).unwrap(), "ok");
+    }
+
+    #[test]
+    fn render_with_limit_within_limit() {
+        let ctx = Context::new().with_list("names", names());
+        let s = "{% for n in names %}{{ n }},{% endfor %}";
+        assert_eq!(render_with_limit(s, &ctx, 6).unwrap(), "a,b,c,");
+        assert_eq!(render_with_limit("", &ctx, 0).unwrap(), "");
+    }
+
+    #[test]
+    fn render_with_limit_exceeded() {
+        let ctx = Context::new().with_list("names", names());
+        let s = "{% for n in names %}{{ n }},{% endfor %}";
+        let err = render_with_limit(s, &ctx, 5).unwrap_err();
+        assert_eq!(err.message, "Rendered output exceeds the limit of 5 bytes");
+        assert_eq!(err.byte_offset, s.find(',').unwrap());
+
+        let err = render_with_limit("0123456789", &ctx, 4).unwrap_err();
+        assert!(err.message.contains("exceeds the limit"));
+        assert_eq!(err.byte_offset, 0);
+
+        // Other errors are passed through unchanged.
+        let err = render_with_limit("{{ nope }}", &ctx, 100).unwrap_err();
+        assert!(err.message.contains("Unknown string identifier"));
+    }
+
+    #[test]
+    fn referenced_identifiers_by_kind() {
+        let s = "{% if a and not b %}{{ X }}{% endif %}{% if ARCH == \"rv\" or a %}{% endif %}\
+                 {% for n in names %}{{ n }}{{ Y | default(\"\") }}{% endfor %}\
+                 {% set S = Z %}{{ S }}";
+        let ids = referenced_identifiers(s).unwrap();
+        let names = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(names(&ids.bools), ["a", "b"]);
+        assert_eq!(names(&ids.strs), ["ARCH", "X", "Y", "Z"]);
+        assert_eq!(names(&ids.lists), ["names"]);
+    }
+
+    #[test]
+    fn referenced_identifiers_respects_binding_scope() {
+        // `n` and `S` are context strings outside the blocks that bind them.
+        let s = "{% for n in xs %}{% set S = \"v\" %}{{ n }}{{ S }}{% endfor %}{{ n }}{{ S }}";
+        let ids = referenced_identifiers(s).unwrap();
+        assert_eq!(ids.strs.into_iter().collect::<Vec<_>>(), ["S", "n"]);
+    }
+
+    #[test]
+    fn referenced_identifiers_needs_valid_structure() {
+        let err = referenced_identifiers("{% if x %}{{ y }}").unwrap_err();
+        assert!(err.message.contains("Unclosed {% if %}"));
     }
 }
@@ -1,7 +1,11 @@
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr;
 
-use buddy_system_allocator::LockedHeap;
+use buddy_system_allocator::{Heap, LockedHeap};
+use foundation::ops::AllocStats;
+
+#[cfg(test)]
+extern crate alloc;
 
 // Upstream buddy allocator. The const generic is the max order, i.e. the maximum
 // heap size is bounded by \(2^\text{ORDER}\) bytes.
@@ -46,3 +50,168 @@ pub(crate) fn realloc(ptr: *mut u8, old_layout: Layout, new_size: usize) -> *mut
 
     unsafe { GlobalAlloc::realloc(&HEAP, ptr, old_layout, new_size) }
 }
+
+pub(crate) fn stats() -> AllocStats {
+    heap_stats(&mut HEAP.lock())
+}
+
+fn heap_stats<const O: usize>(heap: &mut Heap<O>) -> AllocStats {
+    let total = heap.stats_total_bytes();
+    let used = heap.stats_alloc_actual();
+    let free = total - used;
+    AllocStats {
+        total,
+        used,
+        free,
+        largest_free_block: largest_free_block(heap),
+        num_free_blocks: num_free_blocks(heap),
+    }
+}
+
+/// Size of the largest block the heap can hand out right now, found by binary search over
+/// block orders. Each probe is one alloc/dealloc pair, and dealloc merges the split buddies
+/// back, so the heap ends up as it started.
+fn largest_free_block<const O: usize>(heap: &mut Heap<O>) -> usize {
+    let mut fits = |order: usize| {
+        let Ok(layout) = Layout::from_size_align(1 << order, 1) else {
+            return false;
+        };
+        match heap.alloc(layout) {
+            Ok(block) => {
+                heap.dealloc(block, layout);
+                true
+            }
+            Err(()) => false,
+        }
+    };
+
+    // The heap never hands out less than a word.
+    let mut lo = core::mem::size_of::<usize>().trailing_zeros() as usize;
+    let mut hi = O - 1;
+    if !fits(lo) {
+        return 0;
+    }
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        if fits(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    1 << lo
+}
+
+/// Number of blocks on the free lists, which upstream keeps private. Takes every free block
+/// out, largest order first so none is split, then frees them all again. Free lists never
+/// hold two free buddies (dealloc merges them), so this rebuilds the same lists.
+fn num_free_blocks<const O: usize>(heap: &mut Heap<O>) -> usize {
+    // Blocks taken at each order, chained through their first word.
+    let mut taken = [0usize; O];
+    let mut count = 0;
+    let min_order = core::mem::size_of::<usize>().trailing_zeros() as usize;
+    for order in (min_order..O).rev() {
+        let Ok(layout) = Layout::from_size_align(1 << order, 1) else {
+            continue;
+        };
+        while let Ok(block) = heap.alloc(layout) {
+            unsafe { (block.as_ptr() as *mut usize).write(taken[order]) };
+            taken[order] = block.as_ptr() as usize;
+            count += 1;
+        }
+    }
+    for (order, &head) in taken.iter().enumerate() {
+        let mut next = head;
+        while let Some(block) = ptr::NonNull::new(next as *mut u8) {
+            next = unsafe { (block.as_ptr() as *const usize).read() };
+            heap.dealloc(block, Layout::from_size_align(1 << order, 1).unwrap());
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const HEAP_SIZE: usize = 64 * 1024;
+    const BLOCK: usize = 4096;
+
+    /// A heap over one naturally aligned 64 KiB region, so it starts as a single free block.
+    fn with_heap(f: impl FnOnce(&mut Heap<32>)) {
+        let region = Layout::from_size_align(HEAP_SIZE, HEAP_SIZE).unwrap();
+        let mem = unsafe { alloc::alloc::alloc(region) };
+        assert!(!mem.is_null());
+        let mut heap = Heap::<32>::new();
+        unsafe { heap.init(mem as usize, HEAP_SIZE) };
+        f(&mut heap);
+        unsafe { alloc::alloc::dealloc(mem, region) };
+    }
+
+    #[test]
+    fn test_stats_fresh_heap() {
+        with_heap(|heap| {
+            let stats = heap_stats(heap);
+            assert_eq!(
+                stats,
+                AllocStats {
+                    total: HEAP_SIZE,
+                    used: 0,
+                    free: HEAP_SIZE,
+                    largest_free_block: HEAP_SIZE,
+                    num_free_blocks: 1,
+                }
+            );
+            // Probing leaves the heap as it was.
+            assert_eq!(heap_stats(heap), stats);
+        });
+    }
+
+    #[test]
+    fn test_stats_track_alloc_and_free() {
+        with_heap(|heap| {
+            let layout = Layout::from_size_align(BLOCK, 8).unwrap();
+            let block = heap.alloc(layout).unwrap();
+            let stats = heap_stats(heap);
+            assert_eq!(stats.used, BLOCK);
+            assert_eq!(stats.free, HEAP_SIZE - BLOCK);
+            assert_eq!(stats.largest_free_block, HEAP_SIZE / 2);
+            // Splitting down to one block left a free buddy at each of 32, 16, 8 and 4 KiB.
+            assert_eq!(stats.num_free_blocks, 4);
+
+            heap.dealloc(block, layout);
+            let stats = heap_stats(heap);
+            assert_eq!(stats.used, 0);
+            assert_eq!(stats.free, HEAP_SIZE);
+            assert_eq!(stats.largest_free_block, HEAP_SIZE);
+            assert_eq!(stats.num_free_blocks, 1);
+        });
+    }
+
+    #[test]
+    fn test_largest_free_block_shrinks_when_fragmented() {
+        with_heap(|heap| {
+            let layout = Layout::from_size_align(BLOCK, 8).unwrap();
+            let blocks: Vec<_> = (0..HEAP_SIZE / BLOCK)
+                .map(|_| heap.alloc(layout).unwrap())
+                .collect();
+            assert_eq!(heap_stats(heap).largest_free_block, 0);
+
+            // Free every other block: half the heap is free, but no two free blocks are buddies.
+            for block in blocks.iter().step_by(2) {
+                heap.dealloc(*block, layout);
+            }
+            let stats = heap_stats(heap);
+            assert_eq!(stats.free, HEAP_SIZE / 2);
+            assert_eq!(stats.largest_free_block, BLOCK);
+            assert_eq!(stats.num_free_blocks, HEAP_SIZE / BLOCK / 2);
+            assert_eq!(heap_stats(heap), stats);
+
+            for block in blocks.iter().skip(1).step_by(2) {
+                heap.dealloc(*block, layout);
+            }
+            assert_eq!(heap_stats(heap).largest_free_block, HEAP_SIZE);
+        });
+    }
+}
@@ -2,6 +2,7 @@
 
 mod allocator;
 
+pub use foundation::ops::AllocStats;
 use foundation::ops::MemoryOps;
 
 pub const BUDDY_ALLOCATOR_OPS: MemoryOps = MemoryOps {
@@ -9,4 +10,11 @@ pub const BUDDY_ALLOCATOR_OPS: MemoryOps = MemoryOps {
     alloc: allocator::alloc,
     dealloc: allocator::dealloc,
     realloc: allocator::realloc,
+    stats: allocator::stats,
 };
+
+/// Current heap usage. Cheap enough to poll: under the heap lock, a handful of alloc/dealloc
+/// probes plus one pair per free block, independent of how many blocks are live.
+pub fn stats() -> AllocStats {
+    allocator::stats()
+}
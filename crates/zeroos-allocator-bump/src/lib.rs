@@ -2,11 +2,12 @@
 
 mod allocator;
 
-use foundation::ops::MemoryOps;
+use foundation::ops::{noop_stats, MemoryOps};
 
 pub const BUMP_ALLOCATOR_OPS: MemoryOps = MemoryOps {
     init: allocator::init,
     alloc: allocator::alloc,
     dealloc: allocator::dealloc,
     realloc: allocator::realloc,
+    stats: noop_stats,
 };
@@ -1,6 +1,7 @@
 use core::alloc::Layout;
 use core::ptr;
-use linked_list_allocator::LockedHeap;
+use foundation::ops::AllocStats;
+use linked_list_allocator::{Heap, LockedHeap};
 
 #[cfg(test)]
 extern crate alloc;
@@ -59,6 +60,98 @@ pub(crate) fn realloc(ptr: *mut u8, old_layout: Layout, new_size: usize) -> *mut
     new_ptr
 }
 
+pub(crate) fn stats() -> AllocStats {
+    heap_stats(&mut HEAP.lock())
+}
+
+fn heap_stats(heap: &mut Heap) -> AllocStats {
+    let (largest_free_block, num_free_blocks) = free_holes(heap);
+    AllocStats {
+        total: heap.size(),
+        used: heap.used(),
+        free: heap.free(),
+        largest_free_block,
+        num_free_blocks,
+    }
+}
+
+/// Allocation granularity: sizes are rounded up to this, and no hole is smaller than `MIN`.
+const STEP: usize = core::mem::align_of::<usize>();
+const MIN: usize = 2 * core::mem::size_of::<usize>();
+
+fn hole_layout(size: usize) -> Layout {
+    Layout::from_size_align(size, STEP).unwrap()
+}
+
+/// Whether `size` bytes can be allocated; the heap is left as it was.
+fn fits(heap: &mut Heap, size: usize) -> bool {
+    match heap.allocate_first_fit(hole_layout(size)) {
+        Ok(block) => {
+            unsafe { heap.deallocate(block, hole_layout(size)) };
+            true
+        }
+        Err(()) => false,
+    }
+}
+
+/// Size of the largest hole, or 0 if there is none.
+///
+/// A hole only takes an allocation that fills it or leaves room for a new hole behind it, so
+/// `fits` is not monotonic just below the largest hole. `fits(n) || fits(n + STEP)` is: it holds
+/// up to the largest hole and not past it, so that is what the binary search looks for.
+fn largest_hole(heap: &mut Heap) -> usize {
+    let mut lo = MIN;
+    let mut hi = heap.free() / STEP * STEP;
+    let mut fits_near = |size: usize| fits(heap, size) || fits(heap, size + STEP);
+    if hi < lo || !fits_near(lo) {
+        return 0;
+    }
+    while lo < hi {
+        let mid = (lo + (hi - lo) / 2).next_multiple_of(STEP);
+        if fits_near(mid) {
+            lo = mid;
+        } else {
+            hi = mid - STEP;
+        }
+    }
+    lo
+}
+
+/// The largest hole and the number of holes, found by walking the hole list, which upstream
+/// keeps private. Takes out the largest hole whole until none is left, then frees them all
+/// again. Holes are never adjacent (dealloc merges them), so this rebuilds the same list.
+fn free_holes(heap: &mut Heap) -> (usize, usize) {
+    // Holes taken, chained through their first two words as (next, size).
+    let mut taken: *mut usize = ptr::null_mut();
+    let mut largest = 0;
+    let mut count = 0;
+    loop {
+        let size = largest_hole(heap);
+        if size == 0 {
+            break;
+        }
+        let Ok(block) = heap.allocate_first_fit(hole_layout(size)) else {
+            break;
+        };
+        let block = block.as_ptr() as *mut usize;
+        unsafe {
+            block.write(taken as usize);
+            block.add(1).write(size);
+        }
+        taken = block;
+        largest = largest.max(size);
+        count += 1;
+    }
+    while let Some(block) = ptr::NonNull::new(taken) {
+        let size = unsafe {
+            taken = block.as_ptr().read() as *mut usize;
+            block.as_ptr().add(1).read()
+        };
+        unsafe { heap.deallocate(block.cast(), hole_layout(size)) };
+    }
+    (largest, count)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,4 +199,68 @@ mod tests {
         let new_layout = Layout::from_size_align(256, 8).unwrap();
         dealloc(new_ptr, new_layout);
     }
+
+    const STATS_HEAP_SIZE: usize = 4096;
+
+    /// A private heap over a fresh buffer, so the test doesn't share the global one.
+    fn with_heap(f: impl FnOnce(&mut Heap)) {
+        let mut mem = alloc::vec![0usize; STATS_HEAP_SIZE / core::mem::size_of::<usize>()];
+        let mut heap = Heap::empty();
+        unsafe { heap.init(mem.as_mut_ptr().cast(), STATS_HEAP_SIZE) };
+        f(&mut heap);
+        drop(mem);
+    }
+
+    #[test]
+    fn test_stats_track_holes() {
+        with_heap(|heap| {
+            let stats = heap_stats(heap);
+            assert_eq!(
+                stats,
+                AllocStats {
+                    total: STATS_HEAP_SIZE,
+                    used: 0,
+                    free: STATS_HEAP_SIZE,
+                    largest_free_block: STATS_HEAP_SIZE,
+                    num_free_blocks: 1,
+                }
+            );
+            // Walking the holes leaves the heap as it was.
+            assert_eq!(heap_stats(heap), stats);
+
+            let layout = Layout::from_size_align(256, 8).unwrap();
+            let blocks: alloc::vec::Vec<_> = (0..STATS_HEAP_SIZE / 256)
+                .map(|_| heap.allocate_first_fit(layout).unwrap())
+                .collect();
+            let stats = heap_stats(heap);
+            assert_eq!((stats.used, stats.free), (STATS_HEAP_SIZE, 0));
+            assert_eq!((stats.largest_free_block, stats.num_free_blocks), (0, 0));
+
+            // Every other block: half the heap free in 256-byte holes that cannot merge.
+            for block in blocks.iter().step_by(2) {
+                unsafe { heap.deallocate(*block, layout) };
+            }
+            let stats = heap_stats(heap);
+            assert_eq!(stats.free, STATS_HEAP_SIZE / 2);
+            assert_eq!(stats.largest_free_block, 256);
+            assert_eq!(stats.num_free_blocks, STATS_HEAP_SIZE / 256 / 2);
+            assert_eq!(heap_stats(heap), stats);
+
+            // A 100-byte hole rounds to 104; uneven sizes still come out exact.
+            unsafe { heap.deallocate(blocks[1], layout) };
+            let small = Layout::from_size_align(100, 8).unwrap();
+            let partial = heap.allocate_first_fit(small).unwrap();
+            assert_eq!(partial, blocks[0]);
+            let stats = heap_stats(heap);
+            assert_eq!(stats.largest_free_block, 3 * 256 - 104);
+            assert_eq!(stats.num_free_blocks, STATS_HEAP_SIZE / 256 / 2 - 1);
+
+            unsafe { heap.deallocate(partial, small) };
+            for block in blocks.iter().skip(3).step_by(2) {
+                unsafe { heap.deallocate(*block, layout) };
+            }
+            assert_eq!(heap_stats(heap).num_free_blocks, 1);
+            assert_eq!(heap_stats(heap).largest_free_block, STATS_HEAP_SIZE);
+        });
+    }
 }
@@ -2,6 +2,7 @@
 
 mod allocator;
 
+pub use foundation::ops::AllocStats;
 use foundation::ops::MemoryOps;
 
 pub const LINKED_LIST_ALLOCATOR_OPS: MemoryOps = MemoryOps {
@@ -9,4 +10,11 @@ pub const LINKED_LIST_ALLOCATOR_OPS: MemoryOps = MemoryOps {
     alloc: allocator::alloc,
     dealloc: allocator::dealloc,
     realloc: allocator::realloc,
+    stats: allocator::stats,
 };
+
+/// Current heap usage. Finding the free holes takes a few alloc/dealloc probes per hole, each
+/// a walk of the hole list, under the heap lock.
+pub fn stats() -> AllocStats {
+    allocator::stats()
+}
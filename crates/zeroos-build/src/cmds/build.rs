@@ -55,6 +55,23 @@ pub struct BuildArgs {
     #[arg(long, env = "RISCV_GCC_PATH")]
     pub gcc_lib_path: Option<PathBuf>,
 
+    /// Write the linker's map file to PATH (default: `link.map` next to the generated
+    /// `linker.ld`).
+    ///
+    /// Supports placeholders: `<OUT>` (that directory), `<WORKSPACE>`, `<TARGET>`, `<PROFILE>`,
+    /// `<PACKAGE>`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "<OUT>/link.map"
+    )]
+    pub emit_map: Option<String>,
+
+    /// After building, print the size of each loaded section next to the heap and stack.
+    #[arg(long)]
+    pub print_sizes: bool,
+
     /// Arguments after `--` are forwarded to the underlying `cargo build` invocation.
     ///
     /// Example:
@@ -67,6 +84,24 @@ pub const TARGET_NO_STD: &str = "riscv64imac-unknown-none-elf";
 
 pub const TARGET_STD: &str = "riscv64imac-zero-linux-musl";
 
+impl BuildArgs {
+    /// `--target`, or the default target for `--mode`.
+    pub fn target(&self) -> &str {
+        self.target.as_deref().unwrap_or(match self.mode {
+            StdMode::Std => TARGET_STD,
+            StdMode::NoStd => TARGET_NO_STD,
+        })
+    }
+
+    /// Where cargo leaves the package's binary under `target_dir`.
+    pub fn binary_path(&self, target_dir: &Path) -> PathBuf {
+        target_dir
+            .join(self.target())
+            .join(crate::project::detect_profile(&self.cargo_args))
+            .join(&self.package)
+    }
+}
+
 pub fn build_binary(
     workspace_root: &PathBuf,
     args: &BuildArgs,
@@ -89,11 +124,7 @@ pub fn build_binary(
     debug!("stack_size: 0x{:x} ({} bytes)", stack_size, stack_size);
     debug!("heap_size: 0x{:x} ({} bytes)", heap_size, heap_size);
 
-    let default_target = match args.mode {
-        StdMode::Std => TARGET_STD,
-        StdMode::NoStd => TARGET_NO_STD,
-    };
-    let target = args.target.as_deref().unwrap_or(default_target);
+    let target = args.target();
 
     let build_std_arg = match (args.mode, args.fully) {
         (StdMode::Std, _) => Some("-Zbuild-std=core,alloc,std,panic_abort"),
@@ -129,6 +160,7 @@ pub fn build_binary(
     } else {
         config
     };
+    let (heap_size, stack_size) = (config.heap_size(), config.stack_size);
 
     write_linker_script(&linker_script_path, config)?;
 
@@ -144,6 +176,7 @@ pub fn build_binary(
                     target,
                     TargetRenderOptions {
                         backtrace: backtrace_enabled,
+                        ..Default::default()
                     },
                 )
                 .ok();
@@ -153,24 +186,28 @@ pub fn build_binary(
             }
         });
 
-    let mut link_args = vec![
-        format!("-T{}", linker_script_path.display()),
-        "--wrap=__lock".to_string(),
-        "--wrap=__unlock".to_string(),
-        "--wrap=__lockfile".to_string(),
-        "--wrap=__unlockfile".to_string(),
-    ];
+    let map_path = match &args.emit_map {
+        Some(template) => {
+            let path = map_path(template, workspace_root, &crate_out_dir, args, &profile)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            info!("Writing linker map: {}", path.display());
+            Some(path)
+        }
+        None => None,
+    };
 
-    if let Some((musl_lib, gcc_lib)) = toolchain_paths {
+    if let Some((musl_lib, gcc_lib)) = &toolchain_paths {
         info!("Using musl lib: {}", musl_lib.display());
         info!("Using gcc lib:  {}", gcc_lib.display());
-
-        link_args.extend(vec![
-            format!("-L{}", musl_lib.display()),
-            format!("-L{}", gcc_lib.display()),
-            "-lgcc".to_string(),
-        ]);
     }
+    let link_args = link_args(
+        &linker_script_path,
+        map_path.as_deref(),
+        toolchain_paths.as_ref(),
+    );
 
     debug!("link_args count: {}", link_args.len());
     for (i, arg) in link_args.iter().enumerate() {
@@ -237,9 +274,103 @@ pub fn build_binary(
         exit(status.code().unwrap_or(1));
     }
 
+    if args.print_sizes {
+        let binary = args.binary_path(&target_dir);
+        let image =
+            fs::read(&binary).with_context(|| format!("Failed to read {}", binary.display()))?;
+        let sections = crate::host::elf::sections(&image)
+            .with_context(|| format!("Failed to parse {}", binary.display()))?;
+        print!("{}", format_sizes(&sections, heap_size, stack_size));
+    }
+
     Ok(())
 }
 
+/// A table of the loaded (`SHF_ALLOC`) sections' sizes and their total, then the heap and
+/// stack reserved by the linker script.
+fn format_sizes(
+    sections: &[crate::host::elf::Section],
+    heap_size: usize,
+    stack_size: usize,
+) -> String {
+    use std::fmt::Write;
+
+    let mut table = String::new();
+    let _ = writeln!(table, "{:<20} {:>12} {:>12}", "section", "hex", "bytes");
+    let mut row = |name: &str, size: u64| {
+        let _ = writeln!(
+            table,
+            "{:<20} {:>12} {:>12}",
+            name,
+            format!("{:#x}", size),
+            size
+        );
+    };
+    let loaded: Vec<_> = sections
+        .iter()
+        .filter(|s| s.flags & crate::host::elf::SHF_ALLOC != 0 && s.size > 0)
+        .collect();
+    for section in &loaded {
+        row(&section.name, section.size);
+    }
+    row("total", loaded.iter().map(|s| s.size).sum());
+    row("heap", heap_size as u64);
+    row("stack", stack_size as u64);
+    table
+}
+
+/// Linker arguments for the generated script, an optional map file and the musl/gcc libraries.
+fn link_args(
+    linker_script_path: &Path,
+    map_path: Option<&Path>,
+    toolchain_paths: Option<&(PathBuf, PathBuf)>,
+) -> Vec<String> {
+    let mut link_args = vec![
+        format!("-T{}", linker_script_path.display()),
+        "--wrap=__lock".to_string(),
+        "--wrap=__unlock".to_string(),
+        "--wrap=__lockfile".to_string(),
+        "--wrap=__unlockfile".to_string(),
+    ];
+
+    if let Some(path) = map_path {
+        link_args.push(format!("-Map={}", path.display()));
+    }
+
+    if let Some((musl_lib, gcc_lib)) = toolchain_paths {
+        link_args.extend(vec![
+            format!("-L{}", musl_lib.display()),
+            format!("-L{}", gcc_lib.display()),
+            "-lgcc".to_string(),
+        ]);
+    }
+    link_args
+}
+
+/// Expand an `--emit-map` path; `<OUT>` is the directory holding the generated `linker.ld`.
+fn map_path(
+    template: &str,
+    workspace_root: &Path,
+    out_dir: &Path,
+    args: &BuildArgs,
+    profile: &str,
+) -> Result<PathBuf> {
+    let template = template.replace("<OUT>", &out_dir.display().to_string());
+    // Only ask cargo for the package directory if the path needs it.
+    let package_dir = if template.contains("<PACKAGE>") {
+        crate::project::resolve_package_dir(workspace_root, &args.package)?
+    } else {
+        PathBuf::new()
+    };
+    Ok(PathBuf::from(crate::project::expand_emit_path(
+        &template,
+        workspace_root,
+        &package_dir,
+        args.target(),
+        profile,
+    )))
+}
+
 fn write_target_spec(
     target_spec_path: impl AsRef<Path>,
     target: &str,
@@ -267,6 +398,9 @@ fn write_linker_script(
 ) -> Result<(), anyhow::Error> {
     let path = linker_script_path.as_ref();
     debug!("Generating linker script: {}", path.display());
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Invalid memory layout: {}", e))?;
     let script_content = config.render(None);
     fs::write(path, script_content)?;
     debug!("Linker script written successfully");
@@ -343,3 +477,88 @@ fn should_enable_backtrace(args: &BuildArgs, profile: &str) -> bool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        build: BuildArgs,
+    }
+
+    #[test]
+    fn test_format_sizes() {
+        use crate::host::elf::{Section, SHF_ALLOC};
+
+        let section = |name: &str, flags, size| Section {
+            name: name.to_string(),
+            flags,
+            offset: 0,
+            size,
+        };
+        let sections = [
+            section("", 0, 0),
+            section(".text", SHF_ALLOC, 0x1234),
+            section(".data", SHF_ALLOC, 0x40),
+            section(".tdata", SHF_ALLOC, 0),
+            section(".bss", SHF_ALLOC, 0x800),
+            section(".debug_info", 0, 0x9999),
+        ];
+        assert_eq!(
+            format_sizes(&sections, 0x400_0000, 0x80_0000),
+            "\
+section                       hex        bytes
+.text                      0x1234         4660
+.data                        0x40           64
+.bss                        0x800         2048
+total                      0x1a74         6772
+heap                    0x4000000     67108864
+stack                    0x800000      8388608
+"
+        );
+    }
+
+    #[test]
+    fn test_emit_map_link_arg() {
+        let args = Cli::parse_from(["build", "-p", "app", "--emit-map", "--", "--release"]).build;
+        assert_eq!(args.emit_map.as_deref(), Some("<OUT>/link.map"));
+        assert_eq!(args.cargo_args, ["--release"]);
+
+        let out_dir = Path::new("/t/zeroos/app");
+        let path = map_path(
+            args.emit_map.as_deref().unwrap(),
+            Path::new("/ws"),
+            out_dir,
+            &args,
+            "release",
+        )
+        .unwrap();
+        let flags = link_args(&out_dir.join("linker.ld"), Some(&path), None);
+        assert!(flags.contains(&"-Map=/t/zeroos/app/link.map".to_string()));
+
+        let args = Cli::parse_from([
+            "build",
+            "-p",
+            "app",
+            "--emit-map",
+            "<WORKSPACE>/<TARGET>-<PROFILE>.map",
+        ])
+        .build;
+        let path = map_path(
+            args.emit_map.as_deref().unwrap(),
+            Path::new("/ws"),
+            out_dir,
+            &args,
+            "debug",
+        )
+        .unwrap();
+        let flags = link_args(&out_dir.join("linker.ld"), Some(&path), None);
+        assert!(flags.contains(&format!("-Map=/ws/{}-debug.map", TARGET_NO_STD)));
+
+        let flags = link_args(&out_dir.join("linker.ld"), None, None);
+        assert!(!flags.iter().any(|arg| arg.starts_with("-Map=")));
+    }
+}
@@ -51,16 +51,14 @@ pub fn generate_linker_script(args: &GenerateLinkerArgs) -> Result<LinkerGenerat
         .with_memory(ram_start, ram_size)
         .with_heap_size(heap_size)
         .with_stack_size(stack_size)
-        .with_backtrace(args.backtrace);
+        .with_backtrace(args.backtrace)
+        .with_entry_point(args.entry_point.clone());
+    cfg.validate()
+        .map_err(|e| anyhow::anyhow!("Invalid memory layout: {}", e))?;
 
-    let mut script_content = cfg.render(Some(
+    let script_content = cfg.render(Some(
         include_str!("../files/linker.ld.template").to_string(),
     ));
 
-    if args.entry_point != "_start" {
-        script_content =
-            script_content.replace("ENTRY(_start)", &format!("ENTRY({})", args.entry_point));
-    }
-
     Ok(LinkerGeneratorResult { script_content })
 }
@@ -1,9 +1,14 @@
 pub mod build;
 pub mod linker;
+pub mod run;
 pub mod target;
 
 pub use build::{
     build_binary, find_workspace_root, get_or_build_toolchain, parse_address, BuildArgs, StdMode,
 };
 pub use linker::{generate_linker_script, GenerateLinkerArgs, LinkerGeneratorResult};
-pub use target::{generate_target_spec, GenerateTargetArgs};
+pub use run::{
+    parse_htif_exit, run_command, EventFormat, RunArgs, RunEvent, RunOutcome, RunPlan, SpikeArgs,
+    TimedOut,
+};
+pub use target::{generate_target_spec, GenerateTargetArgs, TargetSpecFormat};
@@ -0,0 +1,857 @@
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use log::{debug, warn};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::{exit, ChildStderr, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use std::{fmt, io::BufRead, io::BufReader, io::Write};
+
+use crate::cmds::build::{parse_address, BuildArgs};
+use crate::host::backtrace as sym;
+use crate::host::elf;
+
+/// Spike options shared by `cargo spike run` and `cargo zeroos run`.
+#[derive(Args, Debug, Clone)]
+pub struct SpikeArgs {
+    /// Path to spike executable (defaults to `spike` in PATH; falls back to common install dirs)
+    #[arg(long, env = "SPIKE_PATH")]
+    pub spike: Option<PathBuf>,
+
+    #[arg(long, default_value = "RV64IMAC")]
+    pub isa: String,
+
+    #[arg(long, short = 'n', default_value = "1000000")]
+    pub instructions: u64,
+
+    /// Kill spike after this many seconds of wall-clock time
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Exit 0 if the guest exits with CODE, non-zero otherwise
+    #[arg(long, value_name = "CODE", allow_negative_numbers = true)]
+    pub expect_exit: Option<i32>,
+
+    /// Symbolize `stack backtrace:` frame addresses using addr2line on the host
+    #[arg(long, default_value_t = true)]
+    pub symbolize_backtrace: bool,
+
+    /// Path to addr2line binary (defaults to `riscv64-unknown-elf-addr2line` if found)
+    #[arg(long, env = "RISCV_ADDR2LINE")]
+    pub addr2line: Option<PathBuf>,
+
+    /// Report run events on stderr in this format, one per line
+    #[arg(long, value_enum)]
+    pub events: Option<EventFormat>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    /// JSON lines
+    Json,
+}
+
+/// Progress of a run, for CI to parse; see `SpikeArgs::events`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum RunEvent {
+    BuildStart {
+        package: String,
+        target: String,
+    },
+    SpikeStart {
+        spike: PathBuf,
+        args: Vec<String>,
+    },
+    /// A guest line that looks like a panic message.
+    PanicDetected {
+        line: String,
+    },
+    /// `code` is absent if spike was killed by a signal.
+    ExitCode {
+        code: Option<i32>,
+    },
+    /// The exit code the guest reported through HTIF, when spike printed it.
+    GuestExit {
+        code: i32,
+    },
+    /// Spike ran past `--timeout` and was killed; no `exit-code` follows.
+    TimedOut {
+        secs: u64,
+    },
+}
+
+impl RunEvent {
+    /// Write the event to `out` if `format` asks for events.
+    pub fn emit(&self, format: Option<EventFormat>, out: &mut dyn Write) {
+        if format == Some(EventFormat::Json) {
+            if let Ok(line) = serde_json::to_string(self) {
+                let _ = writeln!(out, "{}", line);
+            }
+        }
+    }
+}
+
+/// Spike was killed for running past `--timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut {
+    pub secs: u64,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spike timed out after {}s and was killed", self.secs)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy)]
+pub struct RunOutcome {
+    /// Spike's own exit status.
+    pub status: ExitStatus,
+    /// The guest's exit code, if spike reported one; see [`parse_htif_exit`].
+    pub guest_exit: Option<i32>,
+}
+
+impl RunOutcome {
+    /// The guest's exit code, falling back to spike's; `None` if spike died from a signal.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.guest_exit.or(self.status.code())
+    }
+}
+
+/// The guest exit code in a line spike prints when the guest writes the HTIF exit command to
+/// `tohost`: `*** FAILED *** (tohost = <code>)` for a nonzero code, `*** PASSED ***` for 0.
+pub fn parse_htif_exit(line: &str) -> Option<i32> {
+    let line = line.trim();
+    if line.starts_with("*** PASSED ***") {
+        return Some(0);
+    }
+    let rest = line.strip_prefix("*** FAILED ***")?.trim_start();
+    let code = rest.strip_prefix("(tohost =")?.strip_suffix(')')?.trim();
+    code.parse().ok()
+}
+
+/// Exit status for a timed-out run, as `timeout(1)` uses.
+const TIMED_OUT_EXIT_CODE: i32 = 124;
+
+/// Whether a guest output line is a panic: `PANIC:` from the no_std handler, or std's
+/// `thread '...' panicked at`.
+fn is_panic_line(line: &str) -> bool {
+    line.starts_with("PANIC: ") || line.contains(" panicked at ")
+}
+
+/// Run an already built binary on spike
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[arg(value_name = "BINARY")]
+    pub binary: PathBuf,
+
+    #[command(flatten)]
+    pub spike: SpikeArgs,
+
+    /// Start of the RAM the binary was linked for; with `--memory-size`, gives spike that
+    /// memory and warns if the binary loads outside it
+    #[arg(long, requires = "memory_size")]
+    pub memory_origin: Option<String>,
+
+    #[arg(long, requires = "memory_origin")]
+    pub memory_size: Option<String>,
+
+    /// Extra argument for spike itself, placed before the binary (repeatable). Spike arguments
+    /// used to trail the binary; arguments after the binary now go to the guest, after `--`
+    #[arg(long = "spike-arg", value_name = "ARG", allow_hyphen_values = true)]
+    pub spike_args: Vec<String>,
+
+    /// Arguments for the guest program, after `--`; it sees them as `argv[1..]`
+    #[arg(last = true, value_name = "ARGS")]
+    pub guest_args: Vec<String>,
+}
+
+/// A guest binary and the spike invocation that runs it.
+#[derive(Debug, Clone)]
+pub struct RunPlan {
+    pub binary: PathBuf,
+    pub spike: SpikeArgs,
+    /// Guest memory as `(origin, size)`, passed to spike as `-m<origin>:<size>`.
+    pub memory: Option<(usize, usize)>,
+    /// Extra spike arguments, placed before the binary.
+    pub extra_args: Vec<String>,
+    /// Guest `argv[1..]`; see [`RunPlan::with_guest_args`].
+    pub guest_args: Vec<String>,
+}
+
+impl RunPlan {
+    /// Plan to run the binary that `build_binary` produces for `build` under `target_dir`, with
+    /// the memory layout it was linked for.
+    pub fn for_build(target_dir: &Path, build: &BuildArgs, spike: SpikeArgs) -> Result<Self> {
+        let memory_origin = parse_address(&build.memory_origin)?;
+        let memory_size = parse_size::parse_size(&build.memory_size)? as usize;
+        Ok(Self {
+            binary: build.binary_path(target_dir),
+            spike,
+            memory: Some((memory_origin, memory_size)),
+            extra_args: Vec::new(),
+            guest_args: Vec::new(),
+        })
+    }
+
+    /// Arguments passed to spike, excluding the spike executable itself.
+    pub fn spike_args(&self) -> Vec<String> {
+        let mut args = vec![format!("--isa={}", self.spike.isa)];
+        if self.spike.instructions > 0 {
+            args.push(format!("--instructions={}", self.spike.instructions));
+        }
+        if let Some((origin, size)) = self.memory {
+            args.push(format!("-m0x{:x}:0x{:x}", origin, size));
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args.push(self.binary.display().to_string());
+        args
+    }
+
+    /// `PT_LOAD` segments of the binary that fall outside `memory`, which usually means the
+    /// binary was linked for a different layout. Empty when there is no memory window.
+    pub fn memory_violations(&self) -> Result<Vec<elf::LoadSegment>> {
+        let Some((origin, size)) = self.memory else {
+            return Ok(Vec::new());
+        };
+        let image = std::fs::read(&self.binary)
+            .with_context(|| format!("Failed to read {}", self.binary.display()))?;
+        let segments = elf::load_segments(&image)
+            .with_context(|| format!("Failed to parse {}", self.binary.display()))?;
+        Ok(segments
+            .into_iter()
+            .filter(|seg| !seg.fits_in(origin as u64, size as u64))
+            .collect())
+    }
+
+    /// A copy of the binary in `dir` with `guest_args` written into its `.zeroos_args`
+    /// section, which the musl runtime turns into `argv` (with the binary name as `argv[0]`).
+    pub fn with_guest_args(&self, dir: &Path) -> Result<PathBuf> {
+        let mut image = std::fs::read(&self.binary)
+            .with_context(|| format!("Failed to read {}", self.binary.display()))?;
+        let (offset, size) = elf::section_range(&image, ARGS_SECTION)
+            .with_context(|| format!("Failed to parse {}", self.binary.display()))?
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} has no {} section; was it built with a runtime that takes arguments?",
+                    self.binary.display(),
+                    ARGS_SECTION
+                )
+            })?;
+
+        let name = self
+            .binary
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let block = encode_args(&name, &self.guest_args)?;
+        if block.len() > size {
+            anyhow::bail!(
+                "guest arguments need {} bytes but {} holds {}",
+                block.len(),
+                ARGS_SECTION,
+                size
+            );
+        }
+        image[offset..offset + block.len()].copy_from_slice(&block);
+
+        let path = dir.join(&name);
+        std::fs::write(&path, image)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Run spike with guest stdout on our stdout and events on stderr, exiting with the
+    /// guest's code if it is not 0, or with 124 if spike timed out. With `--expect-exit`, fail
+    /// unless the guest exited with that code instead.
+    pub fn run(&self) -> Result<()> {
+        let outcome = match self.run_with(&mut std::io::stdout().lock(), &mut std::io::stderr()) {
+            Err(e) if e.is::<TimedOut>() => {
+                eprintln!("Error: {}", e);
+                exit(TIMED_OUT_EXIT_CODE);
+            }
+            result => result?,
+        };
+        let code = outcome.exit_code();
+        if let Some(expected) = self.spike.expect_exit {
+            if code != Some(expected) {
+                anyhow::bail!(
+                    "guest exited with {}, expected {}",
+                    code.map_or("a signal".to_string(), |c| c.to_string()),
+                    expected
+                );
+            }
+            return Ok(());
+        }
+        match code {
+            Some(0) => Ok(()),
+            code => exit(code.unwrap_or(1)),
+        }
+    }
+
+    /// Run spike, streaming (symbolized) guest stdout to `out` and events to `events`.
+    ///
+    /// Fails with [`TimedOut`] if spike outlives `--timeout`; the output up to then, including
+    /// any pending backtrace, is still written.
+    pub fn run_with(&self, out: &mut dyn Write, events: &mut dyn Write) -> Result<RunOutcome> {
+        if !self.binary.exists() {
+            anyhow::bail!("Binary not found: {}", self.binary.display());
+        }
+
+        if !self.guest_args.is_empty() {
+            let dir = tempfile::tempdir().context("Failed to create a directory for the guest")?;
+            let plan = RunPlan {
+                binary: self.with_guest_args(dir.path())?,
+                guest_args: Vec::new(),
+                ..self.clone()
+            };
+            return plan.run_with(out, events);
+        }
+
+        match self.memory_violations() {
+            Ok(violations) => {
+                for seg in violations {
+                    let (origin, size) = self.memory.unwrap_or_default();
+                    warn!(
+                        "{}: segment 0x{:x}..0x{:x} is outside memory 0x{:x}..0x{:x}; \
+                         was it linked for a different --memory-origin/--memory-size?",
+                        self.binary.display(),
+                        seg.paddr,
+                        seg.paddr.saturating_add(seg.memsz),
+                        origin,
+                        origin.saturating_add(size)
+                    );
+                }
+            }
+            Err(e) => debug!("Skipping memory preflight: {:#}", e),
+        }
+
+        debug!("Running binary: {}", self.binary.display());
+        debug!("ISA: {}", self.spike.isa);
+        debug!(
+            "Instructions: {}",
+            if self.spike.instructions == 0 {
+                "unlimited".to_string()
+            } else {
+                self.spike.instructions.to_string()
+            }
+        );
+
+        let spike_path = resolve_spike(self.spike.spike.as_deref())
+            .ok_or_else(|| anyhow::anyhow!("spike not found (set SPIKE_PATH or add it to PATH)"))?;
+
+        let spike_args = self.spike_args();
+        debug!(
+            "Spike command: {} {}",
+            spike_path.display(),
+            spike_args.join(" ")
+        );
+        let mut spike_cmd = Command::new(&spike_path);
+        spike_cmd.args(&spike_args);
+        RunEvent::SpikeStart {
+            spike: spike_path.clone(),
+            args: spike_args,
+        }
+        .emit(self.spike.events, events);
+
+        // Stream spike output so we can optionally symbolize backtraces.
+        spike_cmd.stdout(Stdio::piped());
+        spike_cmd.stderr(Stdio::piped());
+
+        let mut child = spike_cmd
+            .spawn()
+            .context("Failed to execute spike (is it installed?)")?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to capture spike stdout"))?;
+
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to capture spike stderr"))?;
+        let stderr_exit = forward_stderr(stderr);
+
+        let lines = read_lines(stdout);
+        let deadline = self
+            .spike
+            .timeout
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        let mut timed_out = false;
+
+        let addr2line = if self.spike.symbolize_backtrace {
+            sym::resolve_addr2line(self.spike.addr2line.as_deref())
+        } else {
+            None
+        };
+
+        // Backtrace symbolization state: buffer contiguous frame lines and rewrite them.
+        let mut pending_frames: Vec<(usize, String)> = Vec::new(); // (frame_no, addr_hex)
+        let mut in_backtrace = false;
+        let mut guest_exit = None;
+
+        loop {
+            let next = match deadline {
+                Some(deadline) => {
+                    lines.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = match next {
+                Ok(line) => line.context("Failed to read spike stdout")?,
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    timed_out = true;
+                    break;
+                }
+            };
+            if let Some(code) = parse_htif_exit(&line) {
+                guest_exit = Some(code);
+            }
+            if is_panic_line(&line) {
+                RunEvent::PanicDetected {
+                    line: line.trim_end().to_string(),
+                }
+                .emit(self.spike.events, events);
+            }
+
+            if line.trim_end() == "stack backtrace:" {
+                in_backtrace = true;
+                pending_frames.clear();
+                out.write_all(line.as_bytes()).ok();
+                out.flush().ok();
+                continue;
+            }
+
+            if in_backtrace {
+                if let Some((frame_no, addr_hex)) = sym::parse_backtrace_unknown_frame(&line) {
+                    pending_frames.push((frame_no, addr_hex));
+                    continue;
+                }
+
+                if !pending_frames.is_empty() {
+                    flush_symbolized_frames(
+                        out,
+                        &self.binary,
+                        addr2line.as_deref(),
+                        &pending_frames,
+                    );
+                    pending_frames.clear();
+                }
+                in_backtrace = false;
+            }
+
+            out.write_all(line.as_bytes()).ok();
+            out.flush().ok();
+        }
+
+        if in_backtrace && !pending_frames.is_empty() {
+            flush_symbolized_frames(out, &self.binary, addr2line.as_deref(), &pending_frames);
+        }
+
+        if let (true, Some(secs)) = (timed_out, self.spike.timeout) {
+            let _ = child.kill();
+            let _ = child.wait();
+            RunEvent::TimedOut { secs }.emit(self.spike.events, events);
+            return Err(TimedOut { secs }.into());
+        }
+
+        let status = child.wait().context("Failed to wait for spike process")?;
+        let guest_exit = stderr_exit.join().ok().flatten().or(guest_exit);
+        if let Some(code) = guest_exit {
+            RunEvent::GuestExit { code }.emit(self.spike.events, events);
+        }
+        RunEvent::ExitCode {
+            code: status.code(),
+        }
+        .emit(self.spike.events, events);
+
+        Ok(RunOutcome { status, guest_exit })
+    }
+}
+
+pub fn run_command(args: RunArgs) -> Result<()> {
+    let memory = match (&args.memory_origin, &args.memory_size) {
+        (Some(origin), Some(size)) => Some((
+            parse_address(origin)?,
+            parse_size::parse_size(size)? as usize,
+        )),
+        _ => None,
+    };
+    RunPlan {
+        binary: args.binary,
+        spike: args.spike,
+        memory,
+        extra_args: args.spike_args,
+        guest_args: args.guest_args,
+    }
+    .run()
+}
+
+/// The section of a guest image that holds its `argv`.
+const ARGS_SECTION: &str = ".zeroos_args";
+
+/// Most `argv` entries the musl runtime passes to `main`, program name included; mirrors
+/// `zeroos_runtime_musl::MAX_ARGS`.
+const MAX_ARGS: usize = 32;
+
+/// `argv` as the runtime reads it: NUL-terminated strings, then an empty one. An empty or
+/// NUL-containing argument would end the list early, so those are refused, as is an `argv`
+/// the runtime would truncate.
+fn encode_args(name: &str, args: &[String]) -> Result<Vec<u8>> {
+    if args.len() + 1 > MAX_ARGS {
+        anyhow::bail!(
+            "{} guest arguments given, but the runtime passes at most {} after the program name",
+            args.len(),
+            MAX_ARGS - 1
+        );
+    }
+    let mut block = Vec::new();
+    for arg in std::iter::once(name).chain(args.iter().map(String::as_str)) {
+        if arg.is_empty() {
+            anyhow::bail!("empty guest arguments are not supported");
+        }
+        if arg.contains('\0') {
+            anyhow::bail!("guest argument {:?} contains a NUL byte", arg);
+        }
+        block.extend_from_slice(arg.as_bytes());
+        block.push(0);
+    }
+    block.push(0);
+    Ok(block)
+}
+
+/// Spike's stdout, line by line, from a thread so the caller can stop waiting at a deadline.
+/// The channel closes at end of output.
+fn read_lines(stdout: ChildStdout) -> Receiver<std::io::Result<String>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    if tx.send(Ok(line)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Pass spike's stderr through to ours, returning the guest exit code if spike reported one
+/// there, as it does for `*** FAILED ***`.
+fn forward_stderr(stderr: ChildStderr) -> std::thread::JoinHandle<Option<i32>> {
+    std::thread::spawn(move || {
+        let mut guest_exit = None;
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else { break };
+            if let Some(code) = parse_htif_exit(&line) {
+                guest_exit = Some(code);
+            }
+            eprintln!("{}", line);
+        }
+        guest_exit
+    })
+}
+
+fn resolve_spike(explicit: Option<&Path>) -> Option<PathBuf> {
+    if let Some(p) = explicit {
+        return Some(p.to_path_buf());
+    }
+    // Prefer PATH.
+    if let Some(p) = sym::which("spike") {
+        return Some(p);
+    }
+    // Common install locations (keep this list aligned with team conventions).
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let candidates: Vec<PathBuf> = [
+        home.as_ref().map(|h| h.join(".local/bin/spike")),
+        Some(PathBuf::from("/opt/riscv/bin/spike")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    candidates.into_iter().find(|p| p.is_file())
+}
+
+fn flush_symbolized_frames(
+    out: &mut dyn Write,
+    bin: &Path,
+    addr2line: Option<&Path>,
+    frames: &[(usize, String)],
+) {
+    let addrs: Vec<String> = frames
+        .iter()
+        .map(|(_, addr_hex)| addr_hex.clone())
+        .collect();
+    let symbols = match addr2line {
+        Some(a2l) => sym::symbolize_pcs_with_fallback(bin, a2l, &addrs),
+        None => vec![None; frames.len()],
+    };
+    for ((frame_no, addr_hex), sym_str) in frames.iter().zip(symbols) {
+        let addr = format!("0x{}", addr_hex);
+        let sym_str = sym_str.unwrap_or_else(|| "<unknown>".to_string());
+
+        let _ = writeln!(out, "{:>4}: {:>18} - {}", frame_no, addr, sym_str);
+    }
+    let _ = out.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        run: RunArgs,
+    }
+
+    fn plan_with_stub_spike(dir: &Path, script: &str) -> RunPlan {
+        let spike = dir.join("spike");
+        fs::write(&spike, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&spike, fs::Permissions::from_mode(0o755)).unwrap();
+        let binary = dir.join("guest");
+        fs::write(&binary, b"").unwrap();
+
+        RunPlan {
+            binary,
+            spike: SpikeArgs {
+                spike: Some(spike),
+                isa: "RV64IMAC".to_string(),
+                instructions: 0,
+                timeout: None,
+                expect_exit: None,
+                symbolize_backtrace: false,
+                addr2line: None,
+                events: Some(EventFormat::Json),
+            },
+            memory: None,
+            extra_args: Vec::new(),
+            guest_args: Vec::new(),
+        }
+    }
+
+    fn events(stderr: &[u8]) -> Vec<serde_json::Value> {
+        String::from_utf8_lossy(stderr)
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_events_report_panic_and_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = plan_with_stub_spike(dir.path(), "echo hello; echo 'PANIC: boom'; exit 3");
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let outcome = plan.run_with(&mut out, &mut err).unwrap();
+        assert_eq!(outcome.status.code(), Some(3));
+        assert_eq!(outcome.guest_exit, None);
+        assert_eq!(out, b"hello\nPANIC: boom\n");
+
+        let events = events(&err);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["event"], "spike-start");
+        assert_eq!(events[0]["args"][0], "--isa=RV64IMAC");
+        assert_eq!(
+            events[1],
+            serde_json::json!({"event": "panic-detected", "line": "PANIC: boom"})
+        );
+        assert_eq!(
+            events[2],
+            serde_json::json!({"event": "exit-code", "code": 3})
+        );
+    }
+
+    #[test]
+    fn test_no_events_without_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plan = plan_with_stub_spike(dir.path(), "exit 0");
+        plan.spike.events = None;
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert!(plan.run_with(&mut out, &mut err).unwrap().status.success());
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_timeout_kills_spike_and_keeps_backtrace() {
+        let dir = tempfile::tempdir().unwrap();
+        // `exec` so that killing the stub kills the sleep holding stdout too.
+        let mut plan = plan_with_stub_spike(
+            dir.path(),
+            "echo 'stack backtrace:'; echo '   0:         0x80001000 - <unknown>'; exec sleep 30",
+        );
+        plan.spike.timeout = Some(1);
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let start = Instant::now();
+        let e = plan.run_with(&mut out, &mut err).unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(20));
+        assert_eq!(e.downcast_ref::<TimedOut>(), Some(&TimedOut { secs: 1 }));
+        assert_eq!(e.to_string(), "spike timed out after 1s and was killed");
+
+        // The frame seen before the deadline is still printed.
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "stack backtrace:\n   0:         0x80001000 - <unknown>\n"
+        );
+        let events = events(&err);
+        assert_eq!(
+            events.last().unwrap(),
+            &serde_json::json!({"event": "timed-out", "secs": 1})
+        );
+    }
+
+    #[test]
+    fn test_parse_htif_exit() {
+        assert_eq!(parse_htif_exit("*** FAILED *** (tohost = 42)\n"), Some(42));
+        assert_eq!(parse_htif_exit("*** FAILED *** (tohost = 1)"), Some(1));
+        assert_eq!(parse_htif_exit("*** PASSED ***\n"), Some(0));
+        assert_eq!(parse_htif_exit("*** FAILED *** (tohost = x)"), None);
+        assert_eq!(
+            parse_htif_exit("guest says *** FAILED *** (tohost = 3)"),
+            None
+        );
+        assert_eq!(parse_htif_exit("hello"), None);
+    }
+
+    #[test]
+    fn test_guest_exit_code_from_spike_stderr() {
+        let dir = tempfile::tempdir().unwrap();
+        // Spike itself exits 0 here, as with some HTIF setups.
+        let plan = plan_with_stub_spike(dir.path(), "echo '*** FAILED *** (tohost = 42)' >&2");
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let outcome = plan.run_with(&mut out, &mut err).unwrap();
+        assert!(outcome.status.success());
+        assert_eq!(outcome.guest_exit, Some(42));
+        assert_eq!(outcome.exit_code(), Some(42));
+        assert!(events(&err).contains(&serde_json::json!({"event": "guest-exit", "code": 42})));
+    }
+
+    #[test]
+    fn test_memory_violations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plan = plan_with_stub_spike(dir.path(), "exit 0");
+        fs::write(
+            &plan.binary,
+            elf::tests::elf64(&[(1, 0x8000_0000, 0x1000), (1, 0x9000_0000, 0x100)]),
+        )
+        .unwrap();
+
+        assert!(plan.memory_violations().unwrap().is_empty());
+
+        plan.memory = Some((0x8000_0000, 0x1000_0000));
+        assert_eq!(
+            plan.memory_violations().unwrap(),
+            [elf::LoadSegment {
+                paddr: 0x9000_0000,
+                memsz: 0x100
+            }]
+        );
+
+        plan.memory = Some((0x8000_0000, 0x2000_0000));
+        assert!(plan.memory_violations().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_with_guest_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plan = plan_with_stub_spike(dir.path(), "exit 0");
+        let image = elf::tests::with_sections(elf::tests::elf64(&[]), &[(ARGS_SECTION, &[0; 32])]);
+        fs::write(&plan.binary, &image).unwrap();
+        plan.guest_args = vec!["arg1".to_string(), "arg 2".to_string()];
+
+        let out_dir = tempfile::tempdir().unwrap();
+        let patched = fs::read(plan.with_guest_args(out_dir.path()).unwrap()).unwrap();
+        let (offset, size) = elf::section_range(&patched, ARGS_SECTION).unwrap().unwrap();
+        let mut expected = b"guest\0arg1\0arg 2\0\0".to_vec();
+        expected.resize(size, 0);
+        assert_eq!(&patched[offset..offset + size], &expected[..]);
+        assert_eq!(
+            fs::read(&plan.binary).unwrap(),
+            image,
+            "original left alone"
+        );
+
+        plan.guest_args = vec!["x".repeat(32)];
+        assert!(plan.with_guest_args(out_dir.path()).is_err());
+        fs::write(&plan.binary, elf::tests::elf64(&[])).unwrap();
+        assert!(plan.with_guest_args(out_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_parse_spike_and_guest_args() {
+        // As build-syscall-cycles.sh passes spike its trace flags.
+        let cli = Cli::try_parse_from([
+            "run",
+            "guest",
+            "--instructions",
+            "20000000",
+            "--spike-arg",
+            "-l",
+            "--spike-arg",
+            "--log=trace.log",
+        ])
+        .unwrap();
+        assert_eq!(cli.run.spike_args, ["-l", "--log=trace.log"]);
+        assert!(cli.run.guest_args.is_empty());
+
+        let cli = Cli::try_parse_from(["run", "guest", "--", "-l", "x"]).unwrap();
+        assert!(cli.run.spike_args.is_empty());
+        assert_eq!(cli.run.guest_args, ["-l", "x"]);
+
+        // Spike flags no longer trail the binary.
+        assert!(Cli::try_parse_from(["run", "guest", "-l", "--log=trace.log"]).is_err());
+    }
+
+    #[test]
+    fn test_encode_args_rejects_what_argv_cannot_hold() {
+        let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            encode_args("guest", &args(&["a"])).unwrap(),
+            b"guest\0a\0\0"
+        );
+
+        let err = encode_args("guest", &args(&["a", ""])).unwrap_err();
+        assert_eq!(err.to_string(), "empty guest arguments are not supported");
+        assert!(encode_args("guest", &args(&["a\0b"])).is_err());
+
+        let full = vec!["x".to_string(); MAX_ARGS - 1];
+        assert!(encode_args("guest", &full).is_ok());
+        let err = encode_args("guest", &[full, args(&["y"])].concat()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "32 guest arguments given, but the runtime passes at most 31 after the program name"
+        );
+    }
+
+    #[test]
+    fn test_is_panic_line() {
+        assert!(is_panic_line("PANIC: panicked at src/main.rs:3:5:\n"));
+        assert!(is_panic_line(
+            "thread 'main' panicked at src/main.rs:3:5:\n"
+        ));
+        assert!(!is_panic_line("no panic here\n"));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::spec::{
-    get_arch_spec, load_target_profile, parse_target_triple, LLVMConfig, TargetRenderOptions,
+    get_arch_spec, load_target_profile, parse_target_triple, CodeModel, RelocationModel,
+    TargetRenderOptions,
 };
 
 ///      --features "+m,+a,+c" --abi lp64 \
@@ -16,16 +17,61 @@ pub struct GenerateTargetArgs {
     #[arg(long)]
     pub llvm_target: Option<String>,
 
-    /// LLVM ABI (e.g., "lp64", "lp64d", "ilp32"). Can override profile defaults
+    /// LLVM ABI (e.g., "lp64", "lp64d", "ilp32"). Overrides the profile or architecture default
     #[arg(long)]
     pub abi: Option<String>,
 
     #[arg(long)]
     pub features: Option<String>,
 
-    /// LLVM data layout string. Can override profile defaults
+    /// LLVM data layout string. Overrides the profile or architecture default
     #[arg(long)]
     pub data_layout: Option<String>,
+
+    /// Relocation model (default: static)
+    #[arg(long, value_enum)]
+    pub relocation_model: Option<RelocationModel>,
+
+    /// Code model (default: the architecture's, e.g. medium for RISC-V)
+    #[arg(long, value_enum)]
+    pub code_model: Option<CodeModel>,
+}
+
+/// Output format for a generated target spec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TargetSpecFormat {
+    /// Plain JSON, as consumed by `--target <file>.json`
+    #[default]
+    Json,
+    /// A `const TARGET_SPEC: &str` wrapping the JSON, for `include!` from a build script
+    Rust,
+}
+
+impl TargetSpecFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TargetSpecFormat::Json => "json",
+            TargetSpecFormat::Rust => "rs",
+        }
+    }
+
+    pub fn format(self, json: &str) -> String {
+        match self {
+            TargetSpecFormat::Json => json.to_string(),
+            TargetSpecFormat::Rust => {
+                // Use enough `#`s that the JSON can never terminate the raw string early.
+                let mut hashes = String::from("#");
+                while json.contains(&format!("\"{}", hashes)) {
+                    hashes.push('#');
+                }
+                format!(
+                    "const TARGET_SPEC: &str = r{hashes}\"{json}\"{hashes};\n",
+                    hashes = hashes,
+                    json = json
+                )
+            }
+        }
+    }
 }
 
 pub fn generate_target_spec(
@@ -52,31 +98,8 @@ pub fn generate_target_spec(
             .ok_or_else(|| format!("Cannot parse target triple: {}", target))?;
 
         let arch_spec = get_arch_spec(&config.arch);
-
-        // All LLVM properties must be provided by user
-        let llvm_target = args
-            .llvm_target
-            .clone()
-            .ok_or_else(|| "llvm_target is required when not using profile".to_string())?;
-        let features = args
-            .features
-            .clone()
-            .ok_or_else(|| "features is required when not using profile".to_string())?;
-        let abi = args
-            .abi
-            .clone()
-            .ok_or_else(|| "abi is required when not using profile".to_string())?;
-        let data_layout = args
-            .data_layout
-            .clone()
-            .ok_or_else(|| "data_layout is required when not using profile".to_string())?;
-
-        let llvm_config = LLVMConfig {
-            llvm_target,
-            features,
-            abi,
-            data_layout,
-        };
+        // Flags given below override these defaults.
+        let llvm_config = arch_spec.default_llvm_config(&config);
 
         (config, arch_spec, llvm_config)
     };
@@ -94,7 +117,106 @@ pub fn generate_target_spec(
         llvm_config.data_layout = data_layout.clone();
     }
 
+    let render_opts = TargetRenderOptions {
+        relocation_model: args.relocation_model.or(render_opts.relocation_model),
+        code_model: args.code_model.or(render_opts.code_model),
+        ..render_opts
+    };
     let json_content = config.render(&arch_spec, &llvm_config, render_opts)?;
 
     Ok(json_content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_format_embeds_valid_json() {
+        let args = GenerateTargetArgs {
+            profile: Some(crate::spec::PROFILE_RISCV64IMAC_ZERO_LINUX_MUSL.to_string()),
+            ..Default::default()
+        };
+        let json = generate_target_spec(&args, TargetRenderOptions::default()).unwrap();
+        let rust = TargetSpecFormat::Rust.format(&json);
+
+        let prefix = "const TARGET_SPEC: &str = r#\"";
+        let suffix = "\"#;\n";
+        assert!(rust.starts_with(prefix), "{}", rust);
+        assert!(rust.ends_with(suffix), "{}", rust);
+
+        let embedded = &rust[prefix.len()..rust.len() - suffix.len()];
+        let value: serde_json::Value = serde_json::from_str(embedded).unwrap();
+        assert_eq!(value["arch"], "riscv64");
+    }
+
+    #[test]
+    fn test_rust_format_escapes_raw_string_terminator() {
+        let rust = TargetSpecFormat::Rust.format(r##"{"a": "x"#"}"##);
+        assert!(rust.starts_with("const TARGET_SPEC: &str = r##\""));
+        assert!(rust.ends_with("\"##;\n"));
+    }
+
+    #[test]
+    fn test_aarch64_triple_renders() {
+        let args = GenerateTargetArgs {
+            target: Some("aarch64-unknown-none".to_string()),
+            ..Default::default()
+        };
+        let json = generate_target_spec(&args, TargetRenderOptions::default()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["arch"], "aarch64");
+        assert_eq!(value["llvm-target"], "aarch64-unknown-none");
+        assert_eq!(value["target-pointer-width"], "64");
+        assert_eq!(value["max-atomic-width"], 128);
+        assert_eq!(
+            value["data-layout"],
+            get_arch_spec("aarch64").data_layout,
+            "{}",
+            json
+        );
+
+        let args = GenerateTargetArgs {
+            features: Some("+v8a".to_string()),
+            ..args
+        };
+        let json = generate_target_spec(&args, TargetRenderOptions::default()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["features"], "+v8a");
+    }
+
+    #[test]
+    fn test_relocation_and_code_model() {
+        let profile = GenerateTargetArgs {
+            profile: Some(crate::spec::PROFILE_RISCV64IMAC_ZERO_LINUX_MUSL.to_string()),
+            ..Default::default()
+        };
+        let render = |args: &GenerateTargetArgs| -> serde_json::Value {
+            let json = generate_target_spec(args, TargetRenderOptions::default()).unwrap();
+            serde_json::from_str(&json).unwrap()
+        };
+
+        let value = render(&profile);
+        assert_eq!(value["relocation-model"], "static");
+        assert_eq!(value["code-model"], "medium");
+
+        let value = render(&GenerateTargetArgs {
+            code_model: Some(CodeModel::Large),
+            relocation_model: Some(RelocationModel::Pie),
+            ..profile.clone()
+        });
+        assert_eq!(value["code-model"], "large");
+        assert_eq!(value["relocation-model"], "pie");
+
+        let value = render(&GenerateTargetArgs {
+            target: Some("aarch64-unknown-none".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(value["code-model"], "small");
+    }
+
+    #[test]
+    fn test_json_format_is_unchanged() {
+        assert_eq!(TargetSpecFormat::Json.format("{}"), "{}");
+    }
+}
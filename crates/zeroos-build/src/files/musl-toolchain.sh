@@ -341,7 +341,7 @@ PATCH
 PATCH
 fi
 
-hide_output make -j"$(cpu_count)" TARGET=$TARGET MUSL_VER=1.2.3 LINUX_HEADERS_SITE=$LINUX_HEADERS_SITE LINUX_VER=$LINUX_VER GCC_CONFIG_FOR_TARGET="$GCC_CONFIG_FOR_TARGET" COMMON_CONFIG="$COMMON_CONFIG_FOR_BUILD"
+hide_output make -j"${MAKE_JOBS:-$(cpu_count)}" TARGET=$TARGET MUSL_VER=1.2.3 LINUX_HEADERS_SITE=$LINUX_HEADERS_SITE LINUX_VER=$LINUX_VER GCC_CONFIG_FOR_TARGET="$GCC_CONFIG_FOR_TARGET" COMMON_CONFIG="$COMMON_CONFIG_FOR_BUILD"
 hide_output make install TARGET=$TARGET MUSL_VER=1.2.3 LINUX_HEADERS_SITE=$LINUX_HEADERS_SITE LINUX_VER=$LINUX_VER OUTPUT=$OUTPUT GCC_CONFIG_FOR_TARGET="$GCC_CONFIG_FOR_TARGET" COMMON_CONFIG="$COMMON_CONFIG_FOR_BUILD"
 
 printf '!<arch>\n' | tee $OUTPUT/$TARGET/lib/libunwind.a > /dev/null
@@ -61,35 +61,62 @@ pub fn parse_hex(s: &str) -> usize {
 /// Returns a single-line string like:
 /// `my_func at /path/file.rs:123`
 pub fn symbolize_addr(bin: &Path, addr2line: &Path, addr: &str) -> Option<String> {
+    symbolize_addrs(bin, addr2line, &[addr.to_string()])
+        .pop()
+        .flatten()
+}
+
+/// Symbolize several PCs with a single addr2line run; one result per address, in order.
+pub fn symbolize_addrs(bin: &Path, addr2line: &Path, addrs: &[String]) -> Vec<Option<String>> {
+    if addrs.is_empty() {
+        return Vec::new();
+    }
     let output = Command::new(addr2line)
         .args(["-e"])
         .arg(bin)
         .args(["-f", "-C", "-p"])
-        .arg(addr)
-        .output()
-        .ok()?;
+        .args(addrs)
+        .output();
 
-    if !output.status.success() {
-        return None;
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_addr2line_output(&String::from_utf8_lossy(&output.stdout), addrs.len())
+        }
+        _ => vec![None; addrs.len()],
     }
-    let s = String::from_utf8_lossy(&output.stdout).trim().to_string();
+}
+
+/// Split `addr2line -f -C -p` output for `count` addresses into one symbol per address.
+///
+/// Without `-i` each address prints one line; `(inlined by)` continuation lines are skipped
+/// anyway so they cannot shift later results.
+pub fn parse_addr2line_output(stdout: &str, count: usize) -> Vec<Option<String>> {
+    let mut symbols: Vec<Option<String>> = stdout
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("(inlined by)"))
+        .take(count)
+        .map(parse_addr2line_line)
+        .collect();
+    symbols.resize(count, None);
+    symbols
+}
+
+fn parse_addr2line_line(line: &str) -> Option<String> {
+    let s = line.trim();
     if s.is_empty() {
         return None;
     }
 
     // addr2line output can include an address prefix; keep the RHS if present.
-    let s = if let Some((_, rhs)) = s.split_once(": ") {
-        rhs.to_string()
-    } else {
-        s
-    };
+    let s = s.split_once(": ").map_or(s, |(_, rhs)| rhs);
 
     // We prefer `fn at file:line`, but for early boot / assembly stubs we may only be able to
     // recover a symbol name with unknown location (e.g. `foo at ??:?`). Keep the symbol name in
     // that case instead of reporting `<unknown>`.
-    let (func, loc) = s.split_once(" at ").unwrap_or((s.as_str(), ""));
+    let (func, loc) = s.split_once(" at ").unwrap_or((s, ""));
     let func = func.trim();
-    if func.is_empty() || func == "??" {
+    // Unknown addresses print as `?? ??:0`, without the ` at `.
+    if func.is_empty() || func == "??" || func.starts_with("?? ") {
         return None;
     }
     if loc.contains("??:0") || loc.contains("??:?") || loc.is_empty() {
@@ -104,10 +131,95 @@ pub fn symbolize_pc_with_fallback(
     addr2line: &Path,
     addr_hex_no_0x: &str,
 ) -> Option<String> {
-    let addr = format!("0x{}", addr_hex_no_0x);
-    symbolize_addr(bin, addr2line, &addr).or_else(|| {
-        let pc = parse_hex(addr_hex_no_0x);
-        let addr_m4 = format!("0x{:x}", pc.saturating_sub(4));
-        symbolize_addr(bin, addr2line, &addr_m4)
-    })
+    symbolize_pcs_with_fallback(bin, addr2line, &[addr_hex_no_0x.to_string()])
+        .pop()
+        .flatten()
+}
+
+/// `symbolize_pc_with_fallback` for many PCs: one addr2line run for all of them, then one
+/// more at `pc-4` for just those that came back unknown.
+pub fn symbolize_pcs_with_fallback(
+    bin: &Path,
+    addr2line: &Path,
+    addrs_hex_no_0x: &[String],
+) -> Vec<Option<String>> {
+    let addrs: Vec<String> = addrs_hex_no_0x
+        .iter()
+        .map(|hex| format!("0x{}", hex))
+        .collect();
+    let mut symbols = symbolize_addrs(bin, addr2line, &addrs);
+
+    let unknown: Vec<usize> = (0..symbols.len())
+        .filter(|&i| symbols[i].is_none())
+        .collect();
+    let retry: Vec<String> = unknown
+        .iter()
+        .map(|&i| format!("0x{:x}", parse_hex(&addrs_hex_no_0x[i]).saturating_sub(4)))
+        .collect();
+    for (i, symbol) in unknown
+        .into_iter()
+        .zip(symbolize_addrs(bin, addr2line, &retry))
+    {
+        symbols[i] = symbol;
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_parse_addr2line_output_multiple_addresses() {
+        let stdout = "\
+0x0000000080001000: main at /src/main.rs:12
+0x0000000080001040: ?? ??:0
+_start at ??:?
+core::panicking::panic at /rustc/library/core/src/panicking.rs:72
+ (inlined by) core::option::unwrap_failed at /rustc/library/core/src/option.rs:2015
+";
+        assert_eq!(
+            parse_addr2line_output(stdout, 5),
+            [
+                Some("main at /src/main.rs:12".to_string()),
+                None,
+                Some("_start".to_string()),
+                Some(
+                    "core::panicking::panic at /rustc/library/core/src/panicking.rs:72".to_string()
+                ),
+                // addr2line printed fewer lines than addresses.
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_symbolize_pcs_batches_and_retries_only_unknowns() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("calls");
+        let a2l = dir.path().join("addr2line");
+        // Knows 0x1000 and 0x1ffc (the `pc-4` of 0x2000); logs each call's addresses.
+        let script = format!(
+            "#!/bin/sh\nshift 5\necho \"$*\" >> {}\nfor a in \"$@\"; do\n  case $a in\n    0x1000) echo 'a at a.rs:1' ;;\n    0x1ffc) echo 'b at b.rs:2' ;;\n    *) echo '?? ??:0' ;;\n  esac\ndone\n",
+            log.display()
+        );
+        fs::write(&a2l, script).unwrap();
+        fs::set_permissions(&a2l, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let addrs = ["1000", "2000", "3000"].map(String::from);
+        assert_eq!(
+            symbolize_pcs_with_fallback(Path::new("guest"), &a2l, &addrs),
+            [
+                Some("a at a.rs:1".to_string()),
+                Some("b at b.rs:2".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "0x1000 0x2000 0x3000\n0x1ffc 0x2ffc\n"
+        );
+    }
 }
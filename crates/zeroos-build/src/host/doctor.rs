@@ -0,0 +1,184 @@
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// An external host tool that ZeroOS tooling shells out to.
+#[derive(Debug, Clone, Copy)]
+pub struct Tool {
+    /// Display name used in the report.
+    pub name: &'static str,
+    /// Executable names to look for in PATH, in order of preference.
+    pub candidates: &'static [&'static str],
+    /// Argument that makes the tool print its version.
+    pub version_arg: &'static str,
+    /// Whether a missing tool should fail `cargo zeroos doctor`.
+    pub essential: bool,
+    /// What the tool is used for, shown when it is missing.
+    pub purpose: &'static str,
+}
+
+/// Tools checked by `cargo zeroos doctor`.
+pub const TOOLS: &[Tool] = &[
+    Tool {
+        name: "spike",
+        candidates: &["spike"],
+        // spike has no --version; its help banner starts with the version.
+        version_arg: "--help",
+        essential: true,
+        purpose: "running guests (cargo spike run)",
+    },
+    Tool {
+        name: "addr2line",
+        candidates: &["riscv64-unknown-elf-addr2line", "llvm-addr2line"],
+        version_arg: "--version",
+        essential: false,
+        purpose: "symbolizing backtraces",
+    },
+    Tool {
+        name: "git",
+        candidates: &["git"],
+        version_arg: "--version",
+        essential: false,
+        purpose: "build-musl",
+    },
+    Tool {
+        name: "make",
+        candidates: &["make"],
+        version_arg: "--version",
+        essential: false,
+        purpose: "build-musl",
+    },
+    Tool {
+        name: "gcc",
+        candidates: &["gcc"],
+        version_arg: "--version",
+        essential: false,
+        purpose: "build-musl",
+    },
+    Tool {
+        name: "curl",
+        candidates: &["curl"],
+        version_arg: "--version",
+        essential: true,
+        purpose: "install-musl",
+    },
+    Tool {
+        name: "tar",
+        candidates: &["tar"],
+        version_arg: "--version",
+        essential: true,
+        purpose: "install-musl",
+    },
+    Tool {
+        name: "sha256sum",
+        candidates: &["sha256sum", "shasum"],
+        version_arg: "--version",
+        essential: true,
+        purpose: "install-musl",
+    },
+];
+
+/// Result of looking up a single [`Tool`].
+#[derive(Debug, Clone)]
+pub struct ToolStatus {
+    pub tool: Tool,
+    /// Resolved executable, if any candidate was found.
+    pub path: Option<PathBuf>,
+    /// First non-empty line of the version output, if the tool printed one.
+    pub version: Option<String>,
+}
+
+impl ToolStatus {
+    pub fn found(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+/// Check a tool against the current PATH.
+pub fn check_tool(tool: &Tool) -> ToolStatus {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    check_tool_in(tool, &path)
+}
+
+/// Check a tool against an explicit PATH-style search list.
+pub fn check_tool_in(tool: &Tool, search_path: &OsStr) -> ToolStatus {
+    let path = tool.candidates.iter().find_map(|bin| {
+        std::env::split_paths(search_path)
+            .map(|dir| dir.join(bin))
+            .find(|cand| cand.is_file())
+    });
+    let version = path
+        .as_ref()
+        .and_then(|p| tool_version(p, tool.version_arg));
+    ToolStatus {
+        tool: *tool,
+        path,
+        version,
+    }
+}
+
+fn tool_version(bin: &std::path::Path, arg: &str) -> Option<String> {
+    let output = Command::new(bin).arg(arg).output().ok()?;
+    let first_line = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string)
+    };
+    first_line(&output.stdout).or_else(|| first_line(&output.stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn stub(dir: &std::path::Path, name: &str, script: &str) {
+        let p = dir.join(name);
+        fs::write(&p, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&p, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    const FAKE: Tool = Tool {
+        name: "fake",
+        candidates: &["fake-primary", "fake-fallback"],
+        version_arg: "--version",
+        essential: true,
+        purpose: "tests",
+    };
+
+    #[test]
+    fn test_found_with_version() {
+        let dir = tempfile::tempdir().unwrap();
+        stub(dir.path(), "fake-primary", "echo; echo 'fake 1.2.3'");
+
+        let status = check_tool_in(&FAKE, dir.path().as_os_str());
+        assert!(status.found());
+        assert_eq!(status.path.unwrap(), dir.path().join("fake-primary"));
+        assert_eq!(status.version.as_deref(), Some("fake 1.2.3"));
+    }
+
+    #[test]
+    fn test_version_from_stderr_and_fallback_candidate() {
+        let dir = tempfile::tempdir().unwrap();
+        stub(
+            dir.path(),
+            "fake-fallback",
+            "echo 'Fake Simulator 0.9' >&2; exit 1",
+        );
+
+        let status = check_tool_in(&FAKE, dir.path().as_os_str());
+        assert_eq!(status.path.unwrap(), dir.path().join("fake-fallback"));
+        assert_eq!(status.version.as_deref(), Some("Fake Simulator 0.9"));
+    }
+
+    #[test]
+    fn test_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = check_tool_in(&FAKE, dir.path().as_os_str());
+        assert!(!status.found());
+        assert!(status.version.is_none());
+    }
+}
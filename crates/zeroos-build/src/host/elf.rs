@@ -0,0 +1,278 @@
+//! Just enough ELF parsing to check where a guest image loads and find its sections.
+
+use anyhow::{bail, Result};
+
+const PT_LOAD: u32 = 1;
+
+/// A `PT_LOAD` segment, at the physical address spike loads it to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadSegment {
+    pub paddr: u64,
+    pub memsz: u64,
+}
+
+impl LoadSegment {
+    /// Whether the whole segment lies in `[origin, origin + size)`.
+    pub fn fits_in(&self, origin: u64, size: u64) -> bool {
+        let end = self.paddr.checked_add(self.memsz);
+        let window_end = origin.checked_add(size);
+        match (end, window_end) {
+            (Some(end), Some(window_end)) => self.paddr >= origin && end <= window_end,
+            _ => false,
+        }
+    }
+}
+
+/// The `PT_LOAD` segments of a little-endian ELF32 or ELF64 image.
+pub fn load_segments(image: &[u8]) -> Result<Vec<LoadSegment>> {
+    let is64 = elf_class(image)?;
+
+    let u16_at = |off: usize| read_le(image, off, 2);
+    let u32_at = |off: usize| read_le(image, off, 4);
+    let word_at = |off: usize| read_le(image, off, if is64 { 8 } else { 4 });
+
+    let (phoff, phentsize, phnum) = if is64 {
+        (word_at(32)?, u16_at(54)?, u16_at(56)?)
+    } else {
+        (word_at(28)?, u16_at(42)?, u16_at(44)?)
+    };
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let ph = (phoff + i * phentsize) as usize;
+        if u32_at(ph)? != PT_LOAD as u64 {
+            continue;
+        }
+        let (paddr, memsz) = if is64 {
+            (word_at(ph + 24)?, word_at(ph + 40)?)
+        } else {
+            (word_at(ph + 12)?, word_at(ph + 20)?)
+        };
+        segments.push(LoadSegment { paddr, memsz });
+    }
+    Ok(segments)
+}
+
+/// Section flag: the section occupies memory at run time.
+pub const SHF_ALLOC: u64 = 0x2;
+
+/// A section header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub flags: u64,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The sections of a little-endian ELF32 or ELF64 image, in header order.
+pub fn sections(image: &[u8]) -> Result<Vec<Section>> {
+    let is64 = elf_class(image)?;
+    let u16_at = |off: usize| read_le(image, off, 2);
+    let u32_at = |off: usize| read_le(image, off, 4);
+    let word_at = |off: usize| read_le(image, off, if is64 { 8 } else { 4 });
+
+    let (shoff, shentsize, shnum, shstrndx) = if is64 {
+        (word_at(40)?, u16_at(58)?, u16_at(60)?, u16_at(62)?)
+    } else {
+        (word_at(32)?, u16_at(46)?, u16_at(48)?, u16_at(50)?)
+    };
+    // (sh_flags, sh_offset, sh_size) of the section header at `sh`.
+    let header_at = |sh: usize| -> Result<(u64, u64, u64)> {
+        if is64 {
+            Ok((word_at(sh + 8)?, word_at(sh + 24)?, word_at(sh + 32)?))
+        } else {
+            Ok((word_at(sh + 8)?, word_at(sh + 16)?, word_at(sh + 20)?))
+        }
+    };
+    if shnum == 0 {
+        return Ok(Vec::new());
+    }
+
+    let (_, strtab, _) = header_at((shoff + shstrndx * shentsize) as usize)?;
+    let mut sections = Vec::new();
+    for i in 0..shnum {
+        let sh = (shoff + i * shentsize) as usize;
+        let name_off = strtab as usize + u32_at(sh)? as usize;
+        let Some(bytes) = image.get(name_off..) else {
+            bail!("ELF section name out of range at {:#x}", name_off);
+        };
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        let (flags, offset, size) = header_at(sh)?;
+        sections.push(Section {
+            name: String::from_utf8_lossy(&bytes[..len]).into_owned(),
+            flags,
+            offset,
+            size,
+        });
+    }
+    Ok(sections)
+}
+
+/// The file range `(offset, size)` of the section called `name`, if the image has one.
+pub fn section_range(image: &[u8], name: &str) -> Result<Option<(usize, usize)>> {
+    let Some(section) = sections(image)?.into_iter().find(|s| s.name == name) else {
+        return Ok(None);
+    };
+    if section.offset.saturating_add(section.size) > image.len() as u64 {
+        bail!("ELF section {} extends past the end of the file", name);
+    }
+    Ok(Some((section.offset as usize, section.size as usize)))
+}
+
+/// Whether a little-endian ELF image is 64-bit.
+fn elf_class(image: &[u8]) -> Result<bool> {
+    if image.len() < 16 || &image[..4] != b"\x7fELF" {
+        bail!("not an ELF file");
+    }
+    if image[5] != 1 {
+        bail!("only little-endian ELF is supported");
+    }
+    match image[4] {
+        1 => Ok(false),
+        2 => Ok(true),
+        class => bail!("unknown ELF class {}", class),
+    }
+}
+
+fn read_le(image: &[u8], off: usize, len: usize) -> Result<u64> {
+    let Some(bytes) = off.checked_add(len).and_then(|end| image.get(off..end)) else {
+        bail!("ELF truncated at offset {:#x}", off);
+    };
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A minimal ELF64 image with one program header per `(type, paddr, memsz)`.
+    pub(crate) fn elf64(phdrs: &[(u32, u64, u64)]) -> Vec<u8> {
+        let mut image = vec![0u8; 64];
+        image[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        image[32..40].copy_from_slice(&64u64.to_le_bytes());
+        image[54..56].copy_from_slice(&56u16.to_le_bytes());
+        image[56..58].copy_from_slice(&(phdrs.len() as u16).to_le_bytes());
+        for &(p_type, paddr, memsz) in phdrs {
+            let mut ph = [0u8; 56];
+            ph[..4].copy_from_slice(&p_type.to_le_bytes());
+            ph[16..24].copy_from_slice(&paddr.to_le_bytes());
+            ph[24..32].copy_from_slice(&paddr.to_le_bytes());
+            ph[40..48].copy_from_slice(&memsz.to_le_bytes());
+            image.extend_from_slice(&ph);
+        }
+        image
+    }
+
+    #[test]
+    fn test_load_segments_elf64() {
+        let image = elf64(&[(PT_LOAD, 0x8000_0000, 0x1000), (6, 0, 0x40)]);
+        assert_eq!(
+            load_segments(&image).unwrap(),
+            [LoadSegment {
+                paddr: 0x8000_0000,
+                memsz: 0x1000
+            }]
+        );
+    }
+
+    /// Append section headers for `sections` (plus the null section and `.shstrtab`), each
+    /// `(name, contents)`, to an image from [`elf64`].
+    pub(crate) fn with_sections(mut image: Vec<u8>, sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut strtab = vec![0u8];
+        let mut headers = vec![[0u8; 64]];
+        // Header for a section named at `name_off` in the string table, with `contents` appended.
+        let section = |image: &mut Vec<u8>, name_off: usize, contents: &[u8]| {
+            let mut sh = [0u8; 64];
+            sh[..4].copy_from_slice(&(name_off as u32).to_le_bytes());
+            sh[24..32].copy_from_slice(&(image.len() as u64).to_le_bytes());
+            sh[32..40].copy_from_slice(&(contents.len() as u64).to_le_bytes());
+            image.extend_from_slice(contents);
+            sh
+        };
+        for &(name, contents) in sections {
+            headers.push(section(&mut image, strtab.len(), contents));
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+        let shstrtab_name = strtab.len();
+        strtab.extend_from_slice(b".shstrtab\0");
+        headers.push(section(&mut image, shstrtab_name, &strtab));
+
+        let shoff = image.len() as u64;
+        image[40..48].copy_from_slice(&shoff.to_le_bytes());
+        image[58..60].copy_from_slice(&64u16.to_le_bytes());
+        image[60..62].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+        image[62..64].copy_from_slice(&((headers.len() - 1) as u16).to_le_bytes());
+        for sh in headers {
+            image.extend_from_slice(&sh);
+        }
+        image
+    }
+
+    #[test]
+    fn test_section_range() {
+        let image = with_sections(
+            elf64(&[(PT_LOAD, 0x8000_0000, 0x1000)]),
+            &[(".text", &[0x13; 8]), (".zeroos_args", &[0; 16])],
+        );
+        let (offset, size) = section_range(&image, ".zeroos_args").unwrap().unwrap();
+        assert_eq!(size, 16);
+        assert_eq!(&image[offset..offset + size], &[0; 16]);
+        assert_eq!(section_range(&image, ".text").unwrap().unwrap().1, 8);
+        assert_eq!(section_range(&image, ".zeroos").unwrap(), None);
+
+        let names: Vec<_> = sections(&image)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["", ".text", ".zeroos_args", ".shstrtab"]);
+    }
+
+    #[test]
+    fn test_load_segments_elf32() {
+        let mut image = vec![0u8; 52];
+        image[..6].copy_from_slice(b"\x7fELF\x01\x01");
+        image[28..32].copy_from_slice(&52u32.to_le_bytes());
+        image[42..44].copy_from_slice(&32u16.to_le_bytes());
+        image[44..46].copy_from_slice(&1u16.to_le_bytes());
+        let mut ph = [0u8; 32];
+        ph[..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+        ph[12..16].copy_from_slice(&0x8000_0000u32.to_le_bytes());
+        ph[20..24].copy_from_slice(&0x200u32.to_le_bytes());
+        image.extend_from_slice(&ph);
+
+        assert_eq!(
+            load_segments(&image).unwrap(),
+            [LoadSegment {
+                paddr: 0x8000_0000,
+                memsz: 0x200
+            }]
+        );
+    }
+
+    #[test]
+    fn test_load_segments_rejects_bad_input() {
+        assert!(load_segments(b"").is_err());
+        assert!(load_segments(b"#!/bin/sh\nexit 0\n").is_err());
+        let mut truncated = elf64(&[(PT_LOAD, 0, 0x10)]);
+        truncated.truncate(80);
+        assert!(load_segments(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_fits_in() {
+        let seg = LoadSegment {
+            paddr: 0x8000_0000,
+            memsz: 0x1000,
+        };
+        assert!(seg.fits_in(0x8000_0000, 0x1000));
+        assert!(!seg.fits_in(0x8000_0000, 0xfff));
+        assert!(!seg.fits_in(0x8000_0800, 0x10000));
+        assert!(!seg.fits_in(u64::MAX, 2));
+    }
+}
@@ -1 +1,4 @@
 pub mod backtrace;
+pub mod doctor;
+pub mod elf;
+pub mod process;
@@ -0,0 +1,119 @@
+//! Running host commands whose output we need, with uniform error messages.
+
+use std::fmt;
+use std::process::{Command, ExitStatus, Output};
+
+/// Why `run_captured` failed.
+#[derive(Debug)]
+pub enum ProcessError {
+    /// The command could not be started at all.
+    Spawn {
+        command: String,
+        source: std::io::Error,
+    },
+    /// The command ran but exited unsuccessfully.
+    Failed {
+        command: String,
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn { command, source } => write!(f, "Failed to run `{}`: {}", command, source),
+            Self::Failed {
+                command,
+                status,
+                stdout,
+                stderr,
+            } => write!(
+                f,
+                "`{}` failed ({}):\nstdout:\n{}\nstderr:\n{}",
+                command, status, stdout, stderr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Spawn { source, .. } => Some(source),
+            Self::Failed { .. } => None,
+        }
+    }
+}
+
+/// The command line as a user would type it, for error messages.
+pub fn display_command(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|s| s.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run `cmd` to completion with stdout and stderr captured, failing unless it exits
+/// successfully.
+pub fn run_captured(cmd: &mut Command) -> Result<Output, ProcessError> {
+    log::debug!("Running command: {:?}", cmd);
+    let output = cmd.output().map_err(|source| ProcessError::Spawn {
+        command: display_command(cmd),
+        source,
+    })?;
+    if !output.status.success() {
+        return Err(ProcessError::Failed {
+            command: display_command(cmd),
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_captured_success() {
+        let output =
+            run_captured(Command::new("sh").args(["-c", "echo out; echo err >&2"])).unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn test_run_captured_failure() {
+        let err = run_captured(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+            .unwrap_err();
+        let ProcessError::Failed {
+            status,
+            stdout,
+            stderr,
+            ..
+        } = &err
+        else {
+            panic!("expected Failed, got {:?}", err);
+        };
+        assert_eq!(status.code(), Some(3));
+        assert_eq!((stdout.as_str(), stderr.as_str()), ("out\n", "err\n"));
+
+        let message = err.to_string();
+        assert!(message.starts_with("`sh -c echo out; echo err >&2; exit 3` failed"));
+        assert!(message.ends_with("stdout:\nout\n\nstderr:\nerr\n"));
+    }
+
+    #[test]
+    fn test_run_captured_spawn_error() {
+        let err = run_captured(&mut Command::new("/nonexistent/zeroos-test-binary")).unwrap_err();
+        assert!(matches!(err, ProcessError::Spawn { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("Failed to run `/nonexistent/zeroos-test-binary`"));
+    }
+}
@@ -3,6 +3,29 @@ use mini_template as ztpl;
 use std::fs;
 use std::path::Path;
 
+/// A memory region besides the primary RAM, rendered as a line of the `MEMORY` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub origin: usize,
+    pub size: usize,
+    /// Linker attributes such as `rx` or `rw`.
+    pub attrs: String,
+}
+
+impl MemoryRegion {
+    fn end(&self) -> Option<usize> {
+        self.origin.checked_add(self.size)
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "{} ({}) : ORIGIN = {:#x}, LENGTH = {:#x}",
+            self.name, self.attrs, self.origin, self.size
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LinkerConfig {
     pub memory_origin: usize,
@@ -13,8 +36,18 @@ pub struct LinkerConfig {
 
     pub stack_size: usize,
 
+    /// Unmapped gap between the heap end and the stack bottom. When `None`, the template's
+    /// default one-page gap is used and not counted against the heap.
+    pub guard_size: Option<usize>,
+
     pub backtrace: bool,
 
+    /// Symbol the image starts at (`ENTRY(...)`).
+    pub entry_point: String,
+
+    /// Regions declared after the primary [`PRIMARY_REGION`], which holds every section.
+    pub regions: Vec<MemoryRegion>,
+
     template: Option<String>,
 }
 
@@ -31,7 +64,10 @@ impl LinkerConfig {
             memory_size: DEFAULT_MEMORY_SIZE,
             heap_size: None,
             stack_size: DEFAULT_STACK_SIZE,
+            guard_size: None,
             backtrace: false,
+            entry_point: DEFAULT_ENTRY_POINT.to_string(),
+            regions: Vec::new(),
             template: None,
         }
     }
@@ -46,12 +82,38 @@ impl LinkerConfig {
         self
     }
 
+    pub fn with_guard_size(mut self, size: usize) -> Self {
+        self.guard_size = Some(size);
+        self
+    }
+
     pub fn with_memory(mut self, origin: usize, size: usize) -> Self {
         self.memory_origin = origin;
         self.memory_size = size;
         self
     }
 
+    pub fn with_entry_point(mut self, entry_point: impl Into<String>) -> Self {
+        self.entry_point = entry_point.into();
+        self
+    }
+
+    pub fn add_region(
+        mut self,
+        name: impl Into<String>,
+        origin: usize,
+        size: usize,
+        attrs: impl Into<String>,
+    ) -> Self {
+        self.regions.push(MemoryRegion {
+            name: name.into(),
+            origin,
+            size,
+            attrs: attrs.into(),
+        });
+        self
+    }
+
     pub fn with_template(mut self, template: String) -> Self {
         self.template = Some(template);
         self
@@ -62,9 +124,127 @@ impl LinkerConfig {
         self
     }
 
+    /// Like [`heap_size`](Self::heap_size), but errors instead of saturating to zero when the
+    /// stack and guard do not fit in memory.
+    pub fn checked_heap_size(&self) -> Result<usize, String> {
+        if let Some(heap_size) = self.heap_size {
+            return Ok(heap_size);
+        }
+        self.memory_size
+            .checked_sub(self.stack_size)
+            .and_then(|rest| rest.checked_sub(self.guard_size.unwrap_or(0)))
+            .ok_or_else(|| {
+                format!(
+                    "stack size {:#x} plus guard size {:#x} exceeds memory size {:#x}",
+                    self.stack_size,
+                    self.guard_size.unwrap_or(0),
+                    self.memory_size
+                )
+            })
+    }
+
+    /// Check that every size is a nonzero multiple of [`PAGE_SIZE`], that the stack, guard
+    /// and heap fit in memory together, and that the entry point is a symbol name.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut sizes = vec![("memory", self.memory_size), ("stack", self.stack_size)];
+        sizes.extend(self.heap_size.map(|size| ("heap", size)));
+        sizes.extend(self.guard_size.map(|size| ("guard", size)));
+        for (name, size) in sizes {
+            if size == 0 {
+                return Err(format!("{} size must not be zero", name));
+            }
+            if size % PAGE_SIZE != 0 {
+                return Err(format!(
+                    "{} size {:#x} is not a multiple of the page size {:#x}",
+                    name, size, PAGE_SIZE
+                ));
+            }
+        }
+
+        if !is_symbol_name(&self.entry_point) {
+            return Err(format!(
+                "entry point {:?} is not a symbol name",
+                self.entry_point
+            ));
+        }
+        self.validate_regions()?;
+
+        let Some(heap_size) = self.heap_size else {
+            return self.checked_heap_size().map(|_| ());
+        };
+        let total = self
+            .stack_size
+            .checked_add(self.guard_size.unwrap_or(0))
+            .and_then(|total| total.checked_add(heap_size));
+        match total {
+            Some(total) if total <= self.memory_size => Ok(()),
+            _ => Err(format!(
+                "stack size {:#x} plus guard size {:#x} plus heap size {:#x} exceeds memory size {:#x}",
+                self.stack_size,
+                self.guard_size.unwrap_or(0),
+                heap_size,
+                self.memory_size
+            )),
+        }
+    }
+
+    /// Extra regions need distinct symbol names, valid attributes and a nonzero size, and must
+    /// not overlap each other or the primary region.
+    fn validate_regions(&self) -> Result<(), String> {
+        let primary = MemoryRegion {
+            name: PRIMARY_REGION.to_string(),
+            origin: self.memory_origin,
+            size: self.memory_size,
+            attrs: String::new(),
+        };
+        for (i, region) in self.regions.iter().enumerate() {
+            if !is_symbol_name(&region.name) {
+                return Err(format!(
+                    "region name {:?} is not a symbol name",
+                    region.name
+                ));
+            }
+            if region.attrs.is_empty() || !region.attrs.chars().all(|c| "rwxailRWXAIL!".contains(c))
+            {
+                return Err(format!(
+                    "region {} has invalid attributes {:?}",
+                    region.name, region.attrs
+                ));
+            }
+            if region.size == 0 || region.end().is_none() {
+                return Err(format!(
+                    "region {} has invalid size {:#x}",
+                    region.name, region.size
+                ));
+            }
+            for other in std::iter::once(&primary).chain(&self.regions[..i]) {
+                if other.name == region.name {
+                    return Err(format!("region {} is declared twice", region.name));
+                }
+                let overlaps = match (region.end(), other.end()) {
+                    (Some(end), Some(other_end)) => region.origin < other_end && other.origin < end,
+                    _ => true,
+                };
+                if overlaps {
+                    return Err(format!(
+                        "region {} ({:#x}..{:#x}) overlaps {}",
+                        region.name,
+                        region.origin,
+                        region.origin.saturating_add(region.size),
+                        other.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn heap_size(&self) -> usize {
-        self.heap_size
-            .unwrap_or_else(|| self.memory_size.saturating_sub(self.stack_size))
+        self.heap_size.unwrap_or_else(|| {
+            self.memory_size
+                .saturating_sub(self.stack_size)
+                .saturating_sub(self.guard_size.unwrap_or(0))
+        })
     }
 }
 
@@ -74,12 +254,31 @@ pub const DEFAULT_MEMORY_SIZE: usize = 128 * 1024 * 1024;
 
 pub const DEFAULT_STACK_SIZE: usize = 4 * 1024 * 1024;
 
+pub const DEFAULT_GUARD_SIZE: usize = 4096;
+
+pub const DEFAULT_ENTRY_POINT: &str = "_start";
+
+/// The region the templates place every section in (`> RAM`).
+pub const PRIMARY_REGION: &str = "RAM";
+
+/// Whether `name` can be used bare as a linker script symbol or region name.
+fn is_symbol_name(name: &str) -> bool {
+    let is_symbol_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$');
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(is_symbol_char)
+}
+
+/// Granularity the linker script aligns the heap and stack to.
+pub const PAGE_SIZE: usize = 4096;
+
 impl LinkerConfig {
     pub fn render(&self, template: Option<String>) -> String {
         let origin = format!("{:#x}", self.memory_origin);
         let mem_size = format!("{:#x}", self.memory_size);
         let heap_size = format!("{:#x}", self.heap_size());
         let stack_size = format!("{:#x}", self.stack_size);
+        let guard_size = format!("{:#x}", self.guard_size.unwrap_or(DEFAULT_GUARD_SIZE));
 
         let template = template
             .as_deref()
@@ -87,10 +286,17 @@ impl LinkerConfig {
             .unwrap_or(LINKER_SCRIPT_TEMPLATE);
         let ctx = ztpl::Context::new()
             .with_bool("backtrace", self.backtrace)
+            .with_bool("stack_guard", self.guard_size.is_some())
+            .with_str("ENTRY_POINT", self.entry_point.clone())
+            .with_list(
+                "MEMORY_REGIONS",
+                self.regions.iter().map(MemoryRegion::render).collect(),
+            )
             .with_str("MEMORY_ORIGIN", origin)
             .with_str("MEMORY_SIZE", mem_size)
             .with_str("HEAP_SIZE", heap_size)
-            .with_str("STACK_SIZE", stack_size);
+            .with_str("STACK_SIZE", stack_size)
+            .with_str("STACK_GUARD_SIZE", guard_size);
 
         ztpl::render(template, &ctx).unwrap_or_else(|_| template.to_string())
     }
@@ -99,6 +305,9 @@ impl LinkerConfig {
 const LINKER_SCRIPT_TEMPLATE: &str = include_str!("files/linker.ld.template");
 
 pub fn generate_linker_script(config: &LinkerConfig, output_path: &Path) -> Result<()> {
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Invalid memory layout: {}", e))?;
     let script_content = config.render(None);
     fs::write(output_path, script_content)
         .with_context(|| format!("Failed to write linker script to {}", output_path.display()))?;
@@ -133,4 +342,167 @@ mod tests {
 
         assert_eq!(config.heap_size(), 64 * 1024 * 1024);
     }
+
+    #[test]
+    fn test_checked_heap_size() {
+        let config = LinkerConfig::new()
+            .with_memory(0x80000000, 128 * 1024 * 1024)
+            .with_stack_size(8 * 1024 * 1024);
+        assert_eq!(config.checked_heap_size(), Ok(120 * 1024 * 1024));
+
+        let config = config.with_stack_size(256 * 1024 * 1024);
+        assert_eq!(config.heap_size(), 0);
+        let err = config.checked_heap_size().unwrap_err();
+        assert!(err.contains("exceeds memory size"), "{}", err);
+
+        let config = LinkerConfig::new()
+            .with_memory(0x80000000, 1024 * 1024)
+            .with_stack_size(1024 * 1024)
+            .with_guard_size(4096);
+        assert!(config.checked_heap_size().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = LinkerConfig::new()
+            .with_memory(0x80000000, 128 * 1024 * 1024)
+            .with_stack_size(8 * 1024 * 1024)
+            .with_heap_size(64 * 1024 * 1024);
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(LinkerConfig::new().validate(), Ok(()));
+
+        let err = config
+            .clone()
+            .with_heap_size(121 * 1024 * 1024)
+            .validate()
+            .unwrap_err();
+        assert!(err.contains("exceeds memory size"), "{}", err);
+        let err = config
+            .clone()
+            .with_stack_size(256 * 1024 * 1024)
+            .validate()
+            .unwrap_err();
+        assert!(err.contains("exceeds memory size"), "{}", err);
+        assert!(config
+            .clone()
+            .with_heap_size(120 * 1024 * 1024)
+            .with_guard_size(4096)
+            .validate()
+            .is_err());
+
+        let err = config.clone().with_stack_size(0).validate().unwrap_err();
+        assert!(err.contains("stack size must not be zero"), "{}", err);
+        let err = config
+            .clone()
+            .with_heap_size(0x1800)
+            .validate()
+            .unwrap_err();
+        assert!(err.contains("not a multiple of the page size"), "{}", err);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("linker.ld");
+        assert!(
+            generate_linker_script(&config.with_stack_size(usize::MAX & !0xfff), &path).is_err()
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_entry_point() {
+        let script = LinkerConfig::new().render(None);
+        assert_eq!(script.matches("ENTRY(_start)").count(), 1);
+
+        let config = LinkerConfig::new().with_entry_point("my_entry");
+        assert_eq!(config.validate(), Ok(()));
+        let script = config.render(None);
+        assert_eq!(script.matches("ENTRY(my_entry)").count(), 1);
+        assert!(!script.contains("ENTRY(_start)"));
+
+        for bad in ["", "1start", "_start); INCLUDE evil.ld; ENTRY(x"] {
+            let err = LinkerConfig::new()
+                .with_entry_point(bad)
+                .validate()
+                .unwrap_err();
+            assert!(err.contains("not a symbol name"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_memory_regions() {
+        let memory_lines = |script: &str| -> Vec<String> {
+            let start = script.find("MEMORY\n{\n").unwrap() + "MEMORY\n{\n".len();
+            let end = start + script[start..].find('}').unwrap();
+            script[start..end]
+                .lines()
+                .map(|l| l.trim().to_string())
+                .collect()
+        };
+
+        let script = LinkerConfig::new().render(None);
+        assert_eq!(
+            memory_lines(&script),
+            ["RAM (rwx) : ORIGIN = 0x80000000, LENGTH = 0x8000000"]
+        );
+
+        let config = LinkerConfig::new()
+            .with_memory(0x8000_0000, 0x10_0000)
+            .with_stack_size(0x1000)
+            .add_region("FLASH", 0x2000_0000, 0x4_0000, "rx");
+        assert_eq!(config.validate(), Ok(()));
+        let script = config.render(None);
+        assert_eq!(
+            memory_lines(&script),
+            [
+                "RAM (rwx) : ORIGIN = 0x80000000, LENGTH = 0x100000",
+                "FLASH (rx) : ORIGIN = 0x20000000, LENGTH = 0x40000",
+            ]
+        );
+        assert!(script.contains("} > RAM : text"));
+
+        let err = config
+            .clone()
+            .add_region("MMIO", 0x2003_0000, 0x1_0000, "rw")
+            .validate()
+            .unwrap_err();
+        assert!(
+            err.contains("MMIO") && err.contains("overlaps FLASH"),
+            "{}",
+            err
+        );
+        let err = config
+            .clone()
+            .add_region("RAM", 0x1000, 0x1000, "rw")
+            .validate()
+            .unwrap_err();
+        assert!(err.contains("declared twice"), "{}", err);
+        assert!(config
+            .clone()
+            .add_region("IO", 0x1000, 0x1000, "q")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_guard_region() {
+        let config = LinkerConfig::new()
+            .with_memory(0x80000000, 128 * 1024 * 1024)
+            .with_stack_size(8 * 1024 * 1024)
+            .with_guard_size(64 * 1024);
+
+        assert_eq!(config.heap_size(), 120 * 1024 * 1024 - 64 * 1024);
+
+        let script = config.render(None);
+        assert!(script.contains("PROVIDE(__stack_guard_size = 0x10000);"));
+        assert!(
+            script.contains("PROVIDE(_stack_guard_bottom = __stack_bottom - __stack_guard_size);")
+        );
+        assert!(script.contains(&format!("__heap_size = {:#x};", config.heap_size())));
+    }
+
+    #[test]
+    fn test_default_guard_has_no_symbol() {
+        let script = LinkerConfig::new().render(None);
+        assert!(script.contains("PROVIDE(__stack_guard_size = 0x1000);"));
+        assert!(!script.contains("_stack_guard_bottom"));
+    }
 }
@@ -4,6 +4,7 @@ use std::fs;
 use std::path::PathBuf;
 use std::process::exit;
 use tracing::{debug, info};
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
@@ -11,10 +12,32 @@ use tracing_subscriber::EnvFilter;
 #[command(bin_name = "cargo")]
 #[command(version, about, long_about = None)]
 struct Cli {
+    /// Log more (-v for debug, -vv for trace); ignored when RUST_LOG is set
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only log errors; ignored when RUST_LOG is set
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+impl Cli {
+    /// Log level selected by `-v`/`-q`, before `RUST_LOG` is considered.
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::ERROR;
+        }
+        match self.verbose {
+            0 => LevelFilter::INFO,
+            1 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     Zeroos(ZeroosArgs),
@@ -30,13 +53,25 @@ struct ZeroosArgs {
 enum ZeroosCommands {
     Build(ZeroosBuildArgs),
 
+    /// Build a package, then run it on spike
+    Run(ZeroosRunArgs),
+
     BuildMusl(BuildMuslArgs),
 
     InstallMusl(InstallMuslArgs),
 
     FindMusl(FindMuslArgs),
 
+    /// List the toolchain releases available for this host
+    ListMuslReleases(ListMuslReleasesArgs),
+
+    /// Remove an installed musl toolchain
+    CleanMusl(CleanMuslArgs),
+
     Generate(GenerateArgs),
+
+    /// Check the host for the external tools and toolchains ZeroOS needs
+    Doctor(DoctorArgs),
 }
 
 #[derive(Parser)]
@@ -58,6 +93,19 @@ struct ZeroosBuildArgs {
     base: zeroos_build::cmds::BuildArgs,
 }
 
+#[derive(Args, Debug)]
+struct ZeroosRunArgs {
+    #[command(flatten)]
+    base: zeroos_build::cmds::BuildArgs,
+
+    #[command(flatten)]
+    spike: zeroos_build::cmds::SpikeArgs,
+
+    /// Print the binary path and spike command instead of building and running
+    #[arg(long)]
+    dry_run: bool,
+}
+
 #[derive(Args)]
 struct ZeroosGenerateTargetArgs {
     #[command(flatten)]
@@ -68,6 +116,10 @@ struct ZeroosGenerateTargetArgs {
 
     #[arg(long, short = 'o')]
     output: Option<PathBuf>,
+
+    /// Output format: plain JSON, or a Rust `const TARGET_SPEC` for `include!`
+    #[arg(long, value_enum, default_value_t = zeroos_build::cmds::TargetSpecFormat::Json)]
+    emit: zeroos_build::cmds::TargetSpecFormat,
 }
 
 #[derive(Args)]
@@ -92,6 +144,10 @@ struct BuildMuslArgs {
 
     #[arg(long)]
     no_gcc_config: bool,
+
+    /// Parallel make jobs [default: number of logical CPUs]
+    #[arg(long, short = 'j')]
+    jobs: Option<usize>,
 }
 
 #[derive(Parser)]
@@ -100,6 +156,44 @@ struct FindMuslArgs {
     arch: String,
 }
 
+#[derive(Parser)]
+struct ListMuslReleasesArgs {
+    /// GitHub repo in owner/name form (defaults to origin remote or LayerZero-Labs/ZeroOS)
+    #[arg(long)]
+    repo: Option<String>,
+
+    /// Mirror to use instead of GitHub for API requests (defaults to ZEROOS_MUSL_BASE_URL)
+    #[arg(long)]
+    base_url: Option<String>,
+}
+
+#[derive(Parser)]
+struct CleanMuslArgs {
+    #[arg(long, default_value = "riscv64")]
+    arch: String,
+
+    #[arg(long)]
+    output: Option<String>,
+
+    /// Remove the whole output directory, not just this arch's toolchain
+    #[arg(long)]
+    all: bool,
+
+    /// Actually remove; without it the paths are only listed
+    #[arg(long)]
+    force: bool,
+
+    /// Only list what would be removed
+    #[arg(long, conflicts_with = "force")]
+    print: bool,
+}
+
+#[derive(Parser)]
+struct DoctorArgs {
+    #[arg(long, default_value = "riscv64")]
+    arch: String,
+}
+
 #[derive(Parser)]
 struct InstallMuslArgs {
     #[arg(long, default_value = "riscv64")]
@@ -119,22 +213,38 @@ struct InstallMuslArgs {
     /// Replace any existing install
     #[arg(long)]
     force: bool,
+
+    /// Skip checking the download against its published SHA-256
+    #[arg(long, conflicts_with = "sha256")]
+    no_verify: bool,
+
+    /// Expected SHA-256 of the toolchain tarball, overriding the published one
+    #[arg(long, value_name = "HEX")]
+    sha256: Option<String>,
+
+    /// Mirror to use instead of GitHub for API requests and downloads (defaults to
+    /// ZEROOS_MUSL_BASE_URL)
+    #[arg(long)]
+    base_url: Option<String>,
 }
 
 fn main() {
+    let cli = Cli::parse();
+
+    let level = cli.log_level();
     tracing_subscriber::fmt()
         .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::default().add_directive(level.into())),
         )
         .with_target(false)
         .with_level(true)
         .init();
 
-    let cli = Cli::parse();
-
     let result = match cli.command {
         Commands::Zeroos(args) => match args.command {
             ZeroosCommands::Build(args) => build_command(args),
+            ZeroosCommands::Run(args) => run_command(args),
             ZeroosCommands::BuildMusl(args) => {
                 build_musl(args);
                 Ok(())
@@ -147,6 +257,12 @@ fn main() {
                 find_musl(args);
                 Ok(())
             }
+            ZeroosCommands::ListMuslReleases(args) => list_musl_releases(args),
+            ZeroosCommands::CleanMusl(args) => clean_musl(args),
+            ZeroosCommands::Doctor(args) => {
+                doctor(args);
+                Ok(())
+            }
             ZeroosCommands::Generate(gen_args) => match gen_args.command {
                 GenerateCmd::Target(args) => generate_target_command(args),
                 GenerateCmd::Linker(args) => generate_linker_command(args),
@@ -169,14 +285,19 @@ fn expand_tilde(path: &str) -> String {
     path.to_string()
 }
 
-fn build_musl(args: BuildMuslArgs) {
-    let output_dir = match args.output {
+/// `--output`, or `~/.zeroos/musl`.
+fn musl_output_dir(output: Option<String>) -> String {
+    match output {
         Some(path) => expand_tilde(&path),
         None => {
             let home = dirs::home_dir().expect("Could not determine home directory");
             home.join(".zeroos/musl").to_string_lossy().to_string()
         }
-    };
+    }
+}
+
+fn build_musl(args: BuildMuslArgs) {
+    let output_dir = musl_output_dir(args.output);
 
     let gcc_config = if args.no_gcc_config {
         None
@@ -188,7 +309,7 @@ fn build_musl(args: BuildMuslArgs) {
         arch: args.arch.clone(),
         output_dir: output_dir.clone(),
         gcc_config,
-        jobs: None,
+        jobs: args.jobs,
     };
 
     let gcc_config_display = config
@@ -266,14 +387,51 @@ Or set environment variables:
     }
 }
 
-fn install_musl(args: InstallMuslArgs) {
-    let output_dir = match args.output {
-        Some(path) => expand_tilde(&path),
-        None => {
-            let home = dirs::home_dir().expect("Could not determine home directory");
-            home.join(".zeroos/musl").to_string_lossy().to_string()
+fn list_musl_releases(args: ListMuslReleasesArgs) -> Result<()> {
+    let tags =
+        zeroos_build::toolchain::list_musl_releases(args.repo.as_deref(), args.base_url.as_deref())
+            .map_err(anyhow::Error::msg)?;
+    if tags.is_empty() {
+        println!("No musl toolchain releases found for this host");
+    }
+    for tag in tags {
+        println!("{}", tag);
+    }
+    Ok(())
+}
+
+fn clean_musl(args: CleanMuslArgs) -> Result<()> {
+    let output_dir = PathBuf::from(musl_output_dir(args.output));
+    let paths = zeroos_build::toolchain::musl_clean_paths(&output_dir, &args.arch, args.all);
+    if paths.is_empty() {
+        println!(
+            "No {}-linux-musl toolchain under {}",
+            args.arch,
+            output_dir.display()
+        );
+        return Ok(());
+    }
+
+    if !args.force {
+        for path in &paths {
+            println!("{}", path.display());
         }
-    };
+        if !args.print {
+            anyhow::bail!("Refusing to remove the paths above without --force");
+        }
+        return Ok(());
+    }
+
+    for path in zeroos_build::toolchain::clean_musl_toolchain(&output_dir, &args.arch, args.all)
+        .map_err(anyhow::Error::msg)?
+    {
+        println!("Removed {}", path.display());
+    }
+    Ok(())
+}
+
+fn install_musl(args: InstallMuslArgs) {
+    let output_dir = musl_output_dir(args.output);
 
     let config = zeroos_build::toolchain::InstallConfig {
         arch: args.arch.clone(),
@@ -281,6 +439,9 @@ fn install_musl(args: InstallMuslArgs) {
         repo: args.repo.clone(),
         tag: args.tag.clone(),
         force: args.force,
+        verify: !args.no_verify,
+        sha256: args.sha256.clone(),
+        base_url: args.base_url.clone(),
     };
 
     println!(
@@ -308,7 +469,7 @@ Install failed!
 Error: {}
 
 Common issues:
-  - Missing: curl, tar
+  - Missing: curl, tar, sha256sum (or shasum)
   - Network: can't reach GitHub Releases/API
   - Permissions: output dir not writable (use --output ~/path or --force)",
                 e
@@ -318,6 +479,53 @@ Common issues:
     }
 }
 
+fn doctor(args: DoctorArgs) {
+    use zeroos_build::host::doctor::{check_tool, TOOLS};
+
+    let mut missing_essential = false;
+
+    println!("Host tools:");
+    for tool in TOOLS {
+        let status = check_tool(tool);
+        match &status.path {
+            Some(path) => println!(
+                "  [ok]      {:<10} {} ({})",
+                tool.name,
+                status.version.as_deref().unwrap_or("unknown version"),
+                path.display()
+            ),
+            None => {
+                let level = if tool.essential {
+                    missing_essential = true;
+                    "[missing]"
+                } else {
+                    "[warn]   "
+                };
+                println!(
+                    "  {} {:<10} not found in PATH (needed for {})",
+                    level, tool.name, tool.purpose
+                );
+            }
+        }
+    }
+
+    println!("\nMusl toolchain ({}):", args.arch);
+    match zeroos_build::toolchain::discover_toolchain(&args.arch) {
+        Some(paths) => {
+            println!("  [ok]      musl lib: {}", paths.musl_lib.display());
+            println!("  [ok]      gcc lib:  {}", paths.gcc_lib.display());
+        }
+        None => println!(
+            "  [warn]    not found (needed for std builds; run `cargo zeroos install-musl`)"
+        ),
+    }
+
+    if missing_essential {
+        eprintln!("\nSome essential tools are missing.");
+        exit(1);
+    }
+}
+
 fn print_toolchain_paths(paths: &zeroos_build::toolchain::ToolchainPaths, header: &str) {
     println!(
         r"
@@ -361,20 +569,23 @@ fn generate_target_command(cli_args: ZeroosGenerateTargetArgs) -> Result<()> {
         &cli_args.base,
         TargetRenderOptions {
             backtrace: cli_args.backtrace,
+            ..Default::default()
         },
     )
     .map_err(|e| anyhow::anyhow!("{}", e))?;
 
-    let output_path = cli_args
-        .output
-        .unwrap_or_else(|| PathBuf::from(format!("{}.json", target_triple)));
+    let content = cli_args.emit.format(&json_content);
+
+    let output_path = cli_args.output.unwrap_or_else(|| {
+        PathBuf::from(format!("{}.{}", target_triple, cli_args.emit.extension()))
+    });
 
     if let Some(parent) = output_path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
     }
 
-    fs::write(&output_path, &json_content)
+    fs::write(&output_path, &content)
         .with_context(|| format!("Failed to write target spec to {}", output_path.display()))?;
 
     info!("Generated target spec: {}", output_path.display());
@@ -408,26 +619,89 @@ fn generate_linker_command(cli_args: ZeroosGenerateLinkerArgs) -> Result<()> {
 }
 
 fn build_command(args: ZeroosBuildArgs) -> Result<()> {
-    use zeroos_build::cmds::{build_binary, find_workspace_root, get_or_build_toolchain, StdMode};
+    use zeroos_build::cmds::find_workspace_root;
 
     debug!("build_command: {:?}", args);
 
     let workspace_root = find_workspace_root()?;
     debug!("workspace_root: {}", workspace_root.display());
 
-    let fully = args.base.mode == StdMode::Std || args.base.fully;
+    build_package(&workspace_root, &args.base)
+}
+
+fn build_package(workspace_root: &PathBuf, args: &zeroos_build::cmds::BuildArgs) -> Result<()> {
+    use zeroos_build::cmds::{build_binary, get_or_build_toolchain, StdMode};
+
+    let fully = args.mode == StdMode::Std || args.fully;
 
-    let toolchain_paths = if args.base.mode == StdMode::Std || fully {
+    let toolchain_paths = if args.mode == StdMode::Std || fully {
         Some(get_or_build_toolchain(
-            args.base.musl_lib_path.clone(),
-            args.base.gcc_lib_path.clone(),
+            args.musl_lib_path.clone(),
+            args.gcc_lib_path.clone(),
             fully,
         )?)
     } else {
         None
     };
 
-    build_binary(&workspace_root, &args.base, toolchain_paths, None)?;
+    build_binary(workspace_root, args, toolchain_paths, None)
+}
 
-    Ok(())
+fn run_command(args: ZeroosRunArgs) -> Result<()> {
+    use zeroos_build::cmds::{find_workspace_root, RunPlan};
+
+    debug!("run_command: {:?}", args);
+
+    let workspace_root = find_workspace_root()?;
+    let target_dir = zeroos_build::project::get_target_directory(&workspace_root)?;
+    let plan = RunPlan::for_build(&target_dir, &args.base, args.spike)?;
+
+    if args.dry_run {
+        let spike = plan.spike.spike.clone().unwrap_or_else(|| "spike".into());
+        println!("binary: {}", plan.binary.display());
+        println!(
+            "command: {} {}",
+            spike.display(),
+            plan.spike_args().join(" ")
+        );
+        return Ok(());
+    }
+
+    zeroos_build::cmds::RunEvent::BuildStart {
+        package: args.base.package.clone(),
+        target: args.base.target().to_string(),
+    }
+    .emit(plan.spike.events, &mut std::io::stderr());
+    build_package(&workspace_root, &args.base)?;
+    plan.run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(args: &[&str]) -> LevelFilter {
+        let argv = ["cargo", "zeroos"].iter().chain(args).chain(&["doctor"]);
+        Cli::try_parse_from(argv).unwrap().log_level()
+    }
+
+    #[test]
+    fn test_log_level_from_flags() {
+        assert_eq!(level(&[]), LevelFilter::INFO);
+        assert_eq!(level(&["-v"]), LevelFilter::DEBUG);
+        assert_eq!(level(&["-vv"]), LevelFilter::TRACE);
+        assert_eq!(level(&["-v", "-v", "-v"]), LevelFilter::TRACE);
+        assert_eq!(level(&["--quiet"]), LevelFilter::ERROR);
+    }
+
+    #[test]
+    fn test_log_flags_after_subcommand() {
+        let cli = Cli::try_parse_from(["cargo", "zeroos", "doctor", "-v"]).unwrap();
+        assert_eq!(cli.log_level(), LevelFilter::DEBUG);
+    }
+
+    #[test]
+    fn test_quiet_conflicts_with_verbose() {
+        assert!(Cli::try_parse_from(["cargo", "zeroos", "-q", "-v", "doctor"]).is_err());
+    }
 }
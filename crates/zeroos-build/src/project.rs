@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::host::process::{run_captured, ProcessError};
+
 pub fn find_workspace_root() -> Result<PathBuf> {
     let current_dir = std::env::current_dir().context("Failed to get current directory")?;
 
@@ -24,17 +26,17 @@ pub fn find_workspace_root() -> Result<PathBuf> {
 }
 
 pub fn get_target_directory(workspace_root: &PathBuf) -> Result<PathBuf> {
-    let output = Command::new("cargo")
-        .arg("metadata")
-        .arg("--format-version=1")
-        .arg("--no-deps")
-        .current_dir(workspace_root)
-        .output()
-        .context("Failed to run cargo metadata")?;
-
-    if !output.status.success() {
-        return Ok(workspace_root.join("target"));
-    }
+    let output = match run_captured(
+        Command::new("cargo")
+            .arg("metadata")
+            .arg("--format-version=1")
+            .arg("--no-deps")
+            .current_dir(workspace_root),
+    ) {
+        Ok(output) => output,
+        Err(ProcessError::Failed { .. }) => return Ok(workspace_root.join("target")),
+        Err(e) => return Err(e).context("Failed to run cargo metadata"),
+    };
 
     let metadata: serde_json::Value =
         serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata")?;
@@ -57,6 +59,56 @@ pub fn detect_profile(args: &[String]) -> String {
     }
 }
 
+/// Fill in the `<WORKSPACE>`, `<PACKAGE>` (the package's directory), `<TARGET>` and `<PROFILE>`
+/// placeholders of a path for an emitted build artifact.
+pub fn expand_emit_path(
+    template: &str,
+    workspace: &Path,
+    package_dir: &Path,
+    target: &str,
+    profile: &str,
+) -> String {
+    template
+        .replace("<WORKSPACE>", &workspace.display().to_string())
+        .replace("<PACKAGE>", &package_dir.display().to_string())
+        .replace("<TARGET>", target)
+        .replace("<PROFILE>", profile)
+}
+
+/// The directory holding `package_name`'s manifest, from `cargo metadata`.
+pub fn resolve_package_dir(workspace_root: &Path, package_name: &str) -> Result<PathBuf> {
+    let output = run_captured(
+        Command::new("cargo")
+            .args(["metadata", "--format-version", "1", "--no-deps"])
+            .arg("--manifest-path")
+            .arg(workspace_root.join("Cargo.toml")),
+    )?;
+
+    let v: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata JSON")?;
+
+    let packages = v
+        .get("packages")
+        .and_then(|p| p.as_array())
+        .ok_or_else(|| anyhow::anyhow!("cargo metadata JSON: missing `packages` array"))?;
+
+    for pkg in packages {
+        if pkg.get("name").and_then(|n| n.as_str()) != Some(package_name) {
+            continue;
+        }
+        let manifest = pkg
+            .get("manifest_path")
+            .and_then(|m| m.as_str())
+            .ok_or_else(|| anyhow::anyhow!("cargo metadata JSON: package missing manifest_path"))?;
+        let manifest_dir = Path::new(manifest)
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("Invalid manifest_path for {}", package_name))?;
+        return Ok(manifest_dir.to_path_buf());
+    }
+
+    anyhow::bail!("Package not found in cargo metadata: {}", package_name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,3 +1,5 @@
+use super::{CodeModel, LLVMConfig, TargetConfig};
+
 #[derive(Debug, Clone)]
 pub struct ArchSpec {
     pub arch: &'static str,
@@ -9,12 +11,43 @@ pub struct ArchSpec {
     pub max_atomic_width: u32,
     /// Target endianness
     pub endian: &'static str,
+    /// Default LLVM data layout string
+    pub data_layout: &'static str,
+    /// Default LLVM target features
+    pub features: &'static str,
+    /// Default LLVM ABI (e.g., "lp64"; empty for the architecture's only ABI)
+    pub llvm_abi: &'static str,
+    /// Default code model
+    pub code_model: CodeModel,
+}
+
+impl ArchSpec {
+    /// LLVM settings for `config` from this architecture's defaults: the LLVM target is the
+    /// triple with the base architecture and an `unknown` vendor.
+    pub fn default_llvm_config(&self, config: &TargetConfig) -> LLVMConfig {
+        let llvm_target = TargetConfig::new(
+            self.arch.to_string(),
+            "unknown".to_string(),
+            config.os.clone(),
+            config.abi.clone(),
+        )
+        .target_triple();
+        LLVMConfig {
+            llvm_target,
+            features: self.features.to_string(),
+            abi: self.llvm_abi.to_string(),
+            data_layout: self.data_layout.to_string(),
+        }
+    }
 }
 
 pub fn extract_base_arch(arch: &str) -> &str {
     match arch {
         a if a.starts_with("riscv64") => "riscv64",
         a if a.starts_with("riscv32") => "riscv32",
+        // Big-endian needs its own data layout and LLVM target; left unsupported.
+        a if a.starts_with("aarch64_be") => arch,
+        a if a.starts_with("aarch64") => "aarch64",
         _ => arch,
     }
 }
@@ -28,6 +61,10 @@ pub fn get_arch_spec(arch: &str) -> ArchSpec {
             pointer_width: "64",
             max_atomic_width: 64,
             endian: "little",
+            data_layout: "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128",
+            features: "+m,+a,+c",
+            llvm_abi: "lp64",
+            code_model: CodeModel::Medium,
         },
         "riscv32" => ArchSpec {
             arch: "riscv32",
@@ -35,10 +72,65 @@ pub fn get_arch_spec(arch: &str) -> ArchSpec {
             pointer_width: "32",
             max_atomic_width: 32,
             endian: "little",
+            data_layout: "e-m:e-p:32:32-i64:64-n32-S128",
+            features: "+m,+a,+c",
+            llvm_abi: "ilp32",
+            code_model: CodeModel::Medium,
+        },
+        "aarch64" => ArchSpec {
+            arch: "aarch64",
+            cpu: "generic",
+            pointer_width: "64",
+            max_atomic_width: 128,
+            endian: "little",
+            data_layout: "e-m:e-p270:32:32-p271:32:32-p272:64:64-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128-Fn32",
+            features: "+v8a,+strict-align,+neon,+fp-armv8",
+            llvm_abi: "",
+            code_model: CodeModel::Small,
         },
         _ => panic!(
-            "Unsupported architecture: {}. Currently only riscv64 and riscv32 are supported.",
+            "Unsupported architecture: {}. Currently only riscv64, riscv32 and aarch64 are supported.",
             arch
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aarch64_arch_spec() {
+        let spec = get_arch_spec("aarch64");
+        assert_eq!(spec.arch, "aarch64");
+        assert_eq!(spec.cpu, "generic");
+        assert_eq!(spec.pointer_width, "64");
+        assert_eq!(spec.max_atomic_width, 128);
+        assert_eq!(spec.endian, "little");
+        assert!(spec.data_layout.starts_with("e-m:e-"));
+        assert_eq!(extract_base_arch("aarch64_be"), "aarch64_be");
+
+        let config = crate::spec::parse_target_triple("aarch64-unknown-none").unwrap();
+        let llvm = spec.default_llvm_config(&config);
+        assert_eq!(llvm.llvm_target, "aarch64-unknown-none");
+        assert_eq!(llvm.abi, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported architecture: aarch64_be")]
+    fn test_aarch64_be_unsupported() {
+        get_arch_spec("aarch64_be");
+    }
+
+    #[test]
+    fn test_riscv_defaults_match_profile() {
+        let profile =
+            crate::spec::load_target_profile(crate::spec::PROFILE_RISCV64IMAC_ZERO_LINUX_MUSL)
+                .unwrap();
+        let llvm = get_arch_spec(&profile.config.arch).default_llvm_config(&profile.config);
+        assert_eq!(llvm.llvm_target, profile.llvm_config.llvm_target);
+        assert_eq!(llvm.features, profile.llvm_config.features);
+        assert_eq!(llvm.abi, profile.llvm_config.abi);
+        assert_eq!(llvm.data_layout, profile.llvm_config.data_layout);
+    }
+}
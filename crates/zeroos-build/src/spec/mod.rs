@@ -10,6 +10,8 @@ pub use profiles::{
     list_profiles, load_target_profile, TargetProfile, PROFILE_RISCV64IMAC_ZERO_LINUX_MUSL,
 };
 pub use target::TargetConfig;
-pub use utils::{parse_target_triple, TargetRenderOptions};
+pub use utils::{
+    parse_target_triple, validate_target_spec, CodeModel, RelocationModel, TargetRenderOptions,
+};
 
 const GENERIC_LINUX_TEMPLATE: &str = include_str!("../files/generic-linux.json.template");
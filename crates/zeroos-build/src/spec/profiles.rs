@@ -1,4 +1,4 @@
-use super::{ArchSpec, LLVMConfig, TargetConfig};
+use super::{ArchSpec, CodeModel, LLVMConfig, TargetConfig};
 
 pub const PROFILE_RISCV64IMAC_ZERO_LINUX_MUSL: &str = "riscv64imac-zero-linux-musl";
 
@@ -23,6 +23,10 @@ pub fn load_target_profile(profile: &str) -> Option<TargetProfile> {
                 pointer_width: "64",
                 max_atomic_width: 64,
                 endian: "little",
+                data_layout: "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128",
+                features: "+m,+a,+c",
+                llvm_abi: "lp64",
+                code_model: CodeModel::Medium,
             },
             llvm_config: LLVMConfig {
                 llvm_target: "riscv64-unknown-linux-musl".to_string(),
@@ -4,14 +4,65 @@ use crate::spec::llvm::LLVMConfig;
 use crate::spec::ArchSpec;
 use mini_template as ztpl;
 
+/// `relocation-model` of a target spec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RelocationModel {
+    /// Position-dependent code, for images loaded at their link address
+    #[default]
+    Static,
+    Pic,
+    Pie,
+}
+
+impl RelocationModel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RelocationModel::Static => "static",
+            RelocationModel::Pic => "pic",
+            RelocationModel::Pie => "pie",
+        }
+    }
+}
+
+/// `code-model` of a target spec; which ones LLVM accepts depends on the architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CodeModel {
+    Tiny,
+    Small,
+    Kernel,
+    /// RISC-V `medany`: code and data within 2 GiB of each other, anywhere in memory
+    Medium,
+    Large,
+}
+
+impl CodeModel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CodeModel::Tiny => "tiny",
+            CodeModel::Small => "small",
+            CodeModel::Kernel => "kernel",
+            CodeModel::Medium => "medium",
+            CodeModel::Large => "large",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct TargetRenderOptions {
     pub backtrace: bool,
+    /// Defaults to [`RelocationModel::Static`].
+    pub relocation_model: Option<RelocationModel>,
+    /// Defaults to the architecture's [`ArchSpec::code_model`].
+    pub code_model: Option<CodeModel>,
 }
 
 impl Default for TargetRenderOptions {
     fn default() -> Self {
-        Self { backtrace: true }
+        Self {
+            backtrace: true,
+            relocation_model: None,
+            code_model: None,
+        }
     }
 }
 
@@ -49,8 +100,17 @@ impl TargetConfig {
         llvm_config: &LLVMConfig,
         opts: TargetRenderOptions,
     ) -> Result<String, String> {
-        let template = GENERIC_LINUX_TEMPLATE;
+        self.render_template(GENERIC_LINUX_TEMPLATE, arch_spec, llvm_config, opts)
+    }
 
+    /// Render `template` as a target spec and check the result with [`validate_target_spec`].
+    pub fn render_template(
+        &self,
+        template: &str,
+        arch_spec: &ArchSpec,
+        llvm_config: &LLVMConfig,
+        opts: TargetRenderOptions,
+    ) -> Result<String, String> {
         let ctx = ztpl::Context::new()
             .with_str("ARCH", arch_spec.arch)
             .with_str("CPU", arch_spec.cpu)
@@ -64,9 +124,112 @@ impl TargetConfig {
             .with_str("ENV", &self.abi)
             .with_str("VENDOR", &self.vendor)
             .with_str("MAX_ATOMIC_WIDTH", arch_spec.max_atomic_width.to_string())
+            .with_str(
+                "RELOCATION_MODEL",
+                opts.relocation_model.unwrap_or_default().as_str(),
+            )
+            .with_str(
+                "CODE_MODEL",
+                opts.code_model.unwrap_or(arch_spec.code_model).as_str(),
+            )
             // JSON booleans (rendered without quotes in template)
             .with_str("BACKTRACE", if opts.backtrace { "true" } else { "false" });
 
-        ztpl::render(template, &ctx).map_err(|e| e.to_string())
+        let json = ztpl::render(template, &ctx).map_err(|e| e.to_string())?;
+        validate_target_spec(&json)?;
+        Ok(json)
+    }
+}
+
+/// Check that a rendered target spec is a JSON object with the keys rustc cannot do without,
+/// so a broken template fails here rather than deep inside cargo.
+pub fn validate_target_spec(json: &str) -> Result<(), String> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("target spec is not valid JSON: {}", e))?;
+    let spec = value
+        .as_object()
+        .ok_or_else(|| "target spec is not a JSON object".to_string())?;
+
+    for key in ["llvm-target", "data-layout", "arch", "os"] {
+        match spec.get(key) {
+            Some(serde_json::Value::String(s)) if !s.is_empty() => {}
+            Some(serde_json::Value::String(_)) => {
+                return Err(format!("target spec key \"{}\" is empty", key))
+            }
+            Some(other) => {
+                return Err(format!(
+                    "target spec key \"{}\" must be a string, found {}",
+                    key, other
+                ))
+            }
+            None => return Err(format!("target spec is missing \"{}\"", key)),
+        }
+    }
+
+    // Older rustc wants a string here, newer an integer.
+    let width = spec
+        .get("target-pointer-width")
+        .ok_or_else(|| "target spec is missing \"target-pointer-width\"".to_string())?;
+    let parsed = match width {
+        serde_json::Value::String(s) => s.parse::<u32>().ok(),
+        serde_json::Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        _ => None,
+    };
+    match parsed {
+        Some(16 | 32 | 64) => Ok(()),
+        _ => Err(format!(
+            "target spec key \"target-pointer-width\" must be 16, 32 or 64, found {}",
+            width
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::get_arch_spec;
+
+    fn render(template: &str) -> Result<String, String> {
+        let config = parse_target_triple("riscv64imac-zero-linux-musl").unwrap();
+        let arch_spec = get_arch_spec(&config.arch);
+        let llvm_config = arch_spec.default_llvm_config(&config);
+        config.render_template(
+            template,
+            &arch_spec,
+            &llvm_config,
+            TargetRenderOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_generic_template_validates() {
+        assert!(render(GENERIC_LINUX_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn test_broken_template_is_rejected() {
+        // A typo'd placeholder name renders as an error from the template engine itself.
+        assert!(render(r#"{"arch": "{{ ARHC }}"}"#).is_err());
+
+        let missing = GENERIC_LINUX_TEMPLATE.replace("\"llvm-target\": \"{{ LLVM_TARGET }}\",", "");
+        assert_eq!(
+            render(&missing).unwrap_err(),
+            "target spec is missing \"llvm-target\""
+        );
+
+        let empty = GENERIC_LINUX_TEMPLATE.replace("{{ DATA_LAYOUT }}", "");
+        assert!(render(&empty)
+            .unwrap_err()
+            .contains("\"data-layout\" is empty"));
+
+        let unquoted = GENERIC_LINUX_TEMPLATE.replace("\"{{ ARCH }}\"", "{{ ARCH }}");
+        assert!(render(&unquoted)
+            .unwrap_err()
+            .starts_with("target spec is not valid JSON"));
+
+        let width = GENERIC_LINUX_TEMPLATE.replace("{{ POINTER_WIDTH }}", "{{ ENDIAN }}");
+        assert!(render(&width)
+            .unwrap_err()
+            .contains("\"target-pointer-width\" must be 16, 32 or 64"));
     }
 }
@@ -0,0 +1,162 @@
+//! Remembering where the toolchain was found, so builds skip walking the search directories.
+
+extern crate std;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use tracing::debug;
+
+use super::ToolchainPaths;
+
+/// Toolchain paths last resolved for one arch, with the mtimes of the libraries that proved
+/// them valid.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct CachedPaths {
+    musl_lib: PathBuf,
+    gcc_lib: PathBuf,
+    libc_mtime: u64,
+    libgcc_mtime: u64,
+}
+
+/// The cache file's contents, keyed by arch.
+type Cache = BTreeMap<String, CachedPaths>;
+
+/// `~/.zeroos/toolchain-cache.json`.
+pub fn default_cache_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".zeroos/toolchain-cache.json"))
+}
+
+/// Modification time in nanoseconds since the epoch, if `path` exists.
+fn mtime(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos() as u64)
+}
+
+impl CachedPaths {
+    fn new(paths: &ToolchainPaths) -> Option<Self> {
+        Some(Self {
+            musl_lib: paths.musl_lib.clone(),
+            gcc_lib: paths.gcc_lib.clone(),
+            libc_mtime: mtime(&paths.musl_lib.join("libc.a"))?,
+            libgcc_mtime: mtime(&paths.gcc_lib.join("libgcc.a"))?,
+        })
+    }
+
+    /// The paths, if both libraries are still there and unchanged.
+    fn validate(&self) -> Option<ToolchainPaths> {
+        let fresh = mtime(&self.musl_lib.join("libc.a")) == Some(self.libc_mtime)
+            && mtime(&self.gcc_lib.join("libgcc.a")) == Some(self.libgcc_mtime);
+        fresh.then(|| ToolchainPaths {
+            musl_lib: self.musl_lib.clone(),
+            gcc_lib: self.gcc_lib.clone(),
+        })
+    }
+}
+
+fn load(cache_path: &Path) -> Cache {
+    fs::read(cache_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+/// Best effort: a cache that cannot be written just means discovery runs again next time.
+fn store(cache_path: &Path, cache: &Cache) {
+    let written = cache_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| {
+            let json = serde_json::to_vec_pretty(cache).map_err(std::io::Error::other)?;
+            fs::write(cache_path, json)
+        });
+    if let Err(e) = written {
+        debug!(
+            "Failed to write toolchain cache {}: {}",
+            cache_path.display(),
+            e
+        );
+    }
+}
+
+/// The cached paths for `arch` if they are still valid, else those from `discover`, which are
+/// then cached. A stale entry is dropped even if discovery finds nothing.
+pub fn discover_cached(
+    cache_path: &Path,
+    arch: &str,
+    discover: impl FnOnce(&str) -> Option<ToolchainPaths>,
+) -> Option<ToolchainPaths> {
+    let mut cache = load(cache_path);
+    if let Some(entry) = cache.get(arch) {
+        if let Some(paths) = entry.validate() {
+            debug!("Using cached toolchain paths for {}", arch);
+            return Some(paths);
+        }
+        debug!("Cached toolchain paths for {} are stale", arch);
+    }
+
+    let found = discover(arch);
+    match found.as_ref().and_then(CachedPaths::new) {
+        Some(entry) => cache.insert(arch.to_string(), entry),
+        None => cache.remove(arch),
+    };
+    store(cache_path, &cache);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_toolchain(root: &Path) -> ToolchainPaths {
+        let paths = ToolchainPaths {
+            musl_lib: root.join("riscv64-linux-musl/lib"),
+            gcc_lib: root.join("lib/gcc/riscv64-linux-musl/9.4.0"),
+        };
+        fs::create_dir_all(&paths.musl_lib).unwrap();
+        fs::create_dir_all(&paths.gcc_lib).unwrap();
+        fs::write(paths.musl_lib.join("libc.a"), "").unwrap();
+        fs::write(paths.gcc_lib.join("libgcc.a"), "").unwrap();
+        paths
+    }
+
+    #[test]
+    fn test_cache_hit_skips_discovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("toolchain-cache.json");
+        let toolchain = fake_toolchain(dir.path());
+
+        let found = discover_cached(&cache_path, "riscv64", |_| Some(toolchain.clone())).unwrap();
+        assert_eq!(found.musl_lib, toolchain.musl_lib);
+        assert!(cache_path.exists());
+
+        let cached = discover_cached(&cache_path, "riscv64", |_| panic!("cache was not used"));
+        assert_eq!(cached.unwrap().gcc_lib, toolchain.gcc_lib);
+    }
+
+    #[test]
+    fn test_stale_cache_falls_back_to_discovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("toolchain-cache.json");
+        let old = fake_toolchain(&dir.path().join("old"));
+        let new = fake_toolchain(&dir.path().join("new"));
+        discover_cached(&cache_path, "riscv64", |_| Some(old.clone())).unwrap();
+
+        fs::remove_file(old.musl_lib.join("libc.a")).unwrap();
+        let mut discovered = false;
+        let found = discover_cached(&cache_path, "riscv64", |_| {
+            discovered = true;
+            Some(new.clone())
+        });
+        assert!(discovered);
+        assert_eq!(found.unwrap().musl_lib, new.musl_lib);
+        assert_eq!(load(&cache_path)["riscv64"].musl_lib, new.musl_lib);
+
+        // Nothing found: the stale entry goes.
+        fs::remove_file(new.gcc_lib.join("libgcc.a")).unwrap();
+        assert!(discover_cached(&cache_path, "riscv64", |_| None).is_none());
+        assert!(load(&cache_path).is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+extern crate std;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a musl toolchain for `arch` installed under `output_dir` consists of, limited to the
+/// paths that exist. With `all`, that is the whole of `output_dir` instead.
+pub fn musl_clean_paths(output_dir: &Path, arch: &str, all: bool) -> Vec<PathBuf> {
+    if all {
+        return [output_dir.to_path_buf()]
+            .into_iter()
+            .filter(|p| p.exists())
+            .collect();
+    }
+
+    let triple = format!("{}-linux-musl", arch);
+    let mut paths = vec![
+        output_dir.join(&triple),
+        output_dir.join("lib/gcc").join(&triple),
+        output_dir.join("libexec/gcc").join(&triple),
+    ];
+    // The cross tools, e.g. `bin/riscv64-linux-musl-gcc`.
+    if let Ok(entries) = fs::read_dir(output_dir.join("bin")) {
+        let prefix = format!("{}-", triple);
+        let mut tools: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|e| e.path())
+            .collect();
+        tools.sort();
+        paths.extend(tools);
+    }
+    paths.retain(|p| p.symlink_metadata().is_ok());
+    paths
+}
+
+/// Remove the paths `musl_clean_paths` lists, returning them.
+pub fn clean_musl_toolchain(
+    output_dir: &Path,
+    arch: &str,
+    all: bool,
+) -> Result<Vec<PathBuf>, String> {
+    let paths = musl_clean_paths(output_dir, arch, all);
+    for path in &paths {
+        let result = if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        };
+        result.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A two-arch install laid out like musl-cross-make's output.
+    fn fake_install(root: &Path) {
+        for arch in ["riscv64", "riscv32"] {
+            let triple = format!("{}-linux-musl", arch);
+            fs::create_dir_all(root.join(&triple).join("lib")).unwrap();
+            fs::write(root.join(&triple).join("lib/libc.a"), "").unwrap();
+            fs::create_dir_all(root.join("lib/gcc").join(&triple).join("9.4.0")).unwrap();
+            fs::create_dir_all(root.join("bin")).unwrap();
+            fs::write(root.join("bin").join(format!("{}-gcc", triple)), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_clean_removes_only_the_arch() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("musl");
+        fake_install(&root);
+
+        let listed = musl_clean_paths(&root, "riscv64", false);
+        assert_eq!(
+            listed,
+            [
+                root.join("riscv64-linux-musl"),
+                root.join("lib/gcc/riscv64-linux-musl"),
+                root.join("bin/riscv64-linux-musl-gcc"),
+            ]
+        );
+        // Listing is a dry run.
+        assert!(root.join("riscv64-linux-musl").exists());
+
+        assert_eq!(
+            clean_musl_toolchain(&root, "riscv64", false).unwrap(),
+            listed
+        );
+        assert!(listed.iter().all(|p| !p.exists()));
+        assert!(root.join("riscv32-linux-musl/lib/libc.a").exists());
+        assert!(root.join("bin/riscv32-linux-musl-gcc").exists());
+
+        // Nothing left to do the second time.
+        assert!(clean_musl_toolchain(&root, "riscv64", false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_clean_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("musl");
+        fake_install(&root);
+
+        assert_eq!(
+            clean_musl_toolchain(&root, "riscv64", true).unwrap(),
+            std::slice::from_ref(&root)
+        );
+        assert!(!root.exists());
+        assert!(musl_clean_paths(&root, "riscv64", true).is_empty());
+    }
+}
@@ -7,8 +7,10 @@ use std::process::Command;
 use tracing::{debug, info};
 
 use super::{find_toolchain, ToolchainConfig, ToolchainPaths};
+use crate::host::process::run_captured;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct InstallConfig {
     /// Toolchain architecture (e.g. "riscv64", "riscv32")
     pub arch: String,
@@ -20,6 +22,13 @@ pub struct InstallConfig {
     pub tag: Option<String>,
     /// If true, replace any existing install.
     pub force: bool,
+    /// Check the downloaded tarball against its published SHA-256.
+    pub verify: bool,
+    /// Expected SHA-256 (hex), overriding the one published with the release.
+    pub sha256: Option<String>,
+    /// Mirror serving both the GitHub API paths and the release downloads. Falls back to
+    /// `ZEROOS_MUSL_BASE_URL`, then GitHub.
+    pub base_url: Option<String>,
 }
 
 impl Default for InstallConfig {
@@ -34,6 +43,9 @@ impl Default for InstallConfig {
             repo: None,
             tag: None,
             force: false,
+            verify: true,
+            sha256: None,
+            base_url: None,
         }
     }
 }
@@ -138,29 +150,52 @@ fn add_github_api_headers(cmd: &mut Command) {
     }
 }
 
-fn find_asset_download_url(
-    repo: &str,
-    tag: Option<&str>,
-    platform: &str,
-    arch: &str,
-) -> Result<String, String> {
-    let api_url = if let Some(tag) = tag {
-        format!(
-            "https://api.github.com/repos/{}/releases/tags/{}",
-            repo, tag
-        )
-    } else {
-        format!("https://api.github.com/repos/{}/releases/latest", repo)
-    };
+/// Compression of a toolchain tarball, from its file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl ArchiveFormat {
+    const ALL: [Self; 3] = [Self::Gzip, Self::Xz, Self::Zstd];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => ".tar.gz",
+            Self::Xz => ".tar.xz",
+            Self::Zstd => ".tar.zst",
+        }
+    }
 
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|f| name.ends_with(f.extension()))
+    }
+
+    /// `tar` arguments that extract this format from the archive that follows them.
+    pub fn tar_extract_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Gzip => &["xzf"],
+            Self::Xz => &["xJf"],
+            Self::Zstd => &["--zstd", "-xf"],
+        }
+    }
+}
+
+/// GET a GitHub API URL and parse the JSON response.
+fn github_api_get(api_url: &str) -> Result<serde_json::Value, String> {
     let tmp = tempfile::Builder::new()
         .prefix("zeroos-musl-release-")
         .tempfile()
         .map_err(|e| format!("Failed to create temp file: {}", e))?;
     let tmp_path = tmp.path().to_path_buf();
 
+    // No `-f`: the body of an error response says why, e.g. that the rate limit was hit.
     let mut cmd = Command::new("curl");
-    cmd.arg("-fsSL")
+    cmd.arg("-sSL")
         .arg("--retry")
         .arg("5")
         .arg("--retry-all-errors")
@@ -168,9 +203,16 @@ fn find_asset_download_url(
         .arg("1")
         .arg("-o")
         .arg(&tmp_path)
-        .arg(&api_url);
+        .arg("-w")
+        .arg("%{http_code}")
+        .arg(api_url);
     add_github_api_headers(&mut cmd);
-    run(&mut cmd)?;
+    let out = run_captured(&mut cmd).map_err(|e| e.to_string())?;
+    let status = String::from_utf8_lossy(&out.stdout);
+    let status: u16 = status
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unexpected HTTP status from curl: {:?}", status))?;
 
     let bytes = fs::read(&tmp_path).map_err(|e| {
         format!(
@@ -179,43 +221,327 @@ fn find_asset_download_url(
             e
         )
     })?;
-    let v: serde_json::Value = serde_json::from_slice(&bytes)
-        .map_err(|e| format!("Invalid JSON from GitHub API: {}", e))?;
+    parse_api_response(status, &bytes)
+}
+
+/// The JSON body of a successful response, or an error saying what went wrong. Running out of
+/// the anonymous rate limit is common enough on shared runners to get its own message.
+fn parse_api_response(status: u16, body: &[u8]) -> Result<serde_json::Value, String> {
+    if (200..300).contains(&status) {
+        return serde_json::from_slice(body)
+            .map_err(|e| format!("Invalid JSON from GitHub API: {}", e));
+    }
+
+    let message = serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+    if matches!(status, 403 | 429) && message.to_ascii_lowercase().contains("rate limit") {
+        return Err(format!(
+            "GitHub API rate limit exceeded (HTTP {}): {}\nSet GITHUB_TOKEN to raise the limit.",
+            status, message
+        ));
+    }
+    Err(format!("GitHub API returned HTTP {}: {}", status, message))
+}
+
+/// Tags of the releases in a GitHub release list that have a toolchain asset for
+/// `platform`/`arch`, newest first. Drafts are skipped.
+fn tags_with_toolchain(
+    releases: &serde_json::Value,
+    platform: &str,
+    arch: &str,
+) -> Result<Vec<String>, String> {
+    let releases = releases
+        .as_array()
+        .ok_or_else(|| "GitHub API response is not a list of releases".to_string())?;
+    let mut found: Vec<(&str, &str)> = releases
+        .iter()
+        .filter(|r| !r.get("draft").and_then(|d| d.as_bool()).unwrap_or(false))
+        .filter(|r| find_asset(r, platform, arch).is_ok())
+        .filter_map(|r| {
+            let tag = r.get("tag_name")?.as_str()?;
+            let published = r
+                .get("published_at")
+                .and_then(|p| p.as_str())
+                .or_else(|| r.get("created_at")?.as_str())
+                .unwrap_or("");
+            Some((published, tag))
+        })
+        .collect();
+    // RFC 3339 timestamps in UTC sort as strings.
+    found.sort_by(|a, b| b.0.cmp(a.0));
+    Ok(found.into_iter().map(|(_, tag)| tag.to_string()).collect())
+}
+
+const GITHUB_API: &str = "https://api.github.com";
+
+/// `base_url` if given, else `ZEROOS_MUSL_BASE_URL`, without a trailing `/`. `None` means
+/// GitHub itself.
+fn resolve_base_url(base_url: Option<&str>) -> Result<Option<String>, String> {
+    let Some(base) = base_url
+        .map(str::to_string)
+        .or_else(|| std::env::var("ZEROOS_MUSL_BASE_URL").ok())
+        .filter(|s| !s.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+    let base = base.trim().trim_end_matches('/');
+    let host = base
+        .strip_prefix("https://")
+        .or_else(|| base.strip_prefix("http://"))
+        .ok_or_else(|| format!("Base URL must be http:// or https://: {}", base))?;
+    if host.is_empty() {
+        return Err(format!("Base URL has no host: {}", base));
+    }
+    Ok(Some(base.to_string()))
+}
+
+/// The releases API URL for `repo`: one tag, the latest release, or (with `list`) all of them.
+fn releases_api_url(base: Option<&str>, repo: &str, tag: Option<&str>, list: bool) -> String {
+    let root = format!("{}/repos/{}/releases", base.unwrap_or(GITHUB_API), repo);
+    match tag {
+        _ if list => format!("{}?per_page=100", root),
+        Some(tag) => format!("{}/tags/{}", root, tag),
+        None => format!("{}/latest", root),
+    }
+}
+
+/// `url` with its scheme and host replaced by `base`, so downloads go through the mirror too.
+fn mirror_download_url(url: &str, base: Option<&str>) -> String {
+    let Some(base) = base else {
+        return url.to_string();
+    };
+    let path = url
+        .split_once("://")
+        .and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]))
+        .unwrap_or("");
+    format!("{}{}", base, path)
+}
+
+/// `repo` if given, else `ZEROOS_MUSL_TOOLCHAIN_REPO`, the `origin` remote, or upstream.
+fn resolve_repo(repo: Option<&str>) -> String {
+    repo.map(str::to_string)
+        .or_else(|| std::env::var("ZEROOS_MUSL_TOOLCHAIN_REPO").ok())
+        .or_else(default_repo_from_git)
+        .unwrap_or_else(|| "LayerZero-Labs/ZeroOS".to_string())
+}
+
+/// Tags of the releases in `repo` (resolved like `install`) that have a toolchain for this
+/// host, newest first.
+pub fn list_musl_releases(
+    repo: Option<&str>,
+    base_url: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let repo = resolve_repo(repo);
+    let base = resolve_base_url(base_url)?;
+    let api_url = releases_api_url(base.as_deref(), &repo, None, true);
+    tags_with_toolchain(&github_api_get(&api_url)?, host_platform(), host_arch())
+}
+
+/// A release tarball and where its checksum can be found.
+#[derive(Debug, PartialEq, Eq)]
+struct ReleaseAsset {
+    url: String,
+    format: ArchiveFormat,
+    /// From the asset's `digest` field, e.g. `sha256:<hex>`.
+    digest: Option<String>,
+    /// A sibling `<name>.sha256` asset, used when there is no digest.
+    checksum_url: Option<String>,
+}
+
+fn find_asset_download_url(
+    base: Option<&str>,
+    repo: &str,
+    tag: Option<&str>,
+    platform: &str,
+    arch: &str,
+) -> Result<ReleaseAsset, String> {
+    let api_url = releases_api_url(base, repo, tag, false);
+    let v = github_api_get(&api_url)?;
+    let mut asset = find_asset(&v, platform, arch).map_err(|e| {
+        format!(
+            "{} in repo {} (tag={:?})",
+            e.unwrap_or_else(|| format!(
+                "No matching toolchain asset found for {} {}",
+                platform, arch
+            )),
+            repo,
+            tag
+        )
+    })?;
+    asset.url = mirror_download_url(&asset.url, base);
+    asset.checksum_url = asset
+        .checksum_url
+        .map(|url| mirror_download_url(&url, base));
+    Ok(asset)
+}
 
-    let assets = v
+/// The toolchain asset for `platform`/`arch` in a GitHub release JSON document, the smallest
+/// if it is published in several formats. `Err(None)` means no asset matched.
+fn find_asset(
+    release: &serde_json::Value,
+    platform: &str,
+    arch: &str,
+) -> Result<ReleaseAsset, Option<String>> {
+    let assets = release
         .get("assets")
         .and_then(|a| a.as_array())
-        .ok_or_else(|| "GitHub API response missing `assets` array".to_string())?;
-
-    let suffix = format!("-{}-{}.tar.gz", platform, arch);
-    for asset in assets {
-        let name = asset.get("name").and_then(|n| n.as_str()).unwrap_or("");
-        if !name.starts_with("zeroos-musl-toolchain-") || !name.ends_with(&suffix) {
-            continue;
-        }
-        let url = asset
+        .ok_or_else(|| Some("GitHub API response missing `assets` array".to_string()))?;
+    let name_of = |asset: &serde_json::Value| {
+        asset
+            .get("name")
+            .and_then(|n| n.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let url_of = |asset: &serde_json::Value, name: &str| {
+        asset
             .get("browser_download_url")
             .and_then(|u| u.as_str())
-            .ok_or_else(|| format!("Asset {} missing browser_download_url", name))?;
-        return Ok(url.to_string());
+            .map(str::to_string)
+            .ok_or_else(|| Some(format!("Asset {} missing browser_download_url", name)))
+    };
+
+    let stem = format!("-{}-{}", platform, arch);
+    let (asset, name, format) = assets
+        .iter()
+        .filter_map(|asset| {
+            let name = name_of(asset);
+            let format = ArchiveFormat::from_name(&name)?;
+            let matches = name.starts_with("zeroos-musl-toolchain-")
+                && name.strip_suffix(format.extension())?.ends_with(&stem);
+            matches.then_some((asset, name, format))
+        })
+        .min_by_key(|(asset, _, _)| {
+            asset
+                .get("size")
+                .and_then(|s| s.as_u64())
+                .unwrap_or(u64::MAX)
+        })
+        .ok_or(None)?;
+
+    let checksum_name = format!("{}.sha256", name);
+    let checksum_url = match assets.iter().find(|a| name_of(a) == checksum_name) {
+        Some(checksum) => Some(url_of(checksum, &checksum_name)?),
+        None => None,
+    };
+    Ok(ReleaseAsset {
+        url: url_of(asset, &name)?,
+        format,
+        digest: asset
+            .get("digest")
+            .and_then(|d| d.as_str())
+            .map(str::to_string),
+        checksum_url,
+    })
+}
+
+/// Normalize a checksum as found in a `digest` field (`sha256:<hex>`), a `.sha256` file or
+/// `sha256sum` output (`<hex>  <file>`), or given by the user, to lowercase hex.
+fn parse_sha256(text: &str) -> Result<String, String> {
+    let token = text.split_whitespace().next().unwrap_or("");
+    let hex = token.strip_prefix("sha256:").unwrap_or(token);
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("Not a SHA-256 checksum: {:?}", text.trim()));
+    }
+    Ok(hex.to_ascii_lowercase())
+}
+
+/// SHA-256 of `path` as lowercase hex, using `sha256sum` or, where that is missing (macOS),
+/// `shasum -a 256`.
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let out = run_captured(Command::new("sha256sum").arg(path))
+        .or_else(|_| run_captured(Command::new("shasum").args(["-a", "256"]).arg(path)))
+        .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+    parse_sha256(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// Fail unless `path` hashes to `expected` (any form `parse_sha256` accepts).
+pub fn verify_sha256(path: &Path, expected: &str) -> Result<(), String> {
+    let expected = parse_sha256(expected)?;
+    let actual = sha256_file(path)?;
+    if actual != expected {
+        return Err(format!(
+            "SHA-256 mismatch for {}: expected {}, got {} (truncated or tampered download?)",
+            path.display(),
+            expected,
+            actual
+        ));
+    }
+    debug!("Verified SHA-256 of {}: {}", path.display(), actual);
+    Ok(())
+}
+
+/// The checksum to hold the tarball to: the user's override, else the release's.
+fn expected_sha256(
+    config: &InstallConfig,
+    asset: &ReleaseAsset,
+    tmp_dir: &Path,
+) -> Result<String, String> {
+    if let Some(sha256) = &config.sha256 {
+        return Ok(sha256.clone());
     }
+    if let Some(digest) = &asset.digest {
+        return Ok(digest.clone());
+    }
+    let Some(checksum_url) = &asset.checksum_url else {
+        return Err(format!(
+            "No SHA-256 published for {}; pass --sha256 <hex> or --no-verify",
+            asset.url
+        ));
+    };
+    let checksum_file = tmp_dir.join("toolchain.sha256");
+    run(Command::new("curl")
+        .arg("-fsSL")
+        .arg("--retry")
+        .arg("5")
+        .arg("-o")
+        .arg(&checksum_file)
+        .arg(checksum_url))?;
+    fs::read_to_string(&checksum_file)
+        .map_err(|e| format!("Failed to read {}: {}", checksum_file.display(), e))
+}
 
-    Err(format!(
-        "No matching toolchain asset found for {} {} in repo {} (tag={:?})",
-        platform, arch, repo, tag
-    ))
+/// Check that the compiler under `install_dir/bin` targets `arch`, using `gcc -dumpmachine`.
+///
+/// Guards against a mislabeled release asset (e.g. a riscv32 toolchain published as riscv64).
+pub fn verify_toolchain_arch(install_dir: &Path, arch: &str) -> Result<(), String> {
+    let gcc = install_dir
+        .join("bin")
+        .join(format!("{}-linux-musl-gcc", arch));
+    let out = run_captured(Command::new(&gcc).arg("-dumpmachine")).map_err(|e| e.to_string())?;
+
+    let machine = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    if machine.split('-').next() != Some(arch) {
+        return Err(format!(
+            "Toolchain targets {} but {} was requested ({})",
+            machine,
+            arch,
+            gcc.display()
+        ));
+    }
+    debug!("Verified toolchain machine: {}", machine);
+    Ok(())
+}
+
+impl InstallConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        super::load_config(path)
+    }
+}
+
+/// Install the toolchain described by a TOML or JSON [`InstallConfig`] file.
+pub fn install_from_file(path: &Path) -> Result<ToolchainPaths, String> {
+    install_musl_toolchain(&InstallConfig::from_file(path)?)
 }
 
 pub fn install_musl_toolchain(config: &InstallConfig) -> Result<ToolchainPaths, String> {
     let platform = host_platform();
     let arch = host_arch();
 
-    let repo = config
-        .repo
-        .clone()
-        .or_else(|| std::env::var("ZEROOS_MUSL_TOOLCHAIN_REPO").ok())
-        .or_else(default_repo_from_git)
-        .unwrap_or_else(|| "LayerZero-Labs/ZeroOS".to_string());
+    let repo = resolve_repo(config.repo.as_deref());
+    let base = resolve_base_url(config.base_url.as_deref())?;
 
     let output_dir = PathBuf::from(&config.output_dir);
     let target_dir = output_dir.join(format!("{}-linux-musl", config.arch));
@@ -244,7 +570,14 @@ pub fn install_musl_toolchain(config: &InstallConfig) -> Result<ToolchainPaths,
         output_dir.display(),
     );
 
-    let url = find_asset_download_url(&repo, config.tag.as_deref(), platform, arch)?;
+    let asset = find_asset_download_url(
+        base.as_deref(),
+        &repo,
+        config.tag.as_deref(),
+        platform,
+        arch,
+    )?;
+    let url = &asset.url;
     info!("Downloading: {}", url);
 
     let tmp_dir = tempfile::Builder::new()
@@ -252,7 +585,7 @@ pub fn install_musl_toolchain(config: &InstallConfig) -> Result<ToolchainPaths,
         .tempdir()
         .map_err(|e| format!("Failed to create temp dir: {}", e))?;
     let tmp_dir_path = tmp_dir.path().to_path_buf();
-    let tarball = tmp_dir_path.join("toolchain.tar.gz");
+    let tarball = tmp_dir_path.join(format!("toolchain{}", asset.format.extension()));
 
     let mut dl = Command::new("curl");
     dl.arg("-fL")
@@ -263,12 +596,18 @@ pub fn install_musl_toolchain(config: &InstallConfig) -> Result<ToolchainPaths,
         .arg("1")
         .arg("-o")
         .arg(&tarball)
-        .arg(&url);
+        .arg(url);
     run(&mut dl)?;
 
+    if config.verify {
+        verify_sha256(&tarball, &expected_sha256(config, &asset, &tmp_dir_path)?)?;
+    } else {
+        info!("Skipping SHA-256 verification (--no-verify)");
+    }
+
     // Extract into temp, then move into place.
     run(Command::new("tar")
-        .arg("xzf")
+        .args(asset.format.tar_extract_args())
         .arg(&tarball)
         .arg("-C")
         .arg(&tmp_dir_path))?;
@@ -281,6 +620,9 @@ pub fn install_musl_toolchain(config: &InstallConfig) -> Result<ToolchainPaths,
         ));
     }
 
+    // Check the archive before touching an existing install, so a mismatch leaves it intact.
+    verify_toolchain_arch(&extracted_root, &config.arch)?;
+
     ensure_dir(output_dir.parent().unwrap_or(Path::new("/")))?;
 
     if output_dir.exists() {
@@ -328,3 +670,219 @@ pub fn get_or_install_toolchain(
         Err(_e) => install_musl_toolchain(install),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn stub_gcc(root: &Path, arch: &str, machine: &str) {
+        let bin = root.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let gcc = bin.join(format!("{}-linux-musl-gcc", arch));
+        fs::write(&gcc, format!("#!/bin/sh\necho {}\n", machine)).unwrap();
+        fs::set_permissions(&gcc, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_verify_toolchain_arch_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        stub_gcc(dir.path(), "riscv64", "riscv64-linux-musl");
+        verify_toolchain_arch(dir.path(), "riscv64").unwrap();
+    }
+
+    #[test]
+    fn test_verify_toolchain_arch_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        // A riscv32 compiler shipped under the riscv64 name.
+        stub_gcc(dir.path(), "riscv64", "riscv32-linux-musl");
+        let err = verify_toolchain_arch(dir.path(), "riscv64").unwrap_err();
+        assert!(err.contains("targets riscv32-linux-musl but riscv64 was requested"));
+    }
+
+    #[test]
+    fn test_verify_toolchain_arch_missing_compiler() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = verify_toolchain_arch(dir.path(), "riscv64").unwrap_err();
+        assert!(err.contains("Failed to run"));
+    }
+
+    /// SHA-256 of `abc`, from FIPS 180-2.
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn test_verify_sha256_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = dir.path().join("fixture.tar.gz");
+        fs::write(&fixture, "abc").unwrap();
+
+        verify_sha256(&fixture, ABC_SHA256).unwrap();
+        verify_sha256(&fixture, &format!("sha256:{}", ABC_SHA256.to_uppercase())).unwrap();
+        verify_sha256(&fixture, &format!("{}  fixture.tar.gz\n", ABC_SHA256)).unwrap();
+
+        let err = verify_sha256(&fixture, &"0".repeat(64)).unwrap_err();
+        assert!(err.contains("SHA-256 mismatch"), "{}", err);
+        assert!(err.contains(ABC_SHA256), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_sha256_rejects_garbage() {
+        assert!(parse_sha256("").is_err());
+        assert!(parse_sha256("sha256:abc").is_err());
+        assert!(parse_sha256(&"g".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_find_asset_checksums() {
+        let release = serde_json::json!({
+            "assets": [
+                {
+                    "name": "zeroos-musl-toolchain-1-Linux-x86_64.tar.gz",
+                    "browser_download_url": "https://example.com/linux.tar.gz",
+                    "digest": "sha256:00"
+                },
+                {
+                    "name": "zeroos-musl-toolchain-1-Linux-aarch64.tar.gz",
+                    "browser_download_url": "https://example.com/arm.tar.gz",
+                    "size": 300
+                },
+                {
+                    "name": "zeroos-musl-toolchain-1-Linux-aarch64.tar.zst",
+                    "browser_download_url": "https://example.com/arm.tar.zst",
+                    "size": 200
+                },
+                {
+                    "name": "zeroos-musl-toolchain-1-Linux-aarch64.tar.xz",
+                    "browser_download_url": "https://example.com/arm.tar.xz",
+                    "size": 250
+                },
+                {
+                    "name": "zeroos-musl-toolchain-1-Darwin-arm64.tar.gz",
+                    "browser_download_url": "https://example.com/darwin.tar.gz"
+                },
+                {
+                    "name": "zeroos-musl-toolchain-1-Darwin-arm64.tar.gz.sha256",
+                    "browser_download_url": "https://example.com/darwin.tar.gz.sha256"
+                }
+            ]
+        });
+
+        let linux = find_asset(&release, "Linux", "x86_64").unwrap();
+        assert_eq!(linux.url, "https://example.com/linux.tar.gz");
+        assert_eq!(linux.digest.as_deref(), Some("sha256:00"));
+        assert_eq!(linux.checksum_url, None);
+
+        let darwin = find_asset(&release, "Darwin", "arm64").unwrap();
+        assert_eq!(darwin.digest, None);
+        assert_eq!(
+            darwin.checksum_url.as_deref(),
+            Some("https://example.com/darwin.tar.gz.sha256")
+        );
+
+        let arm = find_asset(&release, "Linux", "aarch64").unwrap();
+        assert_eq!(arm.url, "https://example.com/arm.tar.zst");
+        assert_eq!(arm.format, ArchiveFormat::Zstd);
+
+        assert_eq!(find_asset(&release, "Linux", "riscv64"), Err(None));
+    }
+
+    #[test]
+    fn test_archive_format_tar_flags() {
+        let cases = [
+            ("x-Linux-x86_64.tar.gz", ArchiveFormat::Gzip, &["xzf"][..]),
+            ("x-Linux-x86_64.tar.xz", ArchiveFormat::Xz, &["xJf"][..]),
+            (
+                "x-Linux-x86_64.tar.zst",
+                ArchiveFormat::Zstd,
+                &["--zstd", "-xf"][..],
+            ),
+        ];
+        for (name, format, args) in cases {
+            assert_eq!(ArchiveFormat::from_name(name), Some(format));
+            assert_eq!(format.tar_extract_args(), args);
+        }
+        assert_eq!(ArchiveFormat::from_name("x.tar.gz.sha256"), None);
+        assert_eq!(ArchiveFormat::from_name("x.zip"), None);
+    }
+
+    const RELEASES_FIXTURE: &str = include_str!("testdata/releases.json");
+
+    #[test]
+    fn test_tags_with_toolchain_fixture() {
+        let releases: serde_json::Value = serde_json::from_str(RELEASES_FIXTURE).unwrap();
+        assert_eq!(
+            tags_with_toolchain(&releases, "Linux", "x86_64").unwrap(),
+            [
+                "musl-toolchain-musl-1.2.5-gcc-14.2.0",
+                "musl-toolchain-musl-1.2.3-gcc-9.4.0"
+            ]
+        );
+        assert_eq!(
+            tags_with_toolchain(&releases, "Darwin", "arm64").unwrap(),
+            ["musl-toolchain-musl-1.2.5-gcc-14.2.0"]
+        );
+        assert!(tags_with_toolchain(&releases, "Linux", "riscv64")
+            .unwrap()
+            .is_empty());
+        assert!(
+            tags_with_toolchain(&serde_json::json!([]), "Linux", "x86_64")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_api_response() {
+        assert_eq!(
+            parse_api_response(200, b"[]").unwrap(),
+            serde_json::json!([])
+        );
+
+        let limited = br#"{"message":"API rate limit exceeded for 203.0.113.7.","documentation_url":"https://docs.github.com/rest/overview/resources-in-the-rest-api#rate-limiting"}"#;
+        let err = parse_api_response(403, limited).unwrap_err();
+        assert!(
+            err.starts_with("GitHub API rate limit exceeded (HTTP 403)"),
+            "{}",
+            err
+        );
+        assert!(err.contains("GITHUB_TOKEN"), "{}", err);
+
+        let err = parse_api_response(404, br#"{"message":"Not Found"}"#).unwrap_err();
+        assert_eq!(err, "GitHub API returned HTTP 404: Not Found");
+        let err = parse_api_response(502, b"Bad gateway\n").unwrap_err();
+        assert_eq!(err, "GitHub API returned HTTP 502: Bad gateway");
+    }
+
+    #[test]
+    fn test_releases_api_url_base() {
+        assert_eq!(
+            releases_api_url(None, "o/r", Some("t1"), false),
+            "https://api.github.com/repos/o/r/releases/tags/t1"
+        );
+        assert_eq!(
+            releases_api_url(Some("http://mirror.local:8080/gh"), "o/r", None, false),
+            "http://mirror.local:8080/gh/repos/o/r/releases/latest"
+        );
+        assert_eq!(
+            releases_api_url(None, "o/r", None, true),
+            "https://api.github.com/repos/o/r/releases?per_page=100"
+        );
+
+        let url = "https://github.com/o/r/releases/download/t1/x.tar.gz";
+        assert_eq!(mirror_download_url(url, None), url);
+        assert_eq!(
+            mirror_download_url(url, Some("http://mirror.local:8080/gh")),
+            "http://mirror.local:8080/gh/o/r/releases/download/t1/x.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_resolve_base_url_validates_scheme() {
+        assert_eq!(
+            resolve_base_url(Some("https://mirror.example/")).unwrap(),
+            Some("https://mirror.example".to_string())
+        );
+        assert!(resolve_base_url(Some("ftp://mirror.example")).is_err());
+        assert!(resolve_base_url(Some("https://")).is_err());
+    }
+}
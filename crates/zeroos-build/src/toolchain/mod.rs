@@ -1,10 +1,17 @@
 extern crate std;
 
+mod cache;
+mod clean;
 mod discovery;
 mod install;
 
+pub use cache::default_cache_path;
+pub use clean::{clean_musl_toolchain, musl_clean_paths};
 pub use discovery::{discover_toolchain, validate_toolchain_path, ToolchainPaths};
-pub use install::{get_or_install_toolchain, install_musl_toolchain, InstallConfig};
+pub use install::{
+    get_or_install_toolchain, install_from_file, install_musl_toolchain, list_musl_releases,
+    InstallConfig,
+};
 
 use std::format;
 use std::path::{Path, PathBuf};
@@ -80,8 +87,14 @@ pub fn resolve_toolchain_paths(
             .to_string());
     }
 
-    discover_toolchain(&config.arch)
-        .ok_or_else(|| format!("Toolchain not found for architecture: {}", config.arch))
+    // `RISCV_MUSL_PATH` picks a toolchain explicitly; a cached one must not override it.
+    let found = match default_cache_path() {
+        Some(cache_path) if std::env::var_os("RISCV_MUSL_PATH").is_none() => {
+            cache::discover_cached(&cache_path, &config.arch, discover_toolchain)
+        }
+        _ => discover_toolchain(&config.arch),
+    };
+    found.ok_or_else(|| format!("Toolchain not found for architecture: {}", config.arch))
 }
 
 fn validate_musl_lib(musl_lib: &Path) -> std::result::Result<(), std::string::String> {
@@ -128,14 +141,34 @@ fn find_gcc_version_dir(gcc_base: &Path) -> std::result::Result<PathBuf, std::st
     ))
 }
 
-#[derive(Debug, Clone)]
+/// Load a config file: JSON if the extension is `.json`, TOML otherwise. Missing fields take
+/// their defaults.
+fn load_config<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid JSON config {}: {}", path.display(), e))
+    } else {
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid TOML config {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct BuildConfig {
     pub arch: String,
 
     pub output_dir: String,
     /// GCC configuration flags (e.g., "--with-arch=rv64ima --with-abi=lp64")
+    ///
+    /// Unlike the other fields, leaving this out of a config file means no flags rather than
+    /// the default ones, since TOML cannot spell `None` any other way.
+    #[serde(default)]
     pub gcc_config: Option<String>,
 
+    /// Parallel make jobs; defaults to the number of logical CPUs.
     pub jobs: Option<usize>,
 }
 
@@ -154,12 +187,32 @@ impl Default for BuildConfig {
     }
 }
 
+impl BuildConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        load_config(path)
+    }
+
+    /// `jobs`, or the available parallelism when unset.
+    pub fn make_jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+    }
+}
+
+/// Build the toolchain described by a TOML or JSON [`BuildConfig`] file.
+pub fn build_from_file(path: &Path) -> Result<ToolchainPaths, String> {
+    build_musl_toolchain(&BuildConfig::from_file(path)?)
+}
+
 pub fn build_musl_toolchain(
     config: &BuildConfig,
 ) -> std::result::Result<ToolchainPaths, std::string::String> {
     use std::fs;
     use std::io::Write;
-    use std::process::{Command, Stdio};
+    use std::process::Stdio;
 
     let temp_dir = tempfile::Builder::new()
         .prefix(&format!("zeroos-musl-build-{}-", config.arch))
@@ -186,29 +239,18 @@ pub fn build_musl_toolchain(
             .map_err(|e| format!("Failed to set script permissions: {}", e))?;
     }
 
-    let mut cmd = Command::new("bash");
-    cmd.arg(&script_path)
-        .arg(&config.arch)
-        .current_dir(&temp_dir_path)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
-
-    cmd.env("OUTPUT", &config.output_dir);
-
-    if let Some(ref gcc_config) = config.gcc_config {
-        cmd.env("GCC_CONFIG_FOR_TARGET", gcc_config);
-    }
-
-    cmd.env("WORKDIR", &temp_dir_path);
+    let mut cmd = build_script_command(config, &script_path, &temp_dir_path);
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
 
     info!("Building musl toolchain for {}", config.arch);
     info!("This will take 5-10 minutes.");
     info!("Output directory: {}", config.output_dir);
     debug!(
-        "Running: WORKDIR={} OUTPUT={} GCC_CONFIG_FOR_TARGET=\"{}\" bash {} {}",
+        "Running: WORKDIR={} OUTPUT={} GCC_CONFIG_FOR_TARGET=\"{}\" MAKE_JOBS={} bash {} {}",
         temp_dir_path.display(),
         config.output_dir,
         config.gcc_config.as_ref().unwrap_or(&"(none)".to_string()),
+        config.make_jobs(),
         script_path.display(),
         config.arch
     );
@@ -236,3 +278,121 @@ pub fn build_musl_toolchain(
     find_toolchain(&toolchain_config)
         .ok_or_else(|| format!("Built toolchain not found at {}", config.output_dir))
 }
+
+/// The `musl-toolchain.sh` invocation for `config`, run in `workdir`.
+fn build_script_command(
+    config: &BuildConfig,
+    script_path: &Path,
+    workdir: &Path,
+) -> std::process::Command {
+    let mut cmd = std::process::Command::new("bash");
+    cmd.arg(script_path)
+        .arg(&config.arch)
+        .current_dir(workdir)
+        .env("OUTPUT", &config.output_dir)
+        .env("WORKDIR", workdir)
+        .env("MAKE_JOBS", config.make_jobs().to_string());
+
+    if let Some(ref gcc_config) = config.gcc_config {
+        cmd.env("GCC_CONFIG_FOR_TARGET", gcc_config);
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_config_toml_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.toml");
+        let config = BuildConfig {
+            arch: "riscv32".to_string(),
+            output_dir: "/opt/zeroos/musl".to_string(),
+            gcc_config: None,
+            jobs: Some(8),
+        };
+        std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let loaded = BuildConfig::from_file(&path).unwrap();
+        assert_eq!(loaded.arch, "riscv32");
+        assert_eq!(loaded.output_dir, "/opt/zeroos/musl");
+        assert_eq!(loaded.gcc_config, None);
+        assert_eq!(loaded.jobs, Some(8));
+    }
+
+    #[test]
+    fn test_install_config_toml_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("install.toml");
+        let config = InstallConfig {
+            arch: "riscv64".to_string(),
+            output_dir: "/opt/zeroos/musl".to_string(),
+            repo: Some("example/ZeroOS".to_string()),
+            tag: Some("musl-toolchain-musl-1.2.5-gcc-14.2.0".to_string()),
+            force: true,
+            ..InstallConfig::default()
+        };
+        std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let loaded = InstallConfig::from_file(&path).unwrap();
+        assert_eq!(loaded.arch, "riscv64");
+        assert_eq!(loaded.output_dir, "/opt/zeroos/musl");
+        assert_eq!(loaded.repo.as_deref(), Some("example/ZeroOS"));
+        assert_eq!(
+            loaded.tag.as_deref(),
+            Some("musl-toolchain-musl-1.2.5-gcc-14.2.0")
+        );
+        assert!(loaded.force);
+    }
+
+    #[test]
+    fn test_partial_json_config_uses_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("install.json");
+        std::fs::write(&path, r#"{ "tag": "v1" }"#).unwrap();
+
+        let loaded = InstallConfig::from_file(&path).unwrap();
+        let defaults = InstallConfig::default();
+        assert_eq!(loaded.tag.as_deref(), Some("v1"));
+        assert_eq!(loaded.arch, defaults.arch);
+        assert_eq!(loaded.output_dir, defaults.output_dir);
+        assert!(!loaded.force);
+    }
+
+    #[test]
+    fn test_invalid_config_reports_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.toml");
+        std::fs::write(&path, "jobs = \"many\"").unwrap();
+
+        let err = BuildConfig::from_file(&path).unwrap_err();
+        assert!(err.contains("Invalid TOML config"), "{}", err);
+        assert!(err.contains("build.toml"), "{}", err);
+    }
+
+    fn script_env(config: &BuildConfig, key: &str) -> Option<String> {
+        let cmd = build_script_command(config, Path::new("musl-toolchain.sh"), Path::new("/tmp"));
+        cmd.get_envs()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, v)| v)
+            .map(|v| v.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn test_build_script_command_make_jobs() {
+        let config = BuildConfig {
+            jobs: Some(4),
+            ..BuildConfig::default()
+        };
+        assert_eq!(script_env(&config, "MAKE_JOBS").as_deref(), Some("4"));
+
+        let config = BuildConfig {
+            jobs: None,
+            ..BuildConfig::default()
+        };
+        let jobs: usize = script_env(&config, "MAKE_JOBS").unwrap().parse().unwrap();
+        assert!(jobs >= 1);
+    }
+}
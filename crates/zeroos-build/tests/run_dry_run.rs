@@ -0,0 +1,91 @@
+//! `cargo zeroos run --dry-run` reports the binary it would build and the spike command it would
+//! run, without invoking cargo build or spike.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn dry_run(workspace: &Path, args: &[&str]) -> (PathBuf, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-zeroos"))
+        .args(["zeroos", "run", "--dry-run"])
+        .args(args)
+        .current_dir(workspace)
+        .env_remove("SPIKE_PATH")
+        .env_remove("CARGO_TARGET_DIR")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let field = |name: &str| {
+        stdout
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .unwrap_or_else(|| panic!("no `{}` in output:\n{}", name, stdout))
+            .to_string()
+    };
+    (PathBuf::from(field("binary: ")), field("command: "))
+}
+
+/// An empty workspace: `cargo metadata` fails there, so the target dir is `<root>/target`.
+fn workspace() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("Cargo.toml"), "[workspace]\n").unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    (dir, root)
+}
+
+#[test]
+fn dry_run_defaults() {
+    let (_dir, root) = workspace();
+    let (binary, command) = dry_run(&root, &["-p", "fibonacci"]);
+
+    let expected = root.join("target/riscv64imac-unknown-none-elf/debug/fibonacci");
+    assert_eq!(binary, expected);
+    assert_eq!(
+        command,
+        format!(
+            "spike --isa=RV64IMAC --instructions=1000000 -m0x80000000:0x8000000 {}",
+            expected.display()
+        )
+    );
+}
+
+#[test]
+fn dry_run_passes_memory_isa_and_profile_through() {
+    let (_dir, root) = workspace();
+    let (binary, command) = dry_run(
+        &root,
+        &[
+            "-p",
+            "std-smoke",
+            "--mode",
+            "std",
+            "--memory-origin",
+            "0x90000000",
+            "--memory-size",
+            "256Mi",
+            "--isa",
+            "RV64GC",
+            "-n",
+            "0",
+            "--spike",
+            "/opt/spike",
+            "--",
+            "--release",
+        ],
+    );
+
+    let expected = root.join("target/riscv64imac-zero-linux-musl/release/std-smoke");
+    assert_eq!(binary, expected);
+    assert_eq!(
+        command,
+        format!(
+            "/opt/spike --isa=RV64GC -m0x90000000:0x10000000 {}",
+            expected.display()
+        )
+    );
+}
@@ -1,7 +1,48 @@
 #![no_std]
 
 cfg_if::cfg_if! {
-    if #[cfg(feature = "debug")] {
+    if #[cfg(feature = "defmt")] {
+        /// Formats one message into a fixed buffer and emits it as a single `defmt` frame.
+        /// Output longer than the buffer is truncated.
+        pub struct DefmtLine {
+            buf: [u8; 256],
+            len: usize,
+        }
+
+        impl DefmtLine {
+            #[inline(always)]
+            pub const fn new() -> Self {
+                Self { buf: [0; 256], len: 0 }
+            }
+
+            #[inline(always)]
+            pub fn flush(&self) {
+                // Only whole `&str` chunks are appended, so the prefix is valid UTF-8
+                // up to the last chunk that fit.
+                let s = core::str::from_utf8(&self.buf[..self.len]).unwrap_or("");
+                defmt::println!("{=str}", s);
+            }
+        }
+
+        impl Default for DefmtLine {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl core::fmt::Write for DefmtLine {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                let room = self.buf.len() - self.len;
+                let mut n = s.len().min(room);
+                while !s.is_char_boundary(n) {
+                    n -= 1;
+                }
+                self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+                self.len += n;
+                Ok(())
+            }
+        }
+    } else if #[cfg(feature = "debug")] {
         extern "C" {
             fn __debug_write(msg: *const u8, len: usize);
         }
@@ -19,7 +60,31 @@ cfg_if::cfg_if! {
     }
 }
 
-#[cfg(feature = "debug")]
+#[cfg(feature = "defmt")]
+mod macros {
+    // `defmt` frames are line-oriented, so `write!` and `writeln!` both emit one frame.
+    #[macro_export]
+    macro_rules! write {
+        ($($arg:tt)*) => {{
+            use core::fmt::Write;
+            let mut line = $crate::DefmtLine::new();
+            let _ = core::write!(line, $($arg)*);
+            line.flush();
+        }};
+    }
+
+    #[macro_export]
+    macro_rules! writeln {
+        ($($arg:tt)*) => {{
+            use core::fmt::Write;
+            let mut line = $crate::DefmtLine::new();
+            let _ = core::write!(line, $($arg)*);
+            line.flush();
+        }};
+    }
+}
+
+#[cfg(all(feature = "debug", not(feature = "defmt")))]
 mod macros {
     #[macro_export]
     macro_rules! write {
@@ -38,7 +103,7 @@ mod macros {
     }
 }
 
-#[cfg(not(feature = "debug"))]
+#[cfg(not(any(feature = "debug", feature = "defmt")))]
 mod macros {
     #[macro_export]
     macro_rules! write {
@@ -1,17 +1,31 @@
 #![no_std]
 
-use vfs_core::{noop_close, noop_ioctl, noop_seek, FileOps};
+use core::ptr::null_mut;
+use foundation::kfn::mem;
+use foundation::utils::GlobalCell;
+use vfs_core::{
+    forbid_read, forbid_write, noop_capabilities, noop_close, noop_flush, noop_ioctl,
+    noop_max_open, noop_mmap, noop_seek, noop_set_nonblocking, noop_size, noop_stat, DeviceCaps,
+    DeviceFactory, FdEntry, FileOps, Vfs, VfsResult,
+};
 
-fn console_read_eof(_file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
-    0
+/// Write size the console prefers; each HTIF round trip is costly, so batch up to this.
+pub const CONSOLE_BLOCK_SIZE: usize = 512;
+
+fn console_block_size(_file: *mut u8) -> usize {
+    CONSOLE_BLOCK_SIZE
+}
+
+fn console_read_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE
 }
 
-fn console_read_unsupported(_file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
-    -(libc::EBADF as isize)
+fn console_write_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::WRITABLE
 }
 
-fn console_write_unsupported(_file: *mut u8, _buf: *const u8, _count: usize) -> isize {
-    -(libc::EBADF as isize)
+fn console_read_eof(_file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
+    0
 }
 
 pub const fn read_only_fops(read_fn: Option<fn(*mut u8, *mut u8, usize) -> isize>) -> FileOps {
@@ -21,23 +35,778 @@ pub const fn read_only_fops(read_fn: Option<fn(*mut u8, *mut u8, usize) -> isize
         } else {
             console_read_eof
         },
-        write: console_write_unsupported,
+        write: forbid_write,
+        flush: noop_flush,
         release: noop_close,
         llseek: noop_seek,
         ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: noop_max_open,
+        set_nonblocking: noop_set_nonblocking,
+        preferred_block_size: console_block_size,
+        capabilities: console_read_caps,
+        stat: noop_stat,
+        size: noop_size,
     }
 }
 
 pub const fn write_only_fops(write_fn: fn(*mut u8, *const u8, usize) -> isize) -> FileOps {
     FileOps {
-        read: console_read_unsupported,
+        read: forbid_read,
         write: write_fn,
+        flush: noop_flush,
         release: noop_close,
         llseek: noop_seek,
         ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: noop_max_open,
+        set_nonblocking: noop_set_nonblocking,
+        preferred_block_size: console_block_size,
+        capabilities: console_write_caps,
+        stat: noop_stat,
+        size: noop_size,
     }
 }
 
 pub use read_only_fops as stdin_fops;
 pub use write_only_fops as stdout_fops;
 pub use write_only_fops as stderr_fops;
+
+pub type ConsoleReadFn = fn(*mut u8, *mut u8, usize) -> isize;
+pub type ConsoleWriteFn = fn(*mut u8, *const u8, usize) -> isize;
+
+/// Bytes held between the console and its backend, in either direction.
+struct ConsoleBuffer {
+    data: [u8; CONSOLE_BLOCK_SIZE],
+    len: usize,
+}
+
+impl ConsoleBuffer {
+    const fn new() -> Self {
+        Self {
+            data: [0; CONSOLE_BLOCK_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Drop the first `n` bytes.
+    fn consume(&mut self, n: usize) {
+        self.data.copy_within(n..self.len, 0);
+        self.len -= n;
+    }
+
+    /// Append `byte`; the caller makes sure there is room.
+    fn push(&mut self, byte: u8) {
+        self.data[self.len] = byte;
+        self.len += 1;
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == CONSOLE_BLOCK_SIZE
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// How a `ConsoleDevice` hands input to `read`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConsoleMode {
+    /// Bytes as the backend delivers them.
+    #[default]
+    Raw,
+    /// A line at a time, once Enter is pressed. Typed bytes are echoed and backspace (`0x7f` or
+    /// `0x08`) erases the last one. End of input hands over a partial line.
+    Canonical,
+}
+
+/// A buffered terminal over raw backend read/write functions.
+///
+/// Input is read ahead a block at a time. Output is line-buffered: it goes to the backend when
+/// a newline is written, when the buffer fills, or on `flush`. `TCFLSH` discards either side.
+/// `TIOCGWINSZ`/`TIOCSWINSZ` get and set a stored window size, 80x24 unless configured.
+/// The backends get a null `file` pointer, as with `stdin_fops`/`stdout_fops`.
+///
+/// In `ConsoleMode::Canonical`, echo goes through the output side, so a console without
+/// `write_fn` edits lines silently.
+pub struct ConsoleDevice {
+    read_fn: Option<ConsoleReadFn>,
+    write_fn: Option<ConsoleWriteFn>,
+    mode: ConsoleMode,
+    input: ConsoleBuffer,
+    output: ConsoleBuffer,
+    /// The line being edited in canonical mode.
+    line: ConsoleBuffer,
+    /// Whether `line` is complete and may be read.
+    line_done: bool,
+    winsize: libc::winsize,
+}
+
+impl ConsoleDevice {
+    /// A console without `read_fn` or `write_fn` refuses that direction with `EBADF`.
+    pub const fn new(read_fn: Option<ConsoleReadFn>, write_fn: Option<ConsoleWriteFn>) -> Self {
+        Self {
+            read_fn,
+            write_fn,
+            mode: ConsoleMode::Raw,
+            input: ConsoleBuffer::new(),
+            output: ConsoleBuffer::new(),
+            line: ConsoleBuffer::new(),
+            line_done: false,
+            winsize: libc::winsize {
+                ws_row: 24,
+                ws_col: 80,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            },
+        }
+    }
+
+    pub const fn with_window_size(mut self, rows: u16, cols: u16) -> Self {
+        self.winsize.ws_row = rows;
+        self.winsize.ws_col = cols;
+        self
+    }
+
+    /// The window size as last set by `TIOCSWINSZ`.
+    pub fn window_size(&self) -> libc::winsize {
+        self.winsize
+    }
+
+    pub const fn with_mode(mut self, mode: ConsoleMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> ConsoleMode {
+        self.mode
+    }
+
+    /// Switch modes; a line being edited stays buffered and is read as raw input.
+    pub fn set_mode(&mut self, mode: ConsoleMode) {
+        self.mode = mode;
+    }
+
+    /// An fd entry backed by this console; it must outlive every fd using the entry.
+    pub fn entry(&mut self) -> FdEntry {
+        FdEntry {
+            ops: &CONSOLE_FOPS,
+            private_data: self as *mut Self as *mut u8,
+        }
+    }
+
+    /// Make this console openable at `path`, e.g. `/dev/console` or `/dev/tty`. Every open
+    /// shares it, so fds see one set of buffers and one window size.
+    pub fn register(&'static mut self, vfs: &mut Vfs, path: &'static str) -> VfsResult<()> {
+        vfs.register_device_instance(path, self.entry())
+    }
+
+    /// Input read from the backend but not yet returned by `read`, including a line being
+    /// edited.
+    pub fn pending_input(&self) -> usize {
+        self.input.len + self.line.len
+    }
+
+    /// Output accepted by `write` but not yet passed to the backend.
+    pub fn pending_output(&self) -> usize {
+        self.output.len
+    }
+
+    fn read(&mut self, buf: *mut u8, count: usize) -> isize {
+        let Some(read_fn) = self.read_fn else {
+            return forbid_read(null_mut(), buf, count);
+        };
+        if self.mode == ConsoleMode::Canonical {
+            return self.read_line(read_fn, buf, count);
+        }
+        // Left over from canonical mode.
+        if self.line.len > 0 {
+            return Self::copy_out(&mut self.line, buf, count);
+        }
+        if self.input.len == 0 {
+            let n = self.fill_input(read_fn);
+            if n <= 0 {
+                return n;
+            }
+        }
+        Self::copy_out(&mut self.input, buf, count)
+    }
+
+    /// Read a block from the backend into the empty input buffer.
+    fn fill_input(&mut self, read_fn: ConsoleReadFn) -> isize {
+        let n = read_fn(null_mut(), self.input.data.as_mut_ptr(), CONSOLE_BLOCK_SIZE);
+        if n > 0 {
+            self.input.len = n as usize;
+        }
+        n
+    }
+
+    /// Move up to `count` bytes from the front of `from` to the user buffer.
+    fn copy_out(from: &mut ConsoleBuffer, buf: *mut u8, count: usize) -> isize {
+        let n = count.min(from.len);
+        if let Err(e) = unsafe { mem::copy_to_user(buf, &from.data[..n]) } {
+            return e;
+        }
+        from.consume(n);
+        n as isize
+    }
+
+    fn read_line(&mut self, read_fn: ConsoleReadFn, buf: *mut u8, count: usize) -> isize {
+        while !self.line_done {
+            if self.input.len == 0 {
+                let n = self.fill_input(read_fn);
+                if n < 0 {
+                    return n;
+                }
+                if n == 0 {
+                    if self.line.len == 0 {
+                        return 0;
+                    }
+                    self.line_done = true;
+                    break;
+                }
+            }
+            self.cook_input();
+        }
+
+        let n = Self::copy_out(&mut self.line, buf, count);
+        if self.line.len == 0 {
+            self.line_done = false;
+        }
+        n
+    }
+
+    /// Edit buffered input into `line`, echoing it, until the line is complete. Input after
+    /// the end of the line stays buffered for the next one.
+    fn cook_input(&mut self) {
+        let mut used = 0;
+        while used < self.input.len && !self.line_done {
+            let byte = self.input.data[used];
+            used += 1;
+            match byte {
+                0x7f | 0x08 => {
+                    if self.line.len > 0 {
+                        self.line.len -= 1;
+                        self.echo(b"\x08 \x08");
+                    }
+                }
+                b'\r' | b'\n' => {
+                    self.line.push(b'\n');
+                    self.line_done = true;
+                    self.echo(b"\n");
+                }
+                _ => {
+                    self.line.push(byte);
+                    self.echo(&[byte]);
+                    // Keep room for the newline; a full line is handed over as is.
+                    if self.line.len == CONSOLE_BLOCK_SIZE - 1 {
+                        self.line_done = true;
+                    }
+                }
+            }
+        }
+        self.input.consume(used);
+    }
+
+    /// Write `bytes` back to the terminal straight away; dropped if the backend fails.
+    fn echo(&mut self, bytes: &[u8]) {
+        if self.write_fn.is_none() {
+            return;
+        }
+        for &byte in bytes {
+            if self.output.is_full() && self.flush() < 0 {
+                return;
+            }
+            self.output.push(byte);
+        }
+        self.flush();
+    }
+
+    fn write(&mut self, buf: *const u8, count: usize) -> isize {
+        let Some(_) = self.write_fn else {
+            return forbid_write(null_mut(), buf, count);
+        };
+        if let Err(e) = mem::access_ok(buf, count) {
+            return e;
+        }
+
+        let mut done = 0;
+        while done < count {
+            if self.output.is_full() {
+                let ret = self.flush();
+                if ret < 0 {
+                    return if done > 0 { done as isize } else { ret };
+                }
+            }
+            let n = (count - done).min(CONSOLE_BLOCK_SIZE - self.output.len);
+            let chunk = &mut self.output.data[self.output.len..self.output.len + n];
+            if let Err(e) = unsafe { mem::copy_from_user(chunk, buf.add(done)) } {
+                return e;
+            }
+            let newline = chunk.contains(&b'\n');
+            self.output.len += n;
+            done += n;
+            // The chunk is buffered either way; a failed flush is retried on the next one.
+            if newline && self.flush() < 0 {
+                return done as isize;
+            }
+        }
+        count as isize
+    }
+
+    fn flush(&mut self) -> isize {
+        let Some(write_fn) = self.write_fn else {
+            return 0;
+        };
+        while self.output.len > 0 {
+            let n = write_fn(null_mut(), self.output.data.as_ptr(), self.output.len);
+            if n < 0 {
+                return n;
+            }
+            if n == 0 {
+                return -(libc::EIO as isize);
+            }
+            self.output.consume(n as usize);
+        }
+        0
+    }
+
+    fn ioctl(&mut self, request: usize, arg: usize) -> isize {
+        let result = match request as libc::Ioctl {
+            libc::TCFLSH => return self.flush_queue(arg as i32),
+            libc::TIOCGWINSZ => unsafe {
+                mem::write_user_array(arg as *mut libc::winsize, &[self.winsize])
+            },
+            libc::TIOCSWINSZ => unsafe {
+                mem::read_user_array::<libc::winsize, 1>(arg as *const libc::winsize)
+                    .map(|[winsize]| self.winsize = winsize)
+            },
+            _ => return noop_ioctl(null_mut(), request, arg),
+        };
+        match result {
+            Ok(()) => 0,
+            Err(e) => e,
+        }
+    }
+
+    /// `TCFLSH` with one of the `TCIFLUSH`/`TCOFLUSH`/`TCIOFLUSH` selectors.
+    fn flush_queue(&mut self, selector: i32) -> isize {
+        match selector {
+            libc::TCIFLUSH => self.clear_input(),
+            libc::TCOFLUSH => self.output.clear(),
+            libc::TCIOFLUSH => {
+                self.clear_input();
+                self.output.clear();
+            }
+            _ => return -(libc::EINVAL as isize),
+        }
+        0
+    }
+
+    fn clear_input(&mut self) {
+        self.input.clear();
+        self.line.clear();
+        self.line_done = false;
+    }
+
+    fn capabilities(&self) -> DeviceCaps {
+        let mut caps = DeviceCaps::TTY;
+        if self.read_fn.is_some() {
+            caps = caps | DeviceCaps::READABLE;
+        }
+        if self.write_fn.is_some() {
+            caps = caps | DeviceCaps::WRITABLE;
+        }
+        caps
+    }
+}
+
+fn device<'a>(file: *mut u8) -> &'a mut ConsoleDevice {
+    unsafe { &mut *(file as *mut ConsoleDevice) }
+}
+
+fn console_read(file: *mut u8, buf: *mut u8, count: usize) -> isize {
+    device(file).read(buf, count)
+}
+
+fn console_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+    device(file).write(buf, count)
+}
+
+fn console_flush(file: *mut u8) -> isize {
+    device(file).flush()
+}
+
+fn console_ioctl(file: *mut u8, request: usize, arg: usize) -> isize {
+    device(file).ioctl(request, arg)
+}
+
+fn console_caps(file: *mut u8) -> DeviceCaps {
+    device(file).capabilities()
+}
+
+static CONSOLE_FOPS: FileOps = FileOps {
+    read: console_read,
+    write: console_write,
+    flush: console_flush,
+    release: noop_close,
+    llseek: noop_seek,
+    ioctl: console_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: console_block_size,
+    capabilities: console_caps,
+    stat: noop_stat,
+    size: noop_size,
+};
+
+/// How many paths `make_console_factory` can back.
+pub const MAX_CONSOLE_FACTORIES: usize = 4;
+
+/// Backends for a console opened through a factory from `make_console_factory`.
+#[derive(Clone, Copy)]
+pub struct ConsoleFactory {
+    pub read_fn: Option<ConsoleReadFn>,
+    pub write_fn: Option<ConsoleWriteFn>,
+}
+
+type FactoryConsoles = [Option<(&'static str, ConsoleDevice)>; MAX_CONSOLE_FACTORIES];
+
+static FACTORY_CONSOLES: GlobalCell<FactoryConsoles> =
+    GlobalCell::new([const { None }; MAX_CONSOLE_FACTORIES]);
+
+/// Opens of a path no console is bound to: no caps, and both directions fail with `EBADF`.
+static UNBOUND_FOPS: FileOps = FileOps {
+    read: forbid_read,
+    write: forbid_write,
+    capabilities: noop_capabilities,
+    ..write_only_fops(forbid_write)
+};
+
+/// Whether `pattern`, as given to `Vfs::register_device`, covers `path`.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| !rest.contains('/')),
+        None => pattern == path,
+    }
+}
+
+fn console_factory(path: &str) -> FdEntry {
+    FACTORY_CONSOLES.with_mut(|consoles| {
+        consoles
+            .iter_mut()
+            .flatten()
+            .find(|(pattern, _)| pattern_matches(pattern, path))
+            .map(|(_, console)| console.entry())
+            .unwrap_or(FdEntry {
+                ops: &UNBOUND_FOPS,
+                private_data: null_mut(),
+            })
+    })
+}
+
+/// Set up a console over `backends` for `path` and return a factory to pass to
+/// `register_device` with the same `path`. Every open of the path, or of each path a
+/// `prefix*` pattern covers, shares that one console.
+///
+/// Fails with `EEXIST` if `path` already has a console and `ENOMEM` once
+/// `MAX_CONSOLE_FACTORIES` are set up.
+pub fn make_console_factory(
+    path: &'static str,
+    backends: ConsoleFactory,
+) -> VfsResult<DeviceFactory> {
+    FACTORY_CONSOLES.with_mut(|consoles| {
+        if consoles.iter().flatten().any(|(p, _)| *p == path) {
+            return Err(-(libc::EEXIST as isize));
+        }
+        let slot = consoles
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(-(libc::ENOMEM as isize))?;
+        *slot = Some((
+            path,
+            ConsoleDevice::new(backends.read_fn, backends.write_fn),
+        ));
+        Ok(console_factory as DeviceFactory)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_all(_file: *mut u8, _buf: *const u8, count: usize) -> isize {
+        count as isize
+    }
+
+    static STDOUT_FOPS: FileOps = write_only_fops(write_all);
+
+    #[test]
+    fn test_console_block_size_via_vfs() {
+        let mut vfs = Vfs::new();
+        vfs.register_fd(
+            1,
+            FdEntry {
+                ops: &STDOUT_FOPS,
+                private_data: null_mut(),
+            },
+        )
+        .unwrap();
+        assert_eq!(vfs.block_size(1), Ok(CONSOLE_BLOCK_SIZE));
+        assert_eq!(vfs.block_size(2), Err(-(libc::EBADF as isize)));
+    }
+
+    #[test]
+    fn test_console_unsupported_direction_is_ebadf() {
+        let stdin = read_only_fops(None);
+        let mut buf = [0u8; 4];
+        assert_eq!((stdin.read)(null_mut(), buf.as_mut_ptr(), buf.len()), 0);
+        assert_eq!(
+            (stdin.write)(null_mut(), buf.as_ptr(), buf.len()),
+            -(libc::EBADF as isize)
+        );
+        assert_eq!(
+            (STDOUT_FOPS.read)(null_mut(), buf.as_mut_ptr(), buf.len()),
+            -(libc::EBADF as isize)
+        );
+    }
+
+    #[test]
+    fn test_console_capabilities() {
+        let stdin = read_only_fops(None);
+        assert_eq!((stdin.capabilities)(null_mut()), DeviceCaps::READABLE);
+        assert_eq!((STDOUT_FOPS.capabilities)(null_mut()), DeviceCaps::WRITABLE);
+    }
+
+    fn hello_read(_file: *mut u8, buf: *mut u8, count: usize) -> isize {
+        let n = count.min(6);
+        unsafe { core::ptr::copy_nonoverlapping(b"hello\n".as_ptr(), buf, n) };
+        n as isize
+    }
+
+    /// Buffers `hello\n` of input and `abc` of output.
+    fn buffered_console() -> ConsoleDevice {
+        let mut console = ConsoleDevice::new(Some(hello_read), Some(write_all));
+        let mut buf = [0u8; 1];
+        assert_eq!(console.read(buf.as_mut_ptr(), 1), 1);
+        assert_eq!(console.write(b"abc".as_ptr(), 3), 3);
+        assert_eq!(console.pending_input(), 5);
+        assert_eq!(console.pending_output(), 3);
+        console
+    }
+
+    #[test]
+    fn test_console_tcflsh_selectors() {
+        let tcflsh = libc::TCFLSH as usize;
+
+        let mut console = buffered_console();
+        assert_eq!(console.ioctl(tcflsh, libc::TCIFLUSH as usize), 0);
+        assert_eq!(console.pending_input(), 0);
+        assert_eq!(console.pending_output(), 3);
+
+        let mut console = buffered_console();
+        assert_eq!(console.ioctl(tcflsh, libc::TCOFLUSH as usize), 0);
+        assert_eq!(console.pending_input(), 5);
+        assert_eq!(console.pending_output(), 0);
+
+        let mut console = buffered_console();
+        assert_eq!(console.ioctl(tcflsh, libc::TCIOFLUSH as usize), 0);
+        assert_eq!(console.pending_input(), 0);
+        assert_eq!(console.pending_output(), 0);
+
+        let mut console = buffered_console();
+        assert_eq!(console.ioctl(tcflsh, 7), -(libc::EINVAL as isize));
+        assert_eq!(console.pending_input(), 5);
+        assert_eq!(console.pending_output(), 3);
+    }
+
+    #[test]
+    fn test_console_device_via_vfs() {
+        let mut console = buffered_console();
+        let mut vfs = Vfs::new();
+        vfs.register_fd(0, console.entry()).unwrap();
+
+        let mut buf = [0u8; 8];
+        assert_eq!(vfs.read(0, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(&buf[..5], b"ello\n");
+        assert_eq!(vfs.ioctl(0, 0x1234, 0), -(libc::ENOTTY as isize));
+        assert_eq!(
+            vfs.ioctl(0, libc::TCFLSH as usize, libc::TCOFLUSH as usize),
+            0
+        );
+        assert!(vfs.capabilities(0).unwrap().contains(DeviceCaps::TTY));
+        assert_eq!(vfs.write(0, b"line\n".as_ptr(), 5), 5);
+        assert_eq!(console.pending_output(), 0);
+    }
+
+    #[test]
+    fn test_console_device_null_buffer_faults() {
+        let mut console = buffered_console();
+        let efault = -(libc::EFAULT as isize);
+        assert_eq!(console.read(null_mut(), 1), efault);
+        assert_eq!(console.write(core::ptr::null(), 1), efault);
+        assert_eq!(console.pending_input(), 5);
+        assert_eq!(console.pending_output(), 3);
+    }
+
+    /// Hands out `SCRIPT` a few bytes per call, then end of input.
+    fn script_read(_file: *mut u8, buf: *mut u8, count: usize) -> isize {
+        const SCRIPT: &[u8] = b"lz\x7fs\rab\x08\x08\x08cd\nxy";
+        static mut POS: usize = 0;
+        let pos = unsafe { POS };
+        let n = count.min(4).min(SCRIPT.len() - pos);
+        unsafe {
+            core::ptr::copy_nonoverlapping(SCRIPT[pos..].as_ptr(), buf, n);
+            POS += n;
+        }
+        n as isize
+    }
+
+    static mut ECHO: [u8; 64] = [0; 64];
+    static mut ECHO_LEN: usize = 0;
+
+    fn echo_write(_file: *mut u8, buf: *const u8, count: usize) -> isize {
+        unsafe {
+            let echo = &mut *core::ptr::addr_of_mut!(ECHO);
+            core::ptr::copy_nonoverlapping(buf, echo[ECHO_LEN..].as_mut_ptr(), count);
+            ECHO_LEN += count;
+        }
+        count as isize
+    }
+
+    #[test]
+    fn test_canonical_lines_with_backspace() {
+        let mut console = ConsoleDevice::new(Some(script_read), Some(echo_write))
+            .with_mode(ConsoleMode::Canonical);
+        let mut buf = [0u8; 16];
+
+        assert_eq!(console.read(buf.as_mut_ptr(), buf.len()), 3);
+        assert_eq!(&buf[..3], b"ls\n");
+        // A short read leaves the rest of the line for the next one.
+        assert_eq!(console.read(buf.as_mut_ptr(), 2), 2);
+        assert_eq!(&buf[..2], b"cd");
+        assert_eq!(console.read(buf.as_mut_ptr(), buf.len()), 1);
+        assert_eq!(buf[0], b'\n');
+        // End of input hands over the unfinished line, then reports EOF.
+        assert_eq!(console.read(buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(&buf[..2], b"xy");
+        assert_eq!(console.read(buf.as_mut_ptr(), buf.len()), 0);
+
+        let echo = unsafe {
+            core::slice::from_raw_parts(core::ptr::addr_of!(ECHO).cast::<u8>(), ECHO_LEN)
+        };
+        // The third backspace finds the line empty and echoes nothing.
+        assert_eq!(echo, b"lz\x08 \x08s\nab\x08 \x08\x08 \x08cd\nxy");
+    }
+
+    #[test]
+    fn test_raw_mode_is_default() {
+        let console = ConsoleDevice::new(None, None);
+        assert_eq!(console.mode(), ConsoleMode::Raw);
+    }
+
+    #[test]
+    fn test_console_window_size() {
+        let mut console = ConsoleDevice::new(None, None);
+        let mut winsize = console.window_size();
+        assert_eq!((winsize.ws_row, winsize.ws_col), (24, 80));
+
+        winsize.ws_row = 50;
+        winsize.ws_col = 132;
+        let arg = &winsize as *const libc::winsize as usize;
+        assert_eq!(console.ioctl(libc::TIOCSWINSZ as usize, arg), 0);
+
+        let mut got = ConsoleDevice::new(None, None)
+            .with_window_size(1, 1)
+            .window_size();
+        let arg = &mut got as *mut libc::winsize as usize;
+        assert_eq!(console.ioctl(libc::TIOCGWINSZ as usize, arg), 0);
+        assert_eq!((got.ws_row, got.ws_col), (50, 132));
+
+        assert_eq!(
+            console.ioctl(libc::TIOCGWINSZ as usize, 0),
+            -(libc::EFAULT as isize)
+        );
+        assert_eq!(
+            console.ioctl(libc::TIOCGPGRP as usize, arg),
+            -(libc::ENOTTY as isize)
+        );
+    }
+
+    #[test]
+    fn test_console_configured_window_size_via_vfs() {
+        let mut console = ConsoleDevice::new(None, None).with_window_size(40, 100);
+        let mut vfs = Vfs::new();
+        vfs.register_fd(0, console.entry()).unwrap();
+
+        let mut winsize = console.window_size();
+        winsize.ws_row = 0;
+        let arg = &mut winsize as *mut libc::winsize as usize;
+        assert_eq!(vfs.ioctl(0, libc::TIOCGWINSZ as usize, arg), 0);
+        assert_eq!((winsize.ws_row, winsize.ws_col), (40, 100));
+    }
+
+    #[test]
+    fn test_console_registered_at_path() {
+        static mut CONSOLE: ConsoleDevice = ConsoleDevice::new(Some(hello_read), None);
+        let console = unsafe { &mut *core::ptr::addr_of_mut!(CONSOLE) };
+        let mut vfs = Vfs::new();
+        console.register(&mut vfs, "/dev/console").unwrap();
+
+        let a = vfs.open("/dev/console", libc::O_RDWR, 0).unwrap();
+        let b = vfs.open("/dev/console", libc::O_RDWR, 0).unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(vfs.read(a, buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(&buf, b"he");
+        // Opens share the console, so the read-ahead is there for the other fd.
+        assert_eq!(vfs.read(b, buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(&buf, b"ll");
+        assert_eq!(vfs.close(a), 0);
+        assert_eq!(vfs.close(b), 0);
+    }
+
+    #[test]
+    fn test_console_factory_registered_at_path() {
+        let backends = ConsoleFactory {
+            read_fn: Some(hello_read),
+            write_fn: Some(write_all),
+        };
+        let factory = make_console_factory("/dev/ttyF*", backends).unwrap();
+        assert_eq!(
+            make_console_factory("/dev/ttyF*", backends).err(),
+            Some(-(libc::EEXIST as isize))
+        );
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ttyF*", factory).unwrap();
+
+        let a = vfs.open("/dev/ttyF0", libc::O_RDWR, 0).unwrap();
+        let b = vfs.open("/dev/ttyF1", libc::O_RDWR, 0).unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(vfs.read(a, buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(&buf, b"he");
+        assert_eq!(vfs.read(b, buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(&buf, b"ll");
+        assert_eq!(vfs.write(a, b"ok\n".as_ptr(), 3), 3);
+        assert_eq!(vfs.close(a), 0);
+        assert_eq!(vfs.close(b), 0);
+
+        // A path the factory has no console for refuses I/O and claims no capabilities.
+        let unbound = factory("/dev/other");
+        assert_eq!(
+            (unbound.ops.read)(unbound.private_data, buf.as_mut_ptr(), buf.len()),
+            -(libc::EBADF as isize)
+        );
+        assert_eq!(
+            (unbound.ops.write)(unbound.private_data, buf.as_ptr(), buf.len()),
+            -(libc::EBADF as isize)
+        );
+        assert_eq!(
+            (unbound.ops.capabilities)(unbound.private_data),
+            DeviceCaps::empty()
+        );
+    }
+}
@@ -0,0 +1,88 @@
+#![no_std]
+
+use core::ptr::null_mut;
+use foundation::kfn::mem;
+use vfs_core::{
+    noop_close, noop_flush, noop_ioctl, noop_max_open, noop_mmap, noop_preferred_block_size,
+    noop_set_nonblocking, noop_size, noop_stat, DeviceCaps, FdEntry, FileOps, SeekWhence,
+};
+
+/// Reads like `/dev/zero`.
+fn full_read(_file: *mut u8, buf: *mut u8, count: usize) -> isize {
+    match unsafe { mem::clear_user(buf, count) } {
+        Ok(n) => n as isize,
+        Err(e) => e,
+    }
+}
+
+/// Every write fails as if the disk were full, for testing `ENOSPC` handling.
+fn full_write(_file: *mut u8, _buf: *const u8, _count: usize) -> isize {
+    -(libc::ENOSPC as isize)
+}
+
+/// Seeks succeed and stay at 0, as on Linux.
+fn full_seek(_file: *mut u8, _offset: isize, _whence: SeekWhence) -> isize {
+    0
+}
+
+fn full_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE | DeviceCaps::WRITABLE | DeviceCaps::SEEKABLE
+}
+
+pub const FULL_FOPS: FileOps = FileOps {
+    read: full_read,
+    write: full_write,
+    flush: noop_flush,
+    release: noop_close,
+    llseek: full_seek,
+    ioctl: noop_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: noop_preferred_block_size,
+    capabilities: full_caps,
+    stat: noop_stat,
+    size: noop_size,
+};
+
+pub fn full_factory(_path: &str) -> FdEntry {
+    FdEntry {
+        ops: &FULL_FOPS,
+        private_data: null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vfs_core::Vfs;
+
+    #[test]
+    fn test_full_write_is_enospc() {
+        let buf = [0u8; 64];
+        for count in [0, 1, buf.len()] {
+            assert_eq!(
+                full_write(null_mut(), buf.as_ptr(), count),
+                -(libc::ENOSPC as isize)
+            );
+        }
+    }
+
+    #[test]
+    fn test_full_read_zeroes() {
+        let mut buf = [0xFFu8; 64];
+        assert_eq!(full_read(null_mut(), buf.as_mut_ptr(), buf.len()), 64);
+        assert!(buf.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_full_via_vfs() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/full", full_factory).unwrap();
+        let fd = vfs.open("/dev/full", libc::O_RDWR, 0).unwrap();
+
+        assert_eq!(vfs.write(fd, b"data".as_ptr(), 4), -(libc::ENOSPC as isize));
+        assert_eq!(vfs.lseek(fd, 100, libc::SEEK_SET), 0);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_END), 0);
+    }
+}
@@ -1,7 +1,10 @@
 #![no_std]
 
 use core::ptr::null_mut;
-use vfs_core::{noop_close, noop_ioctl, noop_seek, FdEntry, FileOps};
+use vfs_core::{
+    noop_close, noop_flush, noop_ioctl, noop_max_open, noop_mmap, noop_preferred_block_size,
+    noop_seek, noop_set_nonblocking, noop_size, noop_stat, DeviceCaps, FdEntry, FileOps,
+};
 
 fn null_read(_file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
     0
@@ -11,15 +14,27 @@ fn null_write(_file: *mut u8, _buf: *const u8, count: usize) -> isize {
     count as isize
 }
 
+fn null_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE | DeviceCaps::WRITABLE
+}
+
 pub const NULL_FOPS: FileOps = FileOps {
     read: null_read,
     write: null_write,
+    flush: noop_flush,
     release: noop_close,
     llseek: noop_seek,
     ioctl: noop_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: noop_preferred_block_size,
+    capabilities: null_caps,
+    stat: noop_stat,
+    size: noop_size,
 };
 
-pub fn null_factory() -> FdEntry {
+pub fn null_factory(_path: &str) -> FdEntry {
     FdEntry {
         ops: &NULL_FOPS,
         private_data: null_mut(),
@@ -43,4 +58,57 @@ mod tests {
         let result = null_write(null_mut(), buf.as_ptr(), buf.len());
         assert_eq!(result, 64, "/dev/null write should succeed");
     }
+
+    #[test]
+    fn test_null_mmap_via_vfs() {
+        let mut vfs = vfs_core::Vfs::new();
+        vfs.register_device("/dev/null", null_factory).unwrap();
+        let fd = vfs.open("/dev/null", 0, 0).unwrap();
+        let result = vfs.mmap(fd, 0, 4096);
+        assert_eq!(
+            result,
+            -(libc::ENODEV as isize),
+            "/dev/null should not support mmap"
+        );
+    }
+
+    #[test]
+    fn test_null_block_size_via_vfs() {
+        let mut vfs = vfs_core::Vfs::new();
+        vfs.register_device("/dev/null", null_factory).unwrap();
+        let fd = vfs.open("/dev/null", 0, 0).unwrap();
+        assert_eq!(vfs.block_size(fd), Ok(1));
+    }
+
+    #[test]
+    fn test_null_capabilities_via_vfs() {
+        let mut vfs = vfs_core::Vfs::new();
+        vfs.register_device("/dev/null", null_factory).unwrap();
+        let fd = vfs.open("/dev/null", 0, 0).unwrap();
+        assert_eq!(
+            vfs.capabilities(fd),
+            Ok(DeviceCaps::READABLE | DeviceCaps::WRITABLE)
+        );
+    }
+
+    #[test]
+    fn test_null_writev_via_vfs() {
+        let mut vfs = vfs_core::Vfs::new();
+        vfs.register_device("/dev/null", null_factory).unwrap();
+        let fd = vfs.open("/dev/null", libc::O_RDWR, 0).unwrap();
+
+        let (mut a, mut b) = (*b"hello", *b" world!");
+        let iov = [
+            libc::iovec {
+                iov_base: a.as_mut_ptr().cast(),
+                iov_len: a.len(),
+            },
+            libc::iovec {
+                iov_base: b.as_mut_ptr().cast(),
+                iov_len: b.len(),
+            },
+        ];
+        assert_eq!(unsafe { vfs.writev(fd, iov.as_ptr(), iov.len()) }, 12);
+        assert_eq!(unsafe { vfs.readv(fd, iov.as_ptr(), iov.len()) }, 0);
+    }
 }
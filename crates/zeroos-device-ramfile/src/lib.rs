@@ -0,0 +1,243 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use foundation::kfn::mem;
+use vfs_core::{
+    noop_flush, noop_ioctl, noop_max_open, noop_mmap, noop_preferred_block_size,
+    noop_set_nonblocking, seek_offset, DeviceCaps, FdEntry, FileOps, SeekWhence, StatInfo,
+};
+
+/// A seekable file held in memory, for exercising read/write/lseek without a filesystem.
+///
+/// The file and its offset live behind the fd's `private_data`, so dups share the offset. It
+/// is freed when the last fd closes. `O_APPEND` is handled by the VFS seeking to the end.
+#[derive(Debug, Default)]
+pub struct RamFileDevice {
+    data: Vec<u8>,
+    pos: u64,
+}
+
+impl RamFileDevice {
+    pub const fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    pub fn with_contents(data: Vec<u8>) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Move the file into a new fd entry; `release` frees it.
+    pub fn into_entry(self) -> FdEntry {
+        FdEntry {
+            ops: &RAMFILE_FOPS,
+            private_data: Box::into_raw(Box::new(self)) as *mut u8,
+        }
+    }
+
+    fn read(&mut self, buf: *mut u8, count: usize) -> isize {
+        let len = self.data.len() as u64;
+        let start = self.pos.min(len) as usize;
+        let n = count.min(self.data.len() - start);
+        match unsafe { mem::copy_to_user(buf, &self.data[start..start + n]) } {
+            Ok(n) => {
+                self.pos += n as u64;
+                n as isize
+            }
+            Err(e) => e,
+        }
+    }
+
+    /// Write at the offset, zero-filling any gap left by seeking past the end. `EFBIG` if the
+    /// file would outgrow the address space, `ENOSPC` if the heap cannot hold it.
+    fn write(&mut self, buf: *const u8, count: usize) -> isize {
+        if let Err(e) = mem::access_ok(buf, count) {
+            return e;
+        }
+        let Some((start, end)) = usize::try_from(self.pos).ok().and_then(|start| {
+            let end = start.checked_add(count)?;
+            (end <= isize::MAX as usize).then_some((start, end))
+        }) else {
+            return -(libc::EFBIG as isize);
+        };
+        if self.data.len() < end {
+            // A seek far past the end must not take the allocator down with it.
+            if self.data.try_reserve(end - self.data.len()).is_err() {
+                return -(libc::ENOSPC as isize);
+            }
+            self.data.resize(end, 0);
+        }
+        match unsafe { mem::copy_from_user(&mut self.data[start..end], buf) } {
+            Ok(n) => {
+                self.pos = end as u64;
+                n as isize
+            }
+            Err(e) => e,
+        }
+    }
+}
+
+fn device<'a>(file: *mut u8) -> &'a mut RamFileDevice {
+    unsafe { &mut *(file as *mut RamFileDevice) }
+}
+
+fn ramfile_read(file: *mut u8, buf: *mut u8, count: usize) -> isize {
+    device(file).read(buf, count)
+}
+
+fn ramfile_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+    device(file).write(buf, count)
+}
+
+fn ramfile_seek(file: *mut u8, offset: isize, whence: SeekWhence) -> isize {
+    let size = ramfile_size(file);
+    seek_offset(&mut device(file).pos, size, offset, whence)
+}
+
+fn ramfile_size(file: *mut u8) -> Option<u64> {
+    Some(device(file).data.len() as u64)
+}
+
+fn ramfile_release(file: *mut u8) -> isize {
+    drop(unsafe { Box::from_raw(file as *mut RamFileDevice) });
+    0
+}
+
+fn ramfile_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE | DeviceCaps::WRITABLE | DeviceCaps::SEEKABLE
+}
+
+fn ramfile_stat(file: *mut u8) -> StatInfo {
+    StatInfo {
+        mode: libc::S_IFREG | 0o644,
+        size: device(file).data.len() as u64,
+        blksize: 0,
+        rdev: 0,
+    }
+}
+
+pub const RAMFILE_FOPS: FileOps = FileOps {
+    read: ramfile_read,
+    write: ramfile_write,
+    flush: noop_flush,
+    release: ramfile_release,
+    llseek: ramfile_seek,
+    ioctl: noop_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: noop_preferred_block_size,
+    capabilities: ramfile_caps,
+    stat: ramfile_stat,
+    size: ramfile_size,
+};
+
+/// A fresh, empty file for every open.
+pub fn ramfile_factory(_path: &str) -> FdEntry {
+    RamFileDevice::new().into_entry()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vfs_core::{Fd, Vfs};
+
+    fn open_ramfile(vfs: &mut Vfs, flags: i32) -> Fd {
+        vfs.register_device("/tmp/ram", ramfile_factory).unwrap();
+        vfs.open("/tmp/ram", flags, 0).unwrap()
+    }
+
+    fn read_all(vfs: &Vfs, fd: Fd) -> ([u8; 32], usize) {
+        let mut buf = [0u8; 32];
+        let n = vfs.read(fd, buf.as_mut_ptr(), buf.len());
+        assert!(n >= 0, "read failed: {}", n);
+        (buf, n as usize)
+    }
+
+    #[test]
+    fn test_write_seek_read() {
+        let mut vfs = Vfs::new();
+        let fd = open_ramfile(&mut vfs, libc::O_RDWR);
+
+        assert_eq!(vfs.write(fd, b"hello world".as_ptr(), 11), 11);
+        assert_eq!(vfs.lseek(fd, 6, libc::SEEK_SET), 6);
+        let (buf, n) = read_all(&vfs, fd);
+        assert_eq!(&buf[..n], b"world");
+        // At end of file.
+        assert_eq!(read_all(&vfs, fd).1, 0);
+
+        assert_eq!(vfs.lseek(fd, -5, libc::SEEK_END), 6);
+        assert_eq!(vfs.write(fd, b"there".as_ptr(), 5), 5);
+        assert_eq!(vfs.lseek(fd, -11, libc::SEEK_CUR), 0);
+        let (buf, n) = read_all(&vfs, fd);
+        assert_eq!(&buf[..n], b"hello there");
+        assert_eq!(vfs.lseek(fd, -1, libc::SEEK_SET), -(libc::EINVAL as isize));
+    }
+
+    #[test]
+    fn test_write_past_end_zero_fills() {
+        let mut vfs = Vfs::new();
+        let fd = open_ramfile(&mut vfs, libc::O_RDWR);
+
+        assert_eq!(vfs.write(fd, b"ab".as_ptr(), 2), 2);
+        assert_eq!(vfs.lseek(fd, 3, libc::SEEK_END), 5);
+        assert_eq!(vfs.write(fd, b"cd".as_ptr(), 2), 2);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_SET), 0);
+        let (buf, n) = read_all(&vfs, fd);
+        assert_eq!(&buf[..n], b"ab\0\0\0cd");
+    }
+
+    #[test]
+    fn test_write_too_far_past_end_fails() {
+        let mut vfs = Vfs::new();
+        let fd = open_ramfile(&mut vfs, libc::O_RDWR);
+
+        let far = (isize::MAX / 2) as usize;
+        assert_eq!(vfs.lseek(fd, far as isize, libc::SEEK_SET), far as isize);
+        assert_eq!(vfs.write(fd, b"x".as_ptr(), 1), -(libc::ENOSPC as isize));
+        assert_eq!(vfs.lseek(fd, isize::MAX, libc::SEEK_SET), isize::MAX);
+        assert_eq!(vfs.write(fd, b"x".as_ptr(), 1), -(libc::EFBIG as isize));
+
+        // The file is untouched.
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_END), 0);
+    }
+
+    #[test]
+    fn test_append_and_fresh_file_per_open() {
+        let mut vfs = Vfs::new();
+        let fd = open_ramfile(&mut vfs, libc::O_RDWR | libc::O_APPEND);
+        assert_eq!(vfs.write(fd, b"one".as_ptr(), 3), 3);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_SET), 0);
+        assert_eq!(vfs.write(fd, b"two".as_ptr(), 3), 3);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_SET), 0);
+        let (buf, n) = read_all(&vfs, fd);
+        assert_eq!(&buf[..n], b"onetwo");
+
+        let other = vfs.open("/tmp/ram", libc::O_RDWR, 0).unwrap();
+        assert_eq!(read_all(&vfs, other).1, 0);
+        assert_eq!(vfs.close(other), 0);
+        assert_eq!(vfs.close(fd), 0);
+    }
+
+    #[test]
+    fn test_stat_and_contents() {
+        let mut file = RamFileDevice::with_contents(b"xyz".to_vec());
+        let entry = FdEntry {
+            ops: &RAMFILE_FOPS,
+            private_data: &mut file as *mut RamFileDevice as *mut u8,
+        };
+        let stat = (entry.ops.stat)(entry.private_data);
+        assert_eq!((stat.mode & libc::S_IFMT, stat.size), (libc::S_IFREG, 3));
+        assert_eq!((entry.ops.size)(entry.private_data), Some(3));
+        assert_eq!(file.contents(), b"xyz");
+    }
+}
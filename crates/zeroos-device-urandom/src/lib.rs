@@ -1,25 +1,40 @@
 #![no_std]
 
 use core::ptr::null_mut;
+use foundation::kfn::mem;
 
-use vfs_core::FileOps;
+use vfs_core::{
+    forbid_write, noop_flush, noop_max_open, noop_mmap, noop_preferred_block_size,
+    noop_set_nonblocking, noop_size, noop_stat, DeviceCaps, FileOps, SeekWhence,
+};
 
 fn urandom_read(_file: *mut u8, buf: *mut u8, count: usize) -> isize {
-    if count != 0 && buf.is_null() {
-        return -(libc::EFAULT as isize);
+    if let Err(e) = mem::access_ok(buf, count) {
+        return e;
     }
-    unsafe { foundation::kfn::random::krandom(buf, count) }
-}
-
-fn urandom_write(_file: *mut u8, _buf: *const u8, _count: usize) -> isize {
-    -(libc::EBADF as isize)
+    // Generate into a kernel buffer so the user buffer is only touched by `copy_to_user`.
+    // 64 bytes is a whole number of RNG blocks, so the output matches one large fill.
+    let mut chunk = [0u8; 64];
+    let mut done = 0;
+    while done < count {
+        let n = (count - done).min(chunk.len());
+        let ret = unsafe { foundation::kfn::random::krandom(chunk.as_mut_ptr(), n) };
+        if ret < 0 {
+            return ret;
+        }
+        if let Err(e) = unsafe { mem::copy_to_user(buf.wrapping_add(done), &chunk[..n]) } {
+            return e;
+        }
+        done += n;
+    }
+    count as isize
 }
 
 fn urandom_close(_file: *mut u8) -> isize {
     0
 }
 
-fn urandom_seek(_file: *mut u8, _offset: isize, _whence: i32) -> isize {
+fn urandom_seek(_file: *mut u8, _offset: isize, _whence: SeekWhence) -> isize {
     -(libc::ESPIPE as isize)
 }
 
@@ -27,17 +42,52 @@ fn urandom_ioctl(_file: *mut u8, _request: usize, _arg: usize) -> isize {
     -(libc::ENOTTY as isize)
 }
 
+fn urandom_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE
+}
+
 pub const URANDOM_FOPS: FileOps = FileOps {
     read: urandom_read,
-    write: urandom_write,
+    write: forbid_write,
+    flush: noop_flush,
     release: urandom_close,
     llseek: urandom_seek,
     ioctl: urandom_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: noop_preferred_block_size,
+    capabilities: urandom_caps,
+    stat: noop_stat,
+    size: noop_size,
 };
 
-pub fn urandom_factory() -> vfs_core::FdEntry {
+pub fn urandom_factory(_path: &str) -> vfs_core::FdEntry {
     vfs_core::FdEntry {
         ops: &URANDOM_FOPS,
         private_data: null_mut(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urandom_write_is_ebadf() {
+        let buf = [0u8; 4];
+        assert_eq!(
+            (URANDOM_FOPS.write)(null_mut(), buf.as_ptr(), buf.len()),
+            -(libc::EBADF as isize)
+        );
+    }
+
+    #[test]
+    fn test_urandom_capabilities() {
+        // Writes are rejected with EBADF, so only READABLE is reported.
+        assert_eq!(
+            (URANDOM_FOPS.capabilities)(null_mut()),
+            DeviceCaps::READABLE
+        );
+    }
+}
@@ -1,36 +1,44 @@
 #![no_std]
 
 use core::ptr::null_mut;
-use vfs_core::{noop_close, noop_ioctl, noop_seek, FdEntry, FileOps};
+use foundation::kfn::mem;
+use vfs_core::{
+    noop_close, noop_flush, noop_ioctl, noop_max_open, noop_mmap, noop_preferred_block_size,
+    noop_seek, noop_set_nonblocking, noop_size, noop_stat, DeviceCaps, FdEntry, FileOps,
+};
 
 fn zero_read(_file: *mut u8, buf: *mut u8, count: usize) -> isize {
-    if count == 0 {
-        return 0;
-    }
-    if buf.is_null() {
-        return -(libc::EFAULT as isize);
-    }
-
-    unsafe {
-        core::ptr::write_bytes(buf, 0, count);
+    match unsafe { mem::clear_user(buf, count) } {
+        Ok(n) => n as isize,
+        Err(e) => e,
     }
-
-    count as isize
 }
 
 fn zero_write(_file: *mut u8, _buf: *const u8, count: usize) -> isize {
     count as isize
 }
 
+fn zero_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE | DeviceCaps::WRITABLE
+}
+
 pub const ZERO_FOPS: FileOps = FileOps {
     read: zero_read,
     write: zero_write,
+    flush: noop_flush,
     release: noop_close,
     llseek: noop_seek,
     ioctl: noop_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: noop_preferred_block_size,
+    capabilities: zero_caps,
+    stat: noop_stat,
+    size: noop_size,
 };
 
-pub fn zero_factory() -> FdEntry {
+pub fn zero_factory(_path: &str) -> FdEntry {
     FdEntry {
         ops: &ZERO_FOPS,
         private_data: null_mut(),
@@ -55,4 +63,11 @@ mod tests {
         let result = zero_write(null_mut(), buf.as_ptr(), buf.len());
         assert_eq!(result, 64, "/dev/zero write should succeed");
     }
+
+    #[test]
+    fn test_zero_capabilities() {
+        let caps = (ZERO_FOPS.capabilities)(null_mut());
+        assert_eq!(caps, DeviceCaps::READABLE | DeviceCaps::WRITABLE);
+        assert!(!caps.contains(DeviceCaps::SEEKABLE));
+    }
 }
@@ -0,0 +1,16 @@
+//! Linux errno values usable on every target.
+//!
+//! On Linux targets these are `libc`'s own. `libc` defines no errno constants for the
+//! `*-unknown-none-*` targets, so there the same Linux values are spelled out.
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        pub use libc::{EEXIST, EFAULT, EINVAL, ENAMETOOLONG, ENOTTY};
+    } else {
+        pub const EFAULT: i32 = 14;
+        pub const EEXIST: i32 = 17;
+        pub const EINVAL: i32 = 22;
+        pub const ENOTTY: i32 = 25;
+        pub const ENAMETOOLONG: i32 = 36;
+    }
+}
@@ -0,0 +1,463 @@
+//! Linux ioctl request numbers.
+//!
+//! A request packs a direction, an argument size, a type ("magic") byte and a number into one
+//! word, as `_IOC` does in the generic Linux ABI that RISC-V uses. Legacy requests such as
+//! `TCGETS` predate the scheme; they decode to `IoctlDir::None` with a zero size.
+
+use alloc::vec::Vec;
+
+use crate::errno;
+
+pub const NRBITS: u32 = 8;
+pub const TYPEBITS: u32 = 8;
+pub const SIZEBITS: u32 = 14;
+pub const DIRBITS: u32 = 2;
+
+pub const NRSHIFT: u32 = 0;
+pub const TYPESHIFT: u32 = NRSHIFT + NRBITS;
+pub const SIZESHIFT: u32 = TYPESHIFT + TYPEBITS;
+pub const DIRSHIFT: u32 = SIZESHIFT + SIZEBITS;
+
+/// Largest argument size a request can encode.
+pub const MAX_SIZE: usize = (1 << SIZEBITS) - 1;
+
+/// Which way the argument moves, named from the caller's side as in Linux: `Write` passes
+/// data to the device, `Read` gets data back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoctlDir {
+    None,
+    Write,
+    Read,
+    ReadWrite,
+}
+
+impl IoctlDir {
+    pub const ALL: [Self; 4] = [Self::None, Self::Write, Self::Read, Self::ReadWrite];
+
+    /// Decode the two direction bits: `_IOC_WRITE` is 1 and `_IOC_READ` is 2.
+    pub const fn from_u8(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(Self::None),
+            1 => Some(Self::Write),
+            2 => Some(Self::Read),
+            3 => Some(Self::ReadWrite),
+            _ => None,
+        }
+    }
+
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Write => 1,
+            Self::Read => 2,
+            Self::ReadWrite => 3,
+        }
+    }
+}
+
+/// A decoded ioctl request number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoctlCommand {
+    dir: IoctlDir,
+    magic: u8,
+    nr: u8,
+    size: u16,
+}
+
+impl IoctlCommand {
+    /// # Panics
+    /// If `size` is over `MAX_SIZE`; in a const this fails the build.
+    pub const fn new(dir: IoctlDir, magic: u8, nr: u8, size: usize) -> Self {
+        assert!(
+            size <= MAX_SIZE,
+            "ioctl argument size does not fit in SIZEBITS"
+        );
+        Self {
+            dir,
+            magic,
+            nr,
+            size: size as u16,
+        }
+    }
+
+    /// Like `new`, for a size only known at run time: `EINVAL` if it is over `MAX_SIZE`.
+    pub const fn new_sized(
+        dir: IoctlDir,
+        magic: u8,
+        nr: u8,
+        size_bytes: usize,
+    ) -> Result<Self, isize> {
+        if size_bytes > MAX_SIZE {
+            return Err(-(errno::EINVAL as isize));
+        }
+        Ok(Self::new(dir, magic, nr, size_bytes))
+    }
+
+    /// Decode a request number; `None` if it has bits above the 32 Linux uses.
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        if raw > u32::MAX as usize {
+            return None;
+        }
+        let Some(dir) = IoctlDir::from_u8(field(raw, DIRSHIFT, DIRBITS) as u8) else {
+            return None;
+        };
+        Some(Self {
+            dir,
+            magic: field(raw, TYPESHIFT, TYPEBITS) as u8,
+            nr: field(raw, NRSHIFT, NRBITS) as u8,
+            size: field(raw, SIZESHIFT, SIZEBITS) as u16,
+        })
+    }
+
+    pub const fn to_raw(self) -> usize {
+        (self.dir.to_u8() as usize) << DIRSHIFT
+            | (self.size as usize) << SIZESHIFT
+            | (self.magic as usize) << TYPESHIFT
+            | (self.nr as usize) << NRSHIFT
+    }
+
+    pub const fn dir(self) -> IoctlDir {
+        self.dir
+    }
+
+    pub const fn magic(self) -> u8 {
+        self.magic
+    }
+
+    pub const fn nr(self) -> u8 {
+        self.nr
+    }
+
+    /// Argument size the caller declared, for checking it against the buffer a driver expects.
+    pub const fn size_bytes(self) -> usize {
+        self.size as usize
+    }
+
+    /// Whether this is the request `raw`, e.g. `cmd.eq_raw(ior!(b'T', 1, u32))`.
+    pub const fn eq_raw(self, raw: usize) -> bool {
+        self.to_raw() == raw
+    }
+}
+
+/// Handles one request for a device of type `C`; `arg` is the caller's argument word.
+pub type IoctlHandler<C> = fn(device: &mut C, arg: usize) -> isize;
+
+/// Ioctl handlers for a device, keyed by the request's magic and number.
+///
+/// `dispatch` decodes the request and refuses it unless its direction and size are the ones the
+/// handler was registered with, so a handler only sees the argument layout it expects. For
+/// requests with an argument, the pointer has also passed `kfn::mem::access_ok`.
+pub struct IoctlRegistry<C> {
+    handlers: Vec<(IoctlCommand, IoctlHandler<C>)>,
+}
+
+impl<C> Default for IoctlRegistry<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> IoctlRegistry<C> {
+    pub const fn new() -> Self {
+        Self {
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Handle requests matching `cmd`; `EEXIST` if its magic and number are already taken.
+    pub fn register(&mut self, cmd: IoctlCommand, handler: IoctlHandler<C>) -> Result<(), isize> {
+        if self.lookup(cmd.magic, cmd.nr).is_some() {
+            return Err(-(errno::EEXIST as isize));
+        }
+        self.handlers.push((cmd, handler));
+        Ok(())
+    }
+
+    /// Run the handler for `request`.
+    ///
+    /// `ENOTTY` if nothing handles it, `EINVAL` if its direction or size differ from the
+    /// registered ones, and `EFAULT` if its argument pointer fails `access_ok`.
+    pub fn dispatch(&self, device: &mut C, request: usize, arg: usize) -> isize {
+        let Some(cmd) = IoctlCommand::from_raw(request) else {
+            return -(errno::ENOTTY as isize);
+        };
+        let Some((expected, handler)) = self.lookup(cmd.magic, cmd.nr) else {
+            return -(errno::ENOTTY as isize);
+        };
+        if cmd.dir != expected.dir || cmd.size != expected.size {
+            return -(errno::EINVAL as isize);
+        }
+        if cmd.dir != IoctlDir::None {
+            if let Err(e) = crate::kfn::mem::access_ok(arg as *const u8, cmd.size_bytes()) {
+                return e;
+            }
+        }
+        handler(device, arg)
+    }
+
+    fn lookup(&self, magic: u8, nr: u8) -> Option<(IoctlCommand, IoctlHandler<C>)> {
+        self.handlers
+            .iter()
+            .find(|(cmd, _)| cmd.magic == magic && cmd.nr == nr)
+            .copied()
+    }
+}
+
+const fn field(raw: usize, shift: u32, bits: u32) -> usize {
+    (raw >> shift) & ((1 << bits) - 1)
+}
+
+/// `_IO(magic, nr)`: a request without an argument, as a raw number.
+#[macro_export]
+macro_rules! io {
+    ($magic:expr, $nr:expr) => {
+        $crate::ioctl::IoctlCommand::new($crate::ioctl::IoctlDir::None, $magic, $nr, 0).to_raw()
+    };
+}
+
+/// `_IOR(magic, nr, T)`: the device writes a `T` back to the caller.
+#[macro_export]
+macro_rules! ior {
+    ($magic:expr, $nr:expr, $ty:ty) => {
+        $crate::ioctl::IoctlCommand::new(
+            $crate::ioctl::IoctlDir::Read,
+            $magic,
+            $nr,
+            ::core::mem::size_of::<$ty>(),
+        )
+        .to_raw()
+    };
+}
+
+/// `_IOW(magic, nr, T)`: the caller passes a `T` in.
+#[macro_export]
+macro_rules! iow {
+    ($magic:expr, $nr:expr, $ty:ty) => {
+        $crate::ioctl::IoctlCommand::new(
+            $crate::ioctl::IoctlDir::Write,
+            $magic,
+            $nr,
+            ::core::mem::size_of::<$ty>(),
+        )
+        .to_raw()
+    };
+}
+
+/// `_IOWR(magic, nr, T)`: a `T` goes in and comes back updated.
+#[macro_export]
+macro_rules! iowr {
+    ($magic:expr, $nr:expr, $ty:ty) => {
+        $crate::ioctl::IoctlCommand::new(
+            $crate::ioctl::IoctlDir::ReadWrite,
+            $magic,
+            $nr,
+            ::core::mem::size_of::<$ty>(),
+        )
+        .to_raw()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_linux_numbers() {
+        // RNDGETENTCNT, TUNSETIFF, BLKGETSIZE64 and RNDZAPENTCNT from the Linux headers.
+        assert_eq!(crate::ior!(b'R', 0x00, i32), 0x8004_5200);
+        assert_eq!(crate::iow!(b'T', 202, i32), 0x4004_54ca);
+        assert_eq!(crate::ior!(0x12, 114, u64), 0x8008_1272);
+        assert_eq!(crate::io!(b'R', 0x04), 0x5204);
+        assert_eq!(crate::iowr!(b'T', 1, [u8; 3]), 0xc003_5401);
+    }
+
+    #[test]
+    fn test_dir_bits_agree() {
+        for dir in IoctlDir::ALL {
+            assert_eq!(IoctlDir::from_u8(dir.to_u8()), Some(dir));
+            let raw = IoctlCommand::new(dir, 0, 0, 0).to_raw();
+            assert_eq!(raw >> DIRSHIFT, dir.to_u8() as usize);
+        }
+        assert_eq!(IoctlDir::from_u8(4), None);
+        // The Linux bit assignments: _IOC_WRITE = 1, _IOC_READ = 2.
+        assert_eq!(IoctlDir::from_u8(1), Some(IoctlDir::Write));
+        assert_eq!(IoctlDir::from_u8(2), Some(IoctlDir::Read));
+    }
+
+    #[test]
+    fn test_command_round_trip() {
+        for dir in IoctlDir::ALL {
+            for size in [0, 1, 8, MAX_SIZE] {
+                let cmd = IoctlCommand::new(dir, b'T', 0x13, size);
+                assert_eq!(IoctlCommand::from_raw(cmd.to_raw()), Some(cmd));
+            }
+        }
+    }
+
+    #[test]
+    fn test_raw_round_trip_over_range() {
+        // Every 32-bit value decodes; walk a stride that varies every field.
+        let mut raw: u32 = 0;
+        loop {
+            let cmd = IoctlCommand::from_raw(raw as usize).unwrap();
+            assert_eq!(cmd.to_raw(), raw as usize);
+            match raw.checked_add(0x0101_0107) {
+                Some(next) => raw = next,
+                None => break,
+            }
+        }
+        assert_eq!(
+            IoctlCommand::from_raw(u32::MAX as usize),
+            Some(IoctlCommand::new(IoctlDir::ReadWrite, 0xff, 0xff, MAX_SIZE))
+        );
+        if let Ok(raw) = usize::try_from(1u64 << 32) {
+            assert_eq!(IoctlCommand::from_raw(raw), None);
+        }
+    }
+
+    #[test]
+    fn test_each_field_masks_when_all_bits_set() {
+        let ones = |bits: u32| (1usize << bits) - 1;
+        let only = |raw: usize| IoctlCommand::from_raw(raw).unwrap();
+
+        let cmd = only(ones(NRBITS) << NRSHIFT);
+        assert_eq!(
+            (cmd.dir(), cmd.magic(), cmd.nr(), cmd.size_bytes()),
+            (IoctlDir::None, 0, 0xff, 0)
+        );
+        let cmd = only(ones(TYPEBITS) << TYPESHIFT);
+        assert_eq!(
+            (cmd.dir(), cmd.magic(), cmd.nr(), cmd.size_bytes()),
+            (IoctlDir::None, 0xff, 0, 0)
+        );
+        let cmd = only(ones(SIZEBITS) << SIZESHIFT);
+        assert_eq!(
+            (cmd.dir(), cmd.magic(), cmd.nr(), cmd.size_bytes()),
+            (IoctlDir::None, 0, 0, MAX_SIZE)
+        );
+        let cmd = only(ones(DIRBITS) << DIRSHIFT);
+        assert_eq!(
+            (cmd.dir(), cmd.magic(), cmd.nr(), cmd.size_bytes()),
+            (IoctlDir::ReadWrite, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_new_sized_limit() {
+        let cmd = IoctlCommand::new_sized(IoctlDir::Read, b'T', 1, 16383).unwrap();
+        assert_eq!(cmd.size_bytes(), MAX_SIZE);
+        assert_eq!(cmd, IoctlCommand::new(IoctlDir::Read, b'T', 1, MAX_SIZE));
+        assert_eq!(
+            IoctlCommand::new_sized(IoctlDir::Read, b'T', 1, 16384),
+            Err(-(errno::EINVAL as isize))
+        );
+    }
+
+    #[test]
+    fn test_eq_raw_against_macros() {
+        let raw = crate::ior!(b'R', 0x00, i32);
+        let cmd = IoctlCommand::from_raw(raw).unwrap();
+        assert!(cmd.eq_raw(raw));
+        assert!(!cmd.eq_raw(crate::iow!(b'R', 0x00, i32)));
+        assert!(!cmd.eq_raw(crate::ior!(b'R', 0x00, u64)));
+        assert!(IoctlCommand::from_raw(crate::io!(b'R', 0x04))
+            .unwrap()
+            .eq_raw(crate::io!(b'R', 0x04)));
+    }
+
+    #[test]
+    fn test_size_bytes_decodes() {
+        let raw = crate::iowr!(b'T', 9, [u64; 3]);
+        assert_eq!(IoctlCommand::from_raw(raw).unwrap().size_bytes(), 24);
+    }
+
+    #[derive(Default)]
+    struct FakeDevice {
+        value: u32,
+        resets: usize,
+    }
+
+    const GET_VALUE: IoctlCommand =
+        IoctlCommand::new(IoctlDir::Read, b'F', 1, core::mem::size_of::<u32>());
+    const RESET: IoctlCommand = IoctlCommand::new(IoctlDir::None, b'F', 2, 0);
+
+    fn get_value(device: &mut FakeDevice, arg: usize) -> isize {
+        unsafe { *(arg as *mut u32) = device.value };
+        0
+    }
+
+    fn reset(device: &mut FakeDevice, _arg: usize) -> isize {
+        device.resets += 1;
+        device.value = 0;
+        0
+    }
+
+    fn fake_registry() -> IoctlRegistry<FakeDevice> {
+        let mut registry = IoctlRegistry::new();
+        registry.register(GET_VALUE, get_value).unwrap();
+        registry.register(RESET, reset).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_registry_dispatches_by_command() {
+        let registry = fake_registry();
+        let mut device = FakeDevice {
+            value: 7,
+            ..Default::default()
+        };
+        let mut out = 0u32;
+        let arg = &mut out as *mut u32 as usize;
+
+        assert_eq!(registry.dispatch(&mut device, GET_VALUE.to_raw(), arg), 0);
+        assert_eq!(out, 7);
+        assert_eq!(registry.dispatch(&mut device, RESET.to_raw(), 0), 0);
+        assert_eq!((device.value, device.resets), (0, 1));
+    }
+
+    #[test]
+    fn test_registry_rejects_mismatches() {
+        let registry = fake_registry();
+        let mut device = FakeDevice::default();
+        let mut out = 0u64;
+        let arg = &mut out as *mut u64 as usize;
+
+        // Same magic and number, but declaring a u64 argument.
+        let wrong_size = crate::ior!(b'F', 1, u64);
+        assert_eq!(
+            registry.dispatch(&mut device, wrong_size, arg),
+            -(errno::EINVAL as isize)
+        );
+        let wrong_dir = crate::iow!(b'F', 1, u32);
+        assert_eq!(
+            registry.dispatch(&mut device, wrong_dir, arg),
+            -(errno::EINVAL as isize)
+        );
+        assert_eq!(
+            registry.dispatch(&mut device, crate::io!(b'F', 3), 0),
+            -(errno::ENOTTY as isize)
+        );
+        assert_eq!(
+            registry.dispatch(&mut device, GET_VALUE.to_raw(), 0),
+            -(errno::EFAULT as isize)
+        );
+        assert_eq!(out, 0);
+    }
+
+    #[test]
+    fn test_registry_refuses_duplicates() {
+        let mut registry = fake_registry();
+        let other = IoctlCommand::new(IoctlDir::Write, b'F', 2, 4);
+        assert_eq!(
+            registry.register(other, reset),
+            Err(-(errno::EEXIST as isize))
+        );
+    }
+
+    #[test]
+    fn test_legacy_request_decodes_as_none() {
+        let cmd = IoctlCommand::from_raw(0x5413).unwrap();
+        assert_eq!(cmd.dir(), IoctlDir::None);
+        assert_eq!((cmd.magic(), cmd.nr()), (0x54, 0x13));
+    }
+}
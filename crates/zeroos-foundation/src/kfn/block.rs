@@ -0,0 +1,51 @@
+//! Waiting out an operation that would block.
+//!
+//! A device that cannot make progress answers `EAGAIN`. For a blocking fd the VFS then calls
+//! `kblock_yield` and retries, so another thread gets to produce the data.
+
+use crate::utils::GlobalCell;
+
+/// Lets another thread run; returns `false` if none can.
+pub type BlockYieldFn = fn() -> bool;
+
+static BLOCK_YIELD: GlobalCell<Option<BlockYieldFn>> = GlobalCell::new(None);
+
+/// Install the scheduler's yield for blocked operations.
+pub fn set_block_yield(hook: BlockYieldFn) {
+    BLOCK_YIELD.with_mut(|slot| *slot = Some(hook));
+}
+
+/// Forget the hook from `set_block_yield`.
+pub fn clear_block_yield() {
+    BLOCK_YIELD.with_mut(|slot| *slot = None);
+}
+
+/// Yield so a blocked operation can be retried.
+///
+/// `false` means retrying is pointless: no hook is installed or no other thread can run. The
+/// caller should then report `EAGAIN` rather than spin forever.
+#[inline]
+pub fn kblock_yield() -> bool {
+    match BLOCK_YIELD.with(|slot| *slot) {
+        Some(hook) => hook(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn always_runs() -> bool {
+        true
+    }
+
+    #[test]
+    fn test_block_yield_hook() {
+        assert!(!kblock_yield());
+        set_block_yield(always_runs);
+        assert!(kblock_yield());
+        clear_block_yield();
+        assert!(!kblock_yield());
+    }
+}
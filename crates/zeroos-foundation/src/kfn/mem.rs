@@ -0,0 +1,310 @@
+//! Copies between kernel buffers and user memory.
+//!
+//! Devices get user buffers as raw pointers. These helpers keep the pointer check and the copy
+//! in one place, so a device never has to dereference a user pointer itself.
+
+use crate::errno;
+use crate::utils::GlobalCell;
+
+/// User address range as `(base, len)`, once the platform has set one.
+static USER_RANGE: GlobalCell<Option<(usize, usize)>> = GlobalCell::new(None);
+
+/// Limit user accesses to `[base, base + len)`; `access_ok` rejects anything outside it.
+///
+/// Until this is called any non-null range is accepted, which host tests rely on.
+pub fn set_user_range(base: usize, len: usize) {
+    USER_RANGE.with_mut(|range| *range = Some((base, len)));
+}
+
+/// Forget the range from `set_user_range`.
+pub fn clear_user_range() {
+    USER_RANGE.with_mut(|range| *range = None);
+}
+
+/// The range from `set_user_range`, if any.
+pub fn user_range() -> Option<(usize, usize)> {
+    USER_RANGE.with(|range| *range)
+}
+
+/// Check that `len` bytes at `addr` may be accessed for the user.
+///
+/// Rejects null, ranges that wrap the address space and, once `set_user_range` has been
+/// called, ranges not wholly inside the user range, with `EFAULT`. An empty range is always
+/// fine.
+#[inline]
+pub fn access_ok(addr: *const u8, len: usize) -> Result<(), isize> {
+    access_ok_in(addr as usize, len, user_range())
+}
+
+fn access_ok_in(addr: usize, len: usize, range: Option<(usize, usize)>) -> Result<(), isize> {
+    if len == 0 {
+        return Ok(());
+    }
+    let end = match addr.checked_add(len) {
+        Some(end) if addr != 0 => end,
+        _ => return Err(-(errno::EFAULT as isize)),
+    };
+    if let Some((base, size)) = range {
+        // A range running off the top of the address space ends at the top.
+        let limit = base.saturating_add(size);
+        if addr < base || end > limit {
+            return Err(-(errno::EFAULT as isize));
+        }
+    }
+    Ok(())
+}
+
+/// Copy `src` to user memory at `dst`, returning the number of bytes copied.
+///
+/// # Safety
+/// If `access_ok(dst, src.len())` passes, `dst` must be valid for writes of `src.len()` bytes.
+pub unsafe fn copy_to_user(dst: *mut u8, src: &[u8]) -> Result<usize, isize> {
+    access_ok(dst, src.len())?;
+    if !src.is_empty() {
+        core::ptr::copy_nonoverlapping(src.as_ptr(), dst, src.len());
+    }
+    Ok(src.len())
+}
+
+/// Fill `dst` from user memory at `src`, returning the number of bytes copied.
+///
+/// # Safety
+/// If `access_ok(src, dst.len())` passes, `src` must be valid for reads of `dst.len()` bytes.
+pub unsafe fn copy_from_user(dst: &mut [u8], src: *const u8) -> Result<usize, isize> {
+    access_ok(src, dst.len())?;
+    if !dst.is_empty() {
+        core::ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), dst.len());
+    }
+    Ok(dst.len())
+}
+
+/// Zero `len` bytes of user memory at `dst`, returning `len`.
+///
+/// # Safety
+/// If `access_ok(dst, len)` passes, `dst` must be valid for writes of `len` bytes.
+pub unsafe fn clear_user(dst: *mut u8, len: usize) -> Result<usize, isize> {
+    access_ok(dst, len)?;
+    if len != 0 {
+        core::ptr::write_bytes(dst, 0, len);
+    }
+    Ok(len)
+}
+
+/// Read a `[T; N]` from user memory, checking the whole array once instead of per element.
+///
+/// Misaligned `src` is `EFAULT`, like a null one.
+///
+/// # Safety
+/// If the checks pass, `src` must be valid for reads of `N` `T`s.
+pub unsafe fn read_user_array<T: Copy, const N: usize>(src: *const T) -> Result<[T; N], isize> {
+    access_ok(src.cast(), core::mem::size_of::<[T; N]>())?;
+    if !src.is_aligned() {
+        return Err(-(errno::EFAULT as isize));
+    }
+    Ok(src.cast::<[T; N]>().read())
+}
+
+/// Write `values` to user memory at `dst`, checking the whole array once.
+///
+/// # Safety
+/// If the checks pass, `dst` must be valid for writes of `N` `T`s.
+pub unsafe fn write_user_array<T: Copy, const N: usize>(
+    dst: *mut T,
+    values: &[T; N],
+) -> Result<(), isize> {
+    access_ok(dst.cast_const().cast(), core::mem::size_of::<[T; N]>())?;
+    if !dst.is_aligned() {
+        return Err(-(errno::EFAULT as isize));
+    }
+    dst.cast::<[T; N]>().write(*values);
+    Ok(())
+}
+
+/// Borrow the NUL-terminated UTF-8 string at `src`, as for a `const char *` syscall argument.
+///
+/// Null `src`, or a string running out of the user range, is `EFAULT`. A string longer than
+/// `max_len` bytes (before the NUL) is `ENAMETOOLONG`, and invalid UTF-8 is `EINVAL`.
+///
+/// # Safety
+/// Where `access_ok` passes, `src` must be readable up to its NUL or `max_len + 1` bytes, whichever
+/// comes first, and stay unchanged for `'a`.
+pub unsafe fn read_user_cstr<'a>(src: *const u8, max_len: usize) -> Result<&'a str, isize> {
+    let mut len = 0;
+    loop {
+        access_ok(src.wrapping_add(len), 1)?;
+        if *src.add(len) == 0 {
+            break;
+        }
+        if len == max_len {
+            return Err(-(errno::ENAMETOOLONG as isize));
+        }
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(src, len))
+        .map_err(|_| -(errno::EINVAL as isize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_round_trip() {
+        let mut user = [0u8; 8];
+        assert_eq!(unsafe { copy_to_user(user.as_mut_ptr(), b"abcd") }, Ok(4));
+        assert_eq!(&user[..5], b"abcd\0");
+
+        let mut kernel = [0u8; 3];
+        assert_eq!(unsafe { copy_from_user(&mut kernel, user.as_ptr()) }, Ok(3));
+        assert_eq!(&kernel, b"abc");
+    }
+
+    #[test]
+    fn test_clear_user() {
+        let mut user = [0xFFu8; 8];
+        assert_eq!(unsafe { clear_user(user.as_mut_ptr(), 5) }, Ok(5));
+        assert_eq!(user, [0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_read_user_cstr() {
+        assert_eq!(
+            unsafe { read_user_cstr(c"/dev/null".as_ptr().cast(), 64) },
+            Ok("/dev/null")
+        );
+        assert_eq!(unsafe { read_user_cstr(c"".as_ptr().cast(), 0) }, Ok(""));
+        assert_eq!(
+            unsafe { read_user_cstr(core::ptr::null(), 64) },
+            Err(-(errno::EFAULT as isize))
+        );
+    }
+
+    #[test]
+    fn test_read_user_cstr_length_cap() {
+        let path = c"abcd".as_ptr().cast();
+        assert_eq!(unsafe { read_user_cstr(path, 4) }, Ok("abcd"));
+        assert_eq!(
+            unsafe { read_user_cstr(path, 3) },
+            Err(-(errno::ENAMETOOLONG as isize))
+        );
+    }
+
+    #[test]
+    fn test_read_user_cstr_invalid_utf8() {
+        assert_eq!(
+            unsafe { read_user_cstr(c"ab\xffcd".as_ptr().cast(), 64) },
+            Err(-(errno::EINVAL as isize))
+        );
+    }
+
+    #[test]
+    fn test_null_faults() {
+        let null = core::ptr::null_mut();
+        let mut kernel = [0u8; 4];
+        assert_eq!(
+            unsafe { copy_to_user(null, b"abcd") },
+            Err(-(errno::EFAULT as isize))
+        );
+        assert_eq!(
+            unsafe { copy_from_user(&mut kernel, null) },
+            Err(-(errno::EFAULT as isize))
+        );
+        assert_eq!(
+            unsafe { clear_user(null, 4) },
+            Err(-(errno::EFAULT as isize))
+        );
+    }
+
+    #[test]
+    fn test_wrapping_range_faults() {
+        let top = usize::MAX as *mut u8;
+        assert_eq!(access_ok(top, 2), Err(-(errno::EFAULT as isize)));
+        assert_eq!(
+            unsafe { clear_user(top, 2) },
+            Err(-(errno::EFAULT as isize))
+        );
+    }
+
+    #[test]
+    fn test_user_range_bounds() {
+        let range = Some((0x1000, 0x1000));
+        assert_eq!(access_ok_in(0x1000, 0x1000, range), Ok(()));
+        assert_eq!(access_ok_in(0x1800, 0x10, range), Ok(()));
+        // Straddling either edge.
+        assert_eq!(
+            access_ok_in(0xff0, 0x20, range),
+            Err(-(errno::EFAULT as isize))
+        );
+        assert_eq!(
+            access_ok_in(0x1ff0, 0x20, range),
+            Err(-(errno::EFAULT as isize))
+        );
+        // Entirely above or below.
+        assert_eq!(
+            access_ok_in(0x2000, 1, range),
+            Err(-(errno::EFAULT as isize))
+        );
+        assert_eq!(access_ok_in(0x10, 1, range), Err(-(errno::EFAULT as isize)));
+        // Empty accesses are fine anywhere.
+        assert_eq!(access_ok_in(0x2000, 0, range), Ok(()));
+        // Without a range only null and wrapping are rejected.
+        assert_eq!(access_ok_in(0x2000, 1, None), Ok(()));
+    }
+
+    #[test]
+    fn test_set_user_range() {
+        // Other tests share the global range, so only ever set one covering everything.
+        set_user_range(1, usize::MAX);
+        assert_eq!(user_range(), Some((1, usize::MAX)));
+        let mut user = [0u8; 4];
+        assert_eq!(unsafe { copy_to_user(user.as_mut_ptr(), b"ok") }, Ok(2));
+        clear_user_range();
+        assert_eq!(user_range(), None);
+    }
+
+    #[test]
+    fn test_empty_copies_ignore_pointer() {
+        let null = core::ptr::null_mut();
+        assert_eq!(unsafe { copy_to_user(null, &[]) }, Ok(0));
+        assert_eq!(unsafe { copy_from_user(&mut [], null) }, Ok(0));
+        assert_eq!(unsafe { clear_user(null, 0) }, Ok(0));
+    }
+
+    #[test]
+    fn test_user_array_round_trip() {
+        let mut user = [0u32; 4];
+        assert_eq!(
+            unsafe { write_user_array(user.as_mut_ptr(), &[1, 2, 3, 4]) },
+            Ok(())
+        );
+        assert_eq!(user, [1, 2, 3, 4]);
+        assert_eq!(
+            unsafe { read_user_array::<u32, 4>(user.as_ptr()) },
+            Ok([1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn test_user_array_faults() {
+        let null = core::ptr::null_mut::<u32>();
+        assert_eq!(
+            unsafe { read_user_array::<u32, 4>(null) },
+            Err(-(errno::EFAULT as isize))
+        );
+        assert_eq!(
+            unsafe { write_user_array(null, &[0u32; 4]) },
+            Err(-(errno::EFAULT as isize))
+        );
+
+        let mut user = [0u32; 5];
+        let misaligned = unsafe { user.as_mut_ptr().cast::<u8>().add(1) }.cast::<u32>();
+        assert_eq!(
+            unsafe { read_user_array::<u32, 4>(misaligned) },
+            Err(-(errno::EFAULT as isize))
+        );
+        assert_eq!(
+            unsafe { write_user_array(misaligned, &[0u32; 4]) },
+            Err(-(errno::EFAULT as isize))
+        );
+    }
+}
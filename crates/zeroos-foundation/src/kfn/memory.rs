@@ -3,6 +3,8 @@ use core::ptr;
 
 use cfg_if::cfg_if;
 
+use crate::ops::AllocStats;
+
 cfg_if! {
     if #[cfg(feature = "memory")] {
         #[inline]
@@ -24,6 +26,11 @@ cfg_if! {
         pub fn kinit(heap_start: usize, heap_size: usize) {
             unsafe { (crate::KERNEL.memory.init)(heap_start, heap_size) }
         }
+
+        #[inline]
+        pub fn kstats() -> AllocStats {
+            unsafe { (crate::KERNEL.memory.stats)() }
+        }
     } else {
         #[inline]
         #[allow(dead_code)]
@@ -44,6 +51,12 @@ cfg_if! {
         #[inline]
         #[allow(dead_code)]
         pub fn kinit(_heap_start: usize, _heap_size: usize) {}
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kstats() -> AllocStats {
+            AllocStats::default()
+        }
     }
 }
 
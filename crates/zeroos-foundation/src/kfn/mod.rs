@@ -9,6 +9,8 @@ pub fn kexit(code: i32) -> ! {
     unsafe { platform_exit(code) }
 }
 
+pub mod block;
+pub mod mem;
 pub mod thread;
 
 cfg_if! {
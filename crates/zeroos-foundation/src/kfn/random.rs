@@ -13,6 +13,15 @@ cfg_if! {
         pub unsafe fn krandom(buf: *mut u8, len: usize) -> isize {
             (crate::KERNEL.random.fill_bytes)(buf, len)
         }
+
+        #[inline]
+        /// Mix `len` bytes at `seed` into the kernel RNG state.
+        ///
+        /// # Safety
+        /// `seed` must be valid for reads of `len` bytes.
+        pub unsafe fn krandom_reseed(seed: *const u8, len: usize) -> isize {
+            (crate::KERNEL.random.reseed)(seed, len)
+        }
     } else {
         #[inline]
         #[allow(dead_code)]
@@ -25,6 +34,14 @@ cfg_if! {
         pub unsafe fn krandom(_buf: *mut u8, _len: usize) -> isize {
             -1
         }
+
+        #[inline]
+        #[allow(dead_code)]
+        /// # Safety
+        /// `seed` is not used in the stub implementation.
+        pub unsafe fn krandom_reseed(_seed: *const u8, _len: usize) -> isize {
+            -1
+        }
     }
 }
 
@@ -33,11 +33,36 @@ cfg_if! {
             }
         }
 
+        #[inline]
+        pub fn kspawn(entry: usize, stack: usize) -> isize {
+            unsafe { (crate::KERNEL.scheduler.spawn)(entry, stack) }
+        }
+
+        #[inline]
+        pub fn kjoin(tid: usize) -> isize {
+            unsafe { (crate::KERNEL.scheduler.join)(tid) }
+        }
+
         #[inline]
         pub fn ksched_yield() -> isize {
             unsafe { (crate::KERNEL.scheduler.yield_now)() }
         }
 
+        #[inline]
+        pub fn ksleep_until(deadline: u64) -> isize {
+            unsafe { (crate::KERNEL.scheduler.sleep_until)(deadline) }
+        }
+
+        #[inline]
+        pub fn ktick() -> u64 {
+            unsafe { (crate::KERNEL.scheduler.tick)() }
+        }
+
+        #[inline]
+        pub fn kset_tick_source(registered: bool) {
+            unsafe { (crate::KERNEL.scheduler.set_tick_source)(registered) }
+        }
+
         #[inline]
         pub fn kexit_current(code: i32) -> isize {
             unsafe { (crate::KERNEL.scheduler.exit_current)(code) }
@@ -86,12 +111,40 @@ cfg_if! {
             -1
         }
 
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kspawn(_entry: usize, _stack: usize) -> isize {
+            -1
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kjoin(_tid: usize) -> isize {
+            -1
+        }
+
         #[inline]
         #[allow(dead_code)]
         pub fn ksched_yield() -> isize {
             0
         }
 
+        #[inline]
+        #[allow(dead_code)]
+        pub fn ksleep_until(_deadline: u64) -> isize {
+            0
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn ktick() -> u64 {
+            0
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kset_tick_source(_registered: bool) {}
+
         #[inline]
         #[allow(dead_code)]
         pub fn kexit_current(_code: i32) -> isize {
@@ -17,6 +17,20 @@ cfg_if! {
             unsafe { (crate::KERNEL.vfs.write)(fd, buf, count) }
         }
 
+        #[inline]
+        /// # Safety
+        /// `iov` must be null or point to `iovcnt` `struct iovec`s describing valid buffers.
+        pub unsafe fn kreadv(fd: i32, iov: *const u8, iovcnt: usize) -> isize {
+            (crate::KERNEL.vfs.readv)(fd, iov, iovcnt)
+        }
+
+        #[inline]
+        /// # Safety
+        /// `iov` must be null or point to `iovcnt` `struct iovec`s describing valid buffers.
+        pub unsafe fn kwritev(fd: i32, iov: *const u8, iovcnt: usize) -> isize {
+            (crate::KERNEL.vfs.writev)(fd, iov, iovcnt)
+        }
+
         #[inline]
         /// # Safety
         /// `path` must be a valid NUL-terminated string.
@@ -43,6 +57,28 @@ cfg_if! {
         pub fn kfstat(fd: i32, statbuf: *mut u8) -> isize {
             unsafe { (crate::KERNEL.vfs.fstat)(fd, statbuf) }
         }
+
+        #[inline]
+        pub fn kdup(old_fd: i32) -> isize {
+            unsafe { (crate::KERNEL.vfs.dup)(old_fd) }
+        }
+
+        #[inline]
+        pub fn kdup2(old_fd: i32, new_fd: i32) -> isize {
+            unsafe { (crate::KERNEL.vfs.dup2)(old_fd, new_fd) }
+        }
+
+        #[inline]
+        /// # Safety
+        /// `fds` must be null or valid for writing two `i32`s.
+        pub unsafe fn kpipe2(fds: *mut i32, flags: i32) -> isize {
+            (crate::KERNEL.vfs.pipe2)(fds, flags)
+        }
+
+        #[inline]
+        pub fn kfcntl(fd: i32, cmd: i32, arg: usize) -> isize {
+            unsafe { (crate::KERNEL.vfs.fcntl)(fd, cmd, arg) }
+        }
     } else {
         #[inline]
         #[allow(dead_code)]
@@ -60,6 +96,22 @@ cfg_if! {
             -1
         }
 
+        #[inline]
+        #[allow(dead_code)]
+        /// # Safety
+        /// `iov` is not used in the stub implementation.
+        pub unsafe fn kreadv(_fd: i32, _iov: *const u8, _iovcnt: usize) -> isize {
+            -1
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        /// # Safety
+        /// `iov` is not used in the stub implementation.
+        pub unsafe fn kwritev(_fd: i32, _iov: *const u8, _iovcnt: usize) -> isize {
+            -1
+        }
+
         #[inline]
         #[allow(dead_code)]
         /// # Safety
@@ -91,5 +143,31 @@ cfg_if! {
         pub fn kfstat(_fd: i32, _statbuf: *mut u8) -> isize {
             -1
         }
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kdup(_old_fd: i32) -> isize {
+            -1
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kdup2(_old_fd: i32, _new_fd: i32) -> isize {
+            -1
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        /// # Safety
+        /// `fds` is not used in the stub implementation.
+        pub unsafe fn kpipe2(_fds: *mut i32, _flags: i32) -> isize {
+            -1
+        }
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn kfcntl(_fd: i32, _cmd: i32, _arg: usize) -> isize {
+            -1
+        }
     }
 }
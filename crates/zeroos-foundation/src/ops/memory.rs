@@ -6,4 +6,22 @@ pub struct MemoryOps {
     pub alloc: fn(layout: Layout) -> *mut u8,
     pub dealloc: fn(ptr: *mut u8, layout: Layout),
     pub realloc: fn(ptr: *mut u8, old_layout: Layout, new_size: usize) -> *mut u8,
+    pub stats: fn() -> AllocStats,
+}
+
+/// Heap usage snapshot, in bytes except for `num_free_blocks`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub total: usize,
+    pub used: usize,
+    pub free: usize,
+    /// Largest single allocation that could currently succeed.
+    pub largest_free_block: usize,
+    /// Blocks on the free lists; more blocks for the same `free` means more fragmentation.
+    pub num_free_blocks: usize,
+}
+
+/// `stats` for allocators that don't track usage: everything reads as zero.
+pub fn noop_stats() -> AllocStats {
+    AllocStats::default()
 }
@@ -7,7 +7,7 @@ cfg_if! {
         pub(crate) mod memory;
     }
 }
-pub use memory::MemoryOps;
+pub use memory::{noop_stats, AllocStats, MemoryOps};
 
 cfg_if! {
     if #[cfg(feature = "scheduler")] {
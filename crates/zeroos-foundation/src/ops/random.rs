@@ -2,4 +2,5 @@
 pub struct RandomOps {
     pub init: fn(seed: u64),
     pub fill_bytes: unsafe fn(buf: *mut u8, len: usize) -> isize,
+    pub reseed: unsafe fn(seed: *const u8, len: usize) -> isize,
 }
@@ -17,9 +17,26 @@ pub struct SchedulerOps {
         clear_child_tid_ptr: usize,
     ) -> isize,
 
+    /// Spawn a new thread that starts at `entry` on `stack`.
+    pub spawn: fn(entry: usize, stack: usize) -> isize,
+
+    /// Wait for thread `tid` to exit; returns its exit code or a negative errno.
+    pub join: fn(tid: usize) -> isize,
+
     /// Voluntarily yield the CPU to another thread.
     pub yield_now: fn() -> isize,
 
+    /// Block the current thread until the scheduler tick count reaches `deadline`.
+    pub sleep_until: fn(deadline: u64) -> isize,
+
+    /// Advance the scheduler tick count, waking expired sleepers; called from the timer trap.
+    /// Returns the new tick count.
+    pub tick: fn() -> u64,
+
+    /// Record whether a timer trap calls `tick`. Without one, a sleeper that nothing else can
+    /// wake fails with `EDEADLK` instead of spinning forever.
+    pub set_tick_source: fn(registered: bool),
+
     /// Terminate the current thread with the given exit code.
     pub exit_current: fn(code: i32) -> isize,
 
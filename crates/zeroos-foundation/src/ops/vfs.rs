@@ -3,9 +3,16 @@ pub struct VfsOps {
     pub init: fn(),
     pub read: fn(fd: i32, buf: *mut u8, count: usize) -> isize,
     pub write: fn(fd: i32, buf: *const u8, count: usize) -> isize,
+    /// `iov` points to `iovcnt` `struct iovec`s.
+    pub readv: unsafe fn(fd: i32, iov: *const u8, iovcnt: usize) -> isize,
+    pub writev: unsafe fn(fd: i32, iov: *const u8, iovcnt: usize) -> isize,
     pub open: unsafe fn(path: *const u8, flags: i32, mode: u32) -> isize,
     pub close: fn(fd: i32) -> isize,
     pub lseek: fn(fd: i32, offset: isize, whence: i32) -> isize,
     pub ioctl: fn(fd: i32, request: usize, arg: usize) -> isize,
     pub fstat: fn(fd: i32, statbuf: *mut u8) -> isize,
+    pub dup: fn(old_fd: i32) -> isize,
+    pub dup2: fn(old_fd: i32, new_fd: i32) -> isize,
+    pub pipe2: unsafe fn(fds: *mut i32, flags: i32) -> isize,
+    pub fcntl: fn(fd: i32, cmd: i32, arg: usize) -> isize,
 }
@@ -12,6 +12,30 @@ pub fn sys_close(fd: usize) -> isize {
     kfn::vfs::kclose(fd as i32)
 }
 
+pub fn sys_dup(old_fd: usize) -> isize {
+    kfn::vfs::kdup(old_fd as i32)
+}
+
+/// RISC-V has no `dup2`; libc implements it with `dup3(old, new, 0)`.
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: usize) -> isize {
+    if old_fd == new_fd || flags as i32 & !libc::O_CLOEXEC != 0 {
+        return -(libc::EINVAL as isize);
+    }
+    let ret = kfn::vfs::kdup2(old_fd as i32, new_fd as i32);
+    if ret >= 0 && flags as i32 & libc::O_CLOEXEC != 0 {
+        kfn::vfs::kfcntl(ret as i32, libc::F_SETFD, libc::FD_CLOEXEC as usize);
+    }
+    ret
+}
+
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    kfn::vfs::kfcntl(fd as i32, cmd as i32, arg)
+}
+
+pub fn sys_pipe2(fds: usize, flags: usize) -> isize {
+    unsafe { kfn::vfs::kpipe2(fds as *mut i32, flags as i32) }
+}
+
 pub fn sys_read(fd: usize, buf: usize, count: usize) -> isize {
     if count == 0 {
         return 0;
@@ -32,86 +56,12 @@ pub fn sys_write(fd: usize, buf: usize, count: usize) -> isize {
     kfn::vfs::kwrite(fd as i32, buf as *const u8, count)
 }
 
-#[repr(C)]
-struct IoVec {
-    iov_base: *mut u8,
-    iov_len: usize,
-}
-
 pub fn sys_readv(fd: usize, iov: usize, iovcnt: usize) -> isize {
-    if iovcnt == 0 {
-        return -(libc::EINVAL as isize);
-    }
-    if iov == 0 {
-        return -(libc::EFAULT as isize);
-    }
-    if iovcnt > (libc::UIO_MAXIOV as usize) {
-        return -(libc::EINVAL as isize);
-    }
-    if !iov.is_multiple_of(core::mem::align_of::<IoVec>()) {
-        return -(libc::EINVAL as isize);
-    }
-    let iovecs = unsafe { core::slice::from_raw_parts(iov as *const IoVec, iovcnt) };
-    let mut total = 0isize;
-    for v in iovecs {
-        if v.iov_len == 0 {
-            continue;
-        }
-        if v.iov_base.is_null() {
-            return if total > 0 {
-                total
-            } else {
-                -(libc::EFAULT as isize)
-            };
-        }
-        let r = kfn::vfs::kread(fd as i32, v.iov_base, v.iov_len);
-        if r < 0 {
-            return if total > 0 { total } else { r };
-        }
-        total += r;
-        if (r as usize) < v.iov_len {
-            break;
-        }
-    }
-    total
+    unsafe { kfn::vfs::kreadv(fd as i32, iov as *const u8, iovcnt) }
 }
 
 pub fn sys_writev(fd: usize, iov: usize, iovcnt: usize) -> isize {
-    if iovcnt == 0 {
-        return -(libc::EINVAL as isize);
-    }
-    if iov == 0 {
-        return -(libc::EFAULT as isize);
-    }
-    if iovcnt > (libc::UIO_MAXIOV as usize) {
-        return -(libc::EINVAL as isize);
-    }
-    if !iov.is_multiple_of(core::mem::align_of::<IoVec>()) {
-        return -(libc::EINVAL as isize);
-    }
-    let iovecs = unsafe { core::slice::from_raw_parts(iov as *const IoVec, iovcnt) };
-    let mut total = 0isize;
-    for v in iovecs {
-        if v.iov_len == 0 {
-            continue;
-        }
-        if v.iov_base.is_null() {
-            return if total > 0 {
-                total
-            } else {
-                -(libc::EFAULT as isize)
-            };
-        }
-        let r = kfn::vfs::kwrite(fd as i32, v.iov_base as *const u8, v.iov_len);
-        if r < 0 {
-            return if total > 0 { total } else { r };
-        }
-        total += r;
-        if (r as usize) < v.iov_len {
-            break;
-        }
-    }
-    total
+    unsafe { kfn::vfs::kwritev(fd as i32, iov as *const u8, iovcnt) }
 }
 
 pub fn sys_lseek(fd: usize, offset: usize, whence: usize) -> isize {
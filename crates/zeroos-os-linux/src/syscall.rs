@@ -165,6 +165,10 @@ sys_registry! {
     {
         (SYS_openat, handlers::vfs::sys_openat, 4),
         (SYS_close, handlers::vfs::sys_close, 1),
+        (SYS_dup, handlers::vfs::sys_dup, 1),
+        (SYS_dup3, handlers::vfs::sys_dup3, 3),
+        (SYS_pipe2, handlers::vfs::sys_pipe2, 2),
+        (SYS_fcntl, handlers::vfs::sys_fcntl, 3),
         (SYS_read, handlers::vfs::sys_read, 3),
         (SYS_write, handlers::vfs::sys_write, 3),
         (SYS_readv, handlers::vfs::sys_readv, 3),
@@ -1,5 +1,9 @@
 use spin::Mutex;
 
+use crate::Rng;
+
+pub type ChaChaRng = ChaChaState;
+
 #[repr(C)]
 pub struct ChaChaState {
     state: [u32; 16],
@@ -42,6 +46,33 @@ impl ChaChaState {
         Self { state, counter: 0 }
     }
 
+    /// Key the generator directly with 32 seed bytes (little-endian key words), e.g. for
+    /// reproducible test streams.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let mut rng = Self::with_seed(0);
+        rng.set_key(&seed);
+        rng
+    }
+
+    fn set_key(&mut self, key: &[u8; 32]) {
+        for (word, bytes) in self.state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        self.counter = 0;
+    }
+
+    /// Rekey from the next block of output with `seed` folded in, restarting the counter.
+    pub fn reseed(&mut self, seed: &[u8]) {
+        let mut block = [0u8; 64];
+        self.generate_block(&mut block);
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&block[..32]);
+        for (i, &b) in seed.iter().enumerate() {
+            key[i % 32] ^= b;
+        }
+        self.set_key(&key);
+    }
+
     #[inline]
     fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
         state[a] = state[a].wrapping_add(state[b]);
@@ -90,6 +121,13 @@ impl ChaChaState {
         }
     }
 
+    /// The first eight bytes of a fresh block; like `fill_bytes`, the rest of it is dropped.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
     pub fn fill_bytes(&mut self, buf: &mut [u8]) {
         let mut offset = 0;
         let mut block = [0u8; 64];
@@ -104,24 +142,36 @@ impl ChaChaState {
     }
 }
 
+impl Rng for ChaChaState {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        ChaChaState::fill_bytes(self, buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        ChaChaState::next_u64(self)
+    }
+
+    fn reseed(&mut self, seed: &[u8]) {
+        ChaChaState::reseed(self, seed)
+    }
+}
+
 static GLOBAL_RNG: Mutex<ChaChaState> = Mutex::new(ChaChaState::new());
 
 /// # Safety
 /// - `buf` must be non-null and valid for writes of `len` bytes.
 /// - `buf` must not alias any other active mutable reference for the duration of this call.
 pub unsafe fn fill_bytes(buf: *mut u8, len: usize) -> isize {
-    if buf.is_null() {
-        return -1;
-    }
-
-    let mut rng = GLOBAL_RNG.lock();
-    let slice = core::slice::from_raw_parts_mut(buf, len);
-    rng.fill_bytes(slice);
-
-    len as isize
+    crate::fill_global(&GLOBAL_RNG, buf, len)
 }
 
 pub fn init(seed: u64) {
     let mut rng = GLOBAL_RNG.lock();
     *rng = ChaChaState::with_seed(seed);
 }
+
+/// # Safety
+/// `seed` must be valid for reads of `len` bytes (it may be null only if `len` is 0).
+pub unsafe fn reseed(seed: *const u8, len: usize) -> isize {
+    crate::reseed_global(&GLOBAL_RNG, seed, len)
+}
@@ -1,5 +1,7 @@
 use spin::Mutex;
 
+use crate::Rng;
+
 #[repr(C)]
 pub struct LcgState {
     state: u64,
@@ -24,6 +26,23 @@ impl LcgState {
         Self { state: seed }
     }
 
+    #[inline]
+    pub const fn from_seed(seed: [u8; 8]) -> Self {
+        Self::with_seed(u64::from_le_bytes(seed))
+    }
+
+    /// Fold `seed` into the state eight bytes at a time, then step once so even an empty seed
+    /// moves the stream on.
+    pub fn reseed(&mut self, seed: &[u8]) {
+        for chunk in seed.chunks(8) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            self.state ^= u64::from_le_bytes(bytes);
+            self.next_u64();
+        }
+        self.next_u64();
+    }
+
     #[inline]
     pub fn next_u64(&mut self) -> u64 {
         self.state = self
@@ -46,24 +65,36 @@ impl LcgState {
     }
 }
 
+impl Rng for LcgState {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        LcgState::fill_bytes(self, buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        LcgState::next_u64(self)
+    }
+
+    fn reseed(&mut self, seed: &[u8]) {
+        LcgState::reseed(self, seed)
+    }
+}
+
 static GLOBAL_RNG: Mutex<LcgState> = Mutex::new(LcgState::new());
 
 /// # Safety
 /// - `buf` must be non-null and valid for writes of `len` bytes.
 /// - `buf` must not alias any other active mutable reference for the duration of this call.
 pub unsafe fn fill_bytes(buf: *mut u8, len: usize) -> isize {
-    if buf.is_null() {
-        return -1;
-    }
-
-    let mut rng = GLOBAL_RNG.lock();
-    let slice = core::slice::from_raw_parts_mut(buf, len);
-    rng.fill_bytes(slice);
-
-    len as isize
+    crate::fill_global(&GLOBAL_RNG, buf, len)
 }
 
 pub fn init(seed: u64) {
     let mut rng = GLOBAL_RNG.lock();
     *rng = LcgState::with_seed(seed);
 }
+
+/// # Safety
+/// `seed` must be valid for reads of `len` bytes (it may be null only if `len` is 0).
+pub unsafe fn reseed(seed: *const u8, len: usize) -> isize {
+    crate::reseed_global(&GLOBAL_RNG, seed, len)
+}
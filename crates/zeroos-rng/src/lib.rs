@@ -6,16 +6,63 @@ use foundation::ops::RandomOps;
 pub mod chacha;
 pub mod lcg;
 
+pub use chacha::ChaChaRng;
+pub use lcg::LcgState;
+
+use spin::Mutex;
+
+/// Common interface of the RNG backends, whichever feature selects the global one.
+pub trait Rng {
+    fn fill_bytes(&mut self, buf: &mut [u8]);
+
+    fn next_u64(&mut self) -> u64;
+
+    /// Mix `seed` into the state. The stream changes even for an empty seed, so reseeding also
+    /// cuts it off from earlier output.
+    fn reseed(&mut self, seed: &[u8]);
+}
+
+/// Shared body of the backends' `fill_bytes` op.
+///
+/// # Safety
+/// `buf` must be valid for writes of `len` bytes and not aliased for the duration of the call.
+unsafe fn fill_global<R: Rng>(rng: &Mutex<R>, buf: *mut u8, len: usize) -> isize {
+    if buf.is_null() {
+        return -1;
+    }
+    rng.lock()
+        .fill_bytes(core::slice::from_raw_parts_mut(buf, len));
+    len as isize
+}
+
+/// Shared body of the backends' `reseed` op.
+///
+/// # Safety
+/// `seed` must be valid for reads of `len` bytes (it may be null only if `len` is 0).
+unsafe fn reseed_global<R: Rng>(rng: &Mutex<R>, seed: *const u8, len: usize) -> isize {
+    let seed = if len == 0 {
+        &[][..]
+    } else if seed.is_null() {
+        return -1;
+    } else {
+        core::slice::from_raw_parts(seed, len)
+    };
+    rng.lock().reseed(seed);
+    0
+}
+
 #[cfg(feature = "lcg")]
 pub const RNG_OPS: RandomOps = RandomOps {
     init: lcg::init,
     fill_bytes: lcg::fill_bytes,
+    reseed: lcg::reseed,
 };
 
 #[cfg(feature = "chacha")]
 pub const RNG_OPS: RandomOps = RandomOps {
     init: chacha::init,
     fill_bytes: chacha::fill_bytes,
+    reseed: chacha::reseed,
 };
 
 #[cfg(test)]
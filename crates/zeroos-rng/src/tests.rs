@@ -55,6 +55,99 @@ mod chacha_tests {
     }
 }
 
+mod reseed_tests {
+    use crate::chacha::ChaChaRng;
+    use crate::lcg::LcgState;
+    use crate::Rng;
+
+    fn stream<R: Rng>(rng: &mut R) -> [u8; 64] {
+        let mut buf = [0u8; 64];
+        rng.fill_bytes(&mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_chacha_from_seed_reproducible() {
+        let seed = [7u8; 32];
+        let mut rng1 = ChaChaRng::from_seed(seed);
+        let mut rng2 = ChaChaRng::from_seed(seed);
+        assert_eq!(stream(&mut rng1), stream(&mut rng2));
+        assert_eq!(stream(&mut rng1), stream(&mut rng2));
+
+        let mut other = ChaChaRng::from_seed([8u8; 32]);
+        assert_ne!(stream(&mut ChaChaRng::from_seed(seed)), stream(&mut other));
+    }
+
+    #[test]
+    fn test_chacha_reseed_diverges() {
+        let seed = [7u8; 32];
+        let mut rng1 = ChaChaRng::from_seed(seed);
+        let mut rng2 = ChaChaRng::from_seed(seed);
+
+        rng2.reseed(b"extra entropy");
+        assert_ne!(stream(&mut rng1), stream(&mut rng2));
+
+        // Reseeding is itself deterministic.
+        let mut rng3 = ChaChaRng::from_seed(seed);
+        let mut rng4 = ChaChaRng::from_seed(seed);
+        rng3.reseed(b"x");
+        rng4.reseed(b"x");
+        assert_eq!(stream(&mut rng3), stream(&mut rng4));
+
+        // An empty seed still moves the stream on.
+        let mut rng5 = ChaChaRng::from_seed(seed);
+        rng5.reseed(&[]);
+        assert_ne!(stream(&mut ChaChaRng::from_seed(seed)), stream(&mut rng5));
+    }
+
+    #[test]
+    fn test_lcg_from_seed_and_reseed() {
+        let seed = 12345u64.to_le_bytes();
+        let mut rng1 = LcgState::from_seed(seed);
+        let mut rng2 = LcgState::with_seed(12345);
+        assert_eq!(stream(&mut rng1), stream(&mut rng2));
+
+        rng2.reseed(b"extra entropy");
+        assert_ne!(stream(&mut rng1), stream(&mut rng2));
+
+        let mut rng3 = LcgState::from_seed(seed);
+        rng3.reseed(&[]);
+        assert_ne!(stream(&mut LcgState::from_seed(seed)), stream(&mut rng3));
+    }
+}
+
+mod trait_tests {
+    use crate::chacha::ChaChaState;
+    use crate::lcg::LcgState;
+    use crate::Rng;
+
+    fn fills_whole_slice<R: Rng>(mut rng: R) {
+        // Not a multiple of either backend's block size, so the tail is a partial block.
+        let mut buf = [0u8; 203];
+        rng.fill_bytes(&mut buf);
+        for window in buf.chunks(8) {
+            assert!(window.iter().any(|&b| b != 0), "unfilled run in {buf:?}");
+        }
+    }
+
+    fn next_u64_varies<R: Rng>(mut rng: R) {
+        let first = rng.next_u64();
+        assert!((0..16).any(|_| rng.next_u64() != first));
+    }
+
+    #[test]
+    fn test_fill_bytes_fills_whole_slice() {
+        fills_whole_slice(ChaChaState::with_seed(1));
+        fills_whole_slice(LcgState::with_seed(1));
+    }
+
+    #[test]
+    fn test_next_u64_not_constant() {
+        next_u64_varies(ChaChaState::with_seed(1));
+        next_u64_varies(LcgState::with_seed(1));
+    }
+}
+
 mod lcg_tests {
     use crate::lcg::LcgState;
 
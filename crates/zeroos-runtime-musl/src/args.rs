@@ -0,0 +1,61 @@
+/// Size of the `.zeroos_args` block, including the terminating empty string.
+pub const ARGS_BLOCK_SIZE: usize = 512;
+/// Most arguments the bootstrap passes to `main`; later ones are dropped.
+pub const MAX_ARGS: usize = 32;
+
+// Guest `argv`, written into the ELF by the host before it is loaded (`cargo spike run BIN --
+// ARGS...`): NUL-terminated strings back to back, ended by an empty string. All zero means the
+// host passed nothing. `static mut` and `no_mangle` keep the compiler from folding the zeros.
+#[link_section = ".zeroos_args"]
+#[used]
+#[no_mangle]
+pub static mut __zeroos_args: [u8; ARGS_BLOCK_SIZE] = [0; ARGS_BLOCK_SIZE];
+
+/// Split an args block into `out`, returning how many arguments it held. Each slice keeps its
+/// NUL terminator so it can be passed to C as is.
+pub fn split_args<'a>(block: &'a [u8], out: &mut [&'a [u8]]) -> usize {
+    let mut count = 0;
+    let mut rest = block;
+    while count < out.len() {
+        let Some(nul) = rest.iter().position(|&b| b == 0) else {
+            break;
+        };
+        if nul == 0 {
+            break;
+        }
+        out[count] = &rest[..=nul];
+        rest = &rest[nul + 1..];
+        count += 1;
+    }
+    count
+}
+
+/// The arguments the host wrote into `.zeroos_args`; 0 if it wrote none.
+pub fn guest_args(out: &mut [&'static [u8]]) -> usize {
+    // SAFETY: the block is only written by the host, before the guest starts.
+    let block = unsafe {
+        core::slice::from_raw_parts(
+            core::ptr::addr_of!(__zeroos_args).cast::<u8>(),
+            ARGS_BLOCK_SIZE,
+        )
+    };
+    split_args(block, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        let mut out: [&[u8]; 4] = [b""; 4];
+        assert_eq!(split_args(b"prog\0one\0two\0\0\0\0", &mut out), 3);
+        assert_eq!(out[..3], [&b"prog\0"[..], b"one\0", b"two\0"]);
+
+        assert_eq!(split_args(&[0; 16], &mut out), 0);
+        // An unterminated tail is not an argument.
+        assert_eq!(split_args(b"prog\0trunc", &mut out), 1);
+        // Extra arguments are dropped.
+        assert_eq!(split_args(b"a\0b\0c\0d\0e\0\0", &mut out), 4);
+    }
+}
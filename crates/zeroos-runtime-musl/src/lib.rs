@@ -1,11 +1,13 @@
 #![no_std]
 
+mod args;
 #[cfg(feature = "backtrace")]
 mod eh_frame_register;
 mod lock_override;
 mod stack;
 
-pub use stack::build_musl_stack;
+pub use args::{guest_args, ARGS_BLOCK_SIZE, MAX_ARGS};
+pub use stack::{build_musl_stack, musl_stack_required_size, AUXV_LEN};
 
 #[cfg(target_arch = "riscv64")]
 pub mod riscv64;
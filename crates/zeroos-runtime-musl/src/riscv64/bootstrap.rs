@@ -1,4 +1,4 @@
-use crate::build_musl_stack;
+use crate::{build_musl_stack, guest_args, musl_stack_required_size, AUXV_LEN, MAX_ARGS};
 use core::arch::naked_asm;
 
 use foundation::__main_entry;
@@ -23,32 +23,46 @@ pub extern "C" fn _fini() {}
 
 static PROGRAM_NAME: &[u8] = b"zerokernel\0";
 
+#[cfg(feature = "backtrace")]
+const ENV: &[&[u8]] = &[b"RUST_BACKTRACE=full\0"];
+#[cfg(not(feature = "backtrace"))]
+const ENV: &[&[u8]] = &[];
+
 #[no_mangle]
 extern "C" fn __boot_trace_runtime() {
     debug::writeln!("[BOOT] __runtime_bootstrap");
 }
 
-// SAFETY: `MUSL_BUILD_BUFFER` must be large enough for `build_musl_stack` output.
-// adjust `MUSL_BUFFER_SIZE` if musl stack layout grows.
-const MUSL_BUFFER_SIZE: usize = 512;
-const MUSL_BUFFER_BYTES: usize = MUSL_BUFFER_SIZE * core::mem::size_of::<usize>();
+// Sized for the most arguments the bootstrap passes, so `build_musl_stack` always fits.
+const MUSL_BUFFER_BYTES: usize = musl_stack_required_size(MAX_ARGS, ENV.len(), AUXV_LEN);
+const MUSL_BUFFER_SIZE: usize = MUSL_BUFFER_BYTES / core::mem::size_of::<usize>();
+
+// 16-byte aligned so `musl_stack_required_size` is exact for it.
+#[repr(C, align(16))]
+struct MuslBuildBuffer([usize; MUSL_BUFFER_SIZE]);
 
-static mut MUSL_BUILD_BUFFER: [usize; MUSL_BUFFER_SIZE] = [0; MUSL_BUFFER_SIZE];
+static mut MUSL_BUILD_BUFFER: MuslBuildBuffer = MuslBuildBuffer([0; MUSL_BUFFER_SIZE]);
 
 unsafe fn build_musl_in_buffer() -> usize {
     let buffer_ptr = core::ptr::addr_of_mut!(MUSL_BUILD_BUFFER) as *mut usize;
     let buffer_bottom = buffer_ptr as usize;
     let buffer_top = buffer_ptr.add(MUSL_BUFFER_SIZE) as usize;
 
-    let size = build_musl_stack(buffer_top, buffer_bottom, PROGRAM_NAME);
+    // argv from the host if it passed any, else just the program name.
+    let mut args: [&'static [u8]; MAX_ARGS] = [PROGRAM_NAME; MAX_ARGS];
+    let argc = guest_args(&mut args).max(1);
 
-    if size > MUSL_BUFFER_BYTES {
+    let required = musl_stack_required_size(argc, ENV.len(), AUXV_LEN);
+    if required > MUSL_BUFFER_BYTES {
         panic!(
-            "Musl stack overflow! Used {} bytes, buffer is {} bytes",
-            size, MUSL_BUFFER_BYTES
+            "Musl stack overflow! Needs {} bytes, buffer is {} bytes",
+            required, MUSL_BUFFER_BYTES
         );
     }
 
+    let size = build_musl_stack(buffer_top, buffer_bottom, &args[..argc], ENV);
+    debug_assert_eq!(size, required);
+
     size
 }
 
@@ -45,7 +45,7 @@ impl<T> DownwardStack<T> {
         #[cfg(feature = "bounds-checks")]
         {
             if self.sp < self.buffer_bottom {
-                #[cfg(feature = "debug")]
+                #[cfg(any(feature = "debug", feature = "defmt"))]
                 debug::writeln!(
                     "Stack overflow! SP=0x{:x} below stack bottom=0x{:x}, top=0x{:x}",
                     self.sp,
@@ -71,47 +71,6 @@ impl<T> DownwardStack<T> {
     }
 }
 
-impl DownwardStack<usize> {
-    /// Push raw bytes onto the stack, rounded up to `align` bytes.
-    /// Returns a pointer (address) to the start of the bytes.
-    #[inline(always)]
-    #[cfg(feature = "backtrace")]
-    fn push_bytes_aligned(&mut self, bytes: &[u8], align: usize) -> usize {
-        debug_assert!(align.is_power_of_two());
-        let len = bytes.len();
-        let rounded = (len + (align - 1)) & !(align - 1);
-        self.sp -= rounded;
-
-        #[cfg(feature = "bounds-checks")]
-        {
-            if self.sp < self.buffer_bottom {
-                #[cfg(feature = "debug")]
-                debug::writeln!(
-                    "Stack overflow! SP=0x{:x} below stack bottom=0x{:x}, top=0x{:x}",
-                    self.sp,
-                    self.buffer_bottom,
-                    self.buffer_top
-                );
-
-                panic!(
-                    "Stack overflow! SP=0x{:x} below stack bottom=0x{:x}, top=0x{:x}",
-                    self.sp, self.buffer_bottom, self.buffer_top
-                );
-            }
-        }
-
-        unsafe {
-            core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.sp as *mut u8, len);
-            // Zero any padding bytes so the stack contents are deterministic.
-            for i in len..rounded {
-                core::ptr::write((self.sp + i) as *mut u8, 0);
-            }
-        }
-
-        self.sp
-    }
-}
-
 #[inline]
 fn generate_random_bytes(entropy: &[u64]) -> (u64, u64) {
     let mut state = 0x123456789abcdef0u64;
@@ -129,29 +88,62 @@ fn generate_random_bytes(entropy: &[u64]) -> (u64, u64) {
     (random_low, random_high)
 }
 
+/// The 16 `AT_RANDOM` bytes, from the kernel RNG when the `random` feature is on.
+///
+/// musl seeds its stack-protector canary from these. The fallback is a fixed function of
+/// `stack_top`, so without `random` every run gets the same canary and the stack protector
+/// only catches accidental overwrites, not deliberate ones.
+#[inline]
+fn at_random_bytes(stack_top: usize) -> (u64, u64) {
+    #[cfg(feature = "random")]
+    {
+        let mut bytes = [0u8; 16];
+        // SAFETY: `bytes` is valid for 16 bytes of writes.
+        if unsafe { foundation::kfn::random::krandom(bytes.as_mut_ptr(), bytes.len()) } >= 0 {
+            let (low, high) = bytes.split_at(8);
+            return (
+                u64::from_le_bytes(low.try_into().unwrap()),
+                u64::from_le_bytes(high.try_into().unwrap()),
+            );
+        }
+    }
+
+    let entropy = [stack_top as u64, 0xdeadbeef_cafebabe_u64];
+    generate_random_bytes(&entropy)
+}
+
+/// Number of auxv entries `build_musl_stack` pushes, including the `AT_NULL` terminator.
+pub const AUXV_LEN: usize = 14;
+
+/// Bytes `build_musl_stack` uses below a 16-byte aligned `stack_top` for `argc` arguments,
+/// `envc` environment entries and `auxc` auxv entries (counting `AT_NULL`): the pointer
+/// arrays, the 16 `AT_RANDOM` bytes and padding to keep the final sp 16-byte aligned.
+pub const fn musl_stack_required_size(argc: usize, envc: usize, auxc: usize) -> usize {
+    let words = auxc * 2 + (envc + 1) + (argc + 1) + 1;
+    let bytes = 16 + words * core::mem::size_of::<usize>();
+    (bytes + 15) & !15
+}
+
 /// The stack layout follows the System V ABI and Linux kernel conventions.
+/// `args` become `argv` (so `argc == args.len()`) and `env` becomes `envp`, as `KEY=VALUE`
+/// entries; each string must be NUL-terminated.
 /// # Safety
 /// Caller must ensure:
 #[inline]
 pub unsafe fn build_musl_stack(
     stack_top: usize,
     stack_bottom: usize,
-    program_name: &'static [u8],
+    args: &[&[u8]],
+    env: &[&[u8]],
 ) -> usize {
     let mut ds = DownwardStack::<usize>::new(stack_top, stack_bottom);
 
-    // Optional environment variables for musl's `__libc_start_main`:
-    // it computes envp = argv + argc + 1.
-    #[cfg(feature = "backtrace")]
-    let rust_backtrace_ptr =
-        ds.push_bytes_aligned(b"RUST_BACKTRACE=full\0", core::mem::align_of::<usize>());
-
     // In ZeroOS we run as a single static image with no dynamic loader; musl startup does not
     // require AT_PHDR/AT_PHNUM/AT_PHENT/AT_ENTRY for correctness, so we set them to 0.
     let (at_phdr, at_phent, at_phnum, at_entry) = (0usize, 0usize, 0usize, 0usize);
 
     // Prepare auxiliary vector entries
-    let auxv_entries = [
+    let auxv_entries: [(usize, usize); AUXV_LEN] = [
         (AT_PHDR, at_phdr),
         (AT_PHENT, at_phent),
         (AT_PHNUM, at_phnum),
@@ -170,9 +162,15 @@ pub unsafe fn build_musl_stack(
 
     // Generate 16 bytes for AT_RANDOM (Linux kernel standard)
     // Musl's __init_ssp uses first sizeof(uintptr_t) bytes for stack canary
-
-    let entropy = [stack_top as u64, 0xdeadbeef_cafebabe_u64];
-    let (random_low, random_high) = generate_random_bytes(&entropy);
+    let (random_low, random_high) = at_random_bytes(stack_top);
+
+    // argc, argv and envp vary in length; pad above AT_RANDOM so the final sp stays 16-byte
+    // aligned.
+    let words_below = AUXV_LEN * 2 + (env.len() + 1) + (args.len() + 1) + 1;
+    let bytes_below = 16 + words_below * core::mem::size_of::<usize>();
+    while !(ds.sp() - bytes_below).is_multiple_of(16) {
+        ds.push(0);
+    }
 
     // Memory layout after pushes (stack grows downward, lower addresses at bottom):
 
@@ -197,24 +195,30 @@ pub unsafe fn build_musl_stack(
         ds.push(key);
     }
 
-    // envp terminator (always present)
+    // envp terminator (always present); musl's `__libc_start_main` computes
+    // envp = argv + argc + 1.
     ds.push(0);
-    // envp[0] (optional)
-    #[cfg(feature = "backtrace")]
-    ds.push(rust_backtrace_ptr);
+    for var in env.iter().rev() {
+        ds.push(var.as_ptr() as usize);
+    }
 
     // argv terminator
     ds.push(0);
-    ds.push(program_name.as_ptr() as usize);
+    for arg in args.iter().rev() {
+        ds.push(arg.as_ptr() as usize);
+    }
 
-    ds.push(1);
+    ds.push(args.len());
 
     stack_top - ds.sp()
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
     use super::*;
+    use std::vec;
 
     #[test]
     fn test_build_musl_stack_alignment() {
@@ -224,7 +228,8 @@ mod tests {
         let program_name = b"test\0";
 
         unsafe {
-            let new_sp = build_musl_stack(stack_top, stack_top - 4096, program_name);
+            let new_sp =
+                stack_top - build_musl_stack(stack_top, stack_top - 4096, &[program_name], &[]);
 
             assert_eq!(new_sp % 16, 0, "Stack pointer must be 16-byte aligned");
 
@@ -240,7 +245,8 @@ mod tests {
         let program_name = b"myprogram\0";
 
         unsafe {
-            let new_sp = build_musl_stack(stack_top, stack_top - 4096, program_name);
+            let new_sp =
+                stack_top - build_musl_stack(stack_top, stack_top - 4096, &[program_name], &[]);
 
             let argc_ptr = new_sp as *const usize;
             let argc = *argc_ptr;
@@ -260,6 +266,108 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_build_musl_stack_two_args() {
+        let stack_buffer = vec![0u8; 4096];
+        let stack_top = (stack_buffer.as_ptr() as usize) + stack_buffer.len();
+
+        let program_name = b"myprogram\0";
+        let arg1 = b"arg1\0";
+        let arg2 = b"arg2\0";
+        let args: [&[u8]; 3] = [program_name, arg1, arg2];
+
+        unsafe {
+            let new_sp = stack_top - build_musl_stack(stack_top, stack_top - 4096, &args, &[]);
+            assert_eq!(new_sp % 16, 0, "Stack pointer must be 16-byte aligned");
+
+            let words = new_sp as *const usize;
+            assert_eq!(*words, 3, "argc must be 3");
+            for (i, arg) in args.iter().enumerate() {
+                assert_eq!(*words.add(1 + i), arg.as_ptr() as usize, "argv[{}]", i);
+            }
+            assert_eq!(*words.add(4), 0, "argv[3] must be NULL");
+        }
+    }
+
+    #[test]
+    fn test_musl_stack_required_size() {
+        let stack_buffer = vec![0u8; 4096];
+        let stack_top = (stack_buffer.as_ptr() as usize + stack_buffer.len()) & !15;
+
+        let strings: [&[u8]; 4] = [b"a\0", b"b\0", b"C=c\0", b"D=d\0"];
+        for argc in 0..=strings.len() {
+            for envc in 0..=strings.len() {
+                let used = unsafe {
+                    build_musl_stack(
+                        stack_top,
+                        stack_top - 2048,
+                        &strings[..argc],
+                        &strings[..envc],
+                    )
+                };
+                assert_eq!(
+                    used,
+                    musl_stack_required_size(argc, envc, AUXV_LEN),
+                    "argc={} envc={}",
+                    argc,
+                    envc
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_musl_stack_envp() {
+        let stack_buffer = vec![0u8; 4096];
+        let stack_top = (stack_buffer.as_ptr() as usize) + stack_buffer.len();
+
+        let program_name = b"myprogram\0";
+        let vars: [&[u8]; 2] = [b"RUST_BACKTRACE=full\0", b"HOME=/\0"];
+
+        for count in 0..=vars.len() {
+            let env = &vars[..count];
+            unsafe {
+                let sp =
+                    stack_top - build_musl_stack(stack_top, stack_top - 4096, &[program_name], env);
+                assert_eq!(sp % 16, 0, "Stack pointer must be 16-byte aligned");
+
+                // argc, argv[0], argv terminator, then envp.
+                let envp = (sp as *const usize).add(3);
+                for (i, var) in env.iter().enumerate() {
+                    assert_eq!(*envp.add(i), var.as_ptr() as usize, "envp[{}]", i);
+                }
+                assert_eq!(*envp.add(count), 0, "envp[{}] must be NULL", count);
+                // auxv starts right after the terminator.
+                assert_eq!(*envp.add(count + 1), AT_PHDR);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "random"))]
+    fn test_at_random_fallback_is_stable() {
+        let stack_buffer = vec![0u8; 4096];
+        let stack_top = (stack_buffer.as_ptr() as usize) + stack_buffer.len();
+
+        assert_eq!(at_random_bytes(stack_top), at_random_bytes(stack_top));
+        assert_eq!(
+            at_random_bytes(stack_top),
+            generate_random_bytes(&[stack_top as u64, 0xdeadbeef_cafebabe_u64])
+        );
+
+        unsafe {
+            let sp = stack_top - build_musl_stack(stack_top, stack_top - 4096, &[b"prog\0"], &[]);
+            // argc, argv[0], NULL, envp NULL, then the auxv pairs.
+            let mut auxv = (sp as *const usize).add(4);
+            while *auxv != AT_RANDOM {
+                assert_ne!(*auxv, AT_NULL, "AT_RANDOM missing from auxv");
+                auxv = auxv.add(2);
+            }
+            let random = *auxv.add(1) as *const u64;
+            assert_eq!((*random, *random.add(1)), at_random_bytes(stack_top));
+        }
+    }
+
     #[test]
     fn test_generate_random_bytes() {
         let entropy1 = [0x1234567890abcdef_u64, 0xfedcba0987654321_u64];
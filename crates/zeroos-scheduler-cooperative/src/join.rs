@@ -0,0 +1,160 @@
+//! Exit status bookkeeping for joinable threads.
+//!
+//! A thread started with `spawn` is tracked here from birth. When it exits it becomes a zombie
+//! holding its exit code until exactly one `join` reaps it. Threads created through
+//! `spawn_thread` (musl's `clone`) are not tracked: musl joins those itself via the
+//! clear-child-tid futex.
+
+use crate::thread::Tid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// No joinable thread with that tid (never spawned, or already reaped).
+    Unknown,
+    /// Another thread is already joining the target.
+    AlreadyJoined,
+    /// A thread tried to join itself.
+    JoinSelf,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ExitSlot {
+    tid: Tid,
+    /// `Some` once the thread has exited (it is a zombie until reaped).
+    code: Option<i32>,
+    joiner: Option<Tid>,
+}
+
+pub struct ExitTable<const N: usize> {
+    slots: [Option<ExitSlot>; N],
+}
+
+impl<const N: usize> Default for ExitTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ExitTable<N> {
+    pub const fn new() -> Self {
+        Self { slots: [None; N] }
+    }
+
+    fn slot_mut(&mut self, tid: Tid) -> Option<&mut ExitSlot> {
+        self.slots.iter_mut().flatten().find(|slot| slot.tid == tid)
+    }
+
+    /// Start tracking `tid`; false if the table is full.
+    pub fn track(&mut self, tid: Tid) -> bool {
+        let Some(free) = self.slots.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+        *free = Some(ExitSlot {
+            tid,
+            code: None,
+            joiner: None,
+        });
+        true
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(Option::is_some)
+    }
+
+    /// Turn `tid` into a zombie holding `code`. Untracked tids are ignored.
+    pub fn record_exit(&mut self, tid: Tid, code: i32) {
+        if let Some(slot) = self.slot_mut(tid) {
+            slot.code = Some(code);
+        }
+    }
+
+    /// Claim `tid` for `joiner`. Only one joiner may claim a thread.
+    pub fn begin_join(&mut self, joiner: Tid, tid: Tid) -> Result<(), JoinError> {
+        if joiner == tid {
+            return Err(JoinError::JoinSelf);
+        }
+        let slot = self.slot_mut(tid).ok_or(JoinError::Unknown)?;
+        if slot.joiner.is_some() {
+            return Err(JoinError::AlreadyJoined);
+        }
+        slot.joiner = Some(joiner);
+        Ok(())
+    }
+
+    /// Release a claim taken by `begin_join` without reaping.
+    pub fn cancel_join(&mut self, tid: Tid) {
+        if let Some(slot) = self.slot_mut(tid) {
+            slot.joiner = None;
+        }
+    }
+
+    /// If `tid` is a zombie, forget it and return its exit code.
+    pub fn reap(&mut self, tid: Tid) -> Option<i32> {
+        let entry = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.is_some_and(|s| s.tid == tid && s.code.is_some()))?;
+        entry.take().and_then(|slot| slot.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_queue::RunQueue;
+
+    #[test]
+    fn test_join_reads_exit_code() {
+        // Thread 1 spawns thread 2, then joins it.
+        let mut queue = RunQueue::<Tid, 4>::new();
+        let mut exits = ExitTable::<4>::new();
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+        assert!(exits.track(2));
+
+        assert_eq!(exits.begin_join(1, 2), Ok(()));
+        // Thread 2 is still running: thread 1 has to yield.
+        assert_eq!(exits.reap(2), None);
+        assert!(queue.yield_next(|_| true, |_, _| {}));
+        assert_eq!(queue.current(), Some(2));
+
+        // Thread 2 exits, handing the CPU back to the joiner.
+        exits.record_exit(2, 42);
+        assert_eq!(queue.exit_current(|_| true, |_, _| {}), Some(2));
+        assert_eq!(queue.current(), Some(1));
+        assert_eq!(exits.reap(2), Some(42));
+
+        // Reaped: a second join no longer finds it.
+        assert_eq!(exits.begin_join(1, 2), Err(JoinError::Unknown));
+    }
+
+    #[test]
+    fn test_join_errors() {
+        let mut exits = ExitTable::<2>::new();
+        assert!(exits.track(2));
+        assert!(exits.track(3));
+        assert!(!exits.track(4));
+        assert!(exits.is_full());
+
+        assert_eq!(exits.begin_join(1, 9), Err(JoinError::Unknown));
+        assert_eq!(exits.begin_join(2, 2), Err(JoinError::JoinSelf));
+        assert_eq!(exits.begin_join(1, 2), Ok(()));
+        assert_eq!(exits.begin_join(3, 2), Err(JoinError::AlreadyJoined));
+
+        exits.cancel_join(2);
+        assert_eq!(exits.begin_join(3, 2), Ok(()));
+    }
+
+    #[test]
+    fn test_zombie_kept_until_joined() {
+        let mut exits = ExitTable::<2>::new();
+        assert!(exits.track(2));
+        exits.record_exit(2, 3);
+        // Still occupies its slot until someone joins it.
+        assert!(exits.track(5));
+        assert!(exits.is_full());
+        assert_eq!(exits.begin_join(1, 2), Ok(()));
+        assert_eq!(exits.reap(2), Some(3));
+        assert!(!exits.is_full());
+    }
+}
@@ -2,10 +2,16 @@
 
 extern crate alloc;
 
+pub mod join;
 pub mod ops;
+pub mod run_queue;
 pub mod scheduler;
+pub mod sleep;
 pub mod thread;
 
+pub use join::{ExitTable, JoinError};
 pub use ops::SCHEDULER_OPS;
+pub use run_queue::RunQueue;
 pub use scheduler::{Scheduler, MAX_THREADS};
+pub use sleep::SleepQueue;
 pub use thread::{ThreadControlBlock, ThreadState, Tid};
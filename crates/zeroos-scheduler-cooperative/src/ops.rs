@@ -1,7 +1,7 @@
 use crate::scheduler::Scheduler;
 
-// Standard EPERM (Operation not permitted) value for ABI compatibility.
-use libc::EPERM;
+// Standard errno values for ABI compatibility.
+use libc::{EPERM, ESRCH};
 
 pub fn init() -> usize {
     Scheduler::init()
@@ -41,11 +41,40 @@ pub fn spawn_thread(
     .unwrap_or(-EPERM as isize)
 }
 
+pub fn spawn(entry: usize, stack: usize) -> isize {
+    Scheduler::with_mut(|scheduler| scheduler.spawn(entry, stack)).unwrap_or(-EPERM as isize)
+}
+
+pub fn join(tid: usize) -> isize {
+    Scheduler::with_mut(|scheduler| scheduler.join(tid)).unwrap_or(-ESRCH as isize)
+}
+
 pub fn yield_now() -> isize {
     Scheduler::with_mut(|scheduler| scheduler.yield_now());
     0
 }
 
+/// Yield for a blocked operation; with no other thread to run, waiting cannot help.
+pub fn block_yield() -> bool {
+    if thread_count() <= 1 {
+        return false;
+    }
+    yield_now();
+    true
+}
+
+pub fn sleep_until(deadline: u64) -> isize {
+    Scheduler::sleep_until(deadline)
+}
+
+pub fn tick() -> u64 {
+    Scheduler::with_mut(|scheduler| scheduler.tick()).unwrap_or(0)
+}
+
+pub fn set_tick_source(registered: bool) {
+    Scheduler::with_mut(|scheduler| scheduler.set_tick_source(registered));
+}
+
 pub fn exit_current(code: i32) -> isize {
     Scheduler::with_mut(|scheduler| scheduler.exit_current_and_yield(code))
         .unwrap_or_else(|| foundation::kfn::kexit(code))
@@ -87,7 +116,12 @@ pub fn set_tid_address(tidptr: usize) -> isize {
 pub const SCHEDULER_OPS: foundation::ops::SchedulerOps = foundation::ops::SchedulerOps {
     init,
     spawn_thread,
+    spawn,
+    join,
     yield_now,
+    sleep_until,
+    tick,
+    set_tick_source,
     exit_current,
     current_tid,
     thread_count,
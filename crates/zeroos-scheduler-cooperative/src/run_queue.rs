@@ -0,0 +1,234 @@
+//! Fixed-capacity round-robin run queue.
+//!
+//! The queue only decides *which* entry runs next; thread state and the actual context switch
+//! belong to the caller, which passes them in as closures. That keeps the rotation logic free of
+//! arch calls so it can be exercised on the host.
+
+pub struct RunQueue<T, const N: usize> {
+    slots: [Option<T>; N],
+    len: usize,
+    current: usize,
+}
+
+impl<T: Copy, const N: usize> Default for RunQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> RunQueue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [None; N],
+            len: 0,
+            current: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline(always)]
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn current(&self) -> Option<T> {
+        self.get(self.current)
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index < self.len {
+            self.slots[index]
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.slots[..self.len].iter().flatten().copied()
+    }
+
+    /// Append `item` at the back of the queue; hands it back if the queue is full.
+    pub fn push(&mut self, item: T) -> Result<usize, T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        let index = self.len;
+        self.slots[index] = Some(item);
+        self.len += 1;
+        Ok(index)
+    }
+
+    /// Index of the first entry after the current one (wrapping around) that `ready` accepts,
+    /// skipping the current entry itself.
+    fn next_ready(&self, ready: &impl Fn(T) -> bool) -> Option<usize> {
+        (1..self.len)
+            .map(|step| (self.current + step) % self.len)
+            .find(|&i| self.slots[i].is_some_and(ready))
+    }
+
+    /// Make the next ready entry current and call `switch(old, new)`. Returns false, without
+    /// switching, when no other entry is ready.
+    ///
+    /// `switch` runs last: with a real context switch it only returns once some other thread
+    /// switches back, by which time the queue may have changed.
+    pub fn yield_next(&mut self, ready: impl Fn(T) -> bool, switch: impl FnOnce(T, T)) -> bool {
+        let Some(old) = self.current() else {
+            return false;
+        };
+        let Some(next) = self.next_ready(&ready) else {
+            return false;
+        };
+        self.current = next;
+        let new = self.slots[next].expect("run queue slot below len is empty");
+        switch(old, new);
+        true
+    }
+
+    /// Remove the current entry, make the next ready entry current and call `switch(old, new)`.
+    /// Returns the removed entry, or `None` (leaving the queue untouched) when no other entry is
+    /// ready to take over.
+    pub fn exit_current(
+        &mut self,
+        ready: impl Fn(T) -> bool,
+        switch: impl FnOnce(T, T),
+    ) -> Option<T> {
+        let old = self.current()?;
+        let removed = self.current;
+        let next = self.next_ready(&ready)?;
+
+        self.slots.copy_within(removed + 1..self.len, removed);
+        self.len -= 1;
+        self.slots[self.len] = None;
+        self.current = if next > removed { next - 1 } else { next };
+
+        let new = self.slots[self.current].expect("run queue slot below len is empty");
+        switch(old, new);
+        Some(old)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::{Cell, RefCell};
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Thread stand-ins: index into `ready`, with a mock switch that just records each hop.
+    struct Harness<const N: usize> {
+        queue: RunQueue<usize, N>,
+        ready: [Cell<bool>; N],
+        switches: RefCell<Vec<(usize, usize)>>,
+    }
+
+    impl<const N: usize> Harness<N> {
+        fn new(threads: usize) -> Self {
+            let mut queue = RunQueue::new();
+            for t in 0..threads {
+                queue.push(t).unwrap();
+            }
+            Self {
+                queue,
+                ready: core::array::from_fn(|_| Cell::new(true)),
+                switches: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn yield_now(&mut self) -> bool {
+            let ready = |t: usize| self.ready[t].get();
+            let switches = &self.switches;
+            self.queue
+                .yield_next(ready, |old, new| switches.borrow_mut().push((old, new)))
+        }
+
+        fn exit(&mut self) -> Option<usize> {
+            let ready = |t: usize| self.ready[t].get();
+            let switches = &self.switches;
+            self.queue
+                .exit_current(ready, |old, new| switches.borrow_mut().push((old, new)))
+        }
+    }
+
+    #[test]
+    fn test_round_robin_order() {
+        let mut h = Harness::<4>::new(3);
+        for _ in 0..6 {
+            assert!(h.yield_now());
+        }
+        assert_eq!(
+            *h.switches.borrow(),
+            vec![(0, 1), (1, 2), (2, 0), (0, 1), (1, 2), (2, 0)]
+        );
+    }
+
+    #[test]
+    fn test_yield_skips_threads_that_are_not_ready() {
+        let mut h = Harness::<4>::new(3);
+        h.ready[1].set(false);
+        assert!(h.yield_now());
+        assert!(h.yield_now());
+        assert_eq!(*h.switches.borrow(), vec![(0, 2), (2, 0)]);
+
+        // Nothing else ready: stay on the current thread without switching.
+        h.ready[2].set(false);
+        assert!(!h.yield_now());
+        assert_eq!(h.queue.current(), Some(0));
+        assert_eq!(h.switches.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_exit_removes_thread() {
+        let mut h = Harness::<4>::new(3);
+        assert!(h.yield_now()); // 0 -> 1
+        assert_eq!(h.exit(), Some(1)); // 1 exits, 2 takes over
+        assert_eq!(h.queue.len(), 2);
+        assert_eq!(h.queue.iter().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(h.queue.current(), Some(2));
+
+        // The exited thread never comes round again.
+        assert!(h.yield_now());
+        assert!(h.yield_now());
+        assert_eq!(*h.switches.borrow(), vec![(0, 1), (1, 2), (2, 0), (0, 2)]);
+    }
+
+    #[test]
+    fn test_exit_wraps_to_front() {
+        let mut h = Harness::<4>::new(3);
+        assert!(h.yield_now());
+        assert!(h.yield_now()); // now on 2, the last slot
+        assert_eq!(h.exit(), Some(2));
+        assert_eq!(h.queue.current(), Some(0));
+        assert!(h.yield_now());
+        assert_eq!(h.switches.borrow().last(), Some(&(0, 1)));
+    }
+
+    #[test]
+    fn test_exit_without_ready_thread_keeps_queue() {
+        let mut h = Harness::<4>::new(2);
+        h.ready[1].set(false);
+        assert_eq!(h.exit(), None);
+        assert_eq!(h.queue.len(), 2);
+        assert_eq!(h.queue.current(), Some(0));
+        assert!(h.switches.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_push_when_full() {
+        let mut queue = RunQueue::<usize, 2>::new();
+        assert_eq!(queue.push(7), Ok(0));
+        assert_eq!(queue.push(8), Ok(1));
+        assert_eq!(queue.push(9), Err(9));
+        assert!(queue.is_full());
+    }
+}
@@ -1,9 +1,12 @@
+use crate::join::{ExitTable, JoinError};
+use crate::run_queue::RunQueue;
+use crate::sleep::SleepQueue;
 use crate::thread::{ThreadControlBlock, ThreadState, Tid};
 use alloc::boxed::Box;
 use core::ptr::NonNull;
 use foundation::utils::GlobalOption;
 
-use libc::{EAGAIN, EDEADLK, EPERM};
+use libc::{EAGAIN, EDEADLK, EINVAL, EPERM, ESRCH};
 
 use alloc::alloc::Layout;
 use foundation::kfn::arch as karch;
@@ -13,10 +16,15 @@ pub const MAX_THREADS: usize = 64;
 static SCHEDULER: GlobalOption<Scheduler> = GlobalOption::none();
 
 pub struct Scheduler {
-    pub(crate) threads: [Option<NonNull<ThreadControlBlock>>; MAX_THREADS],
-    pub(crate) thread_count: usize,
-    pub(crate) current_index: usize,
+    pub(crate) queue: RunQueue<NonNull<ThreadControlBlock>, MAX_THREADS>,
+    pub(crate) exits: ExitTable<MAX_THREADS>,
+    pub(crate) sleepers: SleepQueue<NonNull<ThreadControlBlock>, MAX_THREADS>,
     pub(crate) next_tid: Tid,
+    /// Whether a timer trap calls `tick`; see `sleep_until`.
+    pub(crate) tick_source: bool,
+    /// The last thread to exit. Its kernel stack was still in use when it switched away, so it
+    /// is freed at the next scheduling point instead.
+    pub(crate) exited: Option<NonNull<ThreadControlBlock>>,
 }
 
 impl Default for Scheduler {
@@ -28,10 +36,12 @@ impl Default for Scheduler {
 impl Scheduler {
     pub const fn new() -> Self {
         Self {
-            threads: [None; MAX_THREADS],
-            thread_count: 0,
-            current_index: 0,
+            queue: RunQueue::new(),
+            exits: ExitTable::new(),
+            sleepers: SleepQueue::new(),
             next_tid: 1,
+            tick_source: false,
+            exited: None,
         }
     }
 
@@ -101,9 +111,7 @@ impl Scheduler {
             }
 
             let ptr = unsafe { NonNull::new_unchecked(Box::into_raw(boot)) };
-            scheduler.threads[0] = Some(ptr);
-            scheduler.thread_count = 1;
-            scheduler.current_index = 0;
+            let _ = scheduler.queue.push(ptr);
             scheduler.next_tid = 2;
 
             unsafe {
@@ -120,15 +128,11 @@ impl Scheduler {
     }
 
     pub fn current_thread(&self) -> Option<NonNull<ThreadControlBlock>> {
-        if self.current_index < self.thread_count {
-            self.threads[self.current_index]
-        } else {
-            None
-        }
+        self.queue.current()
     }
 
     pub fn thread_count(&self) -> usize {
-        self.thread_count
+        self.queue.len()
     }
 
     pub fn current_tid_or_1(&self) -> usize {
@@ -139,63 +143,37 @@ impl Scheduler {
         }
     }
 
-    pub fn yield_now(&mut self) {
-        if let Some(tcb) = self.current_thread() {
-            unsafe {
-                karch::kthread_ctx_set_retval((*tcb.as_ptr()).thread_ctx_ptr_mut(), 0);
-            }
-        }
-        if self.thread_count == 0 {
-            return;
+    /// Free the thread that exited last, now that nothing runs on its kernel stack.
+    fn free_exited(&mut self) {
+        if let Some(tcb) = self.exited.take() {
+            unsafe { ThreadControlBlock::free(tcb) };
         }
+    }
 
-        let current_idx = self.current_index;
-
-        if let Some(current_tcb) = self.threads[current_idx] {
-            unsafe {
-                if (*current_tcb.as_ptr()).state == ThreadState::Running {
-                    (*current_tcb.as_ptr()).state = ThreadState::Ready;
-                }
+    /// Switch to the next ready thread; returns false if there was none to switch to.
+    pub fn yield_now(&mut self) -> bool {
+        self.free_exited();
+        let Some(current_tcb) = self.current_thread() else {
+            return false;
+        };
+        unsafe {
+            karch::kthread_ctx_set_retval((*current_tcb.as_ptr()).thread_ctx_ptr_mut(), 0);
+            if (*current_tcb.as_ptr()).state == ThreadState::Running {
+                (*current_tcb.as_ptr()).state = ThreadState::Ready;
             }
         }
 
-        let Some(next_idx) = self.find_next_ready((current_idx + 1) % self.thread_count) else {
-            if let Some(current_tcb) = self.threads[current_idx] {
-                unsafe {
-                    if (*current_tcb.as_ptr()).state == ThreadState::Ready {
-                        (*current_tcb.as_ptr()).state = ThreadState::Running;
-                    }
-                }
-            }
-            return;
-        };
+        let switched = self.queue.yield_next(is_ready, switch_to);
 
-        if next_idx == current_idx {
-            if let Some(current_tcb) = self.threads[current_idx] {
-                unsafe {
+        // Nothing else to run: keep going on the current thread.
+        if !switched {
+            unsafe {
+                if (*current_tcb.as_ptr()).state == ThreadState::Ready {
                     (*current_tcb.as_ptr()).state = ThreadState::Running;
                 }
             }
-            return;
-        }
-
-        if let Some(next_tcb) = self.threads[next_idx] {
-            unsafe {
-                (*next_tcb.as_ptr()).state = ThreadState::Running;
-            }
-            self.current_index = next_idx;
-        }
-
-        // Perform context switch if needed
-        unsafe {
-            if let (Some(mut old_ptr), Some(new_ptr)) =
-                (self.threads[current_idx], self.threads[self.current_index])
-            {
-                let old_tcb = old_ptr.as_mut();
-                let new_tcb = new_ptr.as_ref();
-                karch::kswitch_to(old_tcb.thread_ctx_ptr_mut(), new_tcb.thread_ctx_ptr());
-            }
         }
+        switched
     }
 
     pub fn wait_on_addr(&mut self, addr: usize, expected: i32) -> isize {
@@ -258,10 +236,11 @@ impl Scheduler {
         clear_child_tid_ptr: usize,
         mepc: usize,
     ) -> isize {
-        if self.thread_count == 0 {
+        if self.queue.is_empty() {
             // Scheduler must be initialized (boot TCB installed) before spawning threads.
             return -EPERM as isize;
         }
+        self.free_exited();
 
         let new_tid = self.next_tid;
         self.next_tid += 1;
@@ -307,11 +286,9 @@ impl Scheduler {
         child_tcb.clear_child_tid = clear_child_tid_ptr;
 
         let child_ptr = unsafe { NonNull::new_unchecked(Box::into_raw(child_tcb)) };
-        if self.thread_count >= MAX_THREADS {
+        if self.queue.push(child_ptr).is_err() {
             return -EPERM as isize;
         }
-        self.threads[self.thread_count] = Some(child_ptr);
-        self.thread_count += 1;
 
         if let Some(parent_tcb) = self.current_thread() {
             unsafe {
@@ -322,40 +299,151 @@ impl Scheduler {
         new_tid as isize
     }
 
-    fn find_next_ready(&self, start_from: usize) -> Option<usize> {
-        for i in start_from..self.thread_count {
-            if let Some(tcb) = self.threads[i] {
-                if unsafe { (*tcb.as_ptr()).state == ThreadState::Ready } {
-                    return Some(i);
-                }
+    /// Start a fresh thread at `entry` on `stack`, rather than cloning the caller like
+    /// `spawn_thread`. The thread is queued ready and first runs when someone yields to it.
+    pub fn spawn(&mut self, entry: usize, stack: usize) -> isize {
+        if self.queue.is_empty() {
+            return -EPERM as isize;
+        }
+        self.free_exited();
+        if self.queue.is_full() || self.exits.is_full() {
+            return -EAGAIN as isize;
+        }
+
+        let tid = self.next_tid;
+        self.next_tid += 1;
+        self.exits.track(tid);
+
+        let mut tcb = Box::new(ThreadControlBlock::new(tid, stack & !0xF, 0, entry));
+        unsafe {
+            let anchor_ptr = tcb.kstack_base as *mut foundation::kfn::scheduler::ThreadAnchor;
+            (*anchor_ptr).task_ptr = Box::as_ref(&tcb) as *const _ as usize;
+
+            // Same bootstrap as a cloned child: return into `ret_from_fork(tf_ptr)`, which
+            // enters the trap frame `ThreadControlBlock::new` set up at `entry`.
+            let tf_addr = foundation::kfn::scheduler::ktrap_frame_addr(anchor_ptr);
+            karch::kthread_ctx_set_retval(tcb.thread_ctx_ptr_mut(), tf_addr);
+            karch::kthread_ctx_set_ra(tcb.thread_ctx_ptr_mut(), karch::kret_from_fork());
+        }
+
+        let ptr = unsafe { NonNull::new_unchecked(Box::into_raw(tcb)) };
+        let _ = self.queue.push(ptr);
+        tid as isize
+    }
+
+    /// Wait for thread `tid` (started with `spawn`) to exit and return its exit code, or a
+    /// negative errno: `ESRCH` for an unknown or already joined tid, `EINVAL` if another thread
+    /// is joining it, `EDEADLK` for a self-join or when nothing else can run.
+    pub fn join(&mut self, tid: Tid) -> isize {
+        let caller = self.current_tid_or_1();
+        if let Err(err) = self.exits.begin_join(caller, tid) {
+            return match err {
+                JoinError::Unknown => -ESRCH as isize,
+                JoinError::AlreadyJoined => -EINVAL as isize,
+                JoinError::JoinSelf => -EDEADLK as isize,
+            };
+        }
+
+        loop {
+            if let Some(code) = self.exits.reap(tid) {
+                self.free_exited();
+                return code as isize;
+            }
+            if !self.yield_now() {
+                self.exits.cancel_join(tid);
+                return -EDEADLK as isize;
             }
         }
-        for i in 0..start_from {
-            if let Some(tcb) = self.threads[i] {
-                if unsafe { (*tcb.as_ptr()).state == ThreadState::Ready } {
-                    return Some(i);
+    }
+
+    /// Park the current thread until the tick clock reaches `deadline`, letting other threads
+    /// run meanwhile. `EDEADLK` if nothing else can run and no tick source is registered, since
+    /// nothing would ever wake it.
+    ///
+    /// Takes the scheduler borrow only around each step: the threads switched to, and the timer
+    /// trap's `tick`, take their own while this one waits.
+    pub fn sleep_until(deadline: u64) -> isize {
+        let tcb = match Self::with_mut(|scheduler| scheduler.park_current(deadline)) {
+            Some(Ok(Some(tcb))) => tcb,
+            Some(Ok(None)) => return 0,
+            Some(Err(err)) => return err,
+            None => return -EPERM as isize,
+        };
+
+        // `tick` flips the state from under us, so it must be re-read from memory each time.
+        while unsafe { core::ptr::read_volatile(core::ptr::addr_of!((*tcb.as_ptr()).state)) }
+            == ThreadState::Sleeping
+        {
+            let waiting = Self::with_mut(|scheduler| {
+                if scheduler.yield_now() || scheduler.tick_source {
+                    return true;
                 }
+                scheduler.sleepers.cancel(tcb);
+                false
+            });
+            if waiting != Some(true) {
+                unsafe { (*tcb.as_ptr()).state = ThreadState::Running };
+                return -EDEADLK as isize;
             }
+            core::hint::spin_loop();
+        }
+        // Woken while spinning rather than switched back to.
+        unsafe { (*tcb.as_ptr()).state = ThreadState::Running };
+        0
+    }
+
+    /// Put the current thread on the sleep queue, or `None` if `deadline` has already passed.
+    fn park_current(
+        &mut self,
+        deadline: u64,
+    ) -> Result<Option<NonNull<ThreadControlBlock>>, isize> {
+        if deadline <= self.sleepers.now() {
+            return Ok(None);
+        }
+        let Some(current_tcb) = self.current_thread() else {
+            return Err(-EPERM as isize);
+        };
+        if self.sleepers.park(current_tcb, deadline).is_err() {
+            return Err(-EAGAIN as isize);
         }
-        None
+        unsafe {
+            (*current_tcb.as_ptr()).state = ThreadState::Sleeping;
+        }
+        Ok(Some(current_tcb))
+    }
+
+    /// See `SchedulerOps::set_tick_source`.
+    pub fn set_tick_source(&mut self, registered: bool) {
+        self.tick_source = registered;
+    }
+
+    /// Advance the tick clock by one and make every thread whose deadline passed runnable.
+    /// Returns the new tick count.
+    pub fn tick(&mut self) -> u64 {
+        self.sleepers.tick(|tcb| unsafe {
+            (*tcb.as_ptr()).state = ThreadState::Ready;
+        })
+    }
+
+    /// Ticks since the scheduler started.
+    pub fn now(&self) -> u64 {
+        self.sleepers.now()
     }
 
     pub fn wake_futex(&mut self, futex_addr: usize, max_count: usize) -> usize {
         let mut woken = 0;
 
-        for i in 0..self.thread_count {
+        for tcb in self.queue.iter() {
             if woken >= max_count {
                 break;
             }
-            if let Some(tcb) = self.threads[i] {
-                unsafe {
-                    if (*tcb.as_ptr()).state == ThreadState::Blocked
-                        && (*tcb.as_ptr()).futex_wait_addr == futex_addr
-                    {
-                        (*tcb.as_ptr()).state = ThreadState::Ready;
-                        (*tcb.as_ptr()).futex_wait_addr = 0;
-                        woken += 1;
-                    }
+            unsafe {
+                if (*tcb.as_ptr()).state == ThreadState::Blocked
+                    && (*tcb.as_ptr()).futex_wait_addr == futex_addr
+                {
+                    (*tcb.as_ptr()).state = ThreadState::Ready;
+                    (*tcb.as_ptr()).futex_wait_addr = 0;
+                    woken += 1;
                 }
             }
         }
@@ -365,7 +453,8 @@ impl Scheduler {
 
     pub fn exit_current_and_yield(&mut self, exit_code: i32) -> isize {
         if let Some(current_tcb) = self.current_thread() {
-            let is_main_thread = unsafe { (*current_tcb.as_ptr()).tid == 1 };
+            let tid = unsafe { (*current_tcb.as_ptr()).tid };
+            let is_main_thread = tid == 1;
 
             unsafe {
                 (*current_tcb.as_ptr()).state = ThreadState::Exited;
@@ -380,11 +469,200 @@ impl Scheduler {
             if is_main_thread {
                 foundation::kfn::kexit(exit_code);
             }
-
-            self.yield_now();
+            // Joinable threads stay zombies, holding the code, until `join` reaps them.
+            self.exits.record_exit(tid, exit_code);
+
+            // With a tick source, the timer trap's `tick` eventually makes a sleeper ready, so
+            // wait for it as `sleep_until` does. Otherwise nothing could ever wake the others.
+            let can_wake = self.tick_source && !self.sleepers.is_empty();
+
+            // The exited TCB and its kernel stack are still in use until the switch completes,
+            // so they are dropped from the queue here and freed by whichever thread schedules
+            // next.
+            self.free_exited();
+            self.exited = Some(current_tcb);
+            while self.queue.exit_current(is_ready, switch_to).is_none() {
+                if !can_wake {
+                    foundation::kfn::kexit(exit_code);
+                }
+                core::hint::spin_loop();
+            }
             0
         } else {
             foundation::kfn::kexit(exit_code);
         }
     }
 }
+
+fn is_ready(tcb: NonNull<ThreadControlBlock>) -> bool {
+    // Read from memory each time: callers poll this while `tick` runs from the timer trap.
+    unsafe {
+        core::ptr::read_volatile(core::ptr::addr_of!((*tcb.as_ptr()).state)) == ThreadState::Ready
+    }
+}
+
+/// Mark `new` running and switch to it; returns once something switches back to `old`.
+fn switch_to(mut old: NonNull<ThreadControlBlock>, new: NonNull<ThreadControlBlock>) {
+    unsafe {
+        (*new.as_ptr()).state = ThreadState::Running;
+        karch::kswitch_to(
+            old.as_mut().thread_ctx_ptr_mut(),
+            new.as_ref().thread_ctx_ptr(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use foundation::ops::{AllocStats, ArchOps, MemoryOps};
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Bytes the test allocator has handed out and not yet had back.
+    static USED: AtomicUsize = AtomicUsize::new(0);
+
+    fn test_alloc(layout: Layout) -> *mut u8 {
+        USED.fetch_add(layout.size(), Ordering::SeqCst);
+        unsafe { std::alloc::alloc_zeroed(layout) }
+    }
+
+    fn test_dealloc(ptr: *mut u8, layout: Layout) {
+        USED.fetch_sub(layout.size(), Ordering::SeqCst);
+        unsafe { std::alloc::dealloc(ptr, layout) }
+    }
+
+    fn test_stats() -> AllocStats {
+        AllocStats {
+            used: USED.load(Ordering::SeqCst),
+            ..AllocStats::default()
+        }
+    }
+
+    const TEST_MEMORY: MemoryOps = MemoryOps {
+        init: |_, _| {},
+        alloc: test_alloc,
+        dealloc: test_dealloc,
+        realloc: |_, _, _| core::ptr::null_mut(),
+        stats: test_stats,
+    };
+
+    /// A thread body, stored in the first word of its context. The fake switch runs it the first
+    /// time the thread is switched to, standing in for the thread running until it switches
+    /// back.
+    type Body = fn();
+
+    unsafe extern "C" fn test_switch_to(_old: *mut u8, new: *const u8) {
+        let slot = new as *mut usize;
+        let body = slot.read();
+        if body != 0 {
+            slot.write(0);
+            core::mem::transmute::<usize, Body>(body)();
+        }
+    }
+
+    fn set_body(tid: Tid, body: Body) {
+        Scheduler::with_mut(|scheduler| {
+            let mut tcb = scheduler
+                .queue
+                .iter()
+                .find(|tcb| unsafe { (*tcb.as_ptr()).tid } == tid)
+                .unwrap();
+            unsafe { (tcb.as_mut().thread_ctx_ptr_mut() as *mut usize).write(body as usize) };
+        });
+    }
+
+    const TEST_ARCH: ArchOps = ArchOps {
+        thread_ctx_size: || 2 * core::mem::size_of::<usize>(),
+        thread_ctx_align: || core::mem::align_of::<usize>(),
+        trap_frame_size: || 256,
+        trap_frame_align: || 16,
+        thread_ctx_init: |_, _, _| {},
+        thread_ctx_set_sp: |_, _| {},
+        thread_ctx_set_tp: |_, _| {},
+        thread_ctx_set_ra: |_, _| {},
+        thread_ctx_set_retval: |_, _| {},
+        switch_to: test_switch_to,
+        ret_from_fork: || 0,
+        trap_frame_clone: |_, _| {},
+        trap_frame_init: |_, _, _, _| {},
+        trap_frame_set_retval: |_, _| {},
+        trap_frame_set_sp: |_, _| {},
+        trap_frame_set_tp: |_, _| {},
+        current_trap_frame: || core::ptr::null_mut(),
+        trap_frame_get_pc: |_| 0,
+        trap_frame_set_pc: |_, _| {},
+        trap_frame_get_nr: |_| 0,
+        trap_frame_get_arg: |_, _| 0,
+        trap_frame_get_cause: |_| 0,
+        trap_frame_get_fault_addr: |_| 0,
+    };
+
+    #[no_mangle]
+    extern "C" fn platform_exit(code: i32) -> ! {
+        panic!("kexit({code})");
+    }
+
+    /// Serializes the tests, which share the global scheduler, and boots a fresh one.
+    fn boot() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        foundation::register_memory(TEST_MEMORY);
+        foundation::register_arch(TEST_ARCH);
+        Scheduler::init();
+        guard
+    }
+
+    fn spawn(body: Body) -> Tid {
+        let tid = Scheduler::with_mut(|scheduler| scheduler.spawn(0, 0)).unwrap();
+        assert!(tid > 0);
+        set_body(tid as Tid, body);
+        tid as Tid
+    }
+
+    fn exit(code: i32) {
+        Scheduler::with_mut(|scheduler| scheduler.exit_current_and_yield(code));
+    }
+
+    #[test]
+    fn test_exit_waits_for_sleeper_with_tick_source() {
+        let _guard = boot();
+        Scheduler::with_mut(|scheduler| scheduler.set_tick_source(true));
+        let tid = spawn(|| {
+            // The timer trap, firing while the exiting thread waits for the sleeper.
+            std::thread::spawn(|| {
+                for _ in 0..3 {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                    Scheduler::with_mut(|scheduler| scheduler.tick());
+                }
+            });
+            exit(5);
+        });
+
+        assert_eq!(Scheduler::sleep_until(3), 0);
+        assert!(Scheduler::with_mut(|scheduler| scheduler.now()).unwrap() >= 3);
+        assert_eq!(
+            Scheduler::with_mut(|scheduler| scheduler.join(tid)),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_spawn_join_frees_exited_threads() {
+        let _guard = boot();
+        let used = || foundation::kfn::memory::kstats().used;
+
+        let mut baseline = None;
+        for _ in 0..8 {
+            let tid = spawn(|| exit(7));
+            assert_eq!(
+                Scheduler::with_mut(|scheduler| scheduler.join(tid)),
+                Some(7)
+            );
+            assert_eq!(*baseline.get_or_insert(used()), used());
+        }
+        assert!(Scheduler::with_mut(|scheduler| scheduler.exited.is_none()).unwrap());
+    }
+}
@@ -0,0 +1,161 @@
+//! Monotonic tick clock and the threads parked on it.
+//!
+//! The timer trap handler advances the clock with `tick`; every parked entry whose deadline has
+//! been reached is handed back to the caller to make runnable again.
+
+pub struct SleepQueue<T, const N: usize> {
+    sleepers: [Option<(T, u64)>; N],
+    now: u64,
+}
+
+impl<T: Copy, const N: usize> Default for SleepQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> SleepQueue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            sleepers: [None; N],
+            now: 0,
+        }
+    }
+
+    /// Ticks since boot.
+    #[inline(always)]
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Whether nothing is parked.
+    pub fn is_empty(&self) -> bool {
+        self.sleepers.iter().all(Option::is_none)
+    }
+
+    /// Park `item` until the clock reaches `deadline`; hands it back if the queue is full.
+    pub fn park(&mut self, item: T, deadline: u64) -> Result<(), T> {
+        match self.sleepers.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some((item, deadline));
+                Ok(())
+            }
+            None => Err(item),
+        }
+    }
+
+    /// Take `item` out of the queue without waking it; false if it was not parked.
+    pub fn cancel(&mut self, item: T) -> bool
+    where
+        T: PartialEq,
+    {
+        match self
+            .sleepers
+            .iter_mut()
+            .find(|slot| matches!(slot, Some((parked, _)) if *parked == item))
+        {
+            Some(slot) => {
+                *slot = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Advance the clock by one tick and pass every entry whose deadline has now passed to
+    /// `wake`. Returns the new tick count.
+    pub fn tick(&mut self, mut wake: impl FnMut(T)) -> u64 {
+        self.now += 1;
+        for slot in self.sleepers.iter_mut() {
+            if let Some((item, deadline)) = *slot {
+                if deadline <= self.now {
+                    *slot = None;
+                    wake(item);
+                }
+            }
+        }
+        self.now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::run_queue::RunQueue;
+    use core::cell::Cell;
+    use std::vec;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_wakes_only_after_deadline() {
+        let mut sleep = SleepQueue::<usize, 4>::new();
+        sleep.park(7, 3).unwrap();
+
+        let mut woken = Vec::new();
+        assert_eq!(sleep.tick(|t| woken.push(t)), 1);
+        assert_eq!(sleep.tick(|t| woken.push(t)), 2);
+        assert!(woken.is_empty());
+        assert_eq!(sleep.tick(|t| woken.push(t)), 3);
+        assert_eq!(woken, vec![7]);
+
+        // Woken once only.
+        sleep.tick(|t| woken.push(t));
+        assert_eq!(woken, vec![7]);
+    }
+
+    #[test]
+    fn test_sleeping_thread_skipped_by_run_queue() {
+        // Three threads; thread 1 sleeps until tick 2, thread 2 until tick 4.
+        let mut queue = RunQueue::<usize, 4>::new();
+        for t in 0..3 {
+            queue.push(t).unwrap();
+        }
+        let ready: [Cell<bool>; 3] = core::array::from_fn(|_| Cell::new(true));
+        let mut sleep = SleepQueue::<usize, 4>::new();
+        for (t, deadline) in [(1, 2), (2, 4)] {
+            ready[t].set(false);
+            sleep.park(t, deadline).unwrap();
+        }
+
+        let mut runs = Vec::new();
+        let mut step = |queue: &mut RunQueue<usize, 4>, sleep: &mut SleepQueue<usize, 4>| {
+            sleep.tick(|t| ready[t].set(true));
+            queue.yield_next(|t| ready[t].get(), |_, _| {});
+            runs.push(queue.current().unwrap());
+        };
+
+        // Tick 1: nobody else is awake yet.
+        step(&mut queue, &mut sleep);
+        // Tick 2: thread 1's deadline passes.
+        step(&mut queue, &mut sleep);
+        // Tick 3: thread 2 still asleep, back to thread 0.
+        step(&mut queue, &mut sleep);
+        // Tick 4: thread 2 wakes, but thread 1 comes first in round-robin order.
+        step(&mut queue, &mut sleep);
+        // Tick 5: thread 2 finally runs.
+        step(&mut queue, &mut sleep);
+
+        assert_eq!(runs, vec![0, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_cancel_never_wakes() {
+        let mut sleep = SleepQueue::<usize, 2>::new();
+        sleep.park(4, 1).unwrap();
+        assert!(sleep.cancel(4));
+        assert!(!sleep.cancel(4));
+
+        let mut woken = Vec::new();
+        sleep.tick(|t| woken.push(t));
+        assert!(woken.is_empty());
+    }
+
+    #[test]
+    fn test_park_when_full() {
+        let mut sleep = SleepQueue::<usize, 1>::new();
+        assert_eq!(sleep.park(1, 10), Ok(()));
+        assert_eq!(sleep.park(2, 10), Err(2));
+    }
+}
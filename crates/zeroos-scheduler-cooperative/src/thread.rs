@@ -1,4 +1,6 @@
 use alloc::alloc::Layout;
+use alloc::boxed::Box;
+use core::ptr::NonNull;
 use foundation::kfn::arch as karch;
 
 /// Thread ID type (arch-independent).
@@ -25,6 +27,8 @@ pub enum ThreadState {
     Ready,
     Running,
     Blocked,
+    /// Parked until a tick deadline; see `Scheduler::sleep_until`.
+    Sleeping,
     Exited,
 }
 
@@ -89,6 +93,22 @@ impl ThreadControlBlock {
         }
     }
 
+    /// Free a TCB made by `new` together with its kernel stack and arch context.
+    ///
+    /// # Safety
+    /// `tcb` must come from `Box::into_raw` of a `new` TCB, must not be used again, and its
+    /// kernel stack must no longer be in use, so never the running thread.
+    pub unsafe fn free(tcb: NonNull<Self>) {
+        let tcb = Box::from_raw(tcb.as_ptr());
+        let ctx_layout =
+            Layout::from_size_align(karch::kthread_ctx_size(), karch::kthread_ctx_align())
+                .expect("invalid thread ctx layout");
+        foundation::kfn::memory::kfree(tcb.thread_ctx.as_mut_ptr(), ctx_layout);
+        let kstack_layout = Layout::from_size_align(tcb.kstack_size, tcb.kstack_size)
+            .expect("invalid kernel stack layout");
+        foundation::kfn::memory::kfree(tcb.kstack_base as *mut u8, kstack_layout);
+    }
+
     // Boot thread is initialized eagerly in `Scheduler::init()`.
 
     #[inline(always)]
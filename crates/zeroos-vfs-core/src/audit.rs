@@ -0,0 +1,123 @@
+//! Record of recently denied opens, for security review (`open-audit` feature).
+
+/// How many denials are kept; older ones are overwritten.
+pub const DENIAL_LOG_CAPACITY: usize = 16;
+
+/// Longest path kept per denial; longer paths are truncated.
+pub const DENIAL_PATH_MAX: usize = 64;
+
+/// An `open` that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpenDenial {
+    path: [u8; DENIAL_PATH_MAX],
+    path_len: usize,
+    /// Positive errno the open failed with, e.g. `ENOENT`.
+    pub errno: i32,
+}
+
+impl OpenDenial {
+    const EMPTY: Self = Self {
+        path: [0; DENIAL_PATH_MAX],
+        path_len: 0,
+        errno: 0,
+    };
+
+    fn new(path: &str, errno: i32) -> Self {
+        let mut path_len = path.len().min(DENIAL_PATH_MAX);
+        while !path.is_char_boundary(path_len) {
+            path_len -= 1;
+        }
+        let mut denial = Self {
+            path_len,
+            errno,
+            ..Self::EMPTY
+        };
+        denial.path[..path_len].copy_from_slice(&path.as_bytes()[..path_len]);
+        denial
+    }
+
+    /// The path that was opened, cut to `DENIAL_PATH_MAX` bytes.
+    pub fn path(&self) -> &str {
+        core::str::from_utf8(&self.path[..self.path_len]).unwrap_or_default()
+    }
+}
+
+/// Ring buffer of the last `DENIAL_LOG_CAPACITY` denied opens.
+#[derive(Clone, Copy, Debug)]
+pub struct DenialLog {
+    entries: [OpenDenial; DENIAL_LOG_CAPACITY],
+    /// Total denials recorded, including overwritten ones.
+    recorded: usize,
+}
+
+impl Default for DenialLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DenialLog {
+    pub const fn new() -> Self {
+        Self {
+            entries: [OpenDenial::EMPTY; DENIAL_LOG_CAPACITY],
+            recorded: 0,
+        }
+    }
+
+    /// Record a denial; `err` is the negative errno `open` returned.
+    pub(crate) fn record(&mut self, path: &str, err: isize) {
+        self.entries[self.recorded % DENIAL_LOG_CAPACITY] = OpenDenial::new(path, -err as i32);
+        self.recorded += 1;
+    }
+
+    /// Denials still held, at most `DENIAL_LOG_CAPACITY`.
+    pub fn len(&self) -> usize {
+        self.recorded.min(DENIAL_LOG_CAPACITY)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recorded == 0
+    }
+
+    /// Denials overwritten because the log was full.
+    pub fn dropped(&self) -> usize {
+        self.recorded - self.len()
+    }
+
+    /// Held denials, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &OpenDenial> {
+        let start = self.recorded - self.len();
+        (start..self.recorded).map(|i| &self.entries[i % DENIAL_LOG_CAPACITY])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_keeps_most_recent() {
+        let mut log = DenialLog::new();
+        for i in 0..DENIAL_LOG_CAPACITY + 2 {
+            log.record(if i % 2 == 0 { "/dev/a" } else { "/dev/b" }, -(i as isize));
+        }
+
+        assert_eq!(log.len(), DENIAL_LOG_CAPACITY);
+        assert_eq!(log.dropped(), 2);
+        assert_eq!(log.iter().next().map(|d| d.errno), Some(2));
+        assert_eq!(
+            log.iter().last().map(|d| d.errno),
+            Some(DENIAL_LOG_CAPACITY as i32 + 1)
+        );
+    }
+
+    #[test]
+    fn test_long_path_truncated_on_char_boundary() {
+        let mut path = [b'a'; DENIAL_PATH_MAX + 8];
+        path[DENIAL_PATH_MAX - 1..DENIAL_PATH_MAX + 1].copy_from_slice("é".as_bytes());
+        let path = core::str::from_utf8(&path).unwrap();
+
+        let denial = OpenDenial::new(path, libc::ENOENT);
+        assert_eq!(denial.path(), &path[..DENIAL_PATH_MAX - 1]);
+    }
+}
@@ -1,5 +1,7 @@
 #![no_std]
 
+extern crate alloc;
+
 pub use foundation::ops::VfsOps;
 
 pub use libc::{
@@ -7,22 +9,140 @@ pub use libc::{
     S_IRUSR, S_IRWXG, S_IRWXO, S_IRWXU, S_IWGRP, S_IWOTH, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR,
 };
 
+mod pipe;
 mod vfs;
 
+pub use pipe::*;
 pub use vfs::*;
 
+#[cfg(feature = "open-audit")]
+mod audit;
+
+#[cfg(feature = "open-audit")]
+pub use audit::*;
+
 pub type Fd = i32;
 
 pub type VfsResult<T> = Result<T, isize>;
 
+/// Typed `whence` for seeks; raw values are validated once in `Vfs::lseek`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeekWhence {
+    Set,
+    Cur,
+    End,
+}
+
+impl SeekWhence {
+    pub const fn from_raw(whence: i32) -> Option<Self> {
+        match whence {
+            libc::SEEK_SET => Some(Self::Set),
+            libc::SEEK_CUR => Some(Self::Cur),
+            libc::SEEK_END => Some(Self::End),
+            _ => None,
+        }
+    }
+
+    pub const fn to_raw(self) -> i32 {
+        match self {
+            Self::Set => libc::SEEK_SET,
+            Self::Cur => libc::SEEK_CUR,
+            Self::End => libc::SEEK_END,
+        }
+    }
+}
+
+/// What a device supports, as a bitset; see `FileOps::capabilities`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceCaps(u32);
+
+impl DeviceCaps {
+    pub const READABLE: Self = Self(1 << 0);
+    pub const WRITABLE: Self = Self(1 << 1);
+    pub const SEEKABLE: Self = Self(1 << 2);
+    pub const TTY: Self = Self(1 << 3);
+    pub const POLLABLE: Self = Self(1 << 4);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Whether every bit of `other` is set in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for DeviceCaps {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+/// What `fstat` reports for a device; see `FileOps::stat`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatInfo {
+    /// File type and permission bits, e.g. `S_IFCHR | 0o666`.
+    pub mode: u32,
+    pub size: u64,
+    /// Preferred I/O size; 0 means use the device's `preferred_block_size`.
+    pub blksize: u64,
+    /// Device number, for character and block devices.
+    pub rdev: u64,
+}
+
+impl StatInfo {
+    /// A read-write character device with no size, like most of `/dev`.
+    pub const fn char_device() -> Self {
+        Self {
+            mode: S_IFCHR | 0o666,
+            size: 0,
+            blksize: 0,
+            rdev: 0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct FileOps {
+    /// Returns the bytes read, 0 at end of file, or a negative errno. A device that cannot be
+    /// read uses `forbid_read`, so the error is the same as for an fd not open for reading.
     pub read: fn(file: *mut u8, buf: *mut u8, count: usize) -> isize,
+    /// Returns the bytes written (possibly short) or a negative errno. A device that cannot be
+    /// written uses `forbid_write`.
     pub write: fn(file: *mut u8, buf: *const u8, count: usize) -> isize,
+    /// Write out any buffered data; `close` runs this before `release`.
+    pub flush: fn(file: *mut u8) -> isize,
     pub release: fn(file: *mut u8) -> isize,
-    pub llseek: fn(file: *mut u8, offset: isize, whence: i32) -> isize,
+    pub llseek: fn(file: *mut u8, offset: isize, whence: SeekWhence) -> isize,
     pub ioctl: fn(file: *mut u8, request: usize, arg: usize) -> isize,
+    pub mmap: fn(file: *mut u8, offset: usize, len: usize) -> isize,
+    pub max_open: fn(file: *mut u8) -> usize,
+    /// Called when `O_NONBLOCK` is set or cleared on an fd for this device, so a device that
+    /// can block may return `EAGAIN` instead.
+    pub set_nonblocking: fn(file: *mut u8, nonblocking: bool) -> isize,
+    /// Write size the device handles best; callers may batch small writes up to this.
+    pub preferred_block_size: fn(file: *mut u8) -> usize,
+    /// Everything the device supports, so the VFS can decide with one call. It must agree with
+    /// the other ops, which stay authoritative (a device without `SEEKABLE` still answers
+    /// `llseek`, with `ESPIPE`).
+    pub capabilities: fn(file: *mut u8) -> DeviceCaps,
+    /// What `fstat` reports for the device.
+    pub stat: fn(file: *mut u8) -> StatInfo,
+    /// Current length of a seekable device, which `SEEK_END` is relative to; `None` for a
+    /// stream. See `seek_offset`.
+    pub size: fn(file: *mut u8) -> Option<u64>,
 }
 
 #[repr(C)]
@@ -32,13 +152,19 @@ pub struct FdEntry {
     pub private_data: *mut u8,
 }
 
-pub type DeviceFactory = fn() -> FdEntry;
+/// Creates the entry for a new fd; receives the concrete path that was opened, which for a
+/// wildcard registration tells the factory which instance is wanted.
+pub type DeviceFactory = fn(path: &str) -> FdEntry;
 
 pub fn noop_close(_file: *mut u8) -> isize {
     0
 }
 
-pub fn noop_seek(_file: *mut u8, _offset: isize, _whence: i32) -> isize {
+pub fn noop_flush(_file: *mut u8) -> isize {
+    0
+}
+
+pub fn noop_seek(_file: *mut u8, _offset: isize, _whence: SeekWhence) -> isize {
     -(libc::ESPIPE as isize)
 }
 
@@ -46,10 +172,77 @@ pub fn noop_ioctl(_file: *mut u8, _request: usize, _arg: usize) -> isize {
     -(libc::ENOTTY as isize)
 }
 
-pub fn noop_read(_file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
+pub fn noop_mmap(_file: *mut u8, _offset: usize, _len: usize) -> isize {
+    -(libc::ENODEV as isize)
+}
+
+pub fn noop_max_open(_file: *mut u8) -> usize {
+    usize::MAX
+}
+
+pub fn noop_set_nonblocking(_file: *mut u8, _nonblocking: bool) -> isize {
+    0
+}
+
+pub fn noop_preferred_block_size(_file: *mut u8) -> usize {
+    1
+}
+
+/// Claims nothing; devices should report what they support.
+pub fn noop_capabilities(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::empty()
+}
+
+pub fn noop_stat(_file: *mut u8) -> StatInfo {
+    StatInfo::char_device()
+}
+
+/// Not seekable; `seek_offset` answers `ESPIPE`.
+pub fn noop_size(_file: *mut u8) -> Option<u64> {
+    None
+}
+
+/// Standard `llseek` arithmetic for a device of `size` bytes whose per-open offset is `pos`.
+///
+/// Updates `pos` and returns it. A `None` size gives `ESPIPE`, like `noop_seek`; a target
+/// before the start gives `EINVAL`. Seeking past the end is allowed.
+pub fn seek_offset(pos: &mut u64, size: Option<u64>, offset: isize, whence: SeekWhence) -> isize {
+    let Some(size) = size else {
+        return -(libc::ESPIPE as isize);
+    };
+    let base = match whence {
+        SeekWhence::Set => 0,
+        SeekWhence::Cur => *pos,
+        SeekWhence::End => size,
+    };
+    let target = match base.checked_add_signed(offset as i64) {
+        Some(target) => target,
+        None if offset < 0 => return -(libc::EINVAL as isize),
+        None => return -(libc::EOVERFLOW as isize),
+    };
+    if target > isize::MAX as u64 {
+        return -(libc::EOVERFLOW as isize);
+    }
+    *pos = target;
+    target as isize
+}
+
+/// `read` for a device that cannot be read: `EBADF`, as for an fd opened write-only.
+pub fn forbid_read(_file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
     -(libc::EBADF as isize)
 }
 
-pub fn noop_write(_file: *mut u8, _buf: *const u8, _count: usize) -> isize {
+/// `write` for a device that cannot be written: `EBADF`, as for an fd opened read-only.
+pub fn forbid_write(_file: *mut u8, _buf: *const u8, _count: usize) -> isize {
     -(libc::EBADF as isize)
 }
+
+/// Same as `forbid_read`; prefer that name so the intent is explicit.
+pub fn noop_read(file: *mut u8, buf: *mut u8, count: usize) -> isize {
+    forbid_read(file, buf, count)
+}
+
+/// Same as `forbid_write`; prefer that name so the intent is explicit.
+pub fn noop_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+    forbid_write(file, buf, count)
+}
@@ -0,0 +1,324 @@
+//! Anonymous pipes: a bounded ring buffer shared by a read end and a write end.
+
+use alloc::boxed::Box;
+use foundation::kfn::mem;
+
+use crate::{
+    forbid_read, forbid_write, noop_flush, noop_ioctl, noop_max_open, noop_mmap, noop_seek,
+    noop_set_nonblocking, noop_size, DeviceCaps, FdEntry, FileOps, StatInfo,
+};
+
+/// Bytes a pipe buffers before writes come up short. Non-blocking writes of at most this many
+/// bytes are all-or-nothing, like POSIX `PIPE_BUF`.
+pub const PIPE_CAPACITY: usize = 4096;
+
+/// State shared by both ends of a pipe; freed when the last end is released.
+pub struct PipeDevice {
+    buf: [u8; PIPE_CAPACITY],
+    /// Index of the oldest unread byte.
+    head: usize,
+    len: usize,
+    readers: usize,
+    writers: usize,
+    /// Set while the write end is `O_NONBLOCK`. Reads need no flag: an empty pipe always
+    /// answers `EAGAIN`.
+    write_nonblocking: bool,
+}
+
+impl PipeDevice {
+    /// Allocate a pipe and return its `(read, write)` entries.
+    pub fn new_pair() -> (FdEntry, FdEntry) {
+        let pipe = Box::into_raw(Box::new(Self {
+            buf: [0; PIPE_CAPACITY],
+            head: 0,
+            len: 0,
+            readers: 1,
+            writers: 1,
+            write_nonblocking: false,
+        }));
+        let private_data = pipe as *mut u8;
+        (
+            FdEntry {
+                ops: &PIPE_READ_FOPS,
+                private_data,
+            },
+            FdEntry {
+                ops: &PIPE_WRITE_FOPS,
+                private_data,
+            },
+        )
+    }
+
+    fn read_into(&mut self, dst: &mut [u8]) -> usize {
+        let n = dst.len().min(self.len);
+        let first = n.min(PIPE_CAPACITY - self.head);
+        dst[..first].copy_from_slice(&self.buf[self.head..self.head + first]);
+        dst[first..n].copy_from_slice(&self.buf[..n - first]);
+        self.head = (self.head + n) % PIPE_CAPACITY;
+        self.len -= n;
+        n
+    }
+
+    fn write_from(&mut self, src: &[u8]) -> usize {
+        let n = src.len().min(PIPE_CAPACITY - self.len);
+        let tail = (self.head + self.len) % PIPE_CAPACITY;
+        let first = n.min(PIPE_CAPACITY - tail);
+        self.buf[tail..tail + first].copy_from_slice(&src[..first]);
+        self.buf[..n - first].copy_from_slice(&src[first..n]);
+        self.len += n;
+        n
+    }
+
+    /// Drop one end; frees the pipe once neither end is open.
+    ///
+    /// # Safety
+    /// `pipe` must come from `new_pair` and not be used again if this returns true.
+    unsafe fn release_end(pipe: *mut Self, reader: bool) -> bool {
+        let state = &mut *pipe;
+        if reader {
+            state.readers -= 1;
+        } else {
+            state.writers -= 1;
+        }
+        if state.readers == 0 && state.writers == 0 {
+            drop(Box::from_raw(pipe));
+            return true;
+        }
+        false
+    }
+}
+
+fn pipe<'a>(file: *mut u8) -> &'a mut PipeDevice {
+    // SAFETY: pipe entries only ever carry the pointer from `new_pair`, live until released.
+    unsafe { &mut *(file as *mut PipeDevice) }
+}
+
+/// An empty pipe with writers reports `EAGAIN`; for a blocking fd the VFS yields and retries.
+fn pipe_read(file: *mut u8, buf: *mut u8, count: usize) -> isize {
+    let pipe = pipe(file);
+    if count == 0 {
+        return 0;
+    }
+    if let Err(e) = mem::access_ok(buf, count) {
+        return e;
+    }
+    if pipe.len == 0 {
+        return if pipe.writers == 0 {
+            0
+        } else {
+            -(libc::EAGAIN as isize)
+        };
+    }
+    let dst = unsafe { core::slice::from_raw_parts_mut(buf, count) };
+    pipe.read_into(dst) as isize
+}
+
+/// Writes what fits. `EAGAIN` means nothing fit, or, for a non-blocking write of at most
+/// `PIPE_CAPACITY` bytes, that not all of it would. For a blocking fd the VFS yields and
+/// retries, so only `O_NONBLOCK` writers see it.
+fn pipe_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+    let pipe = pipe(file);
+    if pipe.readers == 0 {
+        return -(libc::EPIPE as isize);
+    }
+    if count == 0 {
+        return 0;
+    }
+    if let Err(e) = mem::access_ok(buf, count) {
+        return e;
+    }
+    let space = PIPE_CAPACITY - pipe.len;
+    if space == 0 || (pipe.write_nonblocking && count <= PIPE_CAPACITY && count > space) {
+        return -(libc::EAGAIN as isize);
+    }
+    let src = unsafe { core::slice::from_raw_parts(buf, count) };
+    pipe.write_from(src) as isize
+}
+
+fn pipe_read_release(file: *mut u8) -> isize {
+    unsafe { PipeDevice::release_end(file as *mut PipeDevice, true) };
+    0
+}
+
+fn pipe_write_release(file: *mut u8) -> isize {
+    unsafe { PipeDevice::release_end(file as *mut PipeDevice, false) };
+    0
+}
+
+fn pipe_write_set_nonblocking(file: *mut u8, nonblocking: bool) -> isize {
+    pipe(file).write_nonblocking = nonblocking;
+    0
+}
+
+fn pipe_preferred_block_size(_file: *mut u8) -> usize {
+    PIPE_CAPACITY
+}
+
+fn pipe_read_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::READABLE
+}
+
+fn pipe_write_caps(_file: *mut u8) -> DeviceCaps {
+    DeviceCaps::WRITABLE
+}
+
+fn pipe_stat(_file: *mut u8) -> StatInfo {
+    StatInfo {
+        mode: libc::S_IFIFO | 0o600,
+        ..StatInfo::char_device()
+    }
+}
+
+pub const PIPE_READ_FOPS: FileOps = FileOps {
+    read: pipe_read,
+    write: forbid_write,
+    flush: noop_flush,
+    release: pipe_read_release,
+    llseek: noop_seek,
+    ioctl: noop_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: noop_set_nonblocking,
+    preferred_block_size: pipe_preferred_block_size,
+    capabilities: pipe_read_caps,
+    stat: pipe_stat,
+    size: noop_size,
+};
+
+pub const PIPE_WRITE_FOPS: FileOps = FileOps {
+    read: forbid_read,
+    write: pipe_write,
+    flush: noop_flush,
+    release: pipe_write_release,
+    llseek: noop_seek,
+    ioctl: noop_ioctl,
+    mmap: noop_mmap,
+    max_open: noop_max_open,
+    set_nonblocking: pipe_write_set_nonblocking,
+    preferred_block_size: pipe_preferred_block_size,
+    capabilities: pipe_write_caps,
+    stat: pipe_stat,
+    size: noop_size,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::retry_blocking;
+    use crate::Vfs;
+    use foundation::utils::GlobalCell;
+
+    const EAGAIN: isize = -(libc::EAGAIN as isize);
+
+    fn read(vfs: &Vfs, fd: i32, buf: &mut [u8]) -> isize {
+        vfs.read(fd, buf.as_mut_ptr(), buf.len())
+    }
+
+    static FILL_VFS: GlobalCell<Vfs> = GlobalCell::new(Vfs::new());
+
+    /// The first pipe in a fresh `Vfs` reads from fd 3.
+    const FILL_RFD: i32 = 3;
+
+    /// Stands in for a reader thread that drains 100 bytes whenever the writer yields.
+    fn drain_on_yield() -> bool {
+        FILL_VFS.with(|vfs| read(vfs, FILL_RFD, &mut [0u8; 100]));
+        true
+    }
+
+    #[test]
+    fn test_fill_and_drain() {
+        let (rfd, wfd) = FILL_VFS.with_mut(|vfs| vfs.pipe2(0)).unwrap();
+        assert_eq!(rfd, FILL_RFD);
+        let write = |buf: &[u8]| {
+            retry_blocking(&FILL_VFS, wfd, drain_on_yield, |vfs| {
+                vfs.write(wfd, buf.as_ptr(), buf.len())
+            })
+        };
+
+        let data = [7u8; PIPE_CAPACITY + 10];
+        assert_eq!(write(&data[..10]), 10);
+        assert_eq!(write(&data), (PIPE_CAPACITY - 10) as isize);
+        // A blocking writer to the full pipe waits for the reader instead of seeing EAGAIN.
+        assert_eq!(write(b"!"), 1);
+
+        // Wraps around the end of the ring.
+        assert_eq!(write(b"tail"), 4);
+        let mut rest = [0u8; PIPE_CAPACITY];
+        FILL_VFS.with(|vfs| {
+            assert_eq!(read(vfs, rfd, &mut rest), (PIPE_CAPACITY - 95) as isize);
+            assert_eq!(&rest[PIPE_CAPACITY - 100..PIPE_CAPACITY - 95], b"!tail");
+            assert_eq!(read(vfs, rfd, &mut rest), EAGAIN);
+        });
+    }
+
+    #[test]
+    fn test_eof_after_last_writer_closes() {
+        let mut vfs = Vfs::new();
+        let (rfd, wfd) = vfs.pipe2(0).unwrap();
+        let dup_wfd = vfs.dup(wfd).unwrap();
+        assert_eq!(vfs.write(wfd, b"hi".as_ptr(), 2), 2);
+
+        assert_eq!(vfs.close(wfd), 0);
+        let mut buf = [0u8; 8];
+        assert_eq!(read(&vfs, rfd, &mut buf), 2);
+        assert_eq!(read(&vfs, rfd, &mut buf), EAGAIN);
+
+        assert_eq!(vfs.close(dup_wfd), 0);
+        assert_eq!(read(&vfs, rfd, &mut buf), 0);
+        assert_eq!(vfs.close(rfd), 0);
+    }
+
+    #[test]
+    fn test_write_without_readers_is_epipe() {
+        let mut vfs = Vfs::new();
+        let (rfd, wfd) = vfs.pipe2(0).unwrap();
+        assert_eq!(vfs.close(rfd), 0);
+        assert_eq!(vfs.write(wfd, b"x".as_ptr(), 1), -(libc::EPIPE as isize));
+        assert_eq!(vfs.close(wfd), 0);
+    }
+
+    #[test]
+    fn test_nonblocking_write_is_all_or_nothing() {
+        let mut vfs = Vfs::new();
+        let (rfd, wfd) = vfs.pipe2(libc::O_NONBLOCK).unwrap();
+        assert_eq!(
+            vfs.fcntl(wfd, libc::F_GETFL, 0),
+            (libc::O_WRONLY | libc::O_NONBLOCK) as isize
+        );
+
+        let data = [1u8; PIPE_CAPACITY];
+        assert_eq!(
+            vfs.write(wfd, data.as_ptr(), PIPE_CAPACITY - 2),
+            (PIPE_CAPACITY - 2) as isize
+        );
+        assert_eq!(vfs.write(wfd, data.as_ptr(), 3), EAGAIN);
+        assert_eq!(vfs.write(wfd, data.as_ptr(), 2), 2);
+
+        let mut buf = [0u8; PIPE_CAPACITY];
+        assert_eq!(read(&vfs, rfd, &mut buf), PIPE_CAPACITY as isize);
+        assert_eq!(read(&vfs, rfd, &mut buf), EAGAIN);
+    }
+
+    #[test]
+    fn test_bad_user_buffer_is_efault() {
+        let mut vfs = Vfs::new();
+        let (rfd, wfd) = vfs.pipe2(0).unwrap();
+        let efault = -(libc::EFAULT as isize);
+        assert_eq!(vfs.write(wfd, core::ptr::null(), 4), efault);
+        assert_eq!(vfs.write(wfd, b"hi".as_ptr(), 2), 2);
+        assert_eq!(vfs.read(rfd, core::ptr::null_mut(), 2), efault);
+        // The data is still there for a good buffer.
+        assert_eq!(read(&vfs, rfd, &mut [0u8; 2]), 2);
+    }
+
+    #[test]
+    fn test_wrong_end_and_bad_flags() {
+        let mut vfs = Vfs::new();
+        assert_eq!(vfs.pipe2(libc::O_APPEND), Err(-(libc::EINVAL as isize)));
+
+        let (rfd, wfd) = vfs.pipe2(0).unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(vfs.write(rfd, b"x".as_ptr(), 1), -(libc::EBADF as isize));
+        assert_eq!(read(&vfs, wfd, &mut buf), -(libc::EBADF as isize));
+    }
+}
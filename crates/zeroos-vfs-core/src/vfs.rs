@@ -1,12 +1,61 @@
-use crate::{DeviceFactory, Fd, FdEntry, VfsResult};
+use alloc::vec::Vec;
+
+use crate::{DeviceCaps, DeviceFactory, Fd, FdEntry, SeekWhence, VfsResult};
+use foundation::kfn::block::kblock_yield;
 use foundation::utils::GlobalCell;
 
-const MAX_FDS: usize = 256;
+/// Default cap on fd numbers; the table grows on demand up to this. See `Vfs::set_fd_limit`.
+pub const DEFAULT_FD_LIMIT: usize = 1024;
+
+/// Lowest fd handed out by `open`; 0-2 are reserved for stdio via `register_fd`.
+const FIRST_OPEN_FD: usize = 3;
+
+/// Flags that only affect `open` itself and are not kept as status flags. `O_CLOEXEC` lives
+/// on as the descriptor flag `FD_CLOEXEC`.
+const OPEN_ONLY_FLAGS: i32 =
+    libc::O_CREAT | libc::O_EXCL | libc::O_NOCTTY | libc::O_TRUNC | libc::O_CLOEXEC;
+
+/// `FD_CLOEXEC` if `flags` asks for close-on-exec.
+const fn fd_flags_for(flags: i32) -> i32 {
+    if flags & libc::O_CLOEXEC != 0 {
+        libc::FD_CLOEXEC
+    } else {
+        0
+    }
+}
+
+/// How a registered path produces the entry for a new fd.
+#[derive(Clone, Copy)]
+enum DeviceSource {
+    Factory(DeviceFactory),
+    Instance(FdEntry),
+}
+
+/// Kernel-side state for an open descriptor.
+#[derive(Clone, Copy)]
+struct OpenFile {
+    entry: FdEntry,
+    /// Slot in `devices` this fd was opened from, for per-device open accounting.
+    device: Option<usize>,
+    /// Access mode and status flags, as reported by `F_GETFL`.
+    flags: i32,
+    /// Descriptor flags (`FD_CLOEXEC`), as reported by `F_GETFD`. Unlike `flags`, these
+    /// belong to this fd alone and are cleared on `dup`.
+    fd_flags: i32,
+    /// Open file description this fd refers to. `dup` copies it, so duplicated fds share
+    /// status flags and the device is only released when the last of them closes.
+    description: usize,
+}
 
 pub struct Vfs {
-    fd_table: [Option<FdEntry>; MAX_FDS],
-    next_fd: Fd,
-    devices: [(Option<&'static str>, Option<DeviceFactory>); 32],
+    /// Indexed by fd; grows when an fd past the end is handed out, never past `fd_limit`.
+    fd_table: Vec<Option<OpenFile>>,
+    fd_limit: usize,
+    devices: [(Option<&'static str>, Option<DeviceSource>); 32],
+    open_counts: [usize; 32],
+    next_description: usize,
+    #[cfg(feature = "open-audit")]
+    denials: crate::DenialLog,
 }
 
 impl Default for Vfs {
@@ -18,74 +67,226 @@ impl Default for Vfs {
 impl Vfs {
     /// Create a new VFS instance
     pub const fn new() -> Self {
-        const NONE: (Option<&'static str>, Option<DeviceFactory>) = (None, None);
+        Self::with_fd_limit(DEFAULT_FD_LIMIT)
+    }
+
+    /// A VFS whose fds stay below `fd_limit`.
+    pub const fn with_fd_limit(fd_limit: usize) -> Self {
+        const NONE: (Option<&'static str>, Option<DeviceSource>) = (None, None);
         Self {
-            fd_table: [None; MAX_FDS],
-            next_fd: 3,
+            fd_table: Vec::new(),
+            fd_limit,
             devices: [NONE; 32],
+            open_counts: [0; 32],
+            next_description: 0,
+            #[cfg(feature = "open-audit")]
+            denials: crate::DenialLog::new(),
+        }
+    }
+
+    pub fn fd_limit(&self) -> usize {
+        self.fd_limit
+    }
+
+    /// Change the fd limit, like `RLIMIT_NOFILE`. Fds at or above a lowered limit stay open,
+    /// but no new ones are handed out there.
+    pub fn set_fd_limit(&mut self, fd_limit: usize) {
+        self.fd_limit = fd_limit;
+    }
+
+    /// Lowest free fd in `from..fd_limit`.
+    fn lowest_free_fd(&self, from: usize) -> Option<usize> {
+        (from..self.fd_limit).find(|&idx| self.fd_table.get(idx).is_none_or(Option::is_none))
+    }
+
+    /// Put `file` at `fd`, growing the table if needed. `fd` must be below `fd_limit`.
+    fn install(&mut self, fd: usize, file: OpenFile) {
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
         }
+        self.fd_table[fd] = Some(file);
+    }
+
+    fn new_description(&mut self) -> usize {
+        self.next_description += 1;
+        self.next_description
+    }
+
+    /// Whether any open fd still refers to `description`.
+    fn description_in_use(&self, description: usize) -> bool {
+        self.fd_table
+            .iter()
+            .flatten()
+            .any(|file| file.description == description)
     }
 
     pub fn register_fd(&mut self, fd: Fd, entry: FdEntry) -> VfsResult<()> {
-        if fd < 0 || fd as usize >= MAX_FDS {
+        if fd < 0 || fd as usize >= self.fd_limit {
             return Err(-(libc::EINVAL as isize));
         }
-        self.fd_table[fd as usize] = Some(entry);
+        let description = self.new_description();
+        self.install(
+            fd as usize,
+            OpenFile {
+                entry,
+                device: None,
+                flags: 0,
+                fd_flags: 0,
+                description,
+            },
+        );
         Ok(())
     }
 
+    /// Register `factory` for `path`. A trailing `*` matches any rest of a path within the
+    /// last component, so `/dev/ttyS*` covers `/dev/ttyS0` but not `/dev/ttyS/0`.
+    ///
+    /// Fails with `EEXIST` if `path` is already registered.
     pub fn register_device(&mut self, path: &'static str, factory: DeviceFactory) -> VfsResult<()> {
+        self.register_source(path, DeviceSource::Factory(factory))
+    }
+
+    /// Bind an already-constructed device to `path`.
+    ///
+    /// The instance is shared: every `open` of `path` yields a copy of `entry`, so all fds see
+    /// the same `private_data`. `release` still runs on each `close`, so it must not tear down
+    /// state that other fds (or later opens) rely on.
+    pub fn register_device_instance(
+        &mut self,
+        path: &'static str,
+        entry: FdEntry,
+    ) -> VfsResult<()> {
+        self.register_source(path, DeviceSource::Instance(entry))
+    }
+
+    fn register_source(&mut self, path: &'static str, source: DeviceSource) -> VfsResult<()> {
+        if self.devices.iter().any(|(p, _)| *p == Some(path)) {
+            return Err(-(libc::EEXIST as isize));
+        }
         for entry in &mut self.devices {
             if entry.0.is_none() {
-                *entry = (Some(path), Some(factory));
+                *entry = (Some(path), Some(source));
                 return Ok(());
             }
         }
         Err(-(libc::ENOMEM as isize))
     }
 
-    pub fn open(&mut self, path: &str, _flags: i32, _mode: u32) -> VfsResult<Fd> {
-        let factory = self
+    /// Remove the registration for exactly `path` (a wildcard is removed by its pattern).
+    ///
+    /// Fds already open on the device stay usable; they just no longer count against
+    /// `max_open` of whatever is registered next.
+    pub fn unregister_device(&mut self, path: &str) -> VfsResult<()> {
+        let slot = self
             .devices
             .iter()
-            .find(|(p, _)| p.is_some_and(|device_path| device_path == path))
-            .and_then(|(_, f)| *f)
+            .position(|(p, _)| *p == Some(path))
             .ok_or(-(libc::ENOENT as isize))?;
-
-        let mut found: Option<Fd> = None;
-        let start = self.next_fd.max(3) as usize;
-        for idx in start..MAX_FDS {
-            if self.fd_table[idx].is_none() {
-                found = Some(idx as Fd);
-                break;
+        self.devices[slot] = (None, None);
+        self.open_counts[slot] = 0;
+        for file in self.fd_table.iter_mut().flatten() {
+            if file.device == Some(slot) {
+                file.device = None;
             }
         }
-        if found.is_none() {
-            for idx in 3..start.min(MAX_FDS) {
-                if self.fd_table[idx].is_none() {
-                    found = Some(idx as Fd);
-                    break;
-                }
+        Ok(())
+    }
+
+    /// The registration `path` opens: an exact match if there is one, else the wildcard with
+    /// the longest prefix.
+    fn find_device(&self, path: &str) -> Option<(usize, DeviceSource)> {
+        let registered = self
+            .devices
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, (p, source))| Some((slot, (*p)?, (*source)?)));
+
+        let mut best: Option<(usize, usize, DeviceSource)> = None;
+        for (slot, pattern, source) in registered {
+            if pattern == path {
+                return Some((slot, source));
+            }
+            let Some(prefix) = pattern.strip_suffix('*') else {
+                continue;
+            };
+            let matches = path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| !rest.contains('/'));
+            if matches && best.is_none_or(|(_, len, _)| prefix.len() > len) {
+                best = Some((slot, prefix.len(), source));
             }
         }
-        let fd = match found {
-            Some(fd) => fd,
-            None => return Err(-(libc::EMFILE as isize)),
+        best.map(|(slot, _, source)| (slot, source))
+    }
+
+    /// Open the device registered at `path` (see `register_device` for wildcards).
+    ///
+    /// Returns the lowest free fd >= 3, as POSIX requires (stdio aside). Fails with `EMFILE`
+    /// if the table is full and `EBUSY` if the device is already open `max_open` times.
+    pub fn open(&mut self, path: &str, flags: i32, mode: u32) -> VfsResult<Fd> {
+        let result = self.open_device(path, flags, mode);
+        #[cfg(feature = "open-audit")]
+        if let Err(err) = result {
+            self.denials.record(path, err);
+        }
+        result
+    }
+
+    /// Opens that failed, oldest first.
+    #[cfg(feature = "open-audit")]
+    pub fn recent_denials(&self) -> &crate::DenialLog {
+        &self.denials
+    }
+
+    fn open_device(&mut self, path: &str, flags: i32, _mode: u32) -> VfsResult<Fd> {
+        let (slot, source) = self.find_device(path).ok_or(-(libc::ENOENT as isize))?;
+
+        let fd = self
+            .lowest_free_fd(FIRST_OPEN_FD)
+            .ok_or(-(libc::EMFILE as isize))?;
+
+        let entry = match source {
+            DeviceSource::Factory(factory) => factory(path),
+            DeviceSource::Instance(entry) => entry,
         };
-        self.next_fd = if (fd as usize) + 1 < MAX_FDS {
-            fd + 1
-        } else {
-            3
+        // A failed open only undoes what it built: an instance is shared with other fds.
+        let discard = |entry: &FdEntry| {
+            if matches!(source, DeviceSource::Factory(_)) {
+                (entry.ops.release)(entry.private_data);
+            }
         };
+        if self.open_counts[slot] >= (entry.ops.max_open)(entry.private_data) {
+            discard(&entry);
+            return Err(-(libc::EBUSY as isize));
+        }
+        if flags & libc::O_NONBLOCK != 0 {
+            let ret = (entry.ops.set_nonblocking)(entry.private_data, true);
+            if ret < 0 {
+                discard(&entry);
+                return Err(ret);
+            }
+        }
 
-        let entry = factory();
-        self.fd_table[fd as usize] = Some(entry);
+        self.open_counts[slot] += 1;
+        let description = self.new_description();
+        self.install(
+            fd,
+            OpenFile {
+                entry,
+                device: Some(slot),
+                flags: flags & !OPEN_ONLY_FLAGS,
+                fd_flags: fd_flags_for(flags),
+                description,
+            },
+        );
 
-        Ok(fd)
+        Ok(fd as Fd)
     }
 
+    /// A single read attempt; `EAGAIN` is returned as is. The free `read` waits it out on
+    /// blocking fds, and likewise for `write`.
     pub fn read(&self, fd: Fd, buf: *mut u8, count: usize) -> isize {
-        if fd < 0 || fd as usize >= MAX_FDS {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
             return -(libc::EBADF as isize);
         }
         if count != 0 && buf.is_null() {
@@ -93,13 +294,13 @@ impl Vfs {
         }
 
         match self.fd_table[fd as usize] {
-            Some(entry) => (entry.ops.read)(entry.private_data, buf, count),
+            Some(OpenFile { entry, .. }) => (entry.ops.read)(entry.private_data, buf, count),
             None => -(libc::EBADF as isize),
         }
     }
 
     pub fn write(&self, fd: Fd, buf: *const u8, count: usize) -> isize {
-        if fd < 0 || fd as usize >= MAX_FDS {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
             return -(libc::EBADF as isize);
         }
         if count != 0 && buf.is_null() {
@@ -107,59 +308,420 @@ impl Vfs {
         }
 
         match self.fd_table[fd as usize] {
-            Some(entry) => (entry.ops.write)(entry.private_data, buf, count),
+            Some(OpenFile { entry, flags, .. }) => {
+                // Appends start at the current end; streams have no end and ignore the flag.
+                if flags & libc::O_APPEND != 0 {
+                    let r = (entry.ops.llseek)(entry.private_data, 0, SeekWhence::End);
+                    if r < 0 && r != -(libc::ESPIPE as isize) {
+                        return r;
+                    }
+                }
+                (entry.ops.write)(entry.private_data, buf, count)
+            }
             None => -(libc::EBADF as isize),
         }
     }
 
+    /// Read into each `iovec` in turn, returning the total. Stops at the first short or failed
+    /// segment; an error is only returned if nothing was read before it.
+    ///
+    /// # Safety
+    /// `iov` must be null or point to `iovcnt` readable `iovec`s, each of whose buffers is
+    /// null or valid for writes of its length.
+    pub unsafe fn readv(&self, fd: Fd, iov: *const libc::iovec, iovcnt: usize) -> isize {
+        let iovecs = match iovecs(iov, iovcnt) {
+            Ok(iovecs) => iovecs,
+            Err(e) => return e,
+        };
+        transfer_segments(iovecs, |v| self.read(fd, v.iov_base as *mut u8, v.iov_len))
+    }
+
+    /// Write each `iovec` in turn, returning the total; stops like `readv`.
+    ///
+    /// # Safety
+    /// `iov` must be null or point to `iovcnt` readable `iovec`s, each of whose buffers is
+    /// null or valid for reads of its length.
+    pub unsafe fn writev(&self, fd: Fd, iov: *const libc::iovec, iovcnt: usize) -> isize {
+        let iovecs = match iovecs(iov, iovcnt) {
+            Ok(iovecs) => iovecs,
+            Err(e) => return e,
+        };
+        transfer_segments(iovecs, |v| {
+            self.write(fd, v.iov_base as *const u8, v.iov_len)
+        })
+    }
+
+    /// Read until `count` bytes have arrived, going back to the device after short reads.
+    ///
+    /// Returns the total, which is short only at end of file. An error is only returned if
+    /// nothing was read before it.
+    pub fn read_exact(&self, fd: Fd, buf: *mut u8, count: usize) -> isize {
+        transfer_all(count, |done| {
+            self.read(fd, buf.wrapping_add(done), count - done)
+        })
+    }
+
+    /// Write all `count` bytes, going back to the device after short writes; stops like
+    /// `read_exact`, with a device that accepts nothing ending the loop.
+    pub fn write_all(&self, fd: Fd, buf: *const u8, count: usize) -> isize {
+        transfer_all(count, |done| {
+            self.write(fd, buf.wrapping_add(done), count - done)
+        })
+    }
+
     pub fn lseek(&self, fd: Fd, offset: isize, whence: i32) -> isize {
-        if fd < 0 || fd as usize >= MAX_FDS {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
             return -(libc::EBADF as isize);
         }
 
         match self.fd_table[fd as usize] {
-            Some(entry) => (entry.ops.llseek)(entry.private_data, offset, whence),
+            Some(OpenFile { entry, .. }) => match SeekWhence::from_raw(whence) {
+                Some(whence) => (entry.ops.llseek)(entry.private_data, offset, whence),
+                None => -(libc::EINVAL as isize),
+            },
             None => -(libc::EBADF as isize),
         }
     }
 
     pub fn ioctl(&self, fd: Fd, request: usize, arg: usize) -> isize {
-        if fd < 0 || fd as usize >= MAX_FDS {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return -(libc::EBADF as isize);
+        }
+
+        match self.fd_table[fd as usize] {
+            Some(OpenFile { entry, .. }) => (entry.ops.ioctl)(entry.private_data, request, arg),
+            None => -(libc::EBADF as isize),
+        }
+    }
+
+    /// Probe whether the device behind `fd` can be memory-mapped.
+    ///
+    /// Returns the mapped address on success; devices without mapping support report `ENODEV`.
+    pub fn mmap(&self, fd: Fd, offset: usize, len: usize) -> isize {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
             return -(libc::EBADF as isize);
         }
 
         match self.fd_table[fd as usize] {
-            Some(entry) => (entry.ops.ioctl)(entry.private_data, request, arg),
+            Some(OpenFile { entry, .. }) => (entry.ops.mmap)(entry.private_data, offset, len),
             None => -(libc::EBADF as isize),
         }
     }
 
+    /// The device's preferred write size for `fd` (1 if it has no preference).
+    pub fn block_size(&self, fd: Fd) -> VfsResult<usize> {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return Err(-(libc::EBADF as isize));
+        }
+
+        match self.fd_table[fd as usize] {
+            Some(OpenFile { entry, .. }) => {
+                Ok((entry.ops.preferred_block_size)(entry.private_data))
+            }
+            None => Err(-(libc::EBADF as isize)),
+        }
+    }
+
+    /// What the device behind `fd` supports.
+    pub fn capabilities(&self, fd: Fd) -> VfsResult<DeviceCaps> {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return Err(-(libc::EBADF as isize));
+        }
+
+        match self.fd_table[fd as usize] {
+            Some(OpenFile { entry, .. }) => Ok((entry.ops.capabilities)(entry.private_data)),
+            None => Err(-(libc::EBADF as isize)),
+        }
+    }
+
+    /// Set or clear `O_NONBLOCK` on `fd` and every fd sharing its open file description,
+    /// telling the device via `set_nonblocking` when the flag actually changes.
+    pub fn set_nonblocking(&mut self, fd: Fd, nonblocking: bool) -> isize {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return -(libc::EBADF as isize);
+        }
+        let file = match self.fd_table[fd as usize].as_mut() {
+            Some(file) => file,
+            None => return -(libc::EBADF as isize),
+        };
+
+        if nonblocking != (file.flags & libc::O_NONBLOCK != 0) {
+            let ret = (file.entry.ops.set_nonblocking)(file.entry.private_data, nonblocking);
+            if ret < 0 {
+                return ret;
+            }
+        }
+        let description = file.description;
+        for file in self.fd_table.iter_mut().flatten() {
+            if file.description == description {
+                if nonblocking {
+                    file.flags |= libc::O_NONBLOCK;
+                } else {
+                    file.flags &= !libc::O_NONBLOCK;
+                }
+            }
+        }
+        0
+    }
+
+    /// Whether `fd` has `O_NONBLOCK` set.
+    pub fn is_nonblocking(&self, fd: Fd) -> VfsResult<bool> {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return Err(-(libc::EBADF as isize));
+        }
+        match self.fd_table[fd as usize].as_ref() {
+            Some(file) => Ok(file.flags & libc::O_NONBLOCK != 0),
+            None => Err(-(libc::EBADF as isize)),
+        }
+    }
+
+    /// `F_GETFL`/`F_SETFL` on the fd's status flags, and `F_GETFD`/`F_SETFD` on its
+    /// descriptor flags. Toggling `O_NONBLOCK` is forwarded to the device via `set_nonblocking`.
+    pub fn fcntl(&mut self, fd: Fd, cmd: i32, arg: usize) -> isize {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return -(libc::EBADF as isize);
+        }
+        let file = match self.fd_table[fd as usize].as_mut() {
+            Some(file) => file,
+            None => return -(libc::EBADF as isize),
+        };
+
+        match cmd {
+            libc::F_GETFD => file.fd_flags as isize,
+            libc::F_SETFD => {
+                file.fd_flags = arg as i32 & libc::FD_CLOEXEC;
+                0
+            }
+            libc::F_GETFL => file.flags as isize,
+            // Only O_APPEND and O_NONBLOCK may change; the access mode is fixed at open.
+            libc::F_SETFL => {
+                let arg = arg as i32;
+                let description = file.description;
+                let ret = self.set_nonblocking(fd, arg & libc::O_NONBLOCK != 0);
+                if ret < 0 {
+                    return ret;
+                }
+                for file in self.fd_table.iter_mut().flatten() {
+                    if file.description == description {
+                        file.flags = (file.flags & !libc::O_APPEND) | (arg & libc::O_APPEND);
+                    }
+                }
+                0
+            }
+            _ => -(libc::EINVAL as isize),
+        }
+    }
+
+    /// Access mode and status flags of `fd`, as `F_GETFL` reports them; lets callers that
+    /// act for a device check e.g. `O_NONBLOCK`.
+    pub fn status_flags(&self, fd: Fd) -> VfsResult<i32> {
+        Ok(self.open_file(fd)?.flags)
+    }
+
+    /// Duplicate `old_fd` onto the lowest free fd. Both fds refer to the same open file, as
+    /// after POSIX `dup`; the new fd does not inherit `FD_CLOEXEC`.
+    pub fn dup(&mut self, old_fd: Fd) -> VfsResult<Fd> {
+        let file = OpenFile {
+            fd_flags: 0,
+            ..self.open_file(old_fd)?
+        };
+        let fd = self.lowest_free_fd(0).ok_or(-(libc::EMFILE as isize))?;
+        self.install(fd, file);
+        Ok(fd as Fd)
+    }
+
+    /// Make `new_fd` refer to the same open file as `old_fd`, closing whatever `new_fd` had
+    /// open first. Errors from that implicit close are ignored, as POSIX `dup2` does.
+    pub fn dup2(&mut self, old_fd: Fd, new_fd: Fd) -> VfsResult<Fd> {
+        let file = OpenFile {
+            fd_flags: 0,
+            ..self.open_file(old_fd)?
+        };
+        if new_fd < 0 || new_fd as usize >= self.fd_limit {
+            return Err(-(libc::EBADF as isize));
+        }
+        if new_fd == old_fd {
+            return Ok(new_fd);
+        }
+        if self.open_file(new_fd).is_ok() {
+            let _ = self.close(new_fd);
+        }
+        self.install(new_fd as usize, file);
+        Ok(new_fd)
+    }
+
+    /// Create a pipe and return its `(read, write)` fds. `flags` may contain `O_NONBLOCK`,
+    /// which applies to both ends, and `O_CLOEXEC`.
+    pub fn pipe2(&mut self, flags: i32) -> VfsResult<(Fd, Fd)> {
+        if flags & !(libc::O_NONBLOCK | libc::O_CLOEXEC) != 0 {
+            return Err(-(libc::EINVAL as isize));
+        }
+        let read_fd = self.lowest_free_fd(FIRST_OPEN_FD);
+        let write_fd = read_fd.and_then(|fd| self.lowest_free_fd(fd + 1));
+        let (Some(read_fd), Some(write_fd)) = (read_fd, write_fd) else {
+            return Err(-(libc::EMFILE as isize));
+        };
+
+        let (read_end, write_end) = crate::PipeDevice::new_pair();
+        let status = flags & libc::O_NONBLOCK;
+        if status != 0 {
+            let ret = (write_end.ops.set_nonblocking)(write_end.private_data, true);
+            if ret < 0 {
+                (read_end.ops.release)(read_end.private_data);
+                (write_end.ops.release)(write_end.private_data);
+                return Err(ret);
+            }
+        }
+        for (fd, entry, mode) in [
+            (read_fd, read_end, libc::O_RDONLY),
+            (write_fd, write_end, libc::O_WRONLY),
+        ] {
+            let description = self.new_description();
+            self.install(
+                fd,
+                OpenFile {
+                    entry,
+                    device: None,
+                    flags: mode | status,
+                    fd_flags: fd_flags_for(flags),
+                    description,
+                },
+            );
+        }
+        Ok((read_fd as Fd, write_fd as Fd))
+    }
+
+    fn open_file(&self, fd: Fd) -> VfsResult<OpenFile> {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
+            return Err(-(libc::EBADF as isize));
+        }
+        self.fd_table[fd as usize].ok_or(-(libc::EBADF as isize))
+    }
+
+    /// Flush the fd, and release the device if no duplicate of it is still open. The fd is
+    /// freed even if flushing fails; the flush error is returned in that case so buffered data
+    /// loss is not silent.
     pub fn close(&mut self, fd: Fd) -> isize {
-        if fd < 0 || fd as usize >= MAX_FDS {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
             return -(libc::EBADF as isize);
         }
 
         match self.fd_table[fd as usize].take() {
-            Some(entry) => (entry.ops.release)(entry.private_data),
+            Some(file) => {
+                if self.description_in_use(file.description) {
+                    return (file.entry.ops.flush)(file.entry.private_data);
+                }
+                if let Some(slot) = file.device {
+                    self.open_counts[slot] -= 1;
+                }
+                let flushed = (file.entry.ops.flush)(file.entry.private_data);
+                let released = (file.entry.ops.release)(file.entry.private_data);
+                if flushed < 0 {
+                    flushed
+                } else {
+                    released
+                }
+            }
             None => -(libc::EBADF as isize),
         }
     }
 
-    pub fn fstat(&self, fd: Fd, statbuf: *mut libc::stat) -> isize {
-        if fd < 0 || fd as usize >= MAX_FDS {
+    /// Fill `statbuf` from the device's `stat`. Fields the device does not describe are zero,
+    /// except `st_nlink` (1) and `st_blocks` (derived from the size).
+    ///
+    /// # Safety
+    /// `statbuf` must be null or valid for writing a `libc::stat`.
+    pub unsafe fn fstat(&self, fd: Fd, statbuf: *mut libc::stat) -> isize {
+        if fd < 0 || fd as usize >= self.fd_table.len() {
             return -(libc::EBADF as isize);
         }
+        let Some(OpenFile { entry, .. }) = self.fd_table[fd as usize] else {
+            return -(libc::EBADF as isize);
+        };
 
         if statbuf.is_null() {
             return -(libc::EFAULT as isize);
         }
 
-        -(libc::ENOSYS as isize)
+        let info = (entry.ops.stat)(entry.private_data);
+        let blksize = match info.blksize {
+            0 => (entry.ops.preferred_block_size)(entry.private_data) as u64,
+            n => n,
+        };
+
+        // SAFETY: `libc::stat` is plain integers, for which all-zero is valid.
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        st.st_mode = info.mode as _;
+        st.st_nlink = 1;
+        st.st_rdev = info.rdev as _;
+        st.st_size = info.size as _;
+        st.st_blksize = blksize as _;
+        st.st_blocks = info.size.div_ceil(512) as _;
+        statbuf.write(st);
+        0
+    }
+}
+
+/// Validate an `iovec` array the way Linux does before any segment is touched.
+///
+/// # Safety
+/// See `Vfs::readv`.
+unsafe fn iovecs<'a>(iov: *const libc::iovec, iovcnt: usize) -> VfsResult<&'a [libc::iovec]> {
+    if iovcnt == 0 || iovcnt > libc::UIO_MAXIOV as usize {
+        return Err(-(libc::EINVAL as isize));
+    }
+    if iov.is_null() {
+        return Err(-(libc::EFAULT as isize));
+    }
+    if !iov.is_aligned() {
+        return Err(-(libc::EINVAL as isize));
+    }
+    Ok(core::slice::from_raw_parts(iov, iovcnt))
+}
+
+/// Run `op` over the non-empty segments, summing the bytes moved.
+fn transfer_segments(iovecs: &[libc::iovec], mut op: impl FnMut(&libc::iovec) -> isize) -> isize {
+    let mut total = 0isize;
+    for v in iovecs.iter().filter(|v| v.iov_len != 0) {
+        let r = if v.iov_base.is_null() {
+            -(libc::EFAULT as isize)
+        } else {
+            op(v)
+        };
+        if r < 0 {
+            return if total > 0 { total } else { r };
+        }
+        total += r;
+        if (r as usize) < v.iov_len {
+            break;
+        }
     }
+    total
+}
+
+/// Call `op` with the bytes done so far until `count` are done, `op` moves nothing, or it
+/// fails.
+fn transfer_all(count: usize, mut op: impl FnMut(usize) -> isize) -> isize {
+    let mut done = 0;
+    while done < count {
+        let r = op(done);
+        if r < 0 {
+            return if done > 0 { done as isize } else { r };
+        }
+        if r == 0 {
+            break;
+        }
+        done += r as usize;
+    }
+    done as isize
 }
 
 static VFS: GlobalCell<Vfs> = GlobalCell::new(Vfs::new());
 
+pub fn set_fd_limit(fd_limit: usize) {
+    VFS.with_mut(|vfs| vfs.set_fd_limit(fd_limit))
+}
+
 pub fn register_fd(fd: Fd, entry: FdEntry) -> VfsResult<()> {
     VFS.with_mut(|vfs| vfs.register_fd(fd, entry))
 }
@@ -168,12 +730,74 @@ pub fn register_device(path: &'static str, factory: DeviceFactory) -> VfsResult<
     VFS.with_mut(|vfs| vfs.register_device(path, factory))
 }
 
+pub fn register_device_instance(path: &'static str, entry: FdEntry) -> VfsResult<()> {
+    VFS.with_mut(|vfs| vfs.register_device_instance(path, entry))
+}
+
+pub fn unregister_device(path: &str) -> VfsResult<()> {
+    VFS.with_mut(|vfs| vfs.unregister_device(path))
+}
+
+/// A device answering `EAGAIN` on a blocking fd is retried after
+/// `kfn::block::kblock_yield`, until it has data or no other thread can run.
 pub fn read(fd: Fd, buf: *mut u8, count: usize) -> isize {
-    VFS.with(|vfs| vfs.read(fd, buf, count))
+    retry_blocking(&VFS, fd, kblock_yield, |vfs| vfs.read(fd, buf, count))
 }
 
+/// Like `read`, a blocking write to a full device waits for room.
 pub fn write(fd: Fd, buf: *const u8, count: usize) -> isize {
-    VFS.with(|vfs| vfs.write(fd, buf, count))
+    retry_blocking(&VFS, fd, kblock_yield, |vfs| vfs.write(fd, buf, count))
+}
+
+/// Repeat `attempt` while it answers `EAGAIN` on a blocking `fd` and `yield_now` reports that
+/// another thread ran. Each attempt takes its own borrow of `vfs` and looks `fd` up again: the
+/// yield lets other threads close the fd, or the last end of a pipe, before the retry.
+pub(crate) fn retry_blocking(
+    vfs: &GlobalCell<Vfs>,
+    fd: Fd,
+    yield_now: fn() -> bool,
+    mut attempt: impl FnMut(&Vfs) -> isize,
+) -> isize {
+    loop {
+        let ret = vfs.with(|vfs| {
+            let ret = attempt(vfs);
+            let blocking = vfs.is_nonblocking(fd) == Ok(false);
+            (ret != -(libc::EAGAIN as isize) || !blocking).then_some(ret)
+        });
+        match ret {
+            Some(ret) => return ret,
+            None if !yield_now() => return -(libc::EAGAIN as isize),
+            None => {}
+        }
+    }
+}
+
+/// # Safety
+/// See `Vfs::readv`.
+pub unsafe fn readv(fd: Fd, iov: *const libc::iovec, iovcnt: usize) -> isize {
+    VFS.with(|vfs| vfs.readv(fd, iov, iovcnt))
+}
+
+/// # Safety
+/// See `Vfs::writev`.
+pub unsafe fn writev(fd: Fd, iov: *const libc::iovec, iovcnt: usize) -> isize {
+    VFS.with(|vfs| vfs.writev(fd, iov, iovcnt))
+}
+
+unsafe fn readv_raw(fd: Fd, iov: *const u8, iovcnt: usize) -> isize {
+    readv(fd, iov as *const libc::iovec, iovcnt)
+}
+
+unsafe fn writev_raw(fd: Fd, iov: *const u8, iovcnt: usize) -> isize {
+    writev(fd, iov as *const libc::iovec, iovcnt)
+}
+
+pub fn read_exact(fd: Fd, buf: *mut u8, count: usize) -> isize {
+    VFS.with(|vfs| vfs.read_exact(fd, buf, count))
+}
+
+pub fn write_all(fd: Fd, buf: *const u8, count: usize) -> isize {
+    VFS.with(|vfs| vfs.write_all(fd, buf, count))
 }
 
 pub fn lseek(fd: Fd, offset: isize, whence: i32) -> isize {
@@ -184,49 +808,1326 @@ pub fn ioctl(fd: Fd, request: usize, arg: usize) -> isize {
     VFS.with(|vfs| vfs.ioctl(fd, request, arg))
 }
 
+pub fn mmap(fd: Fd, offset: usize, len: usize) -> isize {
+    VFS.with(|vfs| vfs.mmap(fd, offset, len))
+}
+
+pub fn block_size(fd: Fd) -> VfsResult<usize> {
+    VFS.with(|vfs| vfs.block_size(fd))
+}
+
+pub fn capabilities(fd: Fd) -> VfsResult<DeviceCaps> {
+    VFS.with(|vfs| vfs.capabilities(fd))
+}
+
+pub fn fcntl(fd: Fd, cmd: i32, arg: usize) -> isize {
+    VFS.with_mut(|vfs| vfs.fcntl(fd, cmd, arg))
+}
+
+pub fn status_flags(fd: Fd) -> VfsResult<i32> {
+    VFS.with(|vfs| vfs.status_flags(fd))
+}
+
 pub fn close(fd: Fd) -> isize {
     VFS.with_mut(|vfs| vfs.close(fd))
 }
 
-pub fn fstat(fd: Fd, statbuf: *mut libc::stat) -> isize {
+pub fn pipe2(flags: i32) -> VfsResult<(Fd, Fd)> {
+    VFS.with_mut(|vfs| vfs.pipe2(flags))
+}
+
+/// `pipe2` for the syscall layer: stores the read and write fds in `fds[0]` and `fds[1]`.
+///
+/// # Safety
+/// `fds` must be null or valid for writing two `Fd`s.
+pub unsafe fn pipe2_user(fds: *mut Fd, flags: i32) -> isize {
+    let (read_fd, write_fd) = match pipe2(flags) {
+        Ok(pair) => pair,
+        Err(e) => return e,
+    };
+    const FD_SIZE: usize = core::mem::size_of::<Fd>();
+    let mut bytes = [0u8; 2 * FD_SIZE];
+    bytes[..FD_SIZE].copy_from_slice(&read_fd.to_ne_bytes());
+    bytes[FD_SIZE..].copy_from_slice(&write_fd.to_ne_bytes());
+    match foundation::kfn::mem::copy_to_user(fds as *mut u8, &bytes) {
+        Ok(_) => 0,
+        Err(e) => {
+            close(read_fd);
+            close(write_fd);
+            e
+        }
+    }
+}
+
+/// A copy of the global VFS's denied-open log.
+#[cfg(feature = "open-audit")]
+pub fn recent_denials() -> crate::DenialLog {
+    VFS.with(|vfs| *vfs.recent_denials())
+}
+
+pub fn dup(old_fd: Fd) -> isize {
+    VFS.with_mut(|vfs| match vfs.dup(old_fd) {
+        Ok(fd) => fd as isize,
+        Err(e) => e,
+    })
+}
+
+pub fn dup2(old_fd: Fd, new_fd: Fd) -> isize {
+    VFS.with_mut(|vfs| match vfs.dup2(old_fd, new_fd) {
+        Ok(fd) => fd as isize,
+        Err(e) => e,
+    })
+}
+
+/// # Safety
+/// `statbuf` must be null or valid for writing a `libc::stat`.
+pub unsafe fn fstat(fd: Fd, statbuf: *mut libc::stat) -> isize {
     VFS.with(|vfs| vfs.fstat(fd, statbuf))
 }
 
 pub(crate) fn fstat_raw(fd: Fd, statbuf: *mut u8) -> isize {
-    fstat(fd, statbuf as *mut libc::stat)
+    // The syscall layer hands over the guest's buffer, as it does for `read`/`write`.
+    unsafe { fstat(fd, statbuf as *mut libc::stat) }
 }
 
 pub const VFS_OPS: crate::VfsOps = crate::VfsOps {
     init: || {},
     read,
     write,
+    readv: readv_raw,
+    writev: writev_raw,
     open: open_cstr,
     close,
     lseek,
     ioctl,
     fstat: fstat_raw,
+    dup,
+    dup2,
+    pipe2: pipe2_user,
+    fcntl,
 };
 
 /// # Safety
 /// `path` must be a valid NUL-terminated string.
 pub unsafe fn open_cstr(path: *const u8, flags: i32, mode: u32) -> isize {
-    if path.is_null() {
-        return -(libc::EFAULT as isize);
-    }
-
-    let mut len = 0;
-    while *path.add(len) != 0 {
-        len += 1;
-        if len > 4096 {
-            return -(libc::ENAMETOOLONG as isize);
-        }
-    }
-    let slice = core::slice::from_raw_parts(path, len);
-    match core::str::from_utf8(slice) {
+    match foundation::kfn::mem::read_user_cstr(path, 4096) {
         Ok(s) => VFS.with_mut(|vfs| match vfs.open(s, flags, mode) {
             Ok(fd) => fd as isize,
             Err(e) => e,
         }),
-        Err(_) => -(libc::EINVAL as isize),
+        Err(e) => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        noop_capabilities, noop_close, noop_flush, noop_ioctl, noop_max_open, noop_mmap,
+        noop_preferred_block_size, noop_read, noop_seek, noop_set_nonblocking, noop_size,
+        noop_stat, noop_write, FileOps,
+    };
+
+    /// The fd limit the table-full tests use; the old fixed table size.
+    const TEST_FD_LIMIT: usize = 256;
+
+    /// Reads report the byte stored behind `private_data`, so tests can tell instances apart.
+    fn tag_read(file: *mut u8, _buf: *mut u8, _count: usize) -> isize {
+        unsafe { *file as isize }
+    }
+
+    const TAG_FOPS: FileOps = FileOps {
+        read: tag_read,
+        write: noop_write,
+        flush: noop_flush,
+        release: noop_close,
+        llseek: noop_seek,
+        ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: noop_max_open,
+        set_nonblocking: noop_set_nonblocking,
+        preferred_block_size: noop_preferred_block_size,
+        capabilities: noop_capabilities,
+        stat: noop_stat,
+        size: noop_size,
+    };
+
+    /// Seeks report `offset * 10 + whence` so tests can see what the device received.
+    fn echo_seek(_file: *mut u8, offset: isize, whence: SeekWhence) -> isize {
+        offset * 10 + whence.to_raw() as isize
+    }
+
+    fn seekable_caps(_file: *mut u8) -> DeviceCaps {
+        DeviceCaps::SEEKABLE
+    }
+
+    const SEEK_FOPS: FileOps = FileOps {
+        read: noop_read,
+        write: noop_write,
+        flush: noop_flush,
+        release: noop_close,
+        llseek: echo_seek,
+        ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: noop_max_open,
+        set_nonblocking: noop_set_nonblocking,
+        preferred_block_size: noop_preferred_block_size,
+        capabilities: seekable_caps,
+        stat: noop_stat,
+        size: noop_size,
+    };
+
+    fn seek_factory(_path: &str) -> FdEntry {
+        FdEntry {
+            ops: &SEEK_FOPS,
+            private_data: core::ptr::null_mut(),
+        }
+    }
+
+    /// Records the last nonblocking hint in the byte behind `private_data`.
+    fn record_set_nonblocking(file: *mut u8, nonblocking: bool) -> isize {
+        unsafe { *file = nonblocking as u8 };
+        0
+    }
+
+    const NONBLOCK_FOPS: FileOps = FileOps {
+        read: noop_read,
+        write: noop_write,
+        flush: noop_flush,
+        release: noop_close,
+        llseek: noop_seek,
+        ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: noop_max_open,
+        set_nonblocking: record_set_nonblocking,
+        preferred_block_size: noop_preferred_block_size,
+        capabilities: noop_capabilities,
+        stat: noop_stat,
+        size: noop_size,
+    };
+
+    fn single_max_open(_file: *mut u8) -> usize {
+        1
+    }
+
+    const SINGLE_FOPS: FileOps = FileOps {
+        read: noop_read,
+        write: noop_write,
+        flush: noop_flush,
+        release: noop_close,
+        llseek: noop_seek,
+        ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: single_max_open,
+        set_nonblocking: noop_set_nonblocking,
+        preferred_block_size: noop_preferred_block_size,
+        capabilities: noop_capabilities,
+        stat: noop_stat,
+        size: noop_size,
+    };
+
+    fn single_factory(_path: &str) -> FdEntry {
+        FdEntry {
+            ops: &SINGLE_FOPS,
+            private_data: core::ptr::null_mut(),
+        }
+    }
+
+    fn file_stat(_file: *mut u8) -> crate::StatInfo {
+        crate::StatInfo {
+            mode: libc::S_IFREG | 0o644,
+            size: 1234,
+            blksize: 4096,
+            rdev: 0,
+        }
+    }
+
+    const FILE_FOPS: FileOps = FileOps {
+        stat: file_stat,
+        ..SINGLE_FOPS
+    };
+
+    #[test]
+    fn test_fstat_fills_from_device() {
+        let mut vfs = Vfs::new();
+        vfs.register_fd(
+            3,
+            FdEntry {
+                ops: &FILE_FOPS,
+                private_data: core::ptr::null_mut(),
+            },
+        )
+        .unwrap();
+
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        assert_eq!(unsafe { vfs.fstat(3, &mut st) }, 0);
+        assert_eq!(st.st_mode as u32, libc::S_IFREG | 0o644);
+        assert_eq!(st.st_size, 1234);
+        assert_eq!(st.st_blksize, 4096);
+        assert_eq!(st.st_blocks, 3);
+        assert_eq!(st.st_nlink, 1);
+    }
+
+    #[test]
+    fn test_fstat_default_is_char_device() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs.open("/dev/seek", 0, 0).unwrap();
+
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        assert_eq!(unsafe { vfs.fstat(fd, &mut st) }, 0);
+        assert_eq!(st.st_mode as u32 & libc::S_IFMT, libc::S_IFCHR);
+        assert_eq!(st.st_size, 0);
+        // Falls back to the device's preferred block size.
+        assert_eq!(st.st_blksize, 1);
+    }
+
+    #[test]
+    fn test_fstat_errors() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs.open("/dev/seek", 0, 0).unwrap();
+
+        let mut st: libc::stat = unsafe { core::mem::zeroed() };
+        unsafe {
+            assert_eq!(
+                vfs.fstat(fd, core::ptr::null_mut()),
+                -(libc::EFAULT as isize)
+            );
+            assert_eq!(vfs.fstat(fd + 1, &mut st), -(libc::EBADF as isize));
+            assert_eq!(vfs.fstat(-1, &mut st), -(libc::EBADF as isize));
+        }
+    }
+
+    #[test]
+    fn test_capabilities_lookup() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        vfs.register_device("/dev/single", single_factory).unwrap();
+        let seek = vfs.open("/dev/seek", 0, 0).unwrap();
+        let single = vfs.open("/dev/single", 0, 0).unwrap();
+
+        let caps = vfs.capabilities(seek).unwrap();
+        assert!(caps.contains(DeviceCaps::SEEKABLE));
+        assert!(!caps.contains(DeviceCaps::SEEKABLE | DeviceCaps::READABLE));
+        assert_eq!(vfs.capabilities(single), Ok(DeviceCaps::empty()));
+        assert_eq!(vfs.capabilities(99), Err(-(libc::EBADF as isize)));
+    }
+
+    #[test]
+    fn test_register_device_instance_shared_across_opens() {
+        static mut TAG: u8 = 7;
+        let entry = FdEntry {
+            ops: &TAG_FOPS,
+            private_data: core::ptr::addr_of_mut!(TAG),
+        };
+
+        let mut vfs = Vfs::new();
+        vfs.register_device_instance("/dev/tag", entry).unwrap();
+
+        let mut buf = [0u8; 1];
+        let a = vfs.open("/dev/tag", 0, 0).unwrap();
+        let b = vfs.open("/dev/tag", 0, 0).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(vfs.read(a, buf.as_mut_ptr(), 1), 7);
+        assert_eq!(vfs.read(b, buf.as_mut_ptr(), 1), 7);
+
+        unsafe { *core::ptr::addr_of_mut!(TAG) = 9 };
+        assert_eq!(vfs.read(a, buf.as_mut_ptr(), 1), 9);
+
+        assert_eq!(vfs.close(a), 0);
+        assert_eq!(vfs.read(b, buf.as_mut_ptr(), 1), 9);
+    }
+
+    #[test]
+    fn test_register_device_instance_unknown_path() {
+        let mut vfs = Vfs::new();
+        assert_eq!(vfs.open("/dev/tag", 0, 0), Err(-(libc::ENOENT as isize)));
+    }
+
+    #[test]
+    fn test_open_respects_max_open() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ttyS0", single_factory).unwrap();
+
+        let fd = vfs.open("/dev/ttyS0", 0, 0).unwrap();
+        assert_eq!(vfs.open("/dev/ttyS0", 0, 0), Err(-(libc::EBUSY as isize)));
+
+        assert_eq!(vfs.close(fd), 0);
+        assert!(vfs.open("/dev/ttyS0", 0, 0).is_ok());
+    }
+
+    static mut SERIAL_TAGS: [u8; 4] = [10, 11, 12, 13];
+
+    /// Picks the port from the last digit of the opened path, e.g. `/dev/ttyS2` -> 2.
+    fn serial_factory(path: &str) -> FdEntry {
+        let port = (path.as_bytes()[path.len() - 1] - b'0') as usize;
+        FdEntry {
+            ops: &TAG_FOPS,
+            private_data: unsafe { core::ptr::addr_of_mut!(SERIAL_TAGS[port]) },
+        }
+    }
+
+    #[test]
+    fn test_wildcard_passes_opened_path_to_factory() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ttyS*", serial_factory).unwrap();
+
+        let mut buf = [0u8; 1];
+        for (path, tag) in [("/dev/ttyS0", 10), ("/dev/ttyS3", 13)] {
+            let fd = vfs.open(path, 0, 0).unwrap();
+            assert_eq!(vfs.read(fd, buf.as_mut_ptr(), 1), tag);
+        }
+    }
+
+    #[test]
+    fn test_exact_path_beats_wildcard() {
+        static mut EXACT: u8 = 99;
+        let exact = FdEntry {
+            ops: &TAG_FOPS,
+            private_data: core::ptr::addr_of_mut!(EXACT),
+        };
+
+        let mut vfs = Vfs::new();
+        // Registration order must not matter.
+        vfs.register_device("/dev/tty*", seek_factory).unwrap();
+        vfs.register_device("/dev/ttyS*", serial_factory).unwrap();
+        vfs.register_device_instance("/dev/ttyS1", exact).unwrap();
+
+        let mut buf = [0u8; 1];
+        let fd = vfs.open("/dev/ttyS1", 0, 0).unwrap();
+        assert_eq!(vfs.read(fd, buf.as_mut_ptr(), 1), 99);
+        // The longer wildcard prefix wins over `/dev/tty*`.
+        let fd = vfs.open("/dev/ttyS2", 0, 0).unwrap();
+        assert_eq!(vfs.read(fd, buf.as_mut_ptr(), 1), 12);
+        let fd = vfs.open("/dev/ttyUSB0", 0, 0).unwrap();
+        assert_eq!(vfs.lseek(fd, 1, libc::SEEK_SET), 10);
+    }
+
+    #[test]
+    fn test_duplicate_registration_is_eexist() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        assert_eq!(
+            vfs.register_device("/dev/seek", single_factory),
+            Err(-(libc::EEXIST as isize))
+        );
+        assert_eq!(
+            vfs.register_device_instance("/dev/seek", seek_factory("")),
+            Err(-(libc::EEXIST as isize))
+        );
+    }
+
+    #[test]
+    fn test_unregister_device() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs.open("/dev/seek", 0, 0).unwrap();
+
+        assert_eq!(vfs.unregister_device("/dev/seek"), Ok(()));
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Err(-(libc::ENOENT as isize)));
+        assert_eq!(
+            vfs.unregister_device("/dev/seek"),
+            Err(-(libc::ENOENT as isize))
+        );
+
+        // The fd opened before removal still works and closes cleanly.
+        assert_eq!(vfs.lseek(fd, 1, libc::SEEK_SET), 10);
+        assert_eq!(vfs.close(fd), 0);
+    }
+
+    #[test]
+    fn test_unregister_frees_slot_accounting() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/single", single_factory).unwrap();
+        let fd = vfs.open("/dev/single", 0, 0).unwrap();
+        vfs.unregister_device("/dev/single").unwrap();
+
+        vfs.register_device("/dev/single", single_factory).unwrap();
+        let second = vfs.open("/dev/single", 0, 0).unwrap();
+        // Closing the fd from the old registration must not release the new one's slot.
+        assert_eq!(vfs.close(fd), 0);
+        assert_eq!(vfs.open("/dev/single", 0, 0), Err(-(libc::EBUSY as isize)));
+        assert_eq!(vfs.close(second), 0);
+    }
+
+    #[test]
+    fn test_wildcard_without_match_is_enoent() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ttyS*", serial_factory).unwrap();
+        let enoent = Err(-(libc::ENOENT as isize));
+        assert_eq!(vfs.open("/dev/ttyUSB0", 0, 0), enoent);
+        assert_eq!(vfs.open("/dev/ttyS/0", 0, 0), enoent);
+        assert_eq!(vfs.open("/dev/tty", 0, 0), enoent);
+    }
+
+    #[test]
+    fn test_seek_whence_round_trip() {
+        for whence in [SeekWhence::Set, SeekWhence::Cur, SeekWhence::End] {
+            assert_eq!(SeekWhence::from_raw(whence.to_raw()), Some(whence));
+        }
+        assert_eq!(SeekWhence::from_raw(3), None);
+        assert_eq!(SeekWhence::from_raw(-1), None);
+    }
+
+    #[test]
+    fn test_lseek_maps_raw_whence() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs.open("/dev/seek", 0, 0).unwrap();
+
+        assert_eq!(
+            vfs.lseek(fd, 4, libc::SEEK_SET),
+            40 + libc::SEEK_SET as isize
+        );
+        assert_eq!(
+            vfs.lseek(fd, 4, libc::SEEK_CUR),
+            40 + libc::SEEK_CUR as isize
+        );
+        assert_eq!(
+            vfs.lseek(fd, 4, libc::SEEK_END),
+            40 + libc::SEEK_END as isize
+        );
+        assert_eq!(vfs.lseek(fd, 4, 42), -(libc::EINVAL as isize));
+        assert_eq!(vfs.lseek(99, 4, 42), -(libc::EBADF as isize));
+    }
+
+    /// A growable in-memory file; each open gets its own, with the offset kept alongside.
+    struct RamFile {
+        data: Vec<u8>,
+        pos: u64,
+    }
+
+    fn ram_read(file: *mut u8, buf: *mut u8, count: usize) -> isize {
+        let file = unsafe { &mut *(file as *mut RamFile) };
+        let start = (file.pos as usize).min(file.data.len());
+        let n = count.min(file.data.len() - start);
+        unsafe { core::ptr::copy_nonoverlapping(file.data.as_ptr().add(start), buf, n) };
+        file.pos += n as u64;
+        n as isize
+    }
+
+    fn ram_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+        let file = unsafe { &mut *(file as *mut RamFile) };
+        let start = file.pos as usize;
+        let end = start + count;
+        if file.data.len() < end {
+            file.data.resize(end, 0);
+        }
+        unsafe { core::ptr::copy_nonoverlapping(buf, file.data.as_mut_ptr().add(start), count) };
+        file.pos = end as u64;
+        count as isize
+    }
+
+    fn ram_seek(file: *mut u8, offset: isize, whence: SeekWhence) -> isize {
+        let size = ram_size(file);
+        let file = unsafe { &mut *(file as *mut RamFile) };
+        crate::seek_offset(&mut file.pos, size, offset, whence)
+    }
+
+    fn ram_size(file: *mut u8) -> Option<u64> {
+        Some(unsafe { &*(file as *const RamFile) }.data.len() as u64)
+    }
+
+    fn ram_release(file: *mut u8) -> isize {
+        drop(unsafe { alloc::boxed::Box::from_raw(file as *mut RamFile) });
+        0
+    }
+
+    const RAM_FOPS: FileOps = FileOps {
+        read: ram_read,
+        write: ram_write,
+        release: ram_release,
+        llseek: ram_seek,
+        capabilities: seekable_caps,
+        size: ram_size,
+        ..SINGLE_FOPS
+    };
+
+    fn ram_factory(_path: &str) -> FdEntry {
+        let file = alloc::boxed::Box::new(RamFile {
+            data: Vec::new(),
+            pos: 0,
+        });
+        FdEntry {
+            ops: &RAM_FOPS,
+            private_data: alloc::boxed::Box::into_raw(file) as *mut u8,
+        }
+    }
+
+    #[test]
+    fn test_sized_seek_tracks_offset() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ram", ram_factory).unwrap();
+        let fd = vfs.open("/dev/ram", libc::O_RDWR, 0).unwrap();
+        assert_eq!(vfs.write(fd, b"hello world".as_ptr(), 11), 11);
+
+        assert_eq!(vfs.lseek(fd, -5, libc::SEEK_END), 6);
+        let mut buf = [0u8; 8];
+        assert_eq!(vfs.read(fd, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(&buf[..5], b"world");
+
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_CUR), 11);
+        assert_eq!(vfs.lseek(fd, -11, libc::SEEK_CUR), 0);
+        assert_eq!(vfs.lseek(fd, 20, libc::SEEK_SET), 20);
+        assert_eq!(vfs.read(fd, buf.as_mut_ptr(), buf.len()), 0);
+    }
+
+    #[test]
+    fn test_sized_seek_rejects_negative_target() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ram", ram_factory).unwrap();
+        let fd = vfs.open("/dev/ram", libc::O_RDWR, 0).unwrap();
+        assert_eq!(vfs.write(fd, b"abc".as_ptr(), 3), 3);
+
+        let einval = -(libc::EINVAL as isize);
+        assert_eq!(vfs.lseek(fd, -1, libc::SEEK_SET), einval);
+        assert_eq!(vfs.lseek(fd, -4, libc::SEEK_END), einval);
+        assert_eq!(vfs.lseek(fd, -4, libc::SEEK_CUR), einval);
+        // A failed seek leaves the offset alone.
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_CUR), 3);
+        assert_eq!(vfs.lseek(fd, -3, libc::SEEK_END), 0);
+    }
+
+    #[test]
+    fn test_append_writes_at_end() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/ram", ram_factory).unwrap();
+        let fd = vfs
+            .open("/dev/ram", libc::O_RDWR | libc::O_APPEND, 0)
+            .unwrap();
+        assert_eq!(vfs.write(fd, b"abc".as_ptr(), 3), 3);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_SET), 0);
+        assert_eq!(vfs.write(fd, b"de".as_ptr(), 2), 2);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_CUR), 5);
+
+        assert_eq!(vfs.fcntl(fd, libc::F_SETFL, 0), 0);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_SET), 0);
+        assert_eq!(vfs.write(fd, b"X".as_ptr(), 1), 1);
+        assert_eq!(vfs.lseek(fd, 0, libc::SEEK_SET), 0);
+        let mut buf = [0u8; 8];
+        assert_eq!(vfs.read(fd, buf.as_mut_ptr(), buf.len()), 5);
+        assert_eq!(&buf[..5], b"Xbcde");
+    }
+
+    #[test]
+    fn test_seek_offset_without_size_is_espipe() {
+        let mut pos = 7;
+        let espipe = -(libc::ESPIPE as isize);
+        assert_eq!(
+            crate::seek_offset(&mut pos, None, 0, SeekWhence::Set),
+            espipe
+        );
+        assert_eq!(pos, 7);
+        assert_eq!(
+            crate::seek_offset(&mut pos, Some(0), isize::MAX, SeekWhence::Cur),
+            -(libc::EOVERFLOW as isize)
+        );
+    }
+
+    #[test]
+    fn test_open_records_nonblocking() {
+        static mut HINT: u8 = 0;
+        let entry = FdEntry {
+            ops: &NONBLOCK_FOPS,
+            private_data: core::ptr::addr_of_mut!(HINT),
+        };
+
+        let mut vfs = Vfs::new();
+        vfs.register_device_instance("/dev/nb", entry).unwrap();
+
+        let fd = vfs
+            .open(
+                "/dev/nb",
+                libc::O_RDWR | libc::O_NONBLOCK | libc::O_CREAT,
+                0,
+            )
+            .unwrap();
+        assert_eq!(vfs.is_nonblocking(fd), Ok(true));
+        assert_eq!(unsafe { *core::ptr::addr_of!(HINT) }, 1);
+    }
+
+    #[test]
+    fn test_set_nonblocking_after_open() {
+        static mut HINT: u8 = 0;
+        let entry = FdEntry {
+            ops: &NONBLOCK_FOPS,
+            private_data: core::ptr::addr_of_mut!(HINT),
+        };
+
+        let mut vfs = Vfs::new();
+        vfs.register_device_instance("/dev/nb", entry).unwrap();
+        let fd = vfs.open("/dev/nb", libc::O_WRONLY, 0).unwrap();
+        assert_eq!(vfs.is_nonblocking(fd), Ok(false));
+
+        assert_eq!(vfs.set_nonblocking(fd, true), 0);
+        assert_eq!(vfs.is_nonblocking(fd), Ok(true));
+        assert_eq!(unsafe { *core::ptr::addr_of!(HINT) }, 1);
+
+        assert_eq!(vfs.set_nonblocking(fd, false), 0);
+        assert_eq!(vfs.is_nonblocking(fd), Ok(false));
+        assert_eq!(unsafe { *core::ptr::addr_of!(HINT) }, 0);
+
+        assert_eq!(vfs.set_nonblocking(99, true), -(libc::EBADF as isize));
+        assert_eq!(vfs.is_nonblocking(99), Err(-(libc::EBADF as isize)));
+    }
+
+    #[test]
+    fn test_fcntl_setfl_nonblocking() {
+        static mut HINT: u8 = 0;
+        let entry = FdEntry {
+            ops: &NONBLOCK_FOPS,
+            private_data: core::ptr::addr_of_mut!(HINT),
+        };
+
+        let mut vfs = Vfs::new();
+        vfs.register_device_instance("/dev/nb", entry).unwrap();
+        let fd = vfs.open("/dev/nb", libc::O_WRONLY, 0).unwrap();
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFL, 0), libc::O_WRONLY as isize);
+
+        assert_eq!(vfs.fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK as usize), 0);
+        assert_eq!(
+            vfs.fcntl(fd, libc::F_GETFL, 0),
+            (libc::O_WRONLY | libc::O_NONBLOCK) as isize
+        );
+        assert_eq!(unsafe { *core::ptr::addr_of!(HINT) }, 1);
+
+        // The access mode is not changeable through F_SETFL.
+        assert_eq!(vfs.fcntl(fd, libc::F_SETFL, libc::O_RDWR as usize), 0);
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFL, 0), libc::O_WRONLY as isize);
+        assert_eq!(unsafe { *core::ptr::addr_of!(HINT) }, 0);
+
+        assert_eq!(vfs.fcntl(fd, -1, 0), -(libc::EINVAL as isize));
+        assert_eq!(vfs.fcntl(99, libc::F_GETFL, 0), -(libc::EBADF as isize));
+    }
+
+    #[test]
+    fn test_fcntl_fd_flags_round_trip() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs.open("/dev/seek", libc::O_RDONLY, 0).unwrap();
+
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFD, 0), 0);
+        assert_eq!(vfs.fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC as usize), 0);
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFD, 0), libc::FD_CLOEXEC as isize);
+        assert_eq!(vfs.fcntl(fd, libc::F_SETFD, 0), 0);
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFD, 0), 0);
+    }
+
+    #[test]
+    fn test_open_cloexec_is_fd_flag() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs
+            .open("/dev/seek", libc::O_RDONLY | libc::O_CLOEXEC, 0)
+            .unwrap();
+
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFD, 0), libc::FD_CLOEXEC as isize);
+        assert_eq!(vfs.status_flags(fd), Ok(libc::O_RDONLY));
+
+        // Duplicates share status flags but not FD_CLOEXEC.
+        let dup_fd = vfs.dup(fd).unwrap();
+        assert_eq!(vfs.fcntl(dup_fd, libc::F_GETFD, 0), 0);
+        assert_eq!(vfs.fcntl(fd, libc::F_GETFD, 0), libc::FD_CLOEXEC as isize);
+    }
+
+    #[test]
+    fn test_status_flags_track_setfl() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let fd = vfs.open("/dev/seek", libc::O_RDWR, 0).unwrap();
+
+        assert_eq!(vfs.fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK as usize), 0);
+        assert_eq!(vfs.status_flags(fd), Ok(libc::O_RDWR | libc::O_NONBLOCK));
+        assert_eq!(vfs.status_flags(42), Err(-(libc::EBADF as isize)));
+    }
+
+    #[test]
+    fn test_open_returns_lowest_free_fd() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        let open = |vfs: &mut Vfs| vfs.open("/dev/seek", 0, 0).unwrap();
+
+        assert_eq!(open(&mut vfs), 3);
+        assert_eq!(open(&mut vfs), 4);
+        assert_eq!(open(&mut vfs), 5);
+        assert_eq!(open(&mut vfs), 6);
+
+        // A freed low fd is reused before higher ones.
+        assert_eq!(vfs.close(4), 0);
+        assert_eq!(open(&mut vfs), 4);
+
+        // With several holes, the lowest comes first.
+        assert_eq!(vfs.close(5), 0);
+        assert_eq!(vfs.close(3), 0);
+        assert_eq!(open(&mut vfs), 3);
+        assert_eq!(open(&mut vfs), 5);
+        assert_eq!(open(&mut vfs), 7);
+
+        // Closing the most recent fd hands it straight back.
+        assert_eq!(vfs.close(7), 0);
+        assert_eq!(open(&mut vfs), 7);
+    }
+
+    #[test]
+    fn test_open_wraps_to_freed_low_fd_when_table_full() {
+        let mut vfs = Vfs::with_fd_limit(TEST_FD_LIMIT);
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+
+        for expected in FIRST_OPEN_FD..TEST_FD_LIMIT {
+            assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(expected as Fd));
+        }
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Err(-(libc::EMFILE as isize)));
+
+        // After filling the top of the table, a freed low fd is found again.
+        assert_eq!(vfs.close(10), 0);
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(10));
+        assert_eq!(vfs.close(TEST_FD_LIMIT as Fd - 1), 0);
+        assert_eq!(vfs.close(3), 0);
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(3));
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(TEST_FD_LIMIT as Fd - 1));
+    }
+
+    #[test]
+    fn test_fd_table_grows_past_256() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+
+        for expected in FIRST_OPEN_FD..DEFAULT_FD_LIMIT {
+            assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(expected as Fd));
+        }
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Err(-(libc::EMFILE as isize)));
+        assert_eq!(vfs.lseek(300, 1, libc::SEEK_SET), 10);
+
+        // Raising the limit lets the table keep growing.
+        vfs.set_fd_limit(DEFAULT_FD_LIMIT + 1);
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(DEFAULT_FD_LIMIT as Fd));
+    }
+
+    #[test]
+    fn test_fd_limit_bounds_register_and_dup2() {
+        let mut vfs = Vfs::with_fd_limit(8);
+        assert_eq!(
+            vfs.register_fd(8, seek_factory("")),
+            Err(-(libc::EINVAL as isize))
+        );
+        vfs.register_fd(7, seek_factory("")).unwrap();
+        assert_eq!(vfs.dup2(7, 8), Err(-(libc::EBADF as isize)));
+        assert_eq!(vfs.dup2(7, 5), Ok(5));
+
+        // Lowering the limit keeps existing fds usable.
+        vfs.set_fd_limit(4);
+        assert_eq!(vfs.lseek(7, 1, libc::SEEK_SET), 10);
+        assert_eq!(vfs.dup(7), Ok(0));
+        assert_eq!(vfs.close(7), 0);
+    }
+
+    #[test]
+    fn test_open_skips_stdio_fds() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(3));
+        vfs.register_fd(4, seek_factory("/dev/seek")).unwrap();
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Ok(5));
+    }
+
+    /// Spy state for a buffered device: pending bytes and the order of close-time calls.
+    struct Spy {
+        pending: usize,
+        fail_flush: bool,
+        calls: [u8; 4],
+        ncalls: usize,
+    }
+
+    impl Spy {
+        const fn new(fail_flush: bool) -> Self {
+            Self {
+                pending: 0,
+                fail_flush,
+                calls: [0; 4],
+                ncalls: 0,
+            }
+        }
+
+        fn record(&mut self, call: u8) {
+            self.calls[self.ncalls] = call;
+            self.ncalls += 1;
+        }
+    }
+
+    fn spy_write(file: *mut u8, _buf: *const u8, count: usize) -> isize {
+        let spy = unsafe { &mut *(file as *mut Spy) };
+        spy.pending += count;
+        count as isize
+    }
+
+    fn spy_flush(file: *mut u8) -> isize {
+        let spy = unsafe { &mut *(file as *mut Spy) };
+        spy.record(b'f');
+        if spy.fail_flush {
+            return -(libc::EIO as isize);
+        }
+        spy.pending = 0;
+        0
+    }
+
+    fn spy_release(file: *mut u8) -> isize {
+        let spy = unsafe { &mut *(file as *mut Spy) };
+        spy.record(b'r');
+        0
+    }
+
+    const SPY_FOPS: FileOps = FileOps {
+        read: noop_read,
+        write: spy_write,
+        flush: spy_flush,
+        release: spy_release,
+        llseek: noop_seek,
+        ioctl: noop_ioctl,
+        mmap: noop_mmap,
+        max_open: noop_max_open,
+        set_nonblocking: noop_set_nonblocking,
+        preferred_block_size: noop_preferred_block_size,
+        capabilities: noop_capabilities,
+        stat: noop_stat,
+        size: noop_size,
+    };
+
+    const SINGLE_SPY_FOPS: FileOps = FileOps {
+        max_open: single_max_open,
+        ..SPY_FOPS
+    };
+
+    #[test]
+    fn test_busy_open_does_not_release_shared_instance() {
+        let mut spy = Spy::new(false);
+        let mut vfs = Vfs::new();
+        let entry = FdEntry {
+            ops: &SINGLE_SPY_FOPS,
+            private_data: &mut spy as *mut Spy as *mut u8,
+        };
+        vfs.register_device_instance("/dev/spy", entry).unwrap();
+        let fd = vfs.open("/dev/spy", libc::O_WRONLY, 0).unwrap();
+
+        assert_eq!(
+            vfs.open("/dev/spy", libc::O_WRONLY, 0),
+            Err(-(libc::EBUSY as isize))
+        );
+        assert_eq!(spy.ncalls, 0);
+        assert_eq!(vfs.close(fd), 0);
+    }
+
+    fn spy_vfs(spy: &mut Spy) -> (Vfs, Fd) {
+        let mut vfs = Vfs::new();
+        let entry = FdEntry {
+            ops: &SPY_FOPS,
+            private_data: spy as *mut Spy as *mut u8,
+        };
+        vfs.register_device_instance("/dev/spy", entry).unwrap();
+        let fd = vfs.open("/dev/spy", libc::O_WRONLY, 0).unwrap();
+        (vfs, fd)
+    }
+
+    #[test]
+    fn test_close_flushes_before_release() {
+        let mut spy = Spy::new(false);
+        let (mut vfs, fd) = spy_vfs(&mut spy);
+        assert_eq!(vfs.write(fd, b"abc".as_ptr(), 3), 3);
+
+        assert_eq!(vfs.close(fd), 0);
+        assert_eq!(&spy.calls[..spy.ncalls], b"fr");
+        assert_eq!(spy.pending, 0);
+    }
+
+    #[test]
+    fn test_close_reports_flush_error_and_frees_fd() {
+        let mut spy = Spy::new(true);
+        let (mut vfs, fd) = spy_vfs(&mut spy);
+        assert_eq!(vfs.write(fd, b"abc".as_ptr(), 3), 3);
+
+        assert_eq!(vfs.close(fd), -(libc::EIO as isize));
+        assert_eq!(&spy.calls[..spy.ncalls], b"fr");
+        assert_eq!(vfs.close(fd), -(libc::EBADF as isize));
+    }
+
+    #[test]
+    fn test_dup_writes_reach_same_device() {
+        let mut spy = Spy::new(false);
+        let (mut vfs, fd) = spy_vfs(&mut spy);
+        let dup_fd = vfs.dup(fd).unwrap();
+        assert_ne!(dup_fd, fd);
+
+        assert_eq!(vfs.write(fd, b"ab".as_ptr(), 2), 2);
+        assert_eq!(vfs.write(dup_fd, b"cde".as_ptr(), 3), 3);
+        assert_eq!(spy.pending, 5);
+    }
+
+    #[test]
+    fn test_dup_close_one_leaves_other_usable() {
+        let mut spy = Spy::new(false);
+        let (mut vfs, fd) = spy_vfs(&mut spy);
+        let dup_fd = vfs.dup(fd).unwrap();
+
+        assert_eq!(vfs.close(fd), 0);
+        assert_eq!(&spy.calls[..spy.ncalls], b"f");
+        assert_eq!(vfs.write(dup_fd, b"abc".as_ptr(), 3), 3);
+
+        assert_eq!(vfs.close(dup_fd), 0);
+        assert_eq!(&spy.calls[..spy.ncalls], b"ffr");
+    }
+
+    #[test]
+    fn test_dup_takes_lowest_free_fd() {
+        let mut vfs = Vfs::new();
+        vfs.register_fd(1, seek_factory("")).unwrap();
+        assert_eq!(vfs.dup(1), Ok(0));
+        assert_eq!(vfs.dup(1), Ok(2));
+        assert_eq!(vfs.dup(7), Err(-(libc::EBADF as isize)));
+    }
+
+    #[test]
+    fn test_dup2_replaces_open_fd() {
+        let mut spy = Spy::new(false);
+        let (mut vfs, fd) = spy_vfs(&mut spy);
+        vfs.register_fd(2, seek_factory("")).unwrap();
+
+        assert_eq!(vfs.dup2(fd, 2), Ok(2));
+        assert_eq!(vfs.write(2, b"abc".as_ptr(), 3), 3);
+        assert_eq!(spy.pending, 3);
+        assert_eq!(vfs.lseek(2, 1, libc::SEEK_SET), -(libc::ESPIPE as isize));
+
+        assert_eq!(vfs.dup2(fd, fd), Ok(fd));
+        assert_eq!(
+            vfs.dup2(fd, vfs.fd_limit() as Fd),
+            Err(-(libc::EBADF as isize))
+        );
+        assert_eq!(vfs.dup2(9, 2), Err(-(libc::EBADF as isize)));
+    }
+
+    #[test]
+    fn test_dup_shares_status_flags_and_open_count() {
+        let mut vfs = Vfs::new();
+        vfs.register_device("/dev/single", single_factory).unwrap();
+        let fd = vfs.open("/dev/single", libc::O_RDWR, 0).unwrap();
+        let dup_fd = vfs.dup(fd).unwrap();
+
+        assert_eq!(vfs.fcntl(dup_fd, libc::F_SETFL, libc::O_APPEND as usize), 0);
+        assert_eq!(
+            vfs.fcntl(fd, libc::F_GETFL, 0),
+            (libc::O_RDWR | libc::O_APPEND) as isize
+        );
+
+        assert_eq!(vfs.close(fd), 0);
+        assert_eq!(vfs.open("/dev/single", 0, 0), Err(-(libc::EBUSY as isize)));
+        assert_eq!(vfs.close(dup_fd), 0);
+        assert!(vfs.open("/dev/single", 0, 0).is_ok());
+    }
+
+    #[cfg(feature = "open-audit")]
+    #[test]
+    fn test_denied_opens_are_logged() {
+        let mut vfs = Vfs::with_fd_limit(TEST_FD_LIMIT);
+        vfs.register_device("/dev/seek", seek_factory).unwrap();
+        assert_eq!(
+            vfs.open("/dev/missing", 0, 0),
+            Err(-(libc::ENOENT as isize))
+        );
+        for _ in FIRST_OPEN_FD..TEST_FD_LIMIT {
+            vfs.open("/dev/seek", 0, 0).unwrap();
+        }
+        assert_eq!(vfs.open("/dev/seek", 0, 0), Err(-(libc::EMFILE as isize)));
+
+        let log = vfs.recent_denials();
+        assert_eq!(log.len(), 2);
+        let mut denials = log.iter();
+        let first = denials.next().unwrap();
+        assert_eq!((first.path(), first.errno), ("/dev/missing", libc::ENOENT));
+        let second = denials.next().unwrap();
+        assert_eq!((second.path(), second.errno), ("/dev/seek", libc::EMFILE));
+    }
+
+    /// Keeps what is written, up to 8 bytes; later writes come up short.
+    struct Capture {
+        data: [u8; 8],
+        len: usize,
+    }
+
+    fn capture_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+        let capture = unsafe { &mut *(file as *mut Capture) };
+        let n = count.min(capture.data.len() - capture.len);
+        let src = unsafe { core::slice::from_raw_parts(buf, n) };
+        capture.data[capture.len..capture.len + n].copy_from_slice(src);
+        capture.len += n;
+        n as isize
+    }
+
+    /// Reads hand out "abc" per call, however much was asked for.
+    fn abc_read(_file: *mut u8, buf: *mut u8, count: usize) -> isize {
+        let n = count.min(3);
+        unsafe { core::ptr::copy_nonoverlapping(b"abc".as_ptr(), buf, n) };
+        n as isize
+    }
+
+    const CAPTURE_FOPS: FileOps = FileOps {
+        read: abc_read,
+        write: capture_write,
+        ..SPY_FOPS
+    };
+
+    fn iovec(buf: &mut [u8]) -> libc::iovec {
+        libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        }
+    }
+
+    fn capture_vfs(capture: &mut Capture) -> Vfs {
+        let mut vfs = Vfs::new();
+        let entry = FdEntry {
+            ops: &CAPTURE_FOPS,
+            private_data: capture as *mut Capture as *mut u8,
+        };
+        vfs.register_fd(1, entry).unwrap();
+        vfs
+    }
+
+    #[test]
+    fn test_writev_concatenates_segments() {
+        let mut capture = Capture {
+            data: [0; 8],
+            len: 0,
+        };
+        let vfs = capture_vfs(&mut capture);
+        let (mut a, mut empty, mut b) = (*b"ab", [0u8; 0], *b"cde");
+        let iov = [iovec(&mut a), iovec(&mut empty), iovec(&mut b)];
+
+        assert_eq!(unsafe { vfs.writev(1, iov.as_ptr(), iov.len()) }, 5);
+        assert_eq!(&capture.data[..capture.len], b"abcde");
+    }
+
+    #[test]
+    fn test_writev_stops_at_short_segment() {
+        let mut capture = Capture {
+            data: [0; 8],
+            len: 0,
+        };
+        let vfs = capture_vfs(&mut capture);
+        let (mut a, mut b, mut c) = (*b"12345", *b"6789", *b"x");
+        let iov = [iovec(&mut a), iovec(&mut b), iovec(&mut c)];
+
+        assert_eq!(unsafe { vfs.writev(1, iov.as_ptr(), iov.len()) }, 8);
+        assert_eq!(&capture.data, b"12345678");
+        // Nothing fits now, so the device's 0 is the total.
+        assert_eq!(unsafe { vfs.writev(1, iov.as_ptr(), 1) }, 0);
+    }
+
+    #[test]
+    fn test_readv_fills_segments_in_order() {
+        let mut capture = Capture {
+            data: [0; 8],
+            len: 0,
+        };
+        let vfs = capture_vfs(&mut capture);
+        let (mut a, mut b) = ([0u8; 3], [0u8; 4]);
+        let iov = [iovec(&mut a), iovec(&mut b)];
+
+        assert_eq!(unsafe { vfs.readv(1, iov.as_ptr(), iov.len()) }, 6);
+        assert_eq!(&a, b"abc");
+        assert_eq!(&b[..3], b"abc");
+    }
+
+    /// Reads hand out the next unread byte of `data`, one per call, then report end of file.
+    fn trickle_read(file: *mut u8, buf: *mut u8, count: usize) -> isize {
+        let capture = unsafe { &mut *(file as *mut Capture) };
+        if count == 0 || capture.len == capture.data.len() {
+            return 0;
+        }
+        unsafe { *buf = capture.data[capture.len] };
+        capture.len += 1;
+        1
+    }
+
+    fn trickle_write(file: *mut u8, buf: *const u8, count: usize) -> isize {
+        capture_write(file, buf, count.min(1))
+    }
+
+    const TRICKLE_FOPS: FileOps = FileOps {
+        read: trickle_read,
+        write: trickle_write,
+        ..SPY_FOPS
+    };
+
+    fn trickle_vfs(capture: &mut Capture) -> Vfs {
+        let mut vfs = Vfs::new();
+        let entry = FdEntry {
+            ops: &TRICKLE_FOPS,
+            private_data: capture as *mut Capture as *mut u8,
+        };
+        vfs.register_fd(1, entry).unwrap();
+        vfs
+    }
+
+    #[test]
+    fn test_write_all_loops_over_short_writes() {
+        let mut capture = Capture {
+            data: [0; 8],
+            len: 0,
+        };
+        let vfs = trickle_vfs(&mut capture);
+        assert_eq!(vfs.write(1, b"hello".as_ptr(), 5), 1);
+        assert_eq!(vfs.write_all(1, b"world".as_ptr(), 5), 5);
+        assert_eq!(&capture.data[..capture.len], b"hworld");
+        // Only two bytes fit, then the device accepts nothing.
+        assert_eq!(vfs.write_all(1, b"abcd".as_ptr(), 4), 2);
+        assert_eq!(vfs.write_all(1, b"abcd".as_ptr(), 4), 0);
+    }
+
+    #[test]
+    fn test_read_exact_loops_over_short_reads() {
+        let mut capture = Capture {
+            data: *b"abcdefgh",
+            len: 0,
+        };
+        let vfs = trickle_vfs(&mut capture);
+        let mut buf = [0u8; 6];
+        assert_eq!(vfs.read_exact(1, buf.as_mut_ptr(), 6), 6);
+        assert_eq!(&buf, b"abcdef");
+        // End of file cuts the second read short.
+        assert_eq!(vfs.read_exact(1, buf.as_mut_ptr(), 6), 2);
+        assert_eq!(&buf[..2], b"gh");
+    }
+
+    #[test]
+    fn test_read_exact_errors() {
+        let mut capture = Capture {
+            data: [0; 8],
+            len: 0,
+        };
+        let vfs = trickle_vfs(&mut capture);
+        let efault = -(libc::EFAULT as isize);
+        assert_eq!(vfs.read_exact(1, core::ptr::null_mut(), 4), efault);
+        assert_eq!(vfs.write_all(1, core::ptr::null(), 4), efault);
+        assert_eq!(
+            vfs.read_exact(7, [0u8; 1].as_mut_ptr(), 1),
+            -(libc::EBADF as isize)
+        );
+    }
+
+    #[test]
+    fn test_iovec_validation() {
+        let mut capture = Capture {
+            data: [0; 8],
+            len: 0,
+        };
+        let vfs = capture_vfs(&mut capture);
+        let mut a = *b"ab";
+        let iov = [
+            iovec(&mut a),
+            libc::iovec {
+                iov_base: core::ptr::null_mut(),
+                iov_len: 4,
+            },
+        ];
+
+        unsafe {
+            assert_eq!(vfs.writev(1, iov.as_ptr(), 0), -(libc::EINVAL as isize));
+            assert_eq!(
+                vfs.writev(1, core::ptr::null(), 1),
+                -(libc::EFAULT as isize)
+            );
+            // A null segment after data ends the transfer with the partial total.
+            assert_eq!(vfs.writev(1, iov.as_ptr(), 2), 2);
+            assert_eq!(
+                vfs.writev(1, iov[1..].as_ptr(), 1),
+                -(libc::EFAULT as isize)
+            );
+            assert_eq!(vfs.writev(7, iov.as_ptr(), 1), -(libc::EBADF as isize));
+        }
+    }
+
+    extern crate std;
+
+    std::thread_local! {
+        /// Yields taken by the fake scheduler; `None` while it has no other thread to run.
+        static FAKE_YIELDS: core::cell::Cell<Option<usize>> = const { core::cell::Cell::new(None) };
+    }
+
+    std::thread_local! {
+        /// An fd the fake scheduler closes on `SLOW_VFS` while "another thread" runs.
+        static CLOSE_ON_YIELD: core::cell::Cell<Option<Fd>> = const { core::cell::Cell::new(None) };
+    }
+
+    static SLOW_VFS: GlobalCell<Vfs> = GlobalCell::new(Vfs::new());
+
+    fn fake_yield() -> bool {
+        if let Some(fd) = CLOSE_ON_YIELD.with(|fd| fd.take()) {
+            SLOW_VFS.with_mut(|vfs| vfs.close(fd));
+        }
+        FAKE_YIELDS.with(|yields| match yields.get() {
+            Some(n) => {
+                yields.set(Some(n + 1));
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Would block until the fake scheduler has run three times, then reads one byte.
+    fn slow_read(_file: *mut u8, buf: *mut u8, _count: usize) -> isize {
+        if FAKE_YIELDS.with(|yields| yields.get().unwrap_or(0)) < 3 {
+            return -(libc::EAGAIN as isize);
+        }
+        unsafe { *buf = b'!' };
+        1
+    }
+
+    const SLOW_FOPS: FileOps = FileOps {
+        read: slow_read,
+        set_nonblocking: noop_set_nonblocking,
+        ..NONBLOCK_FOPS
+    };
+
+    fn slow_factory(_path: &str) -> FdEntry {
+        FdEntry {
+            ops: &SLOW_FOPS,
+            private_data: core::ptr::null_mut(),
+        }
+    }
+
+    #[test]
+    fn test_blocking_read_yields_until_data() {
+        let (fd, nb_fd) = SLOW_VFS.with_mut(|vfs| {
+            vfs.register_device("/dev/slow", slow_factory).unwrap();
+            let fd = vfs.open("/dev/slow", libc::O_RDONLY, 0).unwrap();
+            let nb_fd = vfs
+                .open("/dev/slow", libc::O_RDONLY | libc::O_NONBLOCK, 0)
+                .unwrap();
+            (fd, nb_fd)
+        });
+        let mut buf = [0u8; 1];
+        let read = |fd, buf: &mut [u8; 1]| {
+            retry_blocking(&SLOW_VFS, fd, fake_yield, |vfs| {
+                vfs.read(fd, buf.as_mut_ptr(), 1)
+            })
+        };
+
+        // Without a scheduler there is nobody to wait for.
+        assert_eq!(read(fd, &mut buf), -(libc::EAGAIN as isize));
+
+        FAKE_YIELDS.with(|yields| yields.set(Some(0)));
+        assert_eq!(read(nb_fd, &mut buf), -(libc::EAGAIN as isize));
+        assert_eq!(FAKE_YIELDS.with(|yields| yields.get()), Some(0));
+
+        assert_eq!(read(fd, &mut buf), 1);
+        assert_eq!(buf[0], b'!');
+        assert_eq!(FAKE_YIELDS.with(|yields| yields.get()), Some(3));
+
+        // An fd closed while the reader was waiting is noticed on the retry.
+        FAKE_YIELDS.with(|yields| yields.set(Some(0)));
+        CLOSE_ON_YIELD.with(|close| close.set(Some(fd)));
+        assert_eq!(read(fd, &mut buf), -(libc::EBADF as isize));
+        assert_eq!(FAKE_YIELDS.with(|yields| yields.get()), Some(1));
+
+        FAKE_YIELDS.with(|yields| yields.set(None));
     }
 }
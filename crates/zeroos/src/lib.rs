@@ -82,9 +82,15 @@ pub mod vfs {
         #[cfg(feature = "vfs-device-console")]
         pub use device_console as console;
 
+        #[cfg(feature = "vfs-device-full")]
+        pub use device_full as full;
+
         #[cfg(feature = "vfs-device-null")]
         pub use device_null as null;
 
+        #[cfg(feature = "vfs-device-ramfile")]
+        pub use device_ramfile as ramfile;
+
         #[cfg(feature = "vfs-device-urandom")]
         pub use device_urandom as urandom;
 
@@ -104,6 +110,19 @@ pub mod rng {
     pub use rng::*;
 }
 
+/// Commonly used kernel types, gated by the features that provide them.
+///
+/// `use zeroos::prelude::*;` covers typical kernel and driver code.
+pub mod prelude {
+    pub use foundation::ioctl::IoctlCommand;
+
+    #[cfg(feature = "vfs")]
+    pub use vfs_core::{Fd, FdEntry, FileOps, VfsResult};
+
+    #[cfg(feature = "arch-riscv")]
+    pub use arch_riscv::TrapFrame;
+}
+
 pub fn initialize() {
     #[cfg(feature = "arch-riscv")]
     foundation::register_arch(arch_riscv::ARCH_OPS);
@@ -124,8 +143,30 @@ pub fn initialize() {
     foundation::register_vfs(vfs_core::VFS_OPS);
 
     #[cfg(feature = "scheduler-cooperative")]
-    foundation::register_scheduler(scheduler_cooperative::SCHEDULER_OPS);
+    {
+        foundation::register_scheduler(scheduler_cooperative::SCHEDULER_OPS);
+        foundation::kfn::block::set_block_yield(scheduler_cooperative::ops::block_yield);
+    }
 
     #[cfg(feature = "random")]
     foundation::register_random(rng::RNG_OPS);
 }
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused_imports)]
+    use crate::prelude::*;
+
+    #[test]
+    fn prelude_compiles() {
+        let cmd: IoctlCommand = IoctlCommand::from_raw(0x5204).unwrap();
+        assert!(cmd.eq_raw(0x5204));
+
+        #[cfg(feature = "vfs")]
+        {
+            let fd: Fd = 3;
+            let result: VfsResult<Fd> = Ok(fd);
+            assert_eq!(result, Ok(3));
+        }
+    }
+}
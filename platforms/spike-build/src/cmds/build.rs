@@ -4,8 +4,9 @@ use log::debug;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use build::cmds::build::{TARGET_NO_STD, TARGET_STD};
 use build::cmds::{BuildArgs, StdMode};
+use build::host::process::run_captured;
+use build::project::{expand_emit_path, resolve_package_dir};
 
 #[derive(Args, Debug)]
 pub struct SpikeBuildArgs {
@@ -88,10 +89,7 @@ fn emit_linker_script(
     out_tpl: &str,
     force: bool,
 ) -> Result<()> {
-    let target = base.target.as_deref().unwrap_or(match base.mode {
-        StdMode::Std => TARGET_STD,
-        StdMode::NoStd => TARGET_NO_STD,
-    });
+    let target = base.target();
     let profile = build::project::detect_profile(&base.cargo_args);
 
     let target_dir = build::project::get_target_directory(&workspace_root.to_path_buf())?;
@@ -115,7 +113,6 @@ fn emit_linker_script(
         &resolve_package_dir(workspace_root, &base.package)?,
         target,
         &profile,
-        &base.package,
     );
     let out_path_raw = PathBuf::from(out_path_str);
     let out_path = out_path_raw;
@@ -142,62 +139,6 @@ fn emit_linker_script(
     Ok(())
 }
 
-fn expand_emit_path(
-    template: &str,
-    workspace: &Path,
-    package_dir: &Path,
-    target: &str,
-    profile: &str,
-    _package: &str,
-) -> String {
-    template
-        .replace("<WORKSPACE>", &workspace.display().to_string())
-        .replace("<PACKAGE>", &package_dir.display().to_string())
-        .replace("<TARGET>", target)
-        .replace("<PROFILE>", profile)
-}
-
-fn resolve_package_dir(workspace_root: &Path, package_name: &str) -> Result<PathBuf> {
-    let output = Command::new("cargo")
-        .args(["metadata", "--format-version", "1", "--no-deps"])
-        .arg("--manifest-path")
-        .arg(workspace_root.join("Cargo.toml"))
-        .output()
-        .context("Failed to run `cargo metadata`")?;
-
-    if !output.status.success() {
-        anyhow::bail!(
-            "`cargo metadata` failed:\nstdout:\n{}\nstderr:\n{}",
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
-
-    let v: serde_json::Value =
-        serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata JSON")?;
-
-    let packages = v
-        .get("packages")
-        .and_then(|p| p.as_array())
-        .ok_or_else(|| anyhow::anyhow!("cargo metadata JSON: missing `packages` array"))?;
-
-    for pkg in packages {
-        if pkg.get("name").and_then(|n| n.as_str()) != Some(package_name) {
-            continue;
-        }
-        let manifest = pkg
-            .get("manifest_path")
-            .and_then(|m| m.as_str())
-            .ok_or_else(|| anyhow::anyhow!("cargo metadata JSON: package missing manifest_path"))?;
-        let manifest_dir = Path::new(manifest)
-            .parent()
-            .ok_or_else(|| anyhow::anyhow!("Invalid manifest_path for {}", package_name))?;
-        return Ok(manifest_dir.to_path_buf());
-    }
-
-    anyhow::bail!("Package not found in cargo metadata: {}", package_name)
-}
-
 fn find_file_named(
     root: &std::path::Path,
     file_name: &str,
@@ -229,20 +170,12 @@ fn find_file_named(
 }
 
 fn find_spike_platform_linker_template(workspace_root: &std::path::Path) -> Result<PathBuf> {
-    let output = Command::new("cargo")
-        .args(["metadata", "--format-version", "1", "--no-deps"])
-        .arg("--manifest-path")
-        .arg(workspace_root.join("Cargo.toml"))
-        .output()
-        .context("Failed to run `cargo metadata`")?;
-
-    if !output.status.success() {
-        anyhow::bail!(
-            "`cargo metadata` failed:\nstdout:\n{}\nstderr:\n{}",
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
+    let output = run_captured(
+        Command::new("cargo")
+            .args(["metadata", "--format-version", "1", "--no-deps"])
+            .arg("--manifest-path")
+            .arg(workspace_root.join("Cargo.toml")),
+    )?;
 
     let v: serde_json::Value =
         serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata JSON")?;
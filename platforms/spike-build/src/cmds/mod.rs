@@ -1,3 +1,2 @@
 pub mod build;
 pub mod generate;
-pub mod run;
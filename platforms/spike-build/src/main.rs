@@ -16,7 +16,7 @@ enum Cli {
 #[derive(clap::Subcommand, Debug)]
 enum SpikeCmd {
     Build(cmds::build::SpikeBuildArgs),
-    Run(cmds::run::RunArgs),
+    Run(build::cmds::RunArgs),
     #[command(subcommand)]
     Generate(cmds::generate::GenerateCmd),
 }
@@ -32,7 +32,7 @@ fn main() {
     let Cli::Spike(cmd) = Cli::parse();
     let result = match cmd {
         SpikeCmd::Build(args) => cmds::build::build_command(args),
-        SpikeCmd::Run(args) => cmds::run::run_command(args),
+        SpikeCmd::Run(args) => build::cmds::run_command(args),
         SpikeCmd::Generate(gen_cmd) => match gen_cmd {
             cmds::generate::GenerateCmd::Target(args) => {
                 cmds::generate::generate_target_command(args)
@@ -1,16 +1,19 @@
 use std::path::{Path, PathBuf};
 
-fn find_upwards(start: &Path, filename: &str) -> Option<PathBuf> {
+/// Every `<ancestor>/<filename>` that exists, from `start` (or its parent, if `start` is a file)
+/// up to the filesystem root, nearest first.
+pub fn find_all(start: &Path, filename: &str) -> Vec<PathBuf> {
     let mut dir = if start.is_dir() {
         start.to_path_buf()
     } else {
         start.parent().unwrap_or(start).to_path_buf()
     };
 
+    let mut found = Vec::new();
     loop {
         let candidate = dir.join(filename);
         if candidate.exists() {
-            return Some(candidate);
+            found.push(candidate);
         }
 
         if !dir.pop() {
@@ -18,7 +21,11 @@ fn find_upwards(start: &Path, filename: &str) -> Option<PathBuf> {
         }
     }
 
-    None
+    found
+}
+
+fn find_upwards(start: &Path, filename: &str) -> Option<PathBuf> {
+    find_all(start, filename).into_iter().next()
 }
 
 pub fn workspace_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -33,3 +40,52 @@ pub fn workspace_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
 
     Ok(lock.parent().unwrap_or(lock.as_path()).to_path_buf())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_all_nearest_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let mid = root.join("a/b");
+        let leaf = mid.join("c/d");
+        fs::create_dir_all(&leaf).unwrap();
+        for dir in [root, mid.as_path(), leaf.as_path()] {
+            fs::write(dir.join("Cargo.toml"), "").unwrap();
+        }
+
+        let found = find_all(&leaf, "Cargo.toml");
+        // Ancestors of the temp dir may hold a Cargo.toml too; ours come first, in order.
+        assert_eq!(
+            &found[..3],
+            [
+                leaf.join("Cargo.toml"),
+                mid.join("Cargo.toml"),
+                root.join("Cargo.toml")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_all_from_file_and_no_match() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("x/y");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(tmp.path().join("x/marker"), "").unwrap();
+        let file = dir.join("file.rs");
+        fs::write(&file, "").unwrap();
+
+        assert_eq!(
+            find_all(&file, "marker").first(),
+            Some(&tmp.path().join("x/marker"))
+        );
+        assert_eq!(
+            find_upwards(&file, "marker"),
+            Some(tmp.path().join("x/marker"))
+        );
+        assert!(find_all(&dir, "zeroos-no-such-file-anywhere").is_empty());
+    }
+}
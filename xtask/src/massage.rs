@@ -31,6 +31,8 @@ pub fn run(args: MassageArgs) -> Result<(), Box<dyn std::error::Error>> {
             command: Some((*cmd).to_string()),
             packages: packages.clone(),
             verbose: args.verbose,
+            output_on_failure: false,
+            keep_going: false,
         })
         .map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
     }